@@ -1,6 +1,5 @@
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use regex;
-use rzozowski;
+use rzozowski::{generate::InputGenerator, SimplifyLevel, SimplifyOptions};
 use std::{hint::black_box, sync::LazyLock};
 
 struct TestPattern {
@@ -106,17 +105,7 @@ fn bench_regex_parse(c: &mut Criterion) {
         group.bench_with_input(
             BenchmarkId::new("rzozowski", pattern.name),
             pattern.pattern,
-            |b, pat| {
-                b.iter(|| {
-                    black_box(match rzozowski::Regex::new(pat) {
-                        Ok(re) => re,
-                        Err(e) => {
-                            eprintln!("Pattern: {pat}");
-                            panic!("{e}");
-                        }
-                    })
-                })
-            },
+            |b, pat| b.iter(|| black_box(rzozowski::Regex::new(pat).unwrap())),
         );
 
         group.bench_with_input(
@@ -161,5 +150,87 @@ fn bench_regex_matches(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_regex_parse, bench_regex_matches);
+fn bench_simplify_levels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simplify_levels");
+
+    // `SimplifyLevel::None` is left out, since its derivatives grow exponentially on the nested
+    // patterns (e.g., `nested_star` takes seconds per match).
+    for (level_name, level) in [
+        ("basic", SimplifyLevel::Basic),
+        ("aggressive", SimplifyLevel::Aggressive),
+    ] {
+        let options = SimplifyOptions::from(level);
+        for pattern in TEST_PATTERNS.iter() {
+            let re = rzozowski::Regex::new(pattern.pattern).unwrap();
+            group.bench_function(BenchmarkId::new(level_name, pattern.name), |b| {
+                b.iter(|| {
+                    let derivative = pattern
+                        .valid_string
+                        .chars()
+                        .fold(re.clone(), |re, c| re.derivative_with(c, &options));
+                    black_box(derivative);
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// Worst-case patterns, whose step counts are also checked against the baselines in the same file
+/// by the `test_pathological_step_counts` test.
+static PATHOLOGICAL_PATTERNS: LazyLock<serde_json::Value> =
+    LazyLock::new(|| serde_json::from_str(include_str!("pathological.json")).unwrap());
+
+fn bench_pathological(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pathological");
+
+    for entry in PATHOLOGICAL_PATTERNS.as_array().unwrap() {
+        let name = entry["name"].as_str().unwrap();
+        let pattern = entry["pattern"].as_str().unwrap();
+        let input = entry["input"].as_str().unwrap();
+
+        group.bench_with_input(BenchmarkId::new("parse", name), pattern, |b, pat| {
+            b.iter(|| black_box(rzozowski::Regex::new(pat).unwrap()))
+        });
+
+        let re = rzozowski::Regex::new(pattern).unwrap();
+        group.bench_with_input(BenchmarkId::new("matches", name), input, |b, input| {
+            b.iter(|| black_box(re.matches(input)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_generated_inputs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generated_inputs");
+    let mut generator = InputGenerator::new(0);
+
+    for pattern in TEST_PATTERNS.iter() {
+        let re = rzozowski::Regex::new(pattern.pattern).unwrap();
+
+        if let Some(near_miss) = generator.near_miss(&re) {
+            group.bench_function(BenchmarkId::new("near_miss", pattern.name), |b| {
+                b.iter(|| black_box(re.matches(&near_miss)))
+            });
+        }
+        if let Some(long_runs) = generator.long_runs(&re, 16) {
+            group.bench_function(BenchmarkId::new("long_runs", pattern.name), |b| {
+                b.iter(|| black_box(re.matches(&long_runs)))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_regex_parse,
+    bench_regex_matches,
+    bench_simplify_levels,
+    bench_pathological,
+    bench_generated_inputs
+);
 criterion_main!(benches);
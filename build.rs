@@ -1,3 +1,24 @@
 pub fn main() {
     monk::init();
+
+    #[cfg(feature = "ffi")]
+    write_ffi_header();
+}
+
+/// Writes a C header for the `ffi` module to `include/rzozowski.h` in `OUT_DIR`.
+#[cfg(feature = "ffi")]
+fn write_ffi_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let header = std::path::Path::new(&out_dir).join("include/rzozowski.h");
+
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("RZOZOWSKI_H")
+        .generate()
+        .expect("Unable to generate the C header")
+        .write_to_file(header);
 }
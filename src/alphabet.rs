@@ -0,0 +1,98 @@
+//! Alphabets that analyses of a regex can be restricted to, since a regex is rarely meant to say
+//! anything about every Unicode character (e.g., `[\x00-\x7f]*` matches every ASCII string, but
+//! not every string).
+
+use crate::charset::CharSet;
+use crate::derivatives::{CharRange, Regex};
+use std::collections::{HashSet, VecDeque};
+
+/// The characters that the strings considered by an analysis are made of (e.g., by
+/// `Regex::is_universal`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum AnalysisAlphabet {
+    /// Every Unicode scalar value.
+    #[default]
+    Unicode,
+    /// The ASCII characters (`\x00` to `\x7f`).
+    Ascii,
+    /// The characters `\x00` to `\xff`, which stand for bytes in a `bytes::Regex`.
+    Bytes,
+    /// The characters in the given set.
+    Custom(CharSet),
+}
+
+impl AnalysisAlphabet {
+    /// Returns the characters in the alphabet.
+    pub fn chars(&self) -> CharSet {
+        match self {
+            Self::Unicode => CharSet::default().complement(),
+            Self::Ascii => CharSet::new(&[CharRange::Range('\0', '\x7f')]),
+            Self::Bytes => CharSet::new(&[CharRange::Range('\0', '\u{ff}')]),
+            Self::Custom(set) => set.clone(),
+        }
+    }
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches every string made of characters in the alphabet,
+    /// otherwise returns `false` (e.g., `[\x00-\x7f]*` is universal over ASCII but not Unicode).
+    ///
+    /// This explores the derivative automaton of the regex, taking one derivative per class of
+    /// `derivative_classes`, so predicates are tested on every character.
+    pub fn is_universal(&self, alphabet: &AnalysisAlphabet) -> bool {
+        let alphabet = alphabet.chars();
+        let mut seen = HashSet::from([self.clone()]);
+        let mut queue = VecDeque::from([self.clone()]);
+
+        while let Some(remainder) = queue.pop_front() {
+            if !remainder.nullable() {
+                return false;
+            }
+
+            for (set, next) in remainder.derivative_classes() {
+                if set.intersection(&alphabet).is_empty() {
+                    continue;
+                }
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        true
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{AnalysisAlphabet, CharRange, CharSet, Regex};
+
+    #[test]
+    fn test_is_universal() {
+        let ascii = Regex::new(r"[\x00-\x7f]*").unwrap();
+        assert!(ascii.is_universal(&AnalysisAlphabet::Ascii));
+        assert!(!ascii.is_universal(&AnalysisAlphabet::Bytes));
+        assert!(!ascii.is_universal(&AnalysisAlphabet::Unicode));
+
+        let bytes = Regex::new(r"[\x00-\xff]*").unwrap();
+        assert!(bytes.is_universal(&AnalysisAlphabet::Bytes));
+        assert!(bytes.is_universal(&AnalysisAlphabet::Ascii));
+
+        let everything = Regex::class(vec![CharRange::Range('\0', char::MAX)]).star();
+        assert!(everything.is_universal(&AnalysisAlphabet::default()));
+    }
+
+    #[test]
+    fn test_is_universal_custom() {
+        let ab = AnalysisAlphabet::Custom(CharSet::new(&[CharRange::Range('a', 'b')]));
+        assert!(Regex::new("(a|b)*").unwrap().is_universal(&ab));
+        assert!(Regex::new("a*(b(a|b)*)?").unwrap().is_universal(&ab));
+        assert!(!Regex::new("a*b*").unwrap().is_universal(&ab));
+        assert!(!Regex::new("(a|b)+").unwrap().is_universal(&ab));
+
+        // only the empty string is made of no characters
+        let none = AnalysisAlphabet::Custom(CharSet::default());
+        assert!(Regex::Epsilon.is_universal(&none));
+        assert!(!Regex::literal('a').is_universal(&none));
+    }
+}
@@ -0,0 +1,424 @@
+//! Matching with the derivatives stored in an arena, rather than as trees of boxes.
+//!
+//! `Regex::derivative` builds every derivative as a new tree, allocating a `Box` per node and
+//! freeing the old tree once it is replaced. `Regex::matches_in` instead stores each distinct node
+//! once in the flat vectors of an `Arena`, referring to its children by index, so that parts shared
+//! between derivatives (e.g., the rest of a concatenation) are never copied, and every node of a
+//! call is freed at once when the arena is reset for the next one.
+
+use crate::derivatives::{ensure_stack, Count, Regex};
+use std::collections::HashMap;
+
+/// A node of a regex in an arena, whose children are the indices of other nodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    Empty,
+    Epsilon,
+    /// A literal, class or predicate, which is the leaf with the given index.
+    Char(u32),
+    /// The rest of a string, which is the leaf with the given index, from the given byte offset.
+    Str(u32, usize),
+    Concat(u32, u32),
+    Or(u32, u32),
+    Count(u32, Count),
+    Shuffle(u32, u32),
+}
+
+/// Memory for the derivatives taken by `Regex::matches_in`, which can be reused between calls to
+/// avoid allocating it again.
+///
+/// Each call starts by clearing the arena, keeping its capacity, so it only ever holds the nodes of
+/// the last call.
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    /// Whether each node is nullable.
+    nullable: Vec<bool>,
+    /// The index of each node, so that each distinct node is only stored once.
+    ids: HashMap<Node, u32>,
+    /// The derivatives taken with respect to the current character, by index.
+    derivatives: HashMap<u32, u32>,
+}
+
+impl Arena {
+    /// The index of `∅`.
+    const EMPTY: u32 = 0;
+    /// The index of `ε`.
+    const EPSILON: u32 = 1;
+
+    /// Creates a new, empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct nodes the last call to `Regex::matches_in` stored in the
+    /// arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns `true` if the arena holds no nodes, otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Removes every node from the arena, keeping the memory for the next call.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.nullable.clear();
+        self.ids.clear();
+        self.derivatives.clear();
+    }
+
+    /// Returns the index of the node, adding it if it is new.
+    fn intern(&mut self, node: Node) -> u32 {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+
+        let nullable = match node {
+            Node::Empty | Node::Char(_) => false,
+            Node::Epsilon => true,
+            // the rest of a string is never empty, since it is replaced by `ε`
+            Node::Str(_, _) => false,
+            Node::Concat(left, right) | Node::Shuffle(left, right) => {
+                self.nullable[left as usize] && self.nullable[right as usize]
+            }
+            Node::Or(left, right) => self.nullable[left as usize] || self.nullable[right as usize],
+            Node::Count(inner, count) => count.bounds().0 == 0 || self.nullable[inner as usize],
+        };
+        let id = u32::try_from(self.nodes.len()).expect("too many nodes in the arena");
+        self.nodes.push(node);
+        self.nullable.push(nullable);
+        self.ids.insert(node, id);
+        id
+    }
+
+    /// Returns the concatenation of two nodes, without `ε`s or `∅`s.
+    fn concat(&mut self, left: u32, right: u32) -> u32 {
+        match (left, right) {
+            (Self::EMPTY, _) | (_, Self::EMPTY) => Self::EMPTY,
+            (Self::EPSILON, other) | (other, Self::EPSILON) => other,
+            _ => self.intern(Node::Concat(left, right)),
+        }
+    }
+
+    /// Returns the shuffle of two nodes, without `ε`s or `∅`s.
+    fn shuffle(&mut self, left: u32, right: u32) -> u32 {
+        match (left, right) {
+            (Self::EMPTY, _) | (_, Self::EMPTY) => Self::EMPTY,
+            (Self::EPSILON, other) | (other, Self::EPSILON) => other,
+            _ => self.intern(Node::Shuffle(left, right)),
+        }
+    }
+
+    /// Adds the alternatives of a nested alternation to `alternatives`.
+    fn collect_alternatives(&self, id: u32, alternatives: &mut Vec<u32>) {
+        ensure_stack(|| match self.nodes[id as usize] {
+            Node::Or(left, right) => {
+                self.collect_alternatives(left, alternatives);
+                self.collect_alternatives(right, alternatives);
+            }
+            Node::Empty => {}
+            _ => alternatives.push(id),
+        });
+    }
+
+    /// Returns the alternation of two nodes, as a sorted and deduplicated list of alternatives in
+    /// which the counts of the same node are merged, so that derivatives cannot grow forever.
+    fn or(&mut self, left: u32, right: u32) -> u32 {
+        match (left, right) {
+            (Self::EMPTY, other) | (other, Self::EMPTY) => return other,
+            _ if left == right => return left,
+            _ => {}
+        }
+
+        let mut alternatives = Vec::new();
+        self.collect_alternatives(left, &mut alternatives);
+        self.collect_alternatives(right, &mut alternatives);
+
+        // r{a,b} ∪ r{c,d} = r{min(a,c),max(b,d)} if the counts overlap or touch, and
+        // rs ∪ rt = r(s ∪ t), which together keep the counters of nested counts from multiplying
+        let mut counts = Vec::new();
+        let mut concats = Vec::new();
+        alternatives.retain(|&id| match self.nodes[id as usize] {
+            Node::Count(inner, count) => {
+                counts.push((inner, count));
+                false
+            }
+            Node::Concat(left, right) => {
+                concats.push((left, right));
+                false
+            }
+            _ => true,
+        });
+        for (inner, counts) in group_by_first(counts) {
+            for count in Count::merge(counts) {
+                let id = self.count(inner, count);
+                alternatives.push(id);
+            }
+        }
+        for (left, rights) in group_by_first(concats) {
+            let right = rights
+                .into_iter()
+                .reduce(|alternation, right| self.or(alternation, right))
+                .unwrap_or(Self::EMPTY);
+            let id = self.concat(left, right);
+            alternatives.push(id);
+        }
+
+        alternatives.sort_unstable();
+        alternatives.dedup();
+        let Some(mut alternation) = alternatives.pop() else {
+            return Self::EMPTY;
+        };
+        while let Some(alternative) = alternatives.pop() {
+            alternation = self.intern(Node::Or(alternative, alternation));
+        }
+        alternation
+    }
+
+    /// Returns a count of a node, normalized like `Regex::simplify` does.
+    fn count(&mut self, inner: u32, count: Count) -> u32 {
+        let (min, max) = count.bounds();
+        if max.is_some_and(|max| min > max) {
+            return Self::EMPTY;
+        }
+        match (inner, max) {
+            (_, Some(0)) | (Self::EPSILON, _) => return Self::EPSILON,
+            (Self::EMPTY, _) if min == 0 => return Self::EPSILON,
+            (Self::EMPTY, _) => return Self::EMPTY,
+            _ if min == 1 && max == Some(1) => return inner,
+            _ => {}
+        }
+
+        // r{n,m} = r{0,m} if r is nullable
+        let min = if self.nullable[inner as usize] {
+            0
+        } else {
+            min
+        };
+        self.intern(Node::Count(inner, Count::from_bounds(min, max)))
+    }
+
+    /// Adds a regex to the arena, returning the index of its root.
+    fn load<'r>(&mut self, regex: &'r Regex, leaves: &mut Vec<&'r Regex>) -> u32 {
+        ensure_stack(|| match regex {
+            Regex::Empty => Self::EMPTY,
+            Regex::Epsilon => Self::EPSILON,
+            Regex::Literal(_) | Regex::Class(_) | Regex::Pred(_) | Regex::Str(_) => {
+                let leaf = u32::try_from(leaves.len()).expect("too many leaves in the arena");
+                leaves.push(regex);
+                if let Regex::Str(s) = regex {
+                    if s.is_empty() {
+                        return Self::EPSILON;
+                    }
+                    return self.intern(Node::Str(leaf, 0));
+                }
+                self.intern(Node::Char(leaf))
+            }
+            Regex::Concat(left, right) => {
+                let (left, right) = (self.load(left, leaves), self.load(right, leaves));
+                self.concat(left, right)
+            }
+            Regex::Or(left, right) => {
+                let (left, right) = (self.load(left, leaves), self.load(right, leaves));
+                self.or(left, right)
+            }
+            Regex::Shuffle(left, right) => {
+                let (left, right) = (self.load(left, leaves), self.load(right, leaves));
+                self.shuffle(left, right)
+            }
+            Regex::Count(inner, count) => {
+                let inner = self.load(inner, leaves);
+                self.count(inner, *count)
+            }
+        })
+    }
+
+    /// Returns the derivative of a node with respect to `c`, taking the derivative of each node
+    /// shared between parts of it only once.
+    fn derivative(&mut self, id: u32, c: char, leaves: &[&Regex]) -> u32 {
+        if let Some(&derivative) = self.derivatives.get(&id) {
+            return derivative;
+        }
+
+        let derivative = ensure_stack(|| match self.nodes[id as usize] {
+            Node::Empty | Node::Epsilon => Self::EMPTY,
+            Node::Char(leaf) => {
+                if leaves[leaf as usize].matches_single_char(c) == Some(true) {
+                    Self::EPSILON
+                } else {
+                    Self::EMPTY
+                }
+            }
+            Node::Str(leaf, offset) => {
+                let Regex::Str(s) = leaves[leaf as usize] else {
+                    unreachable!("string nodes are only made from strings");
+                };
+                match s[offset..].strip_prefix(c) {
+                    Some("") => Self::EPSILON,
+                    Some(_) => self.intern(Node::Str(leaf, offset + c.len_utf8())),
+                    None => Self::EMPTY,
+                }
+            }
+            // D_c(rs) = D_c(r)s ∪ ν(r)D_c(s)
+            Node::Concat(left, right) => {
+                let left_derivative = self.derivative(left, c, leaves);
+                let derivative = self.concat(left_derivative, right);
+                if self.nullable[left as usize] {
+                    let right_derivative = self.derivative(right, c, leaves);
+                    self.or(derivative, right_derivative)
+                } else {
+                    derivative
+                }
+            }
+            Node::Or(left, right) => {
+                let left_derivative = self.derivative(left, c, leaves);
+                let right_derivative = self.derivative(right, c, leaves);
+                self.or(left_derivative, right_derivative)
+            }
+            Node::Shuffle(left, right) => {
+                let left_derivative = self.derivative(left, c, leaves);
+                let right_derivative = self.derivative(right, c, leaves);
+                let (left, right) = (
+                    self.shuffle(left_derivative, right),
+                    self.shuffle(left, right_derivative),
+                );
+                self.or(left, right)
+            }
+            // D_c(r{n,m}) = D_c(r)r{n-1,m-1}
+            Node::Count(inner, count) => {
+                let (min, max) = count.bounds();
+                let inner_derivative = self.derivative(inner, c, leaves);
+                let rest = self.count(
+                    inner,
+                    Count::from_bounds(min.saturating_sub(1), max.map(|max| max - 1)),
+                );
+                self.concat(inner_derivative, rest)
+            }
+        });
+        self.derivatives.insert(id, derivative);
+        derivative
+    }
+}
+
+/// Groups the pairs by their first element, in order of it.
+fn group_by_first<T: Copy + Ord>(mut pairs: Vec<(u32, T)>) -> Vec<(u32, Vec<T>)> {
+    pairs.sort_unstable();
+    let mut groups: Vec<(u32, Vec<T>)> = Vec::new();
+    for (first, second) in pairs {
+        match groups.last_mut() {
+            Some((last, seconds)) if *last == first => seconds.push(second),
+            _ => groups.push((first, vec![second])),
+        }
+    }
+    groups
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches the given string, otherwise returns `false`, storing the
+    /// derivatives in the given arena rather than allocating each of their nodes separately.
+    ///
+    /// The arena is cleared first and keeps its memory afterwards, so reusing one arena for many
+    /// calls avoids allocating at all once it is large enough. Each distinct node is only stored
+    /// once, so parts shared between derivatives are never copied, but none are freed until the
+    /// next call, so very long inputs can make the arena large.
+    pub fn matches_in(&self, s: &str, arena: &mut Arena) -> bool {
+        arena.clear();
+        arena.intern(Node::Empty);
+        arena.intern(Node::Epsilon);
+
+        let mut leaves = Vec::new();
+        let mut state = arena.load(self, &mut leaves);
+        for c in s.chars() {
+            if state == Arena::EMPTY {
+                return false;
+            }
+            arena.derivatives.clear();
+            state = arena.derivative(state, c, &leaves);
+        }
+        arena.nullable[state as usize]
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Arena, Regex};
+
+    #[test]
+    fn test_matches_in_agrees_with_matches() {
+        let patterns = [
+            "abc",
+            "a*",
+            "(a|b)*abb",
+            r"[a-z]+\d{2}",
+            "(a*b*c*)*d+",
+            "(a{2,5}b{3,7}c{1,9}){2,4}",
+            "é+|[α-ω]x",
+            "a?b?",
+            "(ab|a)(bc|c)",
+            "(a?){3}b",
+            "[]|a",
+        ];
+        let inputs = [
+            "", "abc", "aaa", "abb", "babb", "ab12", "ab1", "abcd", "aaabbbc", "ééé", "βx", "x",
+            "cab", "acb", "b", "ab",
+        ];
+        let shuffle = Regex::new("ab")
+            .unwrap()
+            .shuffle(&Regex::new("c+").unwrap());
+        let mut arena = Arena::new();
+        for regex in patterns
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .into_iter()
+            .chain([shuffle])
+        {
+            for s in inputs {
+                assert_eq!(
+                    regex.matches_in(s, &mut arena),
+                    regex.matches(s),
+                    "{regex} {s}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_in_predicates() {
+        let vowel = Regex::pred("vowel", |c| "aeiou".contains(c));
+        let regex = Regex::concat(vowel.plus(), Regex::literal('x'));
+        let mut arena = Arena::new();
+        assert!(regex.matches_in("aeix", &mut arena));
+        assert!(!regex.matches_in("abx", &mut arena));
+        assert!(!regex.matches_in("x", &mut arena));
+    }
+
+    #[test]
+    fn test_arena_reuse() {
+        let regex = Regex::new("(a|b)*abb").unwrap();
+        let mut arena = Arena::new();
+        assert!(arena.is_empty());
+        assert!(!regex.matches_in(&"ab".repeat(1000), &mut arena));
+
+        // the derivatives of a long input are the same few nodes
+        let nodes = arena.len();
+        assert!(nodes < 30, "{nodes}");
+        assert!(regex.matches_in(&format!("{}abb", "ab".repeat(1000)), &mut arena));
+
+        // each call starts from an empty arena
+        assert!(Regex::literal('a').matches_in("a", &mut arena));
+        assert_eq!(arena.len(), 3);
+    }
+
+    #[test]
+    fn test_matches_in_nested_counts() {
+        let regex = Regex::new("(a{2,5}){1,100000}").unwrap();
+        let mut arena = Arena::new();
+        // each character counts down the outer counter, which takes a few new nodes, rather than
+        // adding an alternative for every number of repetitions so far
+        assert!(regex.matches_in(&"a".repeat(5000), &mut arena));
+        assert!(arena.len() < 10 * 5000, "{}", arena.len());
+        assert!(!regex.matches_in("a", &mut arena));
+    }
+}
@@ -0,0 +1,179 @@
+//! Matching with limits on the work done, for patterns and inputs that cannot be trusted.
+
+use crate::derivatives::Regex;
+use crate::stats::count_calls;
+use std::fmt::{Display, Formatter};
+
+/// Limits on the work `Regex::try_matches` may do before giving up.
+///
+/// A limit of `None` is not checked, so the default budget is unlimited (e.g.,
+/// `MatchBudget { max_derivative_calls: Some(100_000), ..MatchBudget::default() }`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MatchBudget {
+    /// The number of calls to the derivative, including recursive calls on subexpressions, after
+    /// which matching stops. This bounds the time spent roughly in proportion to the work done.
+    pub max_derivative_calls: Option<usize>,
+    /// The number of nodes a derivative may have, which bounds the memory used and the work of
+    /// taking the next derivative.
+    pub max_nodes: Option<usize>,
+}
+
+/// Why `Regex::try_matches` gave up before finding out whether the regex matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MatchError {
+    /// More than `limit` calls to the derivative were made, by the character at the byte offset
+    /// `position`.
+    DerivativeCallLimit { limit: usize, position: usize },
+    /// The derivative with respect to the input up to and including the character at the byte
+    /// offset `position` (or the regex itself, with a `position` of 0) has `size` nodes, more than
+    /// `limit`.
+    SizeLimit {
+        limit: usize,
+        size: usize,
+        position: usize,
+    },
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DerivativeCallLimit { limit, position } => write!(
+                f,
+                "Matching gave up at position {position} after more than {limit} derivative calls"
+            ),
+            Self::SizeLimit {
+                limit,
+                size,
+                position,
+            } => write!(
+                f,
+                "Matching gave up at position {position} on a derivative of {size} nodes, more than {limit}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+impl Regex {
+    /// Like `matches`, but gives up with an error once the work done exceeds the budget, so a
+    /// pattern or input from an untrusted source cannot take unbounded time or memory.
+    ///
+    /// The limits are checked after the derivative with respect to each character, so the work is
+    /// only exceeded by at most one derivative, whose size is itself bounded by `max_nodes`.
+    pub fn try_matches(&self, s: &str, budget: MatchBudget) -> Result<bool, MatchError> {
+        if let Some(limit) = budget.max_nodes {
+            let size = self.node_count();
+            if size > limit {
+                return Err(MatchError::SizeLimit {
+                    limit,
+                    size,
+                    position: 0,
+                });
+            }
+        }
+        if self.matches_nothing() {
+            return Ok(false);
+        }
+
+        let mut current = self.clone();
+        let mut derivative_calls = 0;
+        for (position, c) in s.char_indices() {
+            if current == Self::Empty {
+                return Ok(false);
+            }
+
+            let (next, calls, _) = count_calls(|| current.derivative(c));
+            current = next;
+
+            derivative_calls += calls;
+            if let Some(limit) = budget.max_derivative_calls {
+                if derivative_calls > limit {
+                    return Err(MatchError::DerivativeCallLimit { limit, position });
+                }
+            }
+            if let Some(limit) = budget.max_nodes {
+                let size = current.node_count();
+                if size > limit {
+                    return Err(MatchError::SizeLimit {
+                        limit,
+                        size,
+                        position,
+                    });
+                }
+            }
+        }
+
+        Ok(current.nullable())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{MatchBudget, MatchError, Regex};
+
+    #[test]
+    fn test_try_matches() {
+        let regex = Regex::new(r"[a-z]+\d{2}").unwrap();
+        let unlimited = MatchBudget::default();
+        assert_eq!(regex.try_matches("abc12", unlimited), Ok(true));
+        assert_eq!(regex.try_matches("abc1", unlimited), Ok(false));
+
+        let budget = MatchBudget {
+            max_derivative_calls: Some(1000),
+            max_nodes: Some(100),
+        };
+        assert_eq!(regex.try_matches("abc12", budget), Ok(true));
+        assert_eq!(regex.try_matches("1abc12", budget), Ok(false));
+    }
+
+    #[test]
+    fn test_try_matches_derivative_call_limit() {
+        let regex = Regex::new("(a|b)*c").unwrap();
+        let budget = MatchBudget {
+            max_derivative_calls: Some(50),
+            ..MatchBudget::default()
+        };
+        let error = regex.try_matches(&"ab".repeat(100), budget).unwrap_err();
+        let MatchError::DerivativeCallLimit { limit, position } = error else {
+            panic!("{error}");
+        };
+        assert_eq!(limit, 50);
+        assert!(position < 50);
+
+        // a string that can no longer match stops early, within the budget
+        assert_eq!(regex.try_matches(&"c".repeat(1000), budget), Ok(false));
+    }
+
+    #[test]
+    fn test_try_matches_size_limit() {
+        let regex = Regex::new("(a*b*c*)*d+").unwrap();
+        let budget = |max_nodes| MatchBudget {
+            max_nodes: Some(max_nodes),
+            ..MatchBudget::default()
+        };
+
+        // the derivative with respect to `a` is larger than the regex
+        let error = regex.try_matches("abc", budget(15)).unwrap_err();
+        assert_eq!(
+            error,
+            MatchError::SizeLimit {
+                limit: 15,
+                size: 21,
+                position: 0,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Matching gave up at position 0 on a derivative of 21 nodes, more than 15"
+        );
+        assert_eq!(regex.try_matches("abcd", budget(30)), Ok(true));
+
+        // the regex itself is too large
+        assert!(matches!(
+            regex.try_matches("", budget(5)),
+            Err(MatchError::SizeLimit { size: 12, .. })
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+//! Compiling the patterns of a pattern pack (see `pack`) into Rust code from a build script, so that
+//! a program matches its hot patterns with generated functions and pays nothing to parse or compile
+//! them when it starts.
+//!
+//! `compile_patterns` loads the pack, which checks that every pattern compiles and passes its
+//! self-tests, compiles each pattern into a minimized `Dfa`, runs the self-tests again on the DFA,
+//! and writes to the output directory (usually `OUT_DIR`):
+//!
+//! - `<stem>.rs`, named after the pack file, with a function `pub fn <name>(s: &str) -> bool` for
+//!   each pattern (see `Dfa::to_rust`), for including in the program with
+//!   `include!(concat!(env!("OUT_DIR"), "/<stem>.rs"))`
+//! - `<name>.dfa` for each pattern, its DFA written with `Dfa::to_bytes`, for matching with
+//!   `DfaRef` from `include_bytes!` instead
+//!
+//! The names of the patterns must be valid function names. A build script should also print
+//! `cargo:rerun-if-changed=` with the path of the pack, so that the code is generated again when
+//! the pack changes.
+
+use crate::pack::PatternPack;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The Rust code and DFAs generated from a pattern pack.
+#[derive(Debug)]
+struct Generated {
+    /// The Rust code of the function of every pattern.
+    code: String,
+    /// The name of each pattern and the bytes of its DFA.
+    dfas: Vec<(String, Vec<u8>)>,
+}
+
+/// Loads the pack from its text and generates the code and DFAs of its patterns, naming `source`
+/// in a comment at the top of the code.
+fn generate(text: &str, source: &str) -> Result<Generated, String> {
+    let pack = PatternPack::load(text)?;
+    let mut code = format!("// Generated by rzozowski from the pattern pack {source}.\n");
+    let mut dfas = Vec::new();
+
+    for (pattern, (_, regex)) in pack.pack().patterns.iter().zip(pack.iter()) {
+        let dfa = regex
+            .compile()
+            .map_err(|error| {
+                format!(
+                    "Pattern {} does not compile to a DFA: {error}",
+                    pattern.name
+                )
+            })?
+            .minimize();
+        if let Some(s) = pattern.matches.iter().find(|s| !dfa.matches(s)) {
+            return Err(format!(
+                "The DFA of pattern {} does not match {s:?}",
+                pattern.name
+            ));
+        }
+        if let Some(s) = pattern.rejects.iter().find(|s| dfa.matches(s)) {
+            return Err(format!(
+                "The DFA of pattern {} matches {s:?}, which it must reject",
+                pattern.name
+            ));
+        }
+
+        code.push('\n');
+        dfa.write_rust(&mut code, &pattern.name)?;
+        dfas.push((pattern.name.clone(), dfa.to_bytes()));
+    }
+
+    Ok(Generated { code, dfas })
+}
+
+/// Writes the contents to the file at `path`, replacing it if it exists.
+fn write(path: &Path, contents: &[u8]) -> Result<(), String> {
+    fs::write(path, contents).map_err(|error| format!("Cannot write {}: {error}", path.display()))
+}
+
+/// Compiles every pattern of the pattern pack at `pack_path` into a minimized DFA, and writes the
+/// Rust code of a function for each and the bytes of each DFA to `out_dir`, returning the path of
+/// the Rust file (see the module documentation).
+///
+/// Returns an error if the pack cannot be read or loaded, if a pattern cannot be compiled into a
+/// DFA (see `Regex::compile`) or its name cannot be the name of a function, or if a file cannot be
+/// written.
+pub fn compile_patterns(
+    pack_path: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+) -> Result<PathBuf, String> {
+    let pack_path = pack_path.as_ref();
+    let out_dir = out_dir.as_ref();
+    let (Some(file_name), Some(stem)) = (pack_path.file_name(), pack_path.file_stem()) else {
+        return Err(format!("{} is not a file", pack_path.display()));
+    };
+
+    let text = fs::read_to_string(pack_path)
+        .map_err(|error| format!("Cannot read {}: {error}", pack_path.display()))?;
+    let generated = generate(&text, &file_name.to_string_lossy())?;
+
+    fs::create_dir_all(out_dir)
+        .map_err(|error| format!("Cannot create {}: {error}", out_dir.display()))?;
+    for (name, bytes) in &generated.dfas {
+        write(&out_dir.join(format!("{name}.dfa")), bytes)?;
+    }
+    let code_path = out_dir.join(format!("{}.rs", stem.to_string_lossy()));
+    write(&code_path, generated.code.as_bytes())?;
+    Ok(code_path)
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::generate;
+    #[allow(unused_imports)]
+    use crate::dfa::DfaRef;
+
+    #[test]
+    fn test_generate() {
+        let pack = "\
+rzozowski-pack: 1
+
+pattern: snake_case
+regex: [a-z_][a-z0-9_]*
+match: user_id
+reject: 2fast
+
+pattern: ab_then_c
+regex: (ab|a)*c
+";
+        let generated = generate(pack, "identifiers.pack").unwrap();
+        assert!(generated
+            .code
+            .starts_with("// Generated by rzozowski from the pattern pack identifiers.pack.\n"));
+        assert!(generated
+            .code
+            .contains("pub fn snake_case(s: &str) -> bool {"));
+        assert!(generated
+            .code
+            .contains("pub fn ab_then_c(s: &str) -> bool {"));
+
+        let names: Vec<&str> = generated
+            .dfas
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(names, ["snake_case", "ab_then_c"]);
+        let dfa = DfaRef::from_bytes(&generated.dfas[1].1).unwrap();
+        assert!(dfa.matches("abac") && dfa.matches("c") && !dfa.matches("abbc"));
+    }
+
+    #[test]
+    fn test_generate_errors() {
+        let pack = "rzozowski-pack: 1\npattern: digits\nregex: \\d+\nmatch: 12a\n";
+        assert_eq!(
+            generate(pack, "p.pack").unwrap_err(),
+            "Pattern digits does not match \"12a\""
+        );
+
+        let pack = "rzozowski-pack: 1\npattern: two-digits\nregex: \\d{2}\n";
+        assert_eq!(
+            generate(pack, "p.pack").unwrap_err(),
+            "`two-digits` is not a valid function name"
+        );
+
+        let pack = "rzozowski-pack: 1\npattern: nested\nregex: ((a{2,5}b?){3,8}c?)+\n";
+        assert_eq!(
+            generate(pack, "p.pack").unwrap_err(),
+            "Pattern nested does not compile to a DFA: A state of the DFA has more than 1000 nodes"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+//! A builder for configuring how a pattern is compiled into a `Regex`, mirroring `RegexBuilder` in
+//! the `regex` crate.
+
+use crate::derivatives::{Regex, SimplifyLevel, SimplifyOptions};
+use crate::hybrid::{Engine, HybridMatcher};
+#[cfg(feature = "unicode-normalization")]
+use crate::normalize::Normalization;
+use crate::parser::{
+    parse_string_to_regex_simplified_with, ControlCharPolicy, ParseError, ParseFlags,
+};
+use crate::peg::OrderedRegex;
+use std::borrow::Cow;
+
+/// Compiles a pattern into a `Regex` with non-default options (e.g.,
+/// `RegexBuilder::new("abc").case_insensitive(true).build()`).
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    pattern: String,
+    flags: ParseFlags,
+    size_limit: Option<usize>,
+    #[cfg(feature = "unicode-normalization")]
+    normalization: Option<Normalization>,
+    /// The engine `build_hybrid` uses, or `None` to choose one as it is used.
+    engine: Option<Engine>,
+}
+
+impl RegexBuilder {
+    /// Creates a builder for the given pattern, with the same options as `Regex::new`.
+    pub fn new(pattern: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            flags: ParseFlags::default(),
+            size_limit: None,
+            #[cfg(feature = "unicode-normalization")]
+            normalization: None,
+            engine: None,
+        }
+    }
+
+    /// Sets whether literals and classes match regardless of case (off by default).
+    ///
+    /// Only ASCII letters are folded unless the `unicode-case` feature is enabled.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.flags.case_insensitive = yes;
+        self
+    }
+
+    /// Sets whether `.` matches `\n` (off by default).
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut Self {
+        self.flags.dot_matches_new_line = yes;
+        self
+    }
+
+    /// Sets whether `\d`, `\w` and `\s` match any Unicode numeric, alphanumeric and whitespace
+    /// characters respectively, rather than only ASCII ones (off by default).
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.flags.unicode = yes;
+        self
+    }
+
+    /// Sets the maximum depth of nested groups (e.g., `((a))` is nested 2 deep), which is unlimited
+    /// by default.
+    pub fn nest_limit(&mut self, limit: usize) -> &mut Self {
+        self.flags.nest_limit = Some(limit);
+        self
+    }
+
+    /// Sets the maximum number that may appear in a count (e.g., `1000` in `a{2,1000}`), which is
+    /// unlimited by default.
+    pub fn count_limit(&mut self, limit: usize) -> &mut Self {
+        self.flags.count_limit = Some(limit);
+        self
+    }
+
+    /// Sets how control characters (e.g., NUL, tab or newline) and whitespace other than the ASCII
+    /// space (e.g., U+3000) may be written in the pattern, which is `ControlCharPolicy::Literal` by
+    /// default.
+    pub fn control_chars(&mut self, policy: ControlCharPolicy) -> &mut Self {
+        self.flags.control_chars = policy;
+        self
+    }
+
+    /// Sets the maximum number of nodes in the compiled regex, which is unlimited by default.
+    pub fn size_limit(&mut self, limit: usize) -> &mut Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Sets the normalization form the pattern is put in before it is parsed, which is none by
+    /// default. Match inputs with `Regex::matches_normalized` using the same form.
+    ///
+    /// The whole pattern is normalized as text, so a class containing a combining character (e.g.,
+    /// `[e\u{301}]`) may be changed.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn normalization(&mut self, form: Normalization) -> &mut Self {
+        self.normalization = Some(form);
+        self
+    }
+
+    /// Returns the pattern in the configured normalization form.
+    #[cfg(feature = "unicode-normalization")]
+    fn normalized_pattern(&self) -> Cow<'_, str> {
+        self.normalization
+            .map_or(Cow::Borrowed(&self.pattern), |form| {
+                Cow::Owned(form.normalize(&self.pattern))
+            })
+    }
+
+    /// Returns the pattern, since normalization is not enabled.
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn normalized_pattern(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.pattern)
+    }
+
+    /// Tries to compile the pattern into a `Regex` with the configured options.
+    pub fn build(&self) -> Result<Regex, String> {
+        Ok(self.try_build()?)
+    }
+
+    /// Like `build`, but returns a `ParseError`, whose kind tells apart the errors a caller may
+    /// handle differently (e.g., a count over the limit set with `count_limit`).
+    pub fn try_build(&self) -> Result<Regex, ParseError> {
+        self.build_simplified(&SimplifyOptions::default())
+    }
+
+    /// Tries to compile the pattern into an `OrderedRegex` with the configured options, whose
+    /// alternations are ordered so that earlier alternatives win (see `peg`).
+    pub fn build_ordered(&self) -> Result<OrderedRegex, String> {
+        // sorting alternatives would lose the order they were written in
+        self.build_simplified(&SimplifyLevel::Basic.into())
+            .map(OrderedRegex::new)
+            .map_err(String::from)
+    }
+
+    /// Sets the engine the matcher built by `build_hybrid` always uses, rather than choosing one
+    /// from how much input it has matched.
+    pub fn engine(&mut self, engine: Engine) -> &mut Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Tries to compile the pattern into a `HybridMatcher` with the configured options, which uses
+    /// the engine set with `engine`, or otherwise chooses one as it is used.
+    pub fn build_hybrid(&self) -> Result<HybridMatcher, String> {
+        let regex = self.build()?;
+        self.engine.map_or_else(
+            || Ok(HybridMatcher::new(&regex)),
+            |engine| HybridMatcher::with_engine(&regex, engine),
+        )
+    }
+
+    /// Tries to compile the pattern, simplifying it with the given options.
+    fn build_simplified(&self, options: &SimplifyOptions) -> Result<Regex, ParseError> {
+        let regex =
+            parse_string_to_regex_simplified_with(&self.normalized_pattern(), self.flags, options)?;
+
+        if let Some(limit) = self.size_limit {
+            let size = regex.node_count();
+            if size > limit {
+                return Err(format!(
+                    "Compiled regex has {size} nodes, which is more than the limit of {limit}"
+                )
+                .into());
+            }
+        }
+
+        Ok(regex)
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{ControlCharPolicy, Engine, Regex, RegexBuilder};
+    #[allow(unused_imports)]
+    use crate::parser::ParseErrorKind;
+
+    #[test]
+    fn test_builder_default() {
+        let regex = RegexBuilder::new(r"a+\d").build().unwrap();
+        assert_eq!(regex, Regex::new(r"a+\d").unwrap());
+
+        assert!(RegexBuilder::new("a{").build().is_err());
+    }
+
+    #[test]
+    fn test_builder_case_insensitive() {
+        let regex = RegexBuilder::new("abc")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert!(regex.matches("aBC"));
+        assert_eq!(regex, Regex::new("(?i)abc").unwrap());
+    }
+
+    #[test]
+    fn test_builder_dot() {
+        let regex = Regex::new("a.c").unwrap();
+        assert!(regex.matches("abc") && regex.matches("a💕c"));
+        assert!(!regex.matches("a\nc") && !regex.matches("ac"));
+
+        let regex = RegexBuilder::new("a.c")
+            .dot_matches_new_line(true)
+            .build()
+            .unwrap();
+        assert!(regex.matches("abc") && regex.matches("a\nc"));
+
+        // an escaped `.` is still a literal
+        let regex = Regex::new(r"a\.c").unwrap();
+        assert!(regex.matches("a.c") && !regex.matches("abc"));
+    }
+
+    #[test]
+    fn test_builder_unicode() {
+        let ascii = Regex::new(r"\d\w\s").unwrap();
+        let unicode = RegexBuilder::new(r"\d\w\s").unicode(true).build().unwrap();
+
+        assert!(ascii.matches("1a ") && unicode.matches("1a "));
+        assert!(!ascii.matches("٣é\u{3000}"));
+        assert!(unicode.matches("٣é\u{3000}"));
+        assert!(!unicode.matches("a1 "));
+
+        // the same sequence always gives an equal regex
+        let unicode_again = RegexBuilder::new(r"\d\w\s").unicode(true).build().unwrap();
+        assert_eq!(unicode, unicode_again);
+    }
+
+    #[test]
+    fn test_builder_nest_limit() {
+        let pattern = r"((a)|\(\((b))";
+        assert!(RegexBuilder::new(pattern).nest_limit(2).build().is_ok());
+        assert!(RegexBuilder::new(pattern).nest_limit(1).build().is_err());
+        assert!(RegexBuilder::new("a").nest_limit(0).build().is_ok());
+        assert!(RegexBuilder::new("(a)").nest_limit(0).build().is_err());
+
+        // parentheses in classes are members, not groups
+        for pattern in ["[((]", "[]((]", r"[a-z--[(]]", "[(]([)])"] {
+            let regex = RegexBuilder::new(pattern).nest_limit(1).build();
+            assert!(regex.is_ok(), "{pattern}");
+        }
+        assert!(RegexBuilder::new("[](((a)))")
+            .nest_limit(2)
+            .build()
+            .is_err());
+        assert!(RegexBuilder::new("[(](([)]))")
+            .nest_limit(1)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_count_limit() {
+        assert!(RegexBuilder::new("a{100}").count_limit(100).build().is_ok());
+        assert!(RegexBuilder::new("a{2,100}b{100,}")
+            .count_limit(100)
+            .build()
+            .is_ok());
+        assert!(RegexBuilder::new("a{0,1000}").build().is_ok());
+
+        for pattern in ["a{101}", "a{2,101}", "a{101,}", "(a{3}b{1000})*"] {
+            let error = RegexBuilder::new(pattern)
+                .count_limit(100)
+                .build()
+                .unwrap_err();
+            assert!(
+                error.contains("is more than the limit of 100"),
+                "{pattern}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_builder_error_kinds() {
+        let kind = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .count_limit(100)
+                .try_build()
+                .unwrap_err()
+                .kind()
+        };
+
+        assert_eq!(kind("a{101}"), ParseErrorKind::CountOverLimit);
+        assert_eq!(kind("a{2,101}"), ParseErrorKind::CountOverLimit);
+        assert_eq!(
+            kind("a{99999999999999999999}"),
+            ParseErrorKind::CountTooLarge
+        );
+        assert_eq!(
+            kind("a{1,99999999999999999999}"),
+            ParseErrorKind::CountTooLarge
+        );
+        assert_eq!(kind("a{"), ParseErrorKind::Other);
+        assert_eq!(kind("(a"), ParseErrorKind::Other);
+
+        // the message is the same one `build` returns
+        let error = RegexBuilder::new("a{101}")
+            .count_limit(100)
+            .try_build()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            RegexBuilder::new("a{101}")
+                .count_limit(100)
+                .build()
+                .unwrap_err()
+        );
+
+        let error = RegexBuilder::new("a{3}b")
+            .size_limit(1)
+            .try_build()
+            .unwrap_err();
+        assert_eq!(error.kind(), ParseErrorKind::Other);
+    }
+
+    #[test]
+    fn test_builder_control_chars() {
+        let build =
+            |pattern: &str, policy| RegexBuilder::new(pattern).control_chars(policy).build();
+
+        // written as they are, they match themselves by default
+        let regex = build("a\tb[\n\u{3000}]", ControlCharPolicy::Literal).unwrap();
+        assert!(regex.matches("a\tb\n") && regex.matches("a\tb\u{3000}"));
+        assert_eq!(
+            build(r"a\x09b", ControlCharPolicy::Literal).unwrap(),
+            Regex::new("a\tb").unwrap()
+        );
+
+        let policy = ControlCharPolicy::RequireEscape;
+        assert_eq!(
+            build(r"a\x09b[\x00\x0a]", policy).unwrap(),
+            Regex::new("a\tb[\0\n]").unwrap()
+        );
+        assert_eq!(
+            build("a\tb", policy).unwrap_err(),
+            r"Error at position 1: U+0009 must be written as \x09"
+        );
+        assert!(build("[a\0]", policy).is_err());
+        assert!(build("\u{85}", policy).is_err());
+        // characters above `\xff` cannot be escaped
+        assert_eq!(
+            build("a\u{3000}", policy).unwrap_err(),
+            "Error at position 1: U+3000 is not allowed"
+        );
+        // the ASCII space is always allowed
+        assert!(build("a b", policy).is_ok());
+
+        let policy = ControlCharPolicy::Reject;
+        assert!(build("a\nb", policy).is_err());
+        assert!(build("[\u{2028}]", policy).is_err());
+        assert_eq!(
+            build(r"a[\x0a]", policy).unwrap_err(),
+            r"Error at position 2: U+000A is not allowed, even as \x0a"
+        );
+        assert!(build(r"a\x41 [!-ÿ]", policy).is_ok());
+        // an escaped backslash does not start an escape
+        assert!(build(r"\\x0a", policy).is_ok());
+        assert!(build(".", policy).unwrap().matches("\t"));
+    }
+
+    #[test]
+    fn test_builder_size_limit() {
+        assert!(RegexBuilder::new("a*b").size_limit(4).build().is_ok());
+        assert!(RegexBuilder::new("a*b*").size_limit(4).build().is_err());
+    }
+
+    #[test]
+    fn test_builder_engine() {
+        let mut matcher = RegexBuilder::new("[a-z]+").build_hybrid().unwrap();
+        assert_eq!(matcher.engine(), Engine::Derivatives);
+        assert!(matcher.matches("abc"));
+
+        let mut matcher = RegexBuilder::new("(?i)[a-z]+")
+            .engine(Engine::Dfa)
+            .build_hybrid()
+            .unwrap();
+        assert_eq!(matcher.engine(), Engine::Dfa);
+        assert!(matcher.matches("aBc") && !matcher.matches("a1"));
+
+        assert!(RegexBuilder::new("a{")
+            .engine(Engine::LazyDfa)
+            .build_hybrid()
+            .is_err());
+    }
+}
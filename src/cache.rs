@@ -0,0 +1,608 @@
+//! Caching the derivatives of a regex, so that each transition between derivatives is only
+//! calculated the first time it is needed, like a DFA that is built lazily.
+
+use crate::derivatives::Regex;
+use crate::minterms::Minterms;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::mem::{self, size_of};
+
+/// A matcher that caches the derivatives of a regex, numbering each distinct derivative (a state)
+/// as it is found.
+///
+/// Transitions are cached per class of characters that every literal and class in the regex treats
+/// the same (e.g., `[a-z]+\d` has one class for `[a-z]`, one for `[0-9]` and one for every other
+/// character), since the derivatives of the regex only contain its own literals and
+/// classes. A regex with predicates can tell apart characters of the same class, so its transitions
+/// are cached per character instead.
+#[derive(Debug, Clone)]
+pub(crate) struct CachedMatcher {
+    /// The states, numbered in the order they were found. An evicted state is replaced by `∅`
+    /// and its number reused.
+    states: Vec<Regex>,
+    nullable: Vec<bool>,
+    /// The approximate number of bytes each state takes, or 0 if it has been evicted.
+    sizes: Vec<usize>,
+    /// When each state was last used, as a count of the transitions looked up.
+    last_used: Vec<u64>,
+    /// The number of transitions looked up so far.
+    clock: u64,
+    /// The numbers of the evicted states, for reuse.
+    free: Vec<usize>,
+    /// The numbers of the states with each hash, so that each state is only stored once.
+    ids: HashMap<u64, Vec<usize>>,
+    hasher: RandomState,
+    /// The classes of characters, or `None` if the regex has predicates.
+    minterms: Option<Minterms>,
+    /// The state reached from a state by a class (or a character, without classes).
+    transitions: HashMap<(usize, u32), usize>,
+    /// The approximate number of bytes the states and transitions take.
+    memory: usize,
+    /// The number of bytes the cache may take before states are evicted.
+    max_memory: usize,
+    /// The number of times states have been evicted.
+    evictions: usize,
+}
+
+/// The last transition a matcher took, which the next character reuses if it is of the same class
+/// and leaves the same state.
+#[derive(Debug, Clone, Copy, Default)]
+struct Run {
+    from: usize,
+    key: Option<u32>,
+    to: usize,
+}
+
+/// The approximate number of bytes a state takes besides its nodes: its slot in each vector and
+/// its entry in the map of numbers.
+const STATE_OVERHEAD: usize = size_of::<Regex>()
+    + size_of::<bool>()
+    + size_of::<usize>() * 2
+    + size_of::<u64>()
+    + size_of::<(u64, Vec<usize>)>();
+
+/// The approximate number of bytes a cached transition takes.
+const TRANSITION_SIZE: usize = size_of::<((usize, u32), usize)>() + 1;
+
+/// The number of bytes a cache may take by default before states are evicted.
+pub(crate) const DEFAULT_MAX_MEMORY: usize = 8 * 1024 * 1024;
+
+impl CachedMatcher {
+    /// The state of the regex itself.
+    const START: usize = 0;
+
+    /// The number of characters `matches_once` reads before first checking whether caching is
+    /// worth it.
+    const GIVE_UP_AFTER: usize = 16;
+
+    /// The number of times states may be evicted during one match before the rest of the string is
+    /// matched without the cache, since evicting that often means the cache is not being reused.
+    const MAX_EVICTIONS_PER_MATCH: usize = 4;
+
+    pub(crate) fn new(regex: &Regex) -> Self {
+        Self::with_max_memory(regex, DEFAULT_MAX_MEMORY)
+    }
+
+    /// Creates a matcher whose cache takes about `max_memory` bytes at most before the least
+    /// recently used states are evicted.
+    pub(crate) fn with_max_memory(regex: &Regex, max_memory: usize) -> Self {
+        let minterms = (!regex.has_predicate()).then(|| Minterms::new(&[regex]));
+        let mut matcher = Self {
+            states: Vec::new(),
+            nullable: Vec::new(),
+            sizes: Vec::new(),
+            last_used: Vec::new(),
+            clock: 0,
+            free: Vec::new(),
+            ids: HashMap::new(),
+            hasher: RandomState::new(),
+            minterms,
+            transitions: HashMap::new(),
+            memory: 0,
+            max_memory,
+            evictions: 0,
+        };
+        matcher.state_id(regex.clone(), Self::START);
+        matcher
+    }
+
+    /// Returns the regex the matcher matches.
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.states[Self::START]
+    }
+
+    /// Empties the cache and makes it match the given regex instead, keeping its allocations.
+    pub(crate) fn reset(&mut self, regex: &Regex) {
+        self.states.clear();
+        self.nullable.clear();
+        self.sizes.clear();
+        self.last_used.clear();
+        self.clock = 0;
+        self.free.clear();
+        self.ids.clear();
+        self.minterms = (!regex.has_predicate()).then(|| Minterms::new(&[regex]));
+        self.transitions.clear();
+        self.memory = 0;
+        self.evictions = 0;
+        self.state_id(regex.clone(), Self::START);
+    }
+
+    /// Returns the number of the given state, adding it if it is new, and evicting states other
+    /// than `keep` first if the cache is full.
+    fn state_id(&mut self, regex: Regex, keep: usize) -> usize {
+        let hash = self.hasher.hash_one(&regex);
+        if let Some(ids) = self.ids.get(&hash) {
+            if let Some(&id) = ids.iter().find(|&&id| self.states[id] == regex) {
+                return id;
+            }
+        }
+
+        let size = STATE_OVERHEAD + regex.node_count() * size_of::<Regex>();
+        if self.memory + size > self.max_memory && !self.states.is_empty() {
+            self.evict(keep);
+        }
+        self.memory += size;
+        let nullable = regex.nullable();
+        let id = if let Some(id) = self.free.pop() {
+            self.states[id] = regex;
+            self.nullable[id] = nullable;
+            self.sizes[id] = size;
+            id
+        } else {
+            self.states.push(regex);
+            self.nullable.push(nullable);
+            self.sizes.push(size);
+            self.last_used.push(0);
+            self.states.len() - 1
+        };
+        self.last_used[id] = self.clock;
+        self.ids.entry(hash).or_default().push(id);
+        id
+    }
+
+    /// Evicts the least recently used half of the states other than the regex itself and `keep`,
+    /// and every transition from or to them.
+    fn evict(&mut self, keep: usize) {
+        let mut candidates: Vec<usize> = (0..self.states.len())
+            .filter(|&id| id != Self::START && id != keep && self.sizes[id] != 0)
+            .collect();
+        candidates.sort_unstable_by_key(|&id| self.last_used[id]);
+        candidates.truncate(candidates.len().div_ceil(2));
+
+        for &id in &candidates {
+            let regex = mem::replace(&mut self.states[id], Regex::Empty);
+            let hash = self.hasher.hash_one(&regex);
+            if let Some(ids) = self.ids.get_mut(&hash) {
+                ids.retain(|&other| other != id);
+                if ids.is_empty() {
+                    self.ids.remove(&hash);
+                }
+            }
+            self.memory -= self.sizes[id];
+            self.sizes[id] = 0;
+            self.free.push(id);
+        }
+
+        let sizes = &self.sizes;
+        let before = self.transitions.len();
+        self.transitions
+            .retain(|&(from, _), &mut to| sizes[from] != 0 && sizes[to] != 0);
+        self.memory -= (before - self.transitions.len()) * TRANSITION_SIZE;
+        self.evictions += 1;
+    }
+
+    /// Returns the key of the transitions taken by the character `c`.
+    fn transition_key(&self, c: char) -> u32 {
+        self.minterms.as_ref().map_or(u32::from(c), |minterms| {
+            u32::try_from(minterms.class_of(c)).unwrap_or(u32::MAX)
+        })
+    }
+
+    /// Returns the state reached from `state` by the character `c`, whose transitions have the key
+    /// `key`.
+    fn next_state(&mut self, state: usize, key: u32, c: char) -> usize {
+        self.clock += 1;
+        if let Some(&next) = self.transitions.get(&(state, key)) {
+            self.last_used[next] = self.clock;
+            return next;
+        }
+
+        // every state but the regex itself is a derivative, which is already simplified
+        let derivative = if state == Self::START {
+            self.states[state].derivative(c)
+        } else {
+            self.states[state].derivative_of_simplified(c)
+        };
+        let next = self.state_id(derivative, state);
+        self.last_used[next] = self.clock;
+        self.transitions.insert((state, key), next);
+        self.memory += TRANSITION_SIZE;
+        next
+    }
+
+    /// Returns `true` if the regex matches the given string, otherwise returns `false`.
+    pub(crate) fn matches(&mut self, s: &str) -> bool {
+        let mut state = Self::START;
+        let mut run = Run::default();
+        let evictions = self.evictions;
+        for (offset, c) in s.char_indices() {
+            if self.states[state] == Regex::Empty {
+                return false;
+            }
+            if self.evictions - evictions > Self::MAX_EVICTIONS_PER_MATCH {
+                return self.matches_uncached(state, &s[offset..]);
+            }
+            state = self.step(state, c, &mut run);
+        }
+        self.nullable[state]
+    }
+
+    /// Like `matches`, but stops caching once most characters have led to new derivatives, since
+    /// caching them then costs more than it saves (e.g., `a{2,270}`, whose derivatives are all
+    /// different), and takes the rest of the derivatives without caching them.
+    pub(crate) fn matches_once(&mut self, s: &str) -> bool {
+        let mut state = Self::START;
+        let mut run = Run::default();
+        let evictions = self.evictions;
+        let states = self.state_count();
+        for (i, (offset, c)) in s.char_indices().enumerate() {
+            if self.states[state] == Regex::Empty {
+                return false;
+            }
+            // check whether most characters so far led to new derivatives, at exponentially spaced
+            // intervals so that the check is rare
+            let gave_up = i >= Self::GIVE_UP_AFTER
+                && i.is_power_of_two()
+                && self.state_count().saturating_sub(states) > i / 2;
+            if gave_up || self.evictions - evictions > Self::MAX_EVICTIONS_PER_MATCH {
+                return self.matches_uncached(state, &s[offset..]);
+            }
+            state = self.step(state, c, &mut run);
+        }
+        self.nullable[state]
+    }
+
+    /// Returns the state reached from `state` by the character `c`, reusing the last transition
+    /// taken if `c` is of the same class and leaves the same state, so that runs of characters of
+    /// one class (e.g., the digits of `\d+`) do not look up the transition for every character.
+    fn step(&mut self, state: usize, c: char, run: &mut Run) -> usize {
+        let key = self.transition_key(c);
+        if run.from == state && run.key == Some(key) {
+            return run.to;
+        }
+        let next = self.next_state(state, key, c);
+        *run = Run {
+            from: state,
+            key: Some(key),
+            to: next,
+        };
+        next
+    }
+
+    /// Returns `true` if the string matches starting from `state`, taking derivatives without
+    /// caching them.
+    ///
+    /// Once a derivative is its own derivative with respect to a character, it is also its own
+    /// derivative with respect to every character of the same class, so the rest of a run of that
+    /// class is skipped without taking any more derivatives.
+    fn matches_uncached(&self, state: usize, s: &str) -> bool {
+        let mut current = self.states[state].clone();
+        let mut simplified = state != Self::START;
+        let mut last_key = None;
+        let mut looping_key = None;
+        for c in s.chars() {
+            if current == Regex::Empty {
+                return false;
+            }
+            let key = self.minterms.as_ref().map(|minterms| minterms.class_of(c));
+            if key.is_some() && key == looping_key {
+                continue;
+            }
+            let next = if simplified {
+                current.derivative_of_simplified(c)
+            } else {
+                current.derivative(c)
+            };
+            simplified = true;
+            // only compare with the previous derivative within a run, since that is where it pays off
+            looping_key = if key.is_some() && key == last_key && next == current {
+                key
+            } else {
+                None
+            };
+            last_key = key;
+            current = next;
+        }
+        current.nullable()
+    }
+
+    /// Returns the number of distinct derivatives in the cache.
+    pub(crate) fn state_count(&self) -> usize {
+        self.states.len() - self.free.len()
+    }
+
+    /// Returns the approximate number of bytes the cache takes.
+    pub(crate) const fn memory_usage(&self) -> usize {
+        self.memory
+    }
+}
+
+thread_local! {
+    /// The cache `Regex::matches` uses on each thread.
+    static THREAD_CACHE: RefCell<Cache> = const { RefCell::new(Cache::new()) };
+}
+
+/// Runs `f` with this thread's cache, or with a new cache if this thread's is already in use
+/// (e.g., by a predicate that matches with a regex of its own).
+pub(crate) fn with_thread_cache<R>(f: impl FnOnce(&mut Cache) -> R) -> R {
+    THREAD_CACHE.with(|cache| match cache.try_borrow_mut() {
+        Ok(mut cache) => f(&mut cache),
+        Err(_) => f(&mut Cache::new()),
+    })
+}
+
+/// Scratch space for `Regex::matches_with`, which keeps the derivatives found while matching a
+/// regex so that later calls with the same regex reuse them.
+///
+/// A cache can be used with any regex. When it is given a different regex from the last one, it
+/// is emptied but keeps its allocations. Comparing the regexes takes time proportional to their
+/// size, which is much less than taking derivatives. `Regex::matches` uses a cache of its own on
+/// each thread.
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    matcher: Option<CachedMatcher>,
+}
+
+impl Cache {
+    /// Creates an empty cache.
+    pub const fn new() -> Self {
+        Self { matcher: None }
+    }
+
+    /// Returns `true` if the cache holds the derivatives of the regex.
+    pub(crate) fn holds(&self, regex: &Regex) -> bool {
+        self.matcher
+            .as_ref()
+            .is_some_and(|matcher| matcher.regex() == regex)
+    }
+
+    /// Returns the matcher for the regex, emptying the cache first if it was for another regex.
+    pub(crate) fn matcher(&mut self, regex: &Regex) -> &mut CachedMatcher {
+        let matcher = self
+            .matcher
+            .get_or_insert_with(|| CachedMatcher::new(regex));
+        if matcher.regex() != regex {
+            matcher.reset(regex);
+        }
+        matcher
+    }
+}
+
+/// A regex matched like a DFA that is built lazily: each distinct derivative (a state) and each
+/// transition between states is only calculated the first time a string needs it, and kept for the
+/// strings after it.
+///
+/// The cache takes about as many bytes as its memory limit at most (8 MiB by default). Once it is
+/// full, the least recently used half of its states are evicted, and a string that keeps filling it
+/// is matched the rest of the way by taking derivatives without the cache. So a pattern with a huge
+/// number of derivatives (e.g., `(a|b)*a(a|b){20}`) still only takes bounded memory, and is only
+/// as slow as matching without a cache.
+#[derive(Debug, Clone)]
+pub struct LazyDfa {
+    matcher: CachedMatcher,
+}
+
+impl LazyDfa {
+    /// Creates a lazy DFA for the regex with the default memory limit of 8 MiB.
+    pub fn new(regex: &Regex) -> Self {
+        Self {
+            matcher: CachedMatcher::new(regex),
+        }
+    }
+
+    /// Creates a lazy DFA for the regex whose cache takes about `bytes` bytes at most.
+    pub fn with_memory_limit(regex: &Regex, bytes: usize) -> Self {
+        Self {
+            matcher: CachedMatcher::with_max_memory(regex, bytes),
+        }
+    }
+
+    /// Returns `true` if the regex matches the given string, otherwise returns `false`.
+    pub fn matches(&mut self, s: &str) -> bool {
+        self.matcher.matches(s)
+    }
+
+    /// Returns the number of states in the cache.
+    pub fn state_count(&self) -> usize {
+        self.matcher.state_count()
+    }
+
+    /// Returns the approximate number of bytes the cache takes.
+    pub const fn memory_usage(&self) -> usize {
+        self.matcher.memory_usage()
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Cache, CachedMatcher, LazyDfa, Regex};
+    #[allow(unused_imports)]
+    use crate::stats::count_calls;
+
+    #[test]
+    fn test_cached_matcher() {
+        let regex = Regex::new(r"[a-z]+\d{2}").unwrap();
+        let mut matcher = CachedMatcher::new(&regex);
+        for s in [
+            "ab12", "abc12", "a1", "12", "", "zz99", "zz999", "ab12", "a b12",
+        ] {
+            assert_eq!(
+                matcher.matches(s),
+                regex.derivative_str(s).nullable(),
+                "{s}"
+            );
+        }
+
+        // the derivatives are shared between strings
+        let states = matcher.state_count();
+        assert!(matcher.matches("ba21"));
+        assert_eq!(matcher.state_count(), states);
+    }
+
+    #[test]
+    fn test_cached_matcher_classes() {
+        let regex = Regex::new("(a*b*c*)*d+").unwrap();
+        let mut matcher = CachedMatcher::new(&regex);
+        assert!(!matcher.matches(&"abc".repeat(100)));
+        assert!(matcher.matches(&format!("{}d", "abc".repeat(100))));
+        assert!(matcher.matches("aaabbabbacacbacbcadddd"));
+        assert!(!matcher.matches("abcabcabccccc"));
+
+        // characters of the same class share their transitions
+        let regex = Regex::new(r"[a-z]+").unwrap();
+        let mut matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches("abcdefghijklmnopqrstuvwxyz"));
+        assert_eq!(matcher.transitions.len(), 2);
+        assert!(!matcher.matches("ab1"));
+    }
+
+    #[test]
+    fn test_cached_matcher_predicates() {
+        let vowel = Regex::pred("vowel", |c| "aeiou".contains(c));
+        let regex = Regex::concat(vowel.star(), Regex::literal('x'));
+        let mut matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches("aeix"));
+        assert!(!matcher.matches("abx"));
+        assert!(matcher.matches("uox"));
+    }
+
+    #[test]
+    fn test_cached_matcher_gives_up() {
+        // every derivative is different, so caching stops after 16 characters
+        let regex = Regex::new("a{2,270}").unwrap();
+        let mut matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches_once(&"a".repeat(269)));
+        assert_eq!(matcher.state_count(), 17);
+        assert!(!CachedMatcher::new(&regex).matches_once(&"a".repeat(271)));
+        assert!(!CachedMatcher::new(&regex).matches_once(&format!("{}b", "a".repeat(100))));
+
+        // repetitive input keeps using the cache
+        let regex = Regex::new(r"(\w+\s)*\w+").unwrap();
+        let input = "the quick brown fox ".repeat(20);
+        let mut matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches_once(&format!("{input}jumps")));
+        assert!(!CachedMatcher::new(&regex).matches_once(&input));
+        assert!(matcher.state_count() < 8);
+    }
+
+    #[test]
+    fn test_cached_matcher_runs() {
+        // a run of one class loops on a state, so only its first two characters take derivatives
+        let regex = Regex::new(r"x\d+y").unwrap();
+        let matcher = CachedMatcher::new(&regex);
+        let input = format!("x{}y", "0123456789".repeat(100));
+        let (is_match, derivative_calls, _) = count_calls(|| matcher.matches_uncached(0, &input));
+        assert!(is_match);
+        assert!(derivative_calls < 50, "{derivative_calls}");
+        assert!(!matcher.matches_uncached(0, &format!("x{}", "1".repeat(100))));
+        assert!(!matcher.matches_uncached(0, "x12a4y"));
+
+        // a run that does not loop still takes every derivative
+        let regex = Regex::new(r"\d{3,5}").unwrap();
+        let matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches_uncached(0, "1234"));
+        assert!(!matcher.matches_uncached(0, "123456"));
+
+        // predicates can tell apart characters of the same class, so runs are not skipped
+        let regex = Regex::pred("one", |c| c == '1').star();
+        let matcher = CachedMatcher::new(&regex);
+        assert!(matcher.matches_uncached(0, "111"));
+        assert!(!matcher.matches_uncached(0, "112"));
+
+        let regex = Regex::new(r"[a-z]+\d+").unwrap();
+        let mut matcher = CachedMatcher::new(&regex);
+        for s in ["abc123", "abc", "a1b2", "zzzzzzzzz9", ""] {
+            assert_eq!(
+                matcher.matches(s),
+                regex.derivative_str(s).nullable(),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lazy_dfa_memory_limit() {
+        // the derivatives remember the last 12 characters, so there are thousands of them
+        let regex = Regex::new("(a|b)*a(a|b){12}").unwrap();
+        let mut seed = 1_u64;
+        let inputs: Vec<String> = (0..200)
+            .map(|_| {
+                (0..40)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                        if seed >> 63 == 0 {
+                            'a'
+                        } else {
+                            'b'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let limit = 64 * 1024;
+        let mut dfa = LazyDfa::with_memory_limit(&regex, limit);
+        let mut unlimited = LazyDfa::new(&regex);
+        for s in &inputs {
+            assert_eq!(dfa.matches(s), regex.derivative_str(s).nullable(), "{s}");
+            assert!(dfa.memory_usage() <= limit, "{}", dfa.memory_usage());
+            unlimited.matches(s);
+        }
+        assert!(unlimited.memory_usage() > limit);
+        assert!(dfa.state_count() < unlimited.state_count());
+
+        // a cache too small for any state matches without it
+        let mut dfa = LazyDfa::with_memory_limit(&regex, 0);
+        for s in &inputs[..20] {
+            assert_eq!(dfa.matches(s), regex.derivative_str(s).nullable(), "{s}");
+        }
+        assert!(dfa.state_count() < 10);
+    }
+
+    #[test]
+    fn test_matches_with_cache() {
+        let regex = Regex::new(r"(\w+\s)*\w+").unwrap();
+        let input = "the quick brown fox ".repeat(10) + "jumps";
+        let mut cache = Cache::new();
+        assert!(regex.matches_with(&input, &mut cache));
+
+        // the second time, every transition is already in the cache
+        let (matched, derivative_calls, _) = count_calls(|| regex.matches_with(&input, &mut cache));
+        assert!(matched);
+        assert_eq!(derivative_calls, 0);
+        assert!(cache.holds(&regex));
+
+        // another regex empties the cache
+        let other = Regex::new(r"\d+").unwrap();
+        assert!(other.matches_with("12345", &mut cache));
+        assert!(!cache.holds(&regex) && cache.holds(&other));
+        assert!(!regex.matches_with(&format!("{input} "), &mut cache));
+
+        // this thread's cache is used by `matches`
+        assert!(regex.matches(&input));
+        let (_, derivative_calls, _) = count_calls(|| regex.matches("a b"));
+        assert_eq!(derivative_calls, 0);
+    }
+
+    #[test]
+    fn test_matches_in_predicate() {
+        // the predicate matches with a regex of its own while this thread's cache is in use
+        let digits = Regex::new(r"\d+").unwrap();
+        let pred = Regex::pred("digit", move |c| digits.matches(&c.to_string().repeat(20)));
+        let regex = Regex::concat(pred.plus(), Regex::literal('x'));
+        assert!(regex.matches(&format!("{}x", "1".repeat(20))));
+        assert!(!regex.matches(&format!("{}ax", "1".repeat(20))));
+    }
+}
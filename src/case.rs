@@ -0,0 +1,254 @@
+//! Case-insensitive matching, by replacing every literal and class with the set of characters that
+//! are equal to one of its characters ignoring case.
+//!
+//! By default only ASCII letters are folded. With the `unicode-case` feature, every character is
+//! folded using a table generated from the simple (one-to-one) case mappings in Unicode, so `k`
+//! also matches `K` (the Kelvin sign) and `s` also matches `ſ`.
+
+#[cfg(feature = "unicode-case")]
+mod fold_table;
+
+use crate::charset::CharSet;
+use crate::derivatives::{ensure_stack, CharRange, Node, Regex};
+
+/// Returns the case variants of every character from `start` to `end` that has any, which may
+/// include characters in the range.
+#[cfg(feature = "unicode-case")]
+fn range_case_variants(start: char, end: char) -> impl Iterator<Item = char> {
+    let from = fold_table::CASE_FOLDS.partition_point(|(c, _)| *c < start);
+    fold_table::CASE_FOLDS[from..]
+        .iter()
+        .take_while(move |(c, _)| *c <= end)
+        .flat_map(|(_, variants)| variants.iter().copied())
+}
+
+/// Returns the case variants of every ASCII letter from `start` to `end`.
+#[cfg(not(feature = "unicode-case"))]
+fn range_case_variants(start: char, end: char) -> impl Iterator<Item = char> {
+    (start..=end)
+        .take_while(char::is_ascii)
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| {
+            if c.is_ascii_lowercase() {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+}
+
+/// Returns every character in the ranges along with its case variants.
+fn fold_ranges(ranges: &[CharRange]) -> CharSet {
+    let set = CharSet::new(ranges);
+    let variants = set
+        .intervals()
+        .iter()
+        .flat_map(|&(start, end)| range_case_variants(start, end))
+        .map(|variant| (variant, variant));
+
+    CharSet::from_intervals(set.intervals().iter().copied().chain(variants).collect())
+}
+
+/// Returns a literal if the set has exactly one character, otherwise a class.
+fn literal_or_class(set: &CharSet) -> Regex {
+    match set.ranges().as_slice() {
+        [CharRange::Single(c)] => Regex::Literal(*c),
+        ranges => Regex::Class(ranges.to_vec()),
+    }
+}
+
+impl Regex {
+    /// Returns a regex that matches the same strings as this one, ignoring case (e.g., `ab` becomes
+    /// `[Aa][Bb]`).
+    ///
+    /// Predicates are left unchanged, since the characters they match are not known.
+    pub fn case_insensitive(&self) -> Self {
+        ensure_stack(|| match self {
+            Self::Empty | Self::Epsilon | Self::Pred(_) => self.clone(),
+            Self::Literal(c) => literal_or_class(&fold_ranges(&[CharRange::Single(*c)])),
+            Self::Str(s) => {
+                let folded = s
+                    .chars()
+                    .map(|c| literal_or_class(&fold_ranges(&[CharRange::Single(c)])))
+                    .collect::<Vec<_>>();
+                if folded.iter().all(|regex| matches!(regex, Self::Literal(_))) {
+                    self.clone()
+                } else {
+                    Self::concat_all(folded)
+                }
+            }
+            Self::Class(ranges) if ranges.is_empty() => self.clone(),
+            Self::Class(ranges) => literal_or_class(&fold_ranges(ranges)),
+            Self::Concat(left, right) => Self::Concat(
+                Node::new(left.case_insensitive()),
+                Node::new(right.case_insensitive()),
+            ),
+            Self::Or(left, right) => Self::Or(
+                Node::new(left.case_insensitive()),
+                Node::new(right.case_insensitive()),
+            ),
+            Self::Shuffle(left, right) => Self::Shuffle(
+                Node::new(left.case_insensitive()),
+                Node::new(right.case_insensitive()),
+            ),
+            Self::Count(inner, count) => Self::Count(Node::new(inner.case_insensitive()), *count),
+        })
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{fold_ranges, CharRange, Regex};
+
+    #[test]
+    fn test_fold_ranges() {
+        let folded = |ranges: &[CharRange]| fold_ranges(ranges).ranges();
+
+        assert_eq!(
+            folded(&[CharRange::Single('a')]),
+            vec![CharRange::Single('A'), CharRange::Single('a')]
+        );
+        assert_eq!(
+            folded(&[CharRange::Range('X', 'b')]),
+            vec![
+                CharRange::Range('A', 'B'),
+                CharRange::Range('X', 'b'),
+                CharRange::Range('x', 'z')
+            ]
+        );
+        assert_eq!(
+            folded(&[CharRange::Range('0', '9')]),
+            vec![CharRange::Range('0', '9')]
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "unicode-case"))]
+    fn test_fold_ranges_ascii_only() {
+        assert_eq!(
+            fold_ranges(&[CharRange::Single('é')]).ranges(),
+            vec![CharRange::Single('é')]
+        );
+        assert_eq!(
+            fold_ranges(&[CharRange::Single('k')]).ranges(),
+            vec![CharRange::Single('K'), CharRange::Single('k')]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-case")]
+    fn test_fold_ranges_unicode() {
+        assert_eq!(
+            fold_ranges(&[CharRange::Single('é')]).ranges(),
+            vec![CharRange::Single('É'), CharRange::Single('é')]
+        );
+        assert_eq!(
+            fold_ranges(&[CharRange::Single('k')]).ranges(),
+            vec![
+                CharRange::Single('K'),
+                CharRange::Single('k'),
+                CharRange::Single('\u{212A}')
+            ]
+        );
+        // `ß` uppercases to `SS`, which is not a simple mapping
+        assert_eq!(
+            fold_ranges(&[CharRange::Single('ß')]).ranges(),
+            vec![CharRange::Single('ß'), CharRange::Single('ẞ')]
+        );
+
+        let regex = Regex::new("(?i)[a-z]+").unwrap();
+        assert!(regex.matches("\u{212A}ſ"));
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-case")]
+    fn test_fold_table() {
+        use super::fold_table::CASE_FOLDS;
+        use std::collections::BTreeMap;
+
+        fn single_char(mut mapped: impl Iterator<Item = char>) -> Option<char> {
+            let first = mapped.next();
+            mapped.next().is_none().then_some(first).flatten()
+        }
+
+        fn root(parents: &mut [u32], mut c: u32) -> u32 {
+            while parents[c as usize] != c {
+                parents[c as usize] = parents[parents[c as usize] as usize];
+                c = parents[c as usize];
+            }
+            c
+        }
+
+        // join every character with its simple lowercase and uppercase mappings
+        let mut parents = (0..=u32::from(char::MAX)).collect::<Vec<_>>();
+        for c in '\0'..=char::MAX {
+            let mappings = [single_char(c.to_lowercase()), single_char(c.to_uppercase())];
+            for mapped in mappings.into_iter().flatten() {
+                let (c_root, mapped_root) = (
+                    root(&mut parents, u32::from(c)),
+                    root(&mut parents, u32::from(mapped)),
+                );
+                parents[c_root as usize] = mapped_root;
+            }
+        }
+
+        let mut orbits: BTreeMap<u32, Vec<char>> = BTreeMap::new();
+        for c in '\0'..=char::MAX {
+            let c_root = root(&mut parents, u32::from(c));
+            orbits.entry(c_root).or_default().push(c);
+        }
+
+        let mut expected = orbits
+            .values()
+            .filter(|orbit| orbit.len() > 1)
+            .flat_map(|orbit| {
+                orbit.iter().map(|&c| {
+                    let variants = orbit.iter().copied().filter(|&variant| variant != c);
+                    (c, variants.collect::<Vec<_>>())
+                })
+            })
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+
+        let table = CASE_FOLDS
+            .iter()
+            .map(|(c, variants)| (*c, variants.to_vec()))
+            .collect::<Vec<_>>();
+        assert!(
+            table == expected,
+            "The case fold table is out of date with the standard library's Unicode version"
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        let regex = Regex::new("ab").unwrap().case_insensitive();
+        assert_eq!(regex.to_string(), "[Aa][Bb]");
+        for s in ["ab", "AB", "aB", "Ab"] {
+            assert!(regex.matches(s), "{s}");
+        }
+        assert!(!regex.matches("ac"));
+
+        let regex = Regex::new("[a-c]+1").unwrap().case_insensitive();
+        assert!(regex.matches("aBc1"));
+        assert!(!regex.matches("aBd1"));
+
+        let regex = Regex::Class(vec![CharRange::Range('X', 'z')]).case_insensitive();
+        assert!(regex.matches("x") && regex.matches("A") && regex.matches("_"));
+
+        let regex = Regex::new("1|_").unwrap();
+        assert_eq!(regex.case_insensitive(), regex);
+    }
+
+    #[test]
+    fn test_case_insensitive_flag() {
+        let regex = Regex::new("(?i)straße|a{2}").unwrap();
+        for s in ["STRAßE", "Straße", "aA", "Aa"] {
+            assert!(regex.matches(s), "{s}");
+        }
+        assert!(!regex.matches("STRASSE"));
+
+        assert!(Regex::new("(?i)").is_err());
+        assert!(Regex::new("a(?i)b").is_err());
+    }
+}
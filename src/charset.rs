@@ -0,0 +1,437 @@
+use crate::derivatives::CharRange;
+use std::fmt::{Display, Formatter, Write};
+use std::iter::FusedIterator;
+use std::ops::RangeInclusive;
+
+/// Returns the character after `c`, skipping the surrogate range, or `None` if `c` is `char::MAX`.
+pub const fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// Returns the character before `c`, skipping the surrogate range, or `None` if `c` is `'\0'`.
+pub fn prev_char(c: char) -> Option<char> {
+    match c {
+        '\u{E000}' => Some('\u{D7FF}'),
+        _ => (c as u32).checked_sub(1).and_then(char::from_u32),
+    }
+}
+
+/// Returns the number of characters in the inclusive interval, which does not count surrogates.
+const fn interval_len(start: char, end: char) -> usize {
+    let len = end as usize - start as usize + 1;
+    if start <= '\u{D7FF}' && end >= '\u{E000}' {
+        len - 0x800
+    } else {
+        len
+    }
+}
+
+/// Returns a bitset of the ASCII characters in the intervals, with bit `c` set for each character
+/// `c` in them.
+fn ascii_bits(intervals: &[(char, char)]) -> u128 {
+    intervals
+        .iter()
+        .take_while(|&&(start, _)| start.is_ascii())
+        .fold(0, |bits, &(start, end)| {
+            let end = end.min('\x7F');
+            // the bits from `start` to `end` inclusive
+            let upper = u128::MAX >> (127 - end as u32);
+            bits | (upper & (u128::MAX << start as u32))
+        })
+}
+
+/// A set of characters, stored as sorted, non-overlapping, non-adjacent intervals.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CharSet {
+    intervals: Vec<(char, char)>,
+    /// The ASCII characters of the set as a bitset, so `contains` does not search the intervals for
+    /// them.
+    ascii: u128,
+}
+
+impl Display for CharSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_char('[')?;
+        for range in self.ranges() {
+            write!(f, "{range}")?;
+        }
+        f.write_char(']')
+    }
+}
+
+impl CharSet {
+    /// Creates a set containing every character in the given character class ranges.
+    pub fn new(ranges: &[CharRange]) -> Self {
+        Self::from_intervals(
+            ranges
+                .iter()
+                .map(|range| match range {
+                    CharRange::Single(c) => (*c, *c),
+                    CharRange::Range(start, end) => (*start, *end),
+                })
+                .collect(),
+        )
+    }
+
+    /// Creates a set from inclusive `(start, end)` intervals in any order, merging any that overlap
+    /// or touch and dropping any with `start > end`.
+    pub(crate) fn from_intervals(mut intervals: Vec<(char, char)>) -> Self {
+        intervals.retain(|(start, end)| start <= end);
+        intervals.sort_unstable();
+
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(intervals.len());
+        for (start, end) in intervals {
+            match merged.last_mut() {
+                Some((_, last_end)) if next_char(*last_end).map_or(true, |next| start <= next) => {
+                    *last_end = (*last_end).max(end);
+                }
+                _ => merged.push((start, end)),
+            }
+        }
+
+        Self::from_sorted(merged)
+    }
+
+    /// Creates a set from intervals that are already sorted, non-overlapping and non-adjacent.
+    fn from_sorted(intervals: Vec<(char, char)>) -> Self {
+        Self {
+            ascii: ascii_bits(&intervals),
+            intervals,
+        }
+    }
+
+    /// Returns the sorted, non-overlapping, non-adjacent inclusive `(start, end)` intervals of the set.
+    pub(crate) fn intervals(&self) -> &[(char, char)] {
+        &self.intervals
+    }
+
+    /// Returns `true` if the set contains no characters, otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Returns the number of characters in the set.
+    pub fn len(&self) -> usize {
+        self.intervals
+            .iter()
+            .map(|&(start, end)| interval_len(start, end))
+            .sum()
+    }
+
+    /// Returns an iterator over the characters in the set, in order.
+    pub fn iter_chars(&self) -> CharSetChars<'_> {
+        CharSetChars {
+            intervals: self.intervals.iter(),
+            current: None,
+            remaining: self.len(),
+        }
+    }
+
+    /// Returns an iterator over the sorted, non-overlapping, non-adjacent inclusive ranges of the
+    /// set (e.g., `'a'..='c'` and `'x'..='x'` for `[a-cx]`).
+    pub fn iter_ranges(&self) -> impl ExactSizeIterator<Item = RangeInclusive<char>> + '_ {
+        self.intervals.iter().map(|&(start, end)| start..=end)
+    }
+
+    /// Returns `true` if the set contains the given character, otherwise returns `false`.
+    pub fn contains(&self, c: char) -> bool {
+        if c.is_ascii() {
+            return self.ascii & (1 << c as u32) != 0;
+        }
+        self.intervals
+            .binary_search_by(|(start, end)| {
+                if *end < c {
+                    std::cmp::Ordering::Less
+                } else if *start > c {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    /// Returns the set as sorted character class ranges.
+    pub fn ranges(&self) -> Vec<CharRange> {
+        self.intervals
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    CharRange::Single(start)
+                } else {
+                    CharRange::Range(start, end)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the characters that are in `self`, `other`, or both (e.g., `[a-cb-d]` is `[a-d]`).
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_intervals(
+            self.intervals
+                .iter()
+                .chain(&other.intervals)
+                .copied()
+                .collect(),
+        )
+    }
+
+    /// Returns the characters that are not in the set (e.g., the complement of `[b-y]` is
+    /// `[\0-az-\u{10FFFF}]`).
+    pub fn complement(&self) -> Self {
+        Self::from_sorted(vec![('\0', char::MAX)]).difference(self)
+    }
+
+    /// Returns the characters that are in `self` but not in `other` (e.g., `[a-z--[aeiou]]`).
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut result = Vec::new();
+
+        for &(start, end) in &self.intervals {
+            let mut current = Some(start);
+            for &(other_start, other_end) in &other.intervals {
+                let Some(from) = current else { break };
+                if other_end < from || other_start > end {
+                    continue;
+                }
+                if other_start > from {
+                    result.push((from, prev_char(other_start).unwrap()));
+                }
+                current = next_char(other_end).filter(|next| *next <= end);
+            }
+            if let Some(from) = current {
+                result.push((from, end));
+            }
+        }
+
+        Self::from_sorted(result)
+    }
+
+    /// Returns the characters that are in both `self` and `other` (e.g., `[a-z&&[x-~]]`).
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut result = Vec::new();
+
+        for &(start, end) in &self.intervals {
+            for &(other_start, other_end) in &other.intervals {
+                let from = start.max(other_start);
+                let to = end.min(other_end);
+                if from <= to {
+                    result.push((from, to));
+                }
+            }
+        }
+
+        Self::from_intervals(result)
+    }
+}
+
+/// An iterator over the characters in a `CharSet`, returned by `CharSet::iter_chars`.
+#[derive(Debug, Clone)]
+pub struct CharSetChars<'a> {
+    intervals: std::slice::Iter<'a, (char, char)>,
+    /// The rest of the interval being iterated over.
+    current: Option<RangeInclusive<char>>,
+    remaining: usize,
+}
+
+impl Iterator for CharSetChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            // `RangeInclusive<char>` skips surrogates
+            if let Some(c) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(c);
+            }
+            let &(start, end) = self.intervals.next()?;
+            self.current = Some(start..=end);
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for CharSetChars<'_> {}
+
+impl FusedIterator for CharSetChars<'_> {}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn test_charset_new_merges_ranges() {
+        let set = CharSet::new(&[
+            CharRange::Range('d', 'f'),
+            CharRange::Single('a'),
+            CharRange::Range('b', 'c'),
+            CharRange::Range('e', 'h'),
+            CharRange::Single('z'),
+        ]);
+        assert_eq!(
+            set.ranges(),
+            vec![CharRange::Range('a', 'h'), CharRange::Single('z')]
+        );
+        assert!(set.contains('a'));
+        assert!(set.contains('g'));
+        assert!(!set.contains('i'));
+        assert!(set.contains('z'));
+        assert_eq!(set.to_string(), "[a-hz]");
+    }
+
+    #[test]
+    fn test_charset_difference() {
+        let set = CharSet::new(&[CharRange::Range('a', 'z')]).difference(&CharSet::new(&[
+            CharRange::Single('a'),
+            CharRange::Range('m', 'o'),
+        ]));
+        assert_eq!(
+            set.ranges(),
+            vec![CharRange::Range('b', 'l'), CharRange::Range('p', 'z')]
+        );
+
+        let set = CharSet::new(&[CharRange::Range('a', 'c')])
+            .difference(&CharSet::new(&[CharRange::Range('a', 'z')]));
+        assert!(set.is_empty());
+
+        // surrogates are skipped at the boundaries
+        let set = CharSet::new(&[CharRange::Range('\u{D000}', '\u{F000}')])
+            .difference(&CharSet::new(&[CharRange::Range('\u{E000}', '\u{EFFF}')]));
+        assert_eq!(
+            set.ranges(),
+            vec![
+                CharRange::Range('\u{D000}', '\u{D7FF}'),
+                CharRange::Single('\u{F000}')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_charset_intersection() {
+        let set = CharSet::new(&[CharRange::Range('a', 'm'), CharRange::Single('z')])
+            .intersection(&CharSet::new(&[CharRange::Range('k', 'z')]));
+        assert_eq!(
+            set.ranges(),
+            vec![CharRange::Range('k', 'm'), CharRange::Single('z')]
+        );
+
+        let set = CharSet::new(&[CharRange::Range('a', 'c')])
+            .intersection(&CharSet::new(&[CharRange::Single('d')]));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_charset_union() {
+        let set = CharSet::new(&[CharRange::Range('a', 'c'), CharRange::Single('x')]).union(
+            &CharSet::new(&[CharRange::Range('b', 'd'), CharRange::Single('f')]),
+        );
+        assert_eq!(
+            set.ranges(),
+            vec![
+                CharRange::Range('a', 'd'),
+                CharRange::Single('f'),
+                CharRange::Single('x')
+            ]
+        );
+
+        // adjacent intervals are merged
+        let set =
+            CharSet::new(&[CharRange::Single('a')]).union(&CharSet::new(&[CharRange::Single('b')]));
+        assert_eq!(set.ranges(), vec![CharRange::Range('a', 'b')]);
+
+        let set = CharSet::default().union(&CharSet::default());
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_charset_complement() {
+        let set = CharSet::new(&[CharRange::Range('b', 'y')]).complement();
+        assert_eq!(
+            set.ranges(),
+            vec![
+                CharRange::Range('\0', 'a'),
+                CharRange::Range('z', char::MAX)
+            ]
+        );
+        assert!(!set.contains('m'));
+        assert!(set.contains('\u{E000}'));
+        assert_eq!(
+            set.complement(),
+            CharSet::new(&[CharRange::Range('b', 'y')])
+        );
+
+        assert_eq!(
+            CharSet::default().complement().ranges(),
+            vec![CharRange::Range('\0', char::MAX)]
+        );
+        assert!(CharSet::default().complement().complement().is_empty());
+    }
+
+    #[test]
+    fn test_charset_iter_chars() {
+        let set = CharSet::new(&[CharRange::Range('a', 'c'), CharRange::Single('x')]);
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.iter_chars().collect::<String>(), "abcx");
+
+        let mut chars = set.iter_chars();
+        assert_eq!(chars.len(), 4);
+        chars.next();
+        assert_eq!(chars.size_hint(), (3, Some(3)));
+        assert_eq!(chars.by_ref().count(), 3);
+        assert_eq!(chars.next(), None);
+
+        assert_eq!(CharSet::default().len(), 0);
+        assert_eq!(CharSet::default().iter_chars().next(), None);
+
+        // surrogates are not characters
+        let set = CharSet::new(&[CharRange::Range('\u{D7FE}', '\u{E001}')]);
+        assert_eq!(set.len(), 4);
+        assert_eq!(
+            set.iter_chars().collect::<Vec<_>>(),
+            vec!['\u{D7FE}', '\u{D7FF}', '\u{E000}', '\u{E001}']
+        );
+        assert_eq!(CharSet::default().complement().len(), 0x110000 - 0x800);
+    }
+
+    #[test]
+    fn test_charset_iter_ranges() {
+        let set = CharSet::new(&[
+            CharRange::Single('x'),
+            CharRange::Range('a', 'c'),
+            CharRange::Single('d'),
+        ]);
+        let ranges = set.iter_ranges();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges.collect::<Vec<_>>(), vec!['a'..='d', 'x'..='x']);
+    }
+
+    #[test]
+    fn test_charset_contains_around_ascii() {
+        let set = CharSet::new(&[
+            CharRange::Single('\0'),
+            CharRange::Range('a', 'c'),
+            CharRange::Range('~', '\u{80}'),
+        ]);
+        let members: Vec<char> = ('\0'..='\u{100}').filter(|&c| set.contains(c)).collect();
+        assert_eq!(members, vec!['\0', 'a', 'b', 'c', '~', '\x7F', '\u{80}']);
+
+        let complement = set.complement();
+        assert!(!complement.contains('\0'));
+        assert!(complement.contains('\x01'));
+        assert!(!complement.contains('\x7F'));
+        assert!(!complement.contains('\u{80}'));
+        assert!(complement.contains('\u{81}'));
+
+        let ascii = CharSet::new(&[CharRange::Range('\0', '\x7F')]);
+        assert!(('\0'..='\x7F').all(|c| ascii.contains(c)));
+        assert!(!ascii.contains('\u{80}'));
+        assert!(!ascii.complement().contains('\x7F'));
+        assert!(!set.difference(&ascii).contains('~'));
+        assert!(set.difference(&ascii).contains('\u{80}'));
+    }
+}
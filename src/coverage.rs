@@ -0,0 +1,409 @@
+//! Coverage of the alternatives in a regex by a set of sample strings, for checking that test
+//! fixtures exercise every alternative of a complex pattern.
+//!
+//! A sample exercises an alternative if it matches the regex in a way that goes through the
+//! alternative. The alternatives are those of the regex after it has been simplified, so `cat|car`
+//! has the alternatives `t` and `r` of `ca(r|t)`.
+
+use crate::derivatives::{Count, Node, Regex};
+use std::fmt::{Display, Formatter};
+use std::iter::once;
+
+/// An alternative in one of the alternations of a regex, and the samples that exercised it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlternativeCoverage {
+    /// The index of the alternation the alternative is in, counting alternations in the order
+    /// their first alternative appears in the regex.
+    pub alternation: usize,
+    /// The alternative.
+    pub regex: Regex,
+    /// The indices of the samples that exercised the alternative.
+    pub samples: Vec<usize>,
+}
+
+impl AlternativeCoverage {
+    /// Returns `true` if at least one sample exercised the alternative, otherwise returns `false`.
+    pub fn is_covered(&self) -> bool {
+        !self.samples.is_empty()
+    }
+}
+
+/// The alternatives of a regex exercised by a set of samples, returned by `Regex::coverage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Every alternative of the regex, in the order they appear in it.
+    pub alternatives: Vec<AlternativeCoverage>,
+    /// The indices of the samples that did not match the regex.
+    pub unmatched: Vec<usize>,
+}
+
+impl CoverageReport {
+    /// Returns the alternatives that no sample exercised.
+    pub fn uncovered(&self) -> impl Iterator<Item = &AlternativeCoverage> {
+        self.alternatives
+            .iter()
+            .filter(|alternative| !alternative.is_covered())
+    }
+
+    /// Returns `true` if every alternative was exercised by at least one sample, otherwise returns
+    /// `false`.
+    pub fn is_complete(&self) -> bool {
+        self.uncovered().next().is_none()
+    }
+}
+
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for alternative in &self.alternatives {
+            write!(
+                f,
+                "alternation {}, alternative {}: ",
+                alternative.alternation, alternative.regex
+            )?;
+            if alternative.is_covered() {
+                let samples = alternative
+                    .samples
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                writeln!(f, "covered by samples {}", samples.join(", "))?;
+            } else {
+                writeln!(f, "not covered")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// An alternative that a string matched a regex through, returned by `Regex::branches_taken`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchTaken {
+    /// The index of the alternation the alternative is in, counted as in `AlternativeCoverage`.
+    pub alternation: usize,
+    /// The index of the alternative in its alternation, from the left, after simplification (which
+    /// may reorder the alternatives of a pattern).
+    pub branch: usize,
+    /// The alternative.
+    pub regex: Regex,
+}
+
+/// An alternative found in a regex, with the path of child indices leading to it.
+struct Alternative<'a> {
+    alternation: usize,
+    branch: usize,
+    path: Vec<usize>,
+    regex: &'a Regex,
+}
+
+/// Appends the alternatives of the alternation (a chain of `Or`s) at `path` to `branches`.
+fn collect_branches<'a>(
+    regex: &'a Regex,
+    path: &mut Vec<usize>,
+    branches: &mut Vec<(Vec<usize>, &'a Regex)>,
+) {
+    if let Regex::Or(left, right) = regex {
+        for (index, child) in [left, right].into_iter().enumerate() {
+            path.push(index);
+            collect_branches(child, path, branches);
+            path.pop();
+        }
+    } else {
+        branches.push((path.clone(), regex));
+    }
+}
+
+/// Appends the alternatives of every alternation in the regex at `path` to `alternatives`, each
+/// followed by the alternatives nested inside it.
+fn collect_alternatives<'a>(
+    regex: &'a Regex,
+    path: &mut Vec<usize>,
+    alternatives: &mut Vec<Alternative<'a>>,
+    alternations: &mut usize,
+) {
+    let children = match regex {
+        Regex::Or(_, _) => {
+            let alternation = *alternations;
+            *alternations += 1;
+
+            let mut branches = Vec::new();
+            collect_branches(regex, path, &mut branches);
+            for (index, (mut branch_path, branch)) in branches.into_iter().enumerate() {
+                alternatives.push(Alternative {
+                    alternation,
+                    branch: index,
+                    path: branch_path.clone(),
+                    regex: branch,
+                });
+                collect_alternatives(branch, &mut branch_path, alternatives, alternations);
+            }
+            return;
+        }
+        Regex::Concat(left, right) | Regex::Shuffle(left, right) => vec![left, right],
+        Regex::Count(inner, _) => vec![inner],
+        _ => return,
+    };
+
+    for (index, child) in children.into_iter().enumerate() {
+        path.push(index);
+        collect_alternatives(child, path, alternatives, alternations);
+        path.pop();
+    }
+}
+
+/// Returns a regex matching the strings that `inner` repeated `count` times matches with at least
+/// one repetition matched by `through`, a regex matching a subset of what `inner` matches.
+fn count_through(inner: &Regex, count: Count, through: Regex) -> Regex {
+    let repeat = |count| Regex::Count(Node::new(inner.clone()), count);
+    let (min, max) = count.bounds();
+
+    // the repetition matched by `through` is preceded by `before` repetitions
+    if let Some(max) = max {
+        return Regex::or_all((0..max).map(|before| {
+            let after = Count::Range(min.saturating_sub(before + 1), max - before - 1);
+            Regex::concat_all([repeat(Count::Exact(before)), through.clone(), repeat(after)])
+        }));
+    }
+
+    // with no maximum, every `before` from `min - 1` onwards can be followed by any number
+    let min = min.saturating_sub(1);
+    Regex::or_all(
+        (0..min)
+            .map(|before| {
+                let after = Count::AtLeast(min - before);
+                Regex::concat_all([repeat(Count::Exact(before)), through.clone(), repeat(after)])
+            })
+            .chain(once(Regex::concat_all([
+                repeat(Count::AtLeast(min)),
+                through.clone(),
+                repeat(Count::AtLeast(0)),
+            ]))),
+    )
+}
+
+/// Returns a regex matching the strings that the regex matches in a way that goes through the
+/// subexpression at `path`.
+fn through(regex: &Regex, path: &[usize]) -> Regex {
+    let Some((&index, rest)) = path.split_first() else {
+        return regex.clone();
+    };
+
+    match (regex, index) {
+        (Regex::Concat(left, right), 0) => {
+            Regex::concat(through(left, rest), right.as_ref().clone())
+        }
+        (Regex::Concat(left, right), _) => {
+            Regex::concat(left.as_ref().clone(), through(right, rest))
+        }
+        (Regex::Or(left, _), 0) => through(left, rest),
+        (Regex::Or(_, right), _) => through(right, rest),
+        (Regex::Shuffle(left, right), 0) => through(left, rest).shuffle(right),
+        (Regex::Shuffle(left, right), _) => left.shuffle(&through(right, rest)),
+        (Regex::Count(inner, count), _) => count_through(inner, *count, through(inner, rest)),
+        _ => unreachable!("paths only lead through regexes with children"),
+    }
+}
+
+/// Returns every alternative of the regex, in the order they appear in it.
+fn alternatives(regex: &Regex) -> Vec<Alternative<'_>> {
+    let mut alternatives = Vec::new();
+    collect_alternatives(regex, &mut Vec::new(), &mut alternatives, &mut 0);
+    alternatives
+}
+
+impl Regex {
+    /// Returns the alternatives that the string matches the regex through, in the order they
+    /// appear in it, or nothing if the string does not match (e.g., `ab` matches `(a|b)(b|c)`
+    /// through branch 0 of alternation 0 and branch 0 of alternation 1).
+    ///
+    /// A string that can be matched in more than one way takes every alternative that any of those
+    /// ways goes through. To route on patterns that simplification could merge (e.g., `cat|car`,
+    /// which becomes `ca(r|t)`), match them with a `RegexSet` instead.
+    pub fn branches_taken(&self, s: &str) -> Vec<BranchTaken> {
+        if !self.matches(s) {
+            return Vec::new();
+        }
+
+        alternatives(self)
+            .into_iter()
+            .filter(|alternative| through(self, &alternative.path).matches(s))
+            .map(|alternative| BranchTaken {
+                alternation: alternative.alternation,
+                branch: alternative.branch,
+                regex: alternative.regex.clone(),
+            })
+            .collect()
+    }
+
+    /// Reports which alternatives of the regex's alternations were exercised by the given samples,
+    /// and which samples did not match at all.
+    ///
+    /// A sample that can be matched in more than one way exercises every alternative that any of
+    /// those ways goes through.
+    pub fn coverage(&self, samples: &[&str]) -> CoverageReport {
+        let alternatives = alternatives(self);
+
+        let matching = |regex: &Self, matches: bool| {
+            samples
+                .iter()
+                .enumerate()
+                .filter(|(_, sample)| regex.matches(sample) == matches)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>()
+        };
+
+        CoverageReport {
+            alternatives: alternatives
+                .into_iter()
+                .map(|alternative| AlternativeCoverage {
+                    alternation: alternative.alternation,
+                    regex: alternative.regex.clone(),
+                    samples: matching(&through(self, &alternative.path), true),
+                })
+                .collect(),
+            unmatched: matching(self, false),
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{AlternativeCoverage, BranchTaken, Regex};
+
+    #[allow(dead_code)]
+    fn covered(regex: &str, samples: &[&str]) -> Vec<(String, Vec<usize>)> {
+        Regex::new(regex)
+            .unwrap()
+            .coverage(samples)
+            .alternatives
+            .into_iter()
+            .map(|alternative| (alternative.regex.to_string(), alternative.samples))
+            .collect()
+    }
+
+    #[test]
+    fn test_coverage() {
+        let regex = Regex::new("(cat|dog)s?").unwrap();
+        let report = regex.coverage(&["cats", "cat", "bird"]);
+        assert_eq!(
+            report.alternatives,
+            vec![
+                AlternativeCoverage {
+                    alternation: 0,
+                    regex: Regex::new("cat").unwrap(),
+                    samples: vec![0, 1],
+                },
+                AlternativeCoverage {
+                    alternation: 0,
+                    regex: Regex::new("dog").unwrap(),
+                    samples: vec![],
+                },
+            ]
+        );
+        assert_eq!(report.unmatched, vec![2]);
+        assert!(!report.is_complete());
+        assert_eq!(
+            report
+                .uncovered()
+                .map(|a| a.regex.to_string())
+                .collect::<Vec<_>>(),
+            vec!["dog"]
+        );
+        assert_eq!(
+            report.to_string(),
+            "alternation 0, alternative cat: covered by samples 0, 1\n\
+             alternation 0, alternative dog: not covered\n"
+        );
+
+        assert!(regex.coverage(&["cat", "dogs"]).is_complete());
+    }
+
+    #[allow(dead_code)]
+    fn taken(regex: &str, s: &str) -> Vec<(usize, usize, String)> {
+        Regex::new(regex)
+            .unwrap()
+            .branches_taken(s)
+            .into_iter()
+            .map(|taken| (taken.alternation, taken.branch, taken.regex.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_branches_taken() {
+        let regex = Regex::new(r"GET /users|POST /users/\d+").unwrap();
+        assert_eq!(
+            regex.branches_taken("POST /users/12"),
+            vec![BranchTaken {
+                alternation: 0,
+                // simplification sorts the alternatives
+                branch: 0,
+                regex: Regex::new(r"POST /users/\d+").unwrap(),
+            }]
+        );
+        assert_eq!(regex.branches_taken("PUT /users"), vec![]);
+
+        assert_eq!(
+            taken("(a|b)(b|c)", "ab"),
+            vec![(0, 0, "a".to_string()), (1, 0, "b".to_string())]
+        );
+        assert_eq!(
+            taken("x(a|(b|c)d)", "xcd"),
+            vec![(0, 1, "(b|c)d".to_string()), (1, 1, "c".to_string())]
+        );
+
+        // every way of matching counts
+        assert_eq!(
+            taken("a*|(a|b)+", "aa"),
+            vec![
+                (0, 0, "(a)*".to_string()),
+                (0, 1, "((a|b))+".to_string()),
+                (1, 0, "a".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_nested() {
+        assert_eq!(
+            covered("x(a|(b|c)d)", &["xa", "xbd"]),
+            vec![
+                ("a".to_string(), vec![0]),
+                ("(b|c)d".to_string(), vec![1]),
+                ("b".to_string(), vec![1]),
+                ("c".to_string(), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coverage_repeated() {
+        // each repetition can take a different alternative
+        assert_eq!(
+            covered("(ab|cd)*", &["abcd", "", "abab"]),
+            vec![("ab".to_string(), vec![0, 2]), ("cd".to_string(), vec![0])]
+        );
+        assert_eq!(
+            covered("(a|b){2,3}x", &["aax", "abax", "bx", "bbbbx"]),
+            vec![("a".to_string(), vec![0, 1]), ("b".to_string(), vec![1])]
+        );
+        assert_eq!(
+            covered("(a|b){2,}", &["aaab", "b"]),
+            vec![("a".to_string(), vec![0]), ("b".to_string(), vec![0])]
+        );
+    }
+
+    #[test]
+    fn test_coverage_ambiguous() {
+        // `b` can be matched through either `b`
+        assert_eq!(
+            covered("(a|b)*(b|c)*", &["b", "c"]),
+            vec![
+                ("a".to_string(), vec![]),
+                ("b".to_string(), vec![0]),
+                ("b".to_string(), vec![0]),
+                ("c".to_string(), vec![1]),
+            ]
+        );
+        assert!(Regex::new("abc").unwrap().coverage(&[]).is_complete());
+    }
+}
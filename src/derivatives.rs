@@ -1,11 +1,129 @@
+use crate::cache::{with_thread_cache, Cache};
+use crate::charset::{next_char, CharSet};
+use crate::minterms::Minterms;
 use crate::parser::parse_string_to_regex;
-use std::fmt::{Debug, Display, Formatter};
+use crate::stats::{
+    count_calls, measure_simplify_time, record_derivative_call, record_simplify_call,
+    time_simplify, EvalStats, MatchStats,
+};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display, Formatter, Write};
+use std::hash::{Hash, Hasher};
+use std::mem::discriminant;
+use std::ops::{Add, BitOr, Deref, Mul};
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
 
 pub const CLASS_ESCAPE_CHARS: &[char] = &['[', ']', '-', '\\'];
 pub const NON_CLASS_ESCAPE_CHARS: &[char] =
     &['[', ']', '(', ')', '{', '}', '?', '*', '+', '|', '\\', '.'];
 
-fn escape_regex_char(c: char, in_class: bool) -> String {
+/// The number of nodes `simplify` compares structurally before falling back to comparing hashes.
+const SIMPLIFY_EQ_BUDGET: usize = 64;
+
+/// The number of regexes a concatenation or alternation must have for `rebalance` to rebuild it
+/// into a balanced tree.
+const REBALANCE_MIN_LEN: usize = 8;
+
+/// The amount of stack left below which recursion over a regex continues on a new stack segment,
+/// which must be enough for `STACK_CHECK_INTERVAL` levels of recursion.
+const STACK_RED_ZONE: usize = 256 * 1024;
+
+/// The size of each stack segment allocated for recursion over deeply nested regexes.
+const STACK_SEGMENT_SIZE: usize = 2 * 1024 * 1024;
+
+/// The number of levels of recursion between checks of how much stack is left, since checking on
+/// every level noticeably slows down matching.
+const STACK_CHECK_INTERVAL: usize = 16;
+
+/// The length in bytes below which `matches` takes derivatives without caching them, since setting
+/// up the cache would take longer than matching.
+const SHORT_INPUT_LEN: usize = 16;
+
+/// The number of inner regexes of counts whose derivatives are kept on each thread before they are
+/// all forgotten, and the number of characters kept for each.
+const MAX_COUNT_RESIDUALS: usize = 256;
+
+/// The derivatives of a regex with respect to each character, and whether it is nullable.
+type Residuals = HashMap<char, (Regex, bool)>;
+
+thread_local! {
+    static RECURSION_DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    /// The derivatives of the inner regexes of counts with respect to each character, and whether
+    /// each inner regex is nullable (see `count_residual`).
+    static COUNT_RESIDUALS: RefCell<HashMap<Regex, Residuals>> = RefCell::new(HashMap::new());
+}
+
+/// Runs `f`, first switching to a new stack segment on the heap if the stack is nearly full.
+pub(crate) fn grow_stack_if_low<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, f)
+}
+
+/// Runs `f` as one level of recursion over a regex, checking every `STACK_CHECK_INTERVAL` levels
+/// whether to switch to a new stack segment on the heap, so that recursion over deeply nested
+/// regexes (e.g., long concatenations) cannot overflow the stack.
+#[inline]
+pub(crate) fn ensure_stack<R>(f: impl FnOnce() -> R) -> R {
+    let depth = RECURSION_DEPTH.get();
+    RECURSION_DEPTH.set(depth + 1);
+    let result = if depth % STACK_CHECK_INTERVAL == 0 {
+        grow_stack_if_low(f)
+    } else {
+        f()
+    };
+    RECURSION_DEPTH.set(depth);
+    result
+}
+
+/// Returns the derivative of the inner regex `r` of a count with respect to `c`, and whether `r` is
+/// nullable, taking it with `derivative` only the first time on this thread.
+///
+/// `D_c(r{n,m}) = D_c(r)r{n-1,m-1}`, so matching `r{n,m}` takes the same `D_c(r)` at the start of
+/// every repetition, for every `n` and `m` (e.g., `(abc){1,500}` takes `D_a(abc)` up to 500 times),
+/// and it is simplified again each time. Regexes that match a single character or a string are not
+/// kept, since their derivatives are quicker to take than to look up.
+fn count_residual(r: &Regex, c: char, derivative: impl FnOnce() -> (Regex, bool)) -> (Regex, bool) {
+    if !matches!(
+        r,
+        Regex::Concat(_, _) | Regex::Or(_, _) | Regex::Count(_, _) | Regex::Shuffle(_, _)
+    ) {
+        return derivative();
+    }
+
+    let residual = COUNT_RESIDUALS.with_borrow(|residuals| {
+        residuals
+            .get(r)
+            .and_then(|by_char| by_char.get(&c))
+            .cloned()
+    });
+    if let Some(residual) = residual {
+        return residual;
+    }
+
+    // the derivative is taken without borrowing the table, since the derivatives of counts inside
+    // `r` use it too
+    let residual = derivative();
+    COUNT_RESIDUALS.with_borrow_mut(|residuals| {
+        if residuals.len() >= MAX_COUNT_RESIDUALS {
+            residuals.clear();
+        }
+        let by_char = residuals.entry(r.clone()).or_default();
+        if by_char.len() >= MAX_COUNT_RESIDUALS {
+            by_char.clear();
+        }
+        by_char.insert(c, residual.clone());
+    });
+    residual
+}
+
+/// Writes the character, escaped with a backslash if it has a meaning in a pattern (inside a
+/// character class if `in_class`).
+fn write_escaped(f: &mut Formatter<'_>, c: char, in_class: bool) -> std::fmt::Result {
     let to_escape = if in_class {
         CLASS_ESCAPE_CHARS
     } else {
@@ -13,14 +131,13 @@ fn escape_regex_char(c: char, in_class: bool) -> String {
     };
 
     if to_escape.contains(&c) {
-        format!("\\{c}")
-    } else {
-        c.to_string()
+        f.write_char('\\')?;
     }
+    f.write_char(c)
 }
 
 /// A struct that represents a set of characters to be matched in a character class.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum CharRange {
     /// A single character (e.g., `a`).
     Single(char),
@@ -31,13 +148,12 @@ pub enum CharRange {
 impl Display for CharRange {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Single(c) => write!(f, "{}", escape_regex_char(*c, true)),
-            Self::Range(start, end) => write!(
-                f,
-                "{}-{}",
-                escape_regex_char(*start, true),
-                escape_regex_char(*end, true)
-            ),
+            Self::Single(c) => write_escaped(f, *c, true),
+            Self::Range(start, end) => {
+                write_escaped(f, *start, true)?;
+                f.write_char('-')?;
+                write_escaped(f, *end, true)
+            }
         }
     }
 }
@@ -53,7 +169,7 @@ impl CharRange {
 }
 
 /// An enum that represents the number of times a regex can match.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Count {
     /// The regex must match exactly `n` times.
     Exact(usize),
@@ -87,8 +203,393 @@ impl Display for Count {
     }
 }
 
+impl Count {
+    /// Returns the minimum and maximum (if any) number of repetitions allowed by the count.
+    pub(crate) const fn bounds(self) -> (usize, Option<usize>) {
+        match self {
+            Self::Exact(n) => (n, Some(n)),
+            Self::Range(min, max) => (min, Some(max)),
+            Self::AtLeast(min) => (min, None),
+        }
+    }
+
+    /// Returns the count allowing between `min` and `max` (if any) repetitions.
+    pub(crate) const fn from_bounds(min: usize, max: Option<usize>) -> Self {
+        match max {
+            None => Self::AtLeast(min),
+            Some(max) if max == min => Self::Exact(min),
+            Some(max) => Self::Range(min, max),
+        }
+    }
+
+    /// Merges the counts wherever the numbers of repetitions they allow overlap or touch (e.g.,
+    /// `{2,4}` and `{5}` into `{2,5}`), in order of their minimum.
+    pub(crate) fn merge(counts: Vec<Self>) -> Vec<Self> {
+        let mut bounds: Vec<_> = counts.into_iter().map(Self::bounds).collect();
+        bounds.sort_unstable_by_key(|&(min, _)| min);
+        let mut merged: Vec<(usize, Option<usize>)> = Vec::new();
+        for (min, max) in bounds {
+            match merged.last_mut() {
+                Some((_, last_max))
+                    if last_max.map_or(true, |last_max| min <= last_max.saturating_add(1)) =>
+                {
+                    *last_max = last_max.zip(max).map(|(last_max, max)| last_max.max(max));
+                }
+                _ => merged.push((min, max)),
+            }
+        }
+        merged
+            .into_iter()
+            .map(|(min, max)| Self::from_bounds(min, max))
+            .collect()
+    }
+
+    /// Returns the count allowed by repeating `inner` repetitions `outer` times (e.g., `{6,15}` for
+    /// `{2,5}` repeated 3 times), or `None` if some numbers of repetitions in between are not
+    /// allowed (e.g., `{2}` repeated once or twice allows only 2 or 4).
+    pub(crate) fn nest(inner: Self, outer: Self) -> Option<Self> {
+        let (inner_min, inner_max) = inner.bounds();
+        let (outer_min, outer_max) = outer.bounds();
+
+        // `k` inner counts allow between `k * inner_min` and `k * inner_max` repetitions, and if
+        // those for `outer_min` and `outer_min + 1` overlap or touch then so do those for any more
+        if outer_max != Some(outer_min) {
+            let touches = match inner_max {
+                None => outer_min > 0 || inner_min <= 1,
+                Some(inner_max) => {
+                    outer_min.checked_add(1)?.checked_mul(inner_min)?
+                        <= outer_min.checked_mul(inner_max)?.checked_add(1)?
+                }
+            };
+            if !touches {
+                return None;
+            }
+        }
+
+        let min = inner_min.checked_mul(outer_min)?;
+        let max = match (inner_max, outer_max) {
+            (Some(0), _) | (_, Some(0)) => Some(0),
+            (Some(inner_max), Some(outer_max)) => Some(inner_max.checked_mul(outer_max)?),
+            _ => None,
+        };
+        Some(Self::from_bounds(min, max))
+    }
+}
+
+/// How much effort to spend simplifying regexes, such as after taking a derivative.
+///
+/// More simplification keeps derivatives small, but costs time on every step, so the best level
+/// depends on the patterns being matched. The `simplify_levels` benchmark compares `Basic` and
+/// `Aggressive` on the bundled patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SimplifyLevel {
+    /// Apply no simplification rules.
+    None,
+    /// Apply only the rules for `∅` and `ε` that do not need to compare subexpressions (e.g.,
+    /// `r∅ = ∅`, `εr = r`, `r{1} = r`).
+    Basic,
+    /// Apply every rule, including normalizing classes, flattening alternations into a sorted,
+    /// deduplicated list of alternatives (so `(a|b)|a` and `b|a` both simplify to `a|b`), and
+    /// rebalancing long concatenations and alternations.
+    #[default]
+    Aggressive,
+}
+
+/// Which simplification rules to apply and how much of a regex to simplify, for finer control than
+/// `SimplifyLevel` gives (e.g., cheap local rewriting while matching, but full canonicalization when
+/// checking equivalence).
+///
+/// Options can be built from a level and then adjusted (e.g.,
+/// `SimplifyOptions { max_depth: Some(8), ..SimplifyLevel::Basic.into() }`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimplifyOptions {
+    /// Apply the rules for `∅`, `ε`, nested counts and adjacent literals, which do not compare
+    /// subexpressions (e.g., `r∅ = ∅`, `εr = r`, `(r*)* = r*`, `a·b = ab`).
+    pub identities: bool,
+    /// Flatten alternations into a sorted, deduplicated list of alternatives (e.g., `b|a|b = a|b`).
+    pub alternatives: bool,
+    /// Apply the rules that compare subexpressions (e.g., `r*r* = r*`), and, if `alternatives` is
+    /// also set, those that rewrite alternations (e.g., `ε|r = r?`, `r|r* = r*`, `rs|rt = r(s|t)`).
+    pub algebraic: bool,
+    /// Normalize classes (e.g., `[a-a]` = `a`).
+    pub classes: bool,
+    /// Rebuild long concatenations and alternations into balanced trees (see `Regex::rebalance`).
+    pub rebalance: bool,
+    /// The depth below which subexpressions are left unsimplified, if any.
+    pub max_depth: Option<usize>,
+    /// The number of nodes after which the rest of the regex is left unsimplified, if any.
+    pub max_nodes: Option<usize>,
+}
+
+impl Default for SimplifyOptions {
+    fn default() -> Self {
+        SimplifyLevel::default().into()
+    }
+}
+
+impl From<SimplifyLevel> for SimplifyOptions {
+    fn from(level: SimplifyLevel) -> Self {
+        let basic = level != SimplifyLevel::None;
+        let aggressive = level == SimplifyLevel::Aggressive;
+        Self {
+            identities: basic,
+            alternatives: aggressive,
+            algebraic: aggressive,
+            classes: aggressive,
+            rebalance: aggressive,
+            max_depth: None,
+            max_nodes: None,
+        }
+    }
+}
+
+impl SimplifyOptions {
+    /// Returns `true` if no rules are enabled, otherwise returns `false`.
+    const fn is_none(&self) -> bool {
+        !(self.identities || self.alternatives || self.algebraic || self.classes || self.rebalance)
+    }
+}
+
+/// A named predicate that decides whether a single character matches (e.g., `char::is_alphabetic`).
+#[derive(Clone)]
+pub struct Predicate {
+    name: String,
+    /// The order the predicate was created in, shared by its clones, so that predicates with the
+    /// same name are ordered the same way on every run.
+    index: u64,
+    function: Arc<dyn Fn(char) -> bool + Send + Sync>,
+}
+
+/// The index of the next predicate created with `Predicate::new`.
+static NEXT_PREDICATE_INDEX: AtomicU64 = AtomicU64::new(0);
+
+impl Debug for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Predicate").field(&self.name).finish()
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}>", self.name)
+    }
+}
+
+/// Two predicates are equal only if they share the same name and the same underlying function.
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && Arc::ptr_eq(&self.function, &other.function)
+    }
+}
+
+impl Eq for Predicate {}
+
+impl Hash for Predicate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
+/// Predicates are ordered by name, and predicates with the same name by the order they were
+/// created in, so that the order does not depend on where their functions are allocated. The
+/// address of the function only breaks ties, keeping the order consistent with equality.
+impl Ord for Predicate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name
+            .cmp(&other.name)
+            .then_with(|| self.index.cmp(&other.index))
+            .then_with(|| {
+                let address =
+                    |predicate: &Self| Arc::as_ptr(&predicate.function).cast::<()>() as usize;
+                address(self).cmp(&address(other))
+            })
+    }
+}
+
+impl PartialOrd for Predicate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Predicate {
+    /// Creates a new predicate with a name used for debugging and display.
+    pub fn new(
+        name: impl Into<String>,
+        function: impl Fn(char) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            index: NEXT_PREDICATE_INDEX.fetch_add(1, AtomicOrdering::Relaxed),
+            function: Arc::new(function),
+        }
+    }
+
+    /// Returns the name of the predicate.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns `true` if the given character satisfies the predicate, otherwise returns `false`.
+    pub fn test(&self, c: char) -> bool {
+        (self.function)(c)
+    }
+}
+
+/// Splits each of the sets into the sets of its characters that are accepted by the same
+/// predicates, in the order their characters first appear.
+fn split_by_predicates(sets: Vec<CharSet>, predicates: &[Predicate]) -> Vec<CharSet> {
+    if predicates.is_empty() {
+        return sets;
+    }
+
+    let mut split = Vec::new();
+    for set in sets {
+        let mut groups: Vec<(Vec<bool>, Vec<_>)> = Vec::new();
+        let mut accepted = vec![false; predicates.len()];
+        let mut run: Option<(usize, char, char)> = None;
+
+        for c in set.iter_chars() {
+            for (accepts, predicate) in accepted.iter_mut().zip(predicates) {
+                *accepts = predicate.test(c);
+            }
+            if let Some((group, start, end)) = run {
+                if groups[group].0 == accepted && next_char(end) == Some(c) {
+                    run = Some((group, start, c));
+                    continue;
+                }
+                groups[group].1.push((start, end));
+            }
+            let group = groups
+                .iter()
+                .position(|(signature, _)| *signature == accepted)
+                .unwrap_or_else(|| {
+                    groups.push((accepted.clone(), Vec::new()));
+                    groups.len() - 1
+                });
+            run = Some((group, c, c));
+        }
+        if let Some((group, start, end)) = run {
+            groups[group].1.push((start, end));
+        }
+
+        split.extend(
+            groups
+                .into_iter()
+                .map(|(_, intervals)| CharSet::from_intervals(intervals)),
+        );
+    }
+    split
+}
+
+/// A child of a `Regex` (e.g., either side of a `Regex::Concat`), which keeps whether it is
+/// nullable, whether it matches nothing, and its structural hash next to it, so that asking a regex
+/// these or hashing it only looks at its children's flags rather than walking their whole trees.
+///
+/// A node dereferences to the regex it holds, and is created with `Node::new` or `Regex::into`.
+#[derive(Clone)]
+pub struct Node(Box<NodeData>);
+
+/// What a `Node` holds, kept behind one pointer so that a `Regex` is no larger than with plain boxes.
+#[derive(Clone)]
+struct NodeData {
+    regex: Regex,
+    nullable: bool,
+    matches_nothing: bool,
+    hash: u64,
+}
+
+impl Node {
+    /// Creates a node holding the regex, working out its flags and hash from those of its
+    /// children.
+    pub fn new(regex: Regex) -> Self {
+        let nullable = regex.nullable();
+        let matches_nothing = regex.matches_nothing();
+        let hash = regex.structural_hash();
+        Self(Box::new(NodeData {
+            regex,
+            nullable,
+            matches_nothing,
+            hash,
+        }))
+    }
+
+    /// Returns the regex the node holds.
+    pub fn into_inner(mut self) -> Regex {
+        self.take()
+    }
+
+    /// Moves the regex out, leaving `∅` in its place, for a node that is about to be dropped.
+    fn take(&mut self) -> Regex {
+        self.0.regex.take()
+    }
+}
+
+impl From<Regex> for Node {
+    fn from(regex: Regex) -> Self {
+        Self::new(regex)
+    }
+}
+
+impl Deref for Node {
+    type Target = Regex;
+
+    fn deref(&self) -> &Regex {
+        &self.0.regex
+    }
+}
+
+impl AsRef<Regex> for Node {
+    fn as_ref(&self) -> &Regex {
+        &self.0.regex
+    }
+}
+
+impl Debug for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0.regex, f)
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0.regex, f)
+    }
+}
+
+/// Nodes with different hashes hold different regexes, so most unequal nodes are told apart
+/// without comparing their trees.
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.hash == other.0.hash && self.0.regex == other.0.regex
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.0.hash);
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.regex.cmp(&other.0.regex)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// A regular expression.
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Regexes are ordered structurally, first by variant (in the order they are declared) and then by
+/// their contents.
+#[derive(Debug)]
 pub enum Regex {
     /// A regex that does not match any strings.
     Empty,
@@ -97,264 +598,1664 @@ pub enum Regex {
     /// A regex that matches a single character (e.g., `a`).
     Literal(char),
     /// A regex that matches a concatenation of two regexes (e.g., `ab`).
-    Concat(Box<Self>, Box<Self>),
+    Concat(Node, Node),
     /// A regex that matches an alternation of two regexes (e.g., `a|b`).
-    Or(Box<Self>, Box<Self>),
+    Or(Node, Node),
     /// A regex that matches any character in the given character class (e.g., `[a-z]`).
     Class(Vec<CharRange>),
     /// A regex that matches a given regex a specified number of times (e.g., `a{3}` or `a{3,5}`).
-    Count(Box<Self>, Count),
+    Count(Node, Count),
+    /// A regex that matches any single character satisfying the given predicate.
+    Pred(Predicate),
+    /// A regex that matches any interleaving of a string matched by each of two regexes (e.g., `ab⧢c`
+    /// matches `abc`, `acb`, and `cab`).
+    Shuffle(Node, Node),
+    /// A regex that matches a string of two or more characters (e.g., `abc`), which simplification
+    /// folds adjacent literals into so their derivatives do not need to walk a `Concat` tree.
+    Str(String),
+}
+
+// Cloning, comparing, hashing and dropping are implemented by hand so that they do not overflow the
+// stack on deeply nested regexes.
+
+impl Clone for Regex {
+    fn clone(&self) -> Self {
+        ensure_stack(|| match self {
+            Self::Empty => Self::Empty,
+            Self::Epsilon => Self::Epsilon,
+            Self::Literal(c) => Self::Literal(*c),
+            Self::Concat(left, right) => Self::Concat(left.clone(), right.clone()),
+            Self::Or(left, right) => Self::Or(left.clone(), right.clone()),
+            Self::Class(ranges) => Self::Class(ranges.clone()),
+            Self::Count(inner, count) => Self::Count(inner.clone(), *count),
+            Self::Pred(predicate) => Self::Pred(predicate.clone()),
+            Self::Shuffle(left, right) => Self::Shuffle(left.clone(), right.clone()),
+            Self::Str(s) => Self::Str(s.clone()),
+        })
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        match self {
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right)
+                if left.has_children() || right.has_children() =>
+            {
+                ensure_stack(|| {
+                    drop(left.take());
+                    drop(right.take());
+                });
+            }
+            Self::Count(inner, _) if inner.has_children() => {
+                ensure_stack(|| drop(inner.take()));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        // a regex is equal to itself, without comparing every node
+        if ptr::eq(self, other) {
+            return true;
+        }
+        ensure_stack(|| match (self, other) {
+            (Self::Empty, Self::Empty) | (Self::Epsilon, Self::Epsilon) => true,
+            (Self::Literal(a), Self::Literal(b)) => a == b,
+            (Self::Concat(left1, right1), Self::Concat(left2, right2))
+            | (Self::Or(left1, right1), Self::Or(left2, right2))
+            | (Self::Shuffle(left1, right1), Self::Shuffle(left2, right2)) => {
+                left1 == left2 && right1 == right2
+            }
+            (Self::Class(a), Self::Class(b)) => a == b,
+            (Self::Count(inner1, count1), Self::Count(inner2, count2)) => {
+                count1 == count2 && inner1 == inner2
+            }
+            (Self::Pred(a), Self::Pred(b)) => a == b,
+            (Self::Str(a), Self::Str(b)) => a == b,
+            _ => false,
+        })
+    }
+}
+
+impl Eq for Regex {}
+
+/// Hashing a regex only hashes the hashes its children keep (see `Node`), so it takes constant time
+/// however deep the regex is.
+impl Hash for Regex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+        match self {
+            Self::Empty | Self::Epsilon => {}
+            Self::Literal(c) => c.hash(state),
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right) => {
+                left.hash(state);
+                right.hash(state);
+            }
+            Self::Class(ranges) => ranges.hash(state),
+            Self::Count(inner, count) => {
+                inner.hash(state);
+                count.hash(state);
+            }
+            Self::Pred(predicate) => predicate.hash(state),
+            Self::Str(s) => s.hash(state),
+        }
+    }
+}
+
+impl Ord for Regex {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if ptr::eq(self, other) {
+            return Ordering::Equal;
+        }
+        ensure_stack(|| match (self, other) {
+            (Self::Literal(a), Self::Literal(b)) => a.cmp(b),
+            (Self::Concat(left1, right1), Self::Concat(left2, right2))
+            | (Self::Or(left1, right1), Self::Or(left2, right2))
+            | (Self::Shuffle(left1, right1), Self::Shuffle(left2, right2)) => {
+                left1.cmp(left2).then_with(|| right1.cmp(right2))
+            }
+            (Self::Class(a), Self::Class(b)) => a.cmp(b),
+            (Self::Count(inner1, count1), Self::Count(inner2, count2)) => {
+                inner1.cmp(inner2).then_with(|| count1.cmp(count2))
+            }
+            (Self::Pred(a), Self::Pred(b)) => a.cmp(b),
+            (Self::Str(a), Self::Str(b)) => a.cmp(b),
+            _ => self.variant_index().cmp(&other.variant_index()),
+        })
+    }
 }
 
-impl Display for Regex {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Self::Empty => "∅".to_string(),
-                Self::Epsilon => "ε".to_string(),
-                Self::Literal(c) => escape_regex_char(*c, false),
-                Self::Concat(left, right) => format!("{left}{right}"),
-                Self::Or(left, right) => format!("({left}|{right})"),
-                Self::Class(ranges) => {
-                    let ranges_str = ranges
-                        .iter()
-                        .map(|range| range.to_string())
-                        .collect::<String>();
-                    format!("[{ranges_str}]")
-                }
-                Self::Count(inner, quantifier) => {
-                    format!("({inner}){quantifier}")
-                }
+impl PartialOrd for Regex {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        ensure_stack(|| match self {
+            Self::Empty => f.write_char('∅'),
+            Self::Epsilon => f.write_char('ε'),
+            Self::Literal(c) => write_escaped(f, *c, false),
+            Self::Concat(left, right) => write!(f, "{left}{right}"),
+            Self::Or(left, right) => write!(f, "({left}|{right})"),
+            Self::Class(ranges) => {
+                f.write_char('[')?;
+                for range in ranges {
+                    write!(f, "{range}")?;
+                }
+                f.write_char(']')
+            }
+            Self::Count(inner, quantifier) => write!(f, "({inner}){quantifier}"),
+            Self::Pred(predicate) => write!(f, "{predicate}"),
+            Self::Shuffle(left, right) => write!(f, "({left}⧢{right})"),
+            Self::Str(s) => s.chars().try_for_each(|c| write_escaped(f, c, false)),
+        })
+    }
+}
+
+/// `a | b` is the alternation of `a` and `b`, simplified (see `Regex::or`).
+impl BitOr for Regex {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self::or(self, rhs)
+    }
+}
+
+/// `a + b` is the concatenation of `a` and `b`, simplified (see `Regex::concat`).
+impl Add for Regex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::concat(self, rhs)
+    }
+}
+
+/// `a * b` is the concatenation of `a` and `b`, simplified (see `Regex::concat`).
+impl Mul for Regex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::concat(self, rhs)
+    }
+}
+
+impl Regex {
+    /// Returns the position of the regex's variant in the order they are declared.
+    const fn variant_index(&self) -> usize {
+        match self {
+            Self::Empty => 0,
+            Self::Epsilon => 1,
+            Self::Literal(_) => 2,
+            Self::Concat(_, _) => 3,
+            Self::Or(_, _) => 4,
+            Self::Class(_) => 5,
+            Self::Count(_, _) => 6,
+            Self::Pred(_) => 7,
+            Self::Shuffle(_, _) => 8,
+            Self::Str(_) => 9,
+        }
+    }
+
+    /// Returns a regex that matches the given character (e.g., `a`).
+    pub const fn literal(c: char) -> Self {
+        Self::Literal(c)
+    }
+
+    /// Returns a regex that matches the given string (e.g., `abc`), which is `Regex::Epsilon` for the
+    /// empty string and a literal for a single character.
+    pub fn string(s: &str) -> Self {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => Self::Epsilon,
+            (Some(c), None) => Self::Literal(c),
+            _ => Self::Str(s.to_string()),
+        }
+    }
+
+    /// Returns a simplified regex that matches any character in the given ranges (e.g., `[a-z]`).
+    pub fn class(ranges: Vec<CharRange>) -> Self {
+        Self::Class(ranges).simplify()
+    }
+
+    /// Returns a regex that matches `left` followed by `right` (e.g., `ab`), applying the `∅` and
+    /// `ε` simplification rules to the new node and joining adjacent literals into a string.
+    pub fn concat(left: Self, right: Self) -> Self {
+        match (left, right) {
+            (Self::Empty, _) | (_, Self::Empty) => Self::Empty,
+            (Self::Epsilon, other) | (other, Self::Epsilon) => other,
+            (left, right) => Self::concat_joining(left, right),
+        }
+    }
+
+    /// Returns a regex that matches either `left` or `right` (e.g., `a|b`), applying the `∅` and
+    /// `r ∪ r = r` simplification rules to the new node.
+    pub fn or(left: Self, right: Self) -> Self {
+        match (left, right) {
+            (Self::Empty, other) | (other, Self::Empty) => other,
+            (left, right) if left.eq_for_simplify(&right) => left,
+            (left, right) => Self::Or(Node::new(left), Node::new(right)),
+        }
+    }
+
+    /// Returns the concatenation of all the given regexes in order, or `Regex::Epsilon` if there
+    /// are none.
+    pub fn concat_all(regexes: impl IntoIterator<Item = Self>) -> Self {
+        regexes.into_iter().fold(Self::Epsilon, Self::concat)
+    }
+
+    /// Returns the alternation of all the given regexes, or `Regex::Empty` if there are none.
+    pub fn or_all(regexes: impl IntoIterator<Item = Self>) -> Self {
+        regexes.into_iter().fold(Self::Empty, Self::or)
+    }
+
+    /// Returns the simplified alternation of all the given regexes (e.g., `a|b|c`), or
+    /// `Regex::Empty` if there are none.
+    ///
+    /// Unlike `or_all`, which nests each alternative one level deeper than the last, the
+    /// alternatives are combined pairwise into a balanced tree, so the result is only nested
+    /// logarithmically deep in the number of regexes.
+    pub fn union_all(regexes: impl IntoIterator<Item = Self>) -> Self {
+        Self::combine_balanced(regexes.into_iter().map(|regex| regex.simplify()), Self::or)
+            .map_or(Self::Empty, |regex| regex.simplify())
+    }
+
+    /// Returns a simplified regex matching the strings that all the given regexes match, or one
+    /// matching every string if there are none.
+    ///
+    /// The regexes are intersected pairwise in a balanced tree with `intersection`, so this has the
+    /// same cost and limitations.
+    pub fn intersect_all(regexes: impl IntoIterator<Item = Self>) -> Self {
+        Self::combine_balanced(regexes.into_iter().map(|regex| regex.simplify()), |a, b| {
+            a.intersection(&b)
+        })
+        .unwrap_or_else(|| Self::Class(vec![CharRange::Range('\0', char::MAX)]).star())
+    }
+
+    /// Combines the regexes pairwise with `combine` until one is left, or returns `None` if there
+    /// are none.
+    fn combine_balanced(
+        regexes: impl IntoIterator<Item = Self>,
+        combine: impl Fn(Self, Self) -> Self,
+    ) -> Option<Self> {
+        let mut level = regexes.into_iter().collect::<Vec<_>>();
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            let mut regexes = level.into_iter();
+            while let Some(left) = regexes.next() {
+                next.push(match regexes.next() {
+                    Some(right) => combine(left, right),
+                    None => left,
+                });
+            }
+            level = next;
+        }
+
+        level.pop()
+    }
+
+    pub fn star(&self) -> Self {
+        Self::Count(Node::new(self.clone()), Count::AtLeast(0))
+    }
+
+    pub fn plus(&self) -> Self {
+        Self::Count(Node::new(self.clone()), Count::AtLeast(1))
+    }
+
+    pub fn optional(&self) -> Self {
+        Self::Count(Node::new(self.clone()), Count::Range(0, 1))
+    }
+
+    /// Returns a regex that matches any interleaving of a string matched by `self` and a string
+    /// matched by `other`.
+    pub fn shuffle(&self, other: &Self) -> Self {
+        Self::Shuffle(Node::new(self.clone()), Node::new(other.clone()))
+    }
+
+    /// Returns a regex that matches any single character satisfying the given predicate.
+    pub fn pred(
+        name: impl Into<String>,
+        function: impl Fn(char) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self::Pred(Predicate::new(name, function))
+    }
+
+    /// Returns `true` if the regex matches the empty string, otherwise returns `false`.
+    ///
+    /// This takes constant time, since each child keeps whether it is nullable (see `Node`).
+    pub fn nullable(&self) -> bool {
+        match self {
+            Self::Empty => false,
+            Self::Epsilon => true,
+            Self::Literal(_) => false,
+            Self::Concat(left, right) | Self::Shuffle(left, right) => {
+                left.0.nullable && right.0.nullable
+            }
+            Self::Or(left, right) => left.0.nullable || right.0.nullable,
+            Self::Class(_) | Self::Pred(_) => false,
+            Self::Str(s) => s.is_empty(),
+            Self::Count(inner, quantifier) => {
+                let min = match quantifier {
+                    Count::Exact(n) | Count::Range(n, _) | Count::AtLeast(n) => *n,
+                };
+                min == 0 || inner.0.nullable
+            }
+        }
+    }
+
+    /// Returns `true` if the regex matches no strings at all (e.g., `a[]`), otherwise returns
+    /// `false`.
+    ///
+    /// Predicates are assumed to match at least one character. Like `nullable`, this takes
+    /// constant time.
+    pub fn matches_nothing(&self) -> bool {
+        match self {
+            Self::Empty => true,
+            Self::Epsilon | Self::Literal(_) | Self::Str(_) | Self::Pred(_) => false,
+            Self::Class(ranges) => ranges.is_empty(),
+            Self::Concat(left, right) | Self::Shuffle(left, right) => {
+                left.0.matches_nothing || right.0.matches_nothing
+            }
+            Self::Or(left, right) => left.0.matches_nothing && right.0.matches_nothing,
+            Self::Count(inner, count) => {
+                let (min, max) = count.bounds();
+                max.is_some_and(|max| min > max) || (min > 0 && inner.0.matches_nothing)
+            }
+        }
+    }
+
+    /// Returns `true` if the regex is `∅` or the empty class `[]`, which both match nothing.
+    ///
+    /// Simplification keeps `[]` as written, but anything that needs a part matching nothing (e.g.,
+    /// `x*[]`) simplifies to `∅` and alternatives matching nothing are dropped, so a simplified regex
+    /// matches nothing exactly when this is `true`.
+    fn is_dead(&self) -> bool {
+        match self {
+            Self::Empty => true,
+            Self::Class(ranges) => ranges.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// If the regex is nullable, returns `Regex::Epsilon`, otherwise returns `Regex::Empty`.
+    pub fn nullability_regex(&self) -> Self {
+        if self.nullable() {
+            Self::Epsilon
+        } else {
+            Self::Empty
+        }
+    }
+
+    /// If the regex is nullable, returns `Regex::Epsilon`, otherwise returns `Regex::Empty`.
+    #[deprecated(
+        note = "use `nullable` to check nullability, or `nullability_regex` for the old behaviour"
+    )]
+    pub fn is_nullable(&self) -> Self {
+        self.nullability_regex()
+    }
+
+    /// Returns the Brzozowski derivative of the regex with respect to a given character.
+    pub fn derivative(&self, c: char) -> Self {
+        self.derivative_with(c, &SimplifyOptions::default())
+    }
+
+    /// Returns the Brzozowski derivative of the regex with respect to a given character, simplifying
+    /// it with the given options.
+    pub fn derivative_with(&self, c: char, options: &SimplifyOptions) -> Self {
+        self.derivative_and_nullable(c, options, false).0
+    }
+
+    /// Like `derivative`, but for a regex that is already simplified (e.g., a derivative).
+    ///
+    /// The parts of the regex that its derivative copies are then already simplified, as are the
+    /// derivatives of its parts, so only the rules for the nodes joining them are applied, rather
+    /// than simplifying the whole derivative again at every level.
+    pub(crate) fn derivative_of_simplified(&self, c: char) -> Self {
+        self.derivative_and_nullable(c, &SimplifyOptions::default(), true)
+            .0
+    }
+
+    /// Returns the derivative of the regex with respect to a given character, and whether the regex
+    /// itself is nullable.
+    ///
+    /// The derivative of a concatenation `rs` needs to know whether `r` is nullable, which would walk
+    /// all of `r` again at every level of a nested concatenation (e.g., `((ab)c)d`), so each level
+    /// works it out from the nullability of the parts whose derivatives it takes anyway instead.
+    ///
+    /// If `simplified` is `true`, the regex must already be simplified, and only the rules for the
+    /// new nodes are applied (see `derivative_of_simplified`). With a limit on how much is
+    /// simplified, the derivative is simplified as a whole anyway, since that is what the limit
+    /// applies to.
+    fn derivative_and_nullable(
+        &self,
+        c: char,
+        options: &SimplifyOptions,
+        simplified: bool,
+    ) -> (Self, bool) {
+        let local = simplified && options.max_depth.is_none() && options.max_nodes.is_none();
+        // the derivatives of the inner regexes of counts are only kept for the options `matches` uses
+        let memoize_counts = local && *options == SimplifyOptions::default();
+
+        // each new node is built simplified by its own rules if `local`, otherwise as it is, to be
+        // simplified as a whole at the end
+        let simplify_node = |simplify: fn(Self, Self, &SimplifyOptions) -> Self,
+                             left: Self,
+                             right: Self,
+                             raw: fn(Node, Node) -> Self| {
+            if local {
+                time_simplify(|| {
+                    record_simplify_call();
+                    simplify(left, right, options)
+                })
+            } else {
+                raw(Node::new(left), Node::new(right))
+            }
+        };
+        let concat = |left, right| simplify_node(Self::simplify_concat, left, right, Self::Concat);
+        let or = |left, right| simplify_node(Self::simplify_or, left, right, Self::Or);
+        let shuffle =
+            |left, right| simplify_node(Self::simplify_shuffle, left, right, Self::Shuffle);
+        let count = |inner: Self, count| {
+            if local {
+                time_simplify(|| {
+                    record_simplify_call();
+                    Self::simplify_count(inner, count, options)
+                })
+            } else {
+                Self::Count(Node::new(inner), count)
+            }
+        };
+
+        ensure_stack(|| {
+            record_derivative_call();
+            let (derivative, nullable) = match self {
+                Self::Empty => (Self::Empty, false),
+                Self::Epsilon => (Self::Empty, true),
+                Self::Literal(_) | Self::Class(_) | Self::Pred(_) => {
+                    if self.matches_single_char(c) == Some(true) {
+                        (Self::Epsilon, false)
+                    } else {
+                        (Self::Empty, false)
+                    }
+                }
+                Self::Concat(left, right) => match left.matches_single_char(c) {
+                    // D_c(rs) = s or ∅ if r matches a single character, without building εs or ∅s
+                    Some(true) => (right.as_ref().clone(), false),
+                    Some(false) => return (Self::Empty, false),
+                    None => {
+                        let (left_derivative, left_nullable) =
+                            left.derivative_and_nullable(c, options, simplified);
+                        let left_derivative = concat(left_derivative, right.as_ref().clone());
+
+                        // D_c(rs) = D_c(r)s ∪ ν(r)D_c(s), so D_c(s) is only needed if r is nullable,
+                        // and rs can only be nullable if r is
+                        if left_nullable {
+                            let left_derivative = if local {
+                                left_derivative
+                            } else {
+                                left_derivative.simplify_step(options)
+                            };
+                            let (right_derivative, right_nullable) =
+                                right.derivative_and_nullable(c, options, simplified);
+                            (or(left_derivative, right_derivative), right_nullable)
+                        } else {
+                            (left_derivative, false)
+                        }
+                    }
+                },
+                Self::Or(left, right) => {
+                    let (left_derivative, left_nullable) =
+                        left.derivative_and_nullable(c, options, simplified);
+                    let (right_derivative, right_nullable) =
+                        right.derivative_and_nullable(c, options, simplified);
+                    (
+                        or(left_derivative, right_derivative),
+                        left_nullable || right_nullable,
+                    )
+                }
+                // D_c(cs) = s, without walking a tree of literals
+                Self::Str(s) => match s.strip_prefix(c) {
+                    Some(rest) => (Self::string(rest), s.is_empty()),
+                    None => return (Self::Empty, s.is_empty()),
+                },
+                Self::Shuffle(left, right) => {
+                    let (left_derivative, left_nullable) =
+                        left.derivative_and_nullable(c, options, simplified);
+                    let (right_derivative, right_nullable) =
+                        right.derivative_and_nullable(c, options, simplified);
+                    (
+                        or(
+                            shuffle(left_derivative, right.as_ref().clone()),
+                            shuffle(left.as_ref().clone(), right_derivative),
+                        ),
+                        left_nullable && right_nullable,
+                    )
+                }
+                // r{0} only matches the empty string
+                Self::Count(inner, count @ (Count::Exact(0) | Count::Range(_, 0))) => {
+                    (Self::Empty, count.bounds().0 == 0 || inner.nullable())
+                }
+                Self::Count(inner, inner_count) => {
+                    let new_count = match inner_count {
+                        Count::Exact(n) => Count::Exact(n.saturating_sub(1)),
+                        Count::Range(min, max) => {
+                            Count::Range(min.saturating_sub(1), max.saturating_sub(1))
+                        }
+                        Count::AtLeast(min) => Count::AtLeast(min.saturating_sub(1)),
+                    };
+
+                    let inner_derivative = || inner.derivative_and_nullable(c, options, simplified);
+                    let (inner_derivative, inner_nullable) = if memoize_counts {
+                        count_residual(inner, c, inner_derivative)
+                    } else {
+                        inner_derivative()
+                    };
+                    (
+                        concat(inner_derivative, count(inner.as_ref().clone(), new_count)),
+                        inner_count.bounds().0 == 0 || inner_nullable,
+                    )
+                }
+            };
+            if local {
+                (derivative, nullable)
+            } else {
+                (derivative.simplify_step(options), nullable)
+            }
+        })
+    }
+
+    /// Simplifies the regex.
+    pub fn simplify(&self) -> Self {
+        self.simplify_with(&SimplifyOptions::default())
+    }
+
+    /// Simplifies the regex, applying only the rules enabled in the given options.
+    pub fn simplify_with(&self, options: &SimplifyOptions) -> Self {
+        let simplified = self.simplify_step(options);
+        if options.rebalance {
+            simplified.rebalance()
+        } else {
+            simplified
+        }
+    }
+
+    /// Simplifies a regex built while taking a derivative, without rebalancing concatenations,
+    /// since that would walk the whole regex at every level of the derivative. Alternations are
+    /// still rebuilt balanced if the options ask for it.
+    fn simplify_step(&self, options: &SimplifyOptions) -> Self {
+        time_simplify(|| {
+            let mut node_budget = options.max_nodes;
+            self.simplify_within(options, 0, &mut node_budget)
+        })
+    }
+
+    /// Rebuilds every concatenation and alternation of at least 8 regexes into a balanced tree
+    /// (e.g., `((((ab)c)d)e)…` into `((ab)(cd))((ef)(gh))`), so the regex is only nested
+    /// logarithmically deep in their length. The regex matches the same strings.
+    ///
+    /// Long spines are common in machine-generated patterns, and every recursive operation (e.g.,
+    /// taking a derivative) walks them one level at a time.
+    pub fn rebalance(&self) -> Self {
+        ensure_stack(|| match self {
+            Self::Concat(left, right) | Self::Or(left, right) => {
+                let concat = matches!(self, Self::Concat(_, _));
+                let mut parts = Vec::new();
+                self.collect_spine(concat, &mut parts);
+                if parts.len() < REBALANCE_MIN_LEN {
+                    let (left, right) = (Node::new(left.rebalance()), Node::new(right.rebalance()));
+                    return if concat {
+                        Self::Concat(left, right)
+                    } else {
+                        Self::Or(left, right)
+                    };
+                }
+
+                let parts = parts.into_iter().map(Self::rebalance);
+                Self::combine_balanced(parts, |left, right| {
+                    if concat {
+                        Self::Concat(Node::new(left), Node::new(right))
+                    } else {
+                        Self::Or(Node::new(left), Node::new(right))
+                    }
+                })
+                .unwrap()
+            }
+            Self::Shuffle(left, right) => {
+                Self::Shuffle(Node::new(left.rebalance()), Node::new(right.rebalance()))
+            }
+            Self::Count(inner, count) => Self::Count(Node::new(inner.rebalance()), *count),
+            _ => self.clone(),
+        })
+    }
+
+    /// Adds the regexes that a nested concatenation (if `concat` is `true`) or alternation joins to
+    /// `parts`, in order (e.g., `a`, `b` and `c` for `(ab)c`).
+    fn collect_spine<'a>(&'a self, concat: bool, parts: &mut Vec<&'a Self>) {
+        ensure_stack(|| match self {
+            Self::Concat(left, right) if concat => {
+                left.collect_spine(concat, parts);
+                right.collect_spine(concat, parts);
+            }
+            Self::Or(left, right) if !concat => {
+                left.collect_spine(concat, parts);
+                right.collect_spine(concat, parts);
+            }
+            _ => parts.push(self),
+        });
+    }
+
+    /// Simplifies the regex, which is nested `depth` levels deep, leaving it as it is once the
+    /// maximum depth or the node budget is reached.
+    fn simplify_within(
+        &self,
+        options: &SimplifyOptions,
+        depth: usize,
+        node_budget: &mut Option<usize>,
+    ) -> Self {
+        ensure_stack(|| {
+            record_simplify_call();
+            if options.is_none() || options.max_depth.is_some_and(|max_depth| depth > max_depth) {
+                return self.clone();
+            }
+            match node_budget {
+                Some(0) => return self.clone(),
+                Some(remaining) => *remaining -= 1,
+                None => {}
+            }
+            let simplify = |regex: &Self, node_budget: &mut Option<usize>| {
+                regex.simplify_within(options, depth + 1, node_budget)
+            };
+
+            match self {
+                Self::Empty => Self::Empty,
+                Self::Epsilon => Self::Epsilon,
+                Self::Literal(c) => Self::Literal(*c),
+                Self::Str(s) => Self::string(s),
+                Self::Pred(predicate) => Self::Pred(predicate.clone()),
+                Self::Concat(left, right) => {
+                    let (left, right) = (simplify(left, node_budget), simplify(right, node_budget));
+                    Self::simplify_concat(left, right, options)
+                }
+                Self::Or(left, right) => {
+                    let (left, right) = (simplify(left, node_budget), simplify(right, node_budget));
+                    Self::simplify_or(left, right, options)
+                }
+                Self::Shuffle(left, right) => {
+                    let (left, right) = (simplify(left, node_budget), simplify(right, node_budget));
+                    Self::simplify_shuffle(left, right, options)
+                }
+                Self::Class(_) if !options.classes => self.clone(),
+                Self::Class(ranges) => {
+                    // Merge overlapping and adjacent ranges (e.g., [a-cb-d] = [a-d])
+                    let ranges = CharSet::new(ranges).ranges();
+                    match ranges.as_slice() {
+                        [CharRange::Single(c)] => Self::Literal(*c),
+                        _ => Self::Class(ranges),
+                    }
+                }
+                Self::Count(inner, count) => {
+                    Self::simplify_count(simplify(inner, node_budget), *count, options)
+                }
+            }
+        })
+    }
+
+    /// Applies the rules for concatenations to the concatenation of two simplified regexes, without
+    /// simplifying them again.
+    fn simplify_concat(left: Self, right: Self, options: &SimplifyOptions) -> Self {
+        if !options.identities {
+            return Self::Concat(Node::new(left), Node::new(right));
+        }
+
+        // r∅ = ∅r = ∅, and the same for anything else that matches nothing (e.g., `[]`)
+        if left.is_dead() || right.is_dead() {
+            return Self::Empty;
+        }
+
+        // εr = rε = r
+        if left == Self::Epsilon {
+            return right;
+        }
+        if right == Self::Epsilon {
+            return left;
+        }
+
+        // r*r* = r*
+        if options.algebraic {
+            if let Self::Count(_, Count::AtLeast(0)) = right {
+                let left_last = match &left {
+                    Self::Concat(_, last) => last,
+                    other => other,
+                };
+                if left_last.eq_for_simplify(&right) {
+                    return left;
+                }
+            }
+        }
+
+        // a·b = ab
+        Self::concat_joining(left, right)
+    }
+
+    /// Applies the rules for alternations to the alternation of two simplified regexes, without
+    /// simplifying them again.
+    fn simplify_or(left: Self, right: Self, options: &SimplifyOptions) -> Self {
+        // r ∪ ∅ = ∅ ∪ r = r
+        if options.identities {
+            if left.is_dead() {
+                return right;
+            }
+            if right.is_dead() {
+                return left;
+            }
+        }
+
+        if !options.alternatives {
+            return Self::Or(Node::new(left), Node::new(right));
+        }
+
+        let mut alternatives = Vec::new();
+        left.collect_alternatives(&mut alternatives);
+        right.collect_alternatives(&mut alternatives);
+        Self::or_of_alternatives(alternatives, options)
+    }
+
+    /// Applies the rules for shuffles to the shuffle of two simplified regexes, without simplifying
+    /// them again.
+    fn simplify_shuffle(left: Self, right: Self, options: &SimplifyOptions) -> Self {
+        if !options.identities {
+            return Self::Shuffle(Node::new(left), Node::new(right));
+        }
+
+        // r⧢∅ = ∅⧢r = ∅
+        if left.is_dead() || right.is_dead() {
+            return Self::Empty;
+        }
+
+        // ε⧢r = r⧢ε = r
+        if left == Self::Epsilon {
+            return right;
+        }
+        if right == Self::Epsilon {
+            return left;
+        }
+
+        Self::Shuffle(Node::new(left), Node::new(right))
+    }
+
+    /// Applies the identities for counts to a count of a simplified regex, without simplifying it
+    /// again.
+    fn simplify_count(inner_simplified: Self, count: Count, options: &SimplifyOptions) -> Self {
+        if !options.identities {
+            return Self::Count(Node::new(inner_simplified), count);
+        }
+
+        // r{n,n} = r{n}
+        let count = match count {
+            Count::Range(min, max) if min == max => Count::Exact(min),
+            other => other,
+        };
+
+        // r{n,m} = ∅ for n > m
+        if let Count::Range(min, max) = count {
+            if min > max {
+                return Self::Empty;
+            }
+        }
+
+        // r{n,m} = r{0,m} and r{n,} = r* if r is nullable, since r can match ε for each repetition
+        // that is not needed
+        let count = match count {
+            Count::Exact(n) | Count::Range(_, n) if n > 0 && inner_simplified.nullable() => {
+                Count::Range(0, n)
+            }
+            Count::AtLeast(_) if inner_simplified.nullable() => Count::AtLeast(0),
+            other => other,
+        };
+
+        // ∅* = ε* = ε
+        if let Count::AtLeast(0) = count {
+            if inner_simplified == Self::Empty {
+                return Self::Epsilon;
+            }
+        }
+
+        // (r*)* = r*
+        if let Count::AtLeast(0) = count {
+            if let Self::Count(_, Count::AtLeast(0)) = inner_simplified {
+                return inner_simplified;
+            }
+        }
+
+        // (r?)* = (r+)* = (r?)+ = (r*)+ = r*
+        if let (Count::AtLeast(0), Self::Count(r, Count::Range(0, 1) | Count::AtLeast(1)))
+        | (Count::AtLeast(1), Self::Count(r, Count::Range(0, 1) | Count::AtLeast(0))) =
+            (count, &inner_simplified)
+        {
+            return Self::Count(r.clone(), Count::AtLeast(0));
+        }
+
+        // (ε)+ = ε
+        if let Count::AtLeast(1) = count {
+            if inner_simplified == Self::Epsilon {
+                return Self::Epsilon;
+            }
+        }
+
+        // (r*){n,m} = r* for m > 0
+        if let Self::Count(_, Count::AtLeast(0)) = inner_simplified {
+            if count.bounds().1 != Some(0) {
+                return inner_simplified;
+            }
+        }
+
+        // ∅{0,m} = ε, ∅{n,m} = ∅ for n > 0
+        if inner_simplified.is_dead() {
+            return if count.bounds().0 == 0 {
+                Self::Epsilon
+            } else {
+                Self::Empty
+            };
+        }
+        // ε{n,m} = ε
+        if inner_simplified == Self::Epsilon {
+            return Self::Epsilon;
+        }
+
+        // r{a,b}{c,d} = r{ac,bd} if every number of repetitions in between is allowed, so that the
+        // derivatives of nested counts do not keep one alternative per way of splitting the input
+        if let Self::Count(r, inner_count) = &inner_simplified {
+            if let Some(count) = Count::nest(*inner_count, count) {
+                return Self::simplify_count((**r).clone(), count, options);
+            }
+        }
+
+        // r{0} = ε
+        if let Count::Exact(0) = count {
+            return Self::Epsilon;
+        }
+        // r{1} = r
+        if let Count::Exact(1) = count {
+            return inner_simplified;
+        }
+
+        Self::Count(Node::new(inner_simplified), count)
+    }
+
+    /// Returns a regex that matches the reverse of every string matched by the regex.
+    pub fn reverse(&self) -> Self {
+        ensure_stack(|| match self {
+            Self::Empty | Self::Epsilon | Self::Literal(_) | Self::Class(_) | Self::Pred(_) => {
+                self.clone()
+            }
+            Self::Concat(left, right) => {
+                Self::Concat(Node::new(right.reverse()), Node::new(left.reverse()))
+            }
+            Self::Or(left, right) => {
+                Self::Or(Node::new(left.reverse()), Node::new(right.reverse()))
+            }
+            Self::Shuffle(left, right) => {
+                Self::Shuffle(Node::new(left.reverse()), Node::new(right.reverse()))
+            }
+            Self::Count(inner, count) => Self::Count(Node::new(inner.reverse()), *count),
+            Self::Str(s) => Self::Str(s.chars().rev().collect()),
+        })
+    }
+
+    /// Compares two regexes structurally, visiting at most `node_budget` pairs of nodes.
+    ///
+    /// Returns `Some(true)` or `Some(false)` if equality was decided within the budget, otherwise
+    /// returns `None`.
+    pub fn eq_bounded(&self, other: &Self, node_budget: usize) -> Option<bool> {
+        let mut stack = vec![(self, other)];
+        let mut visited = 0;
+
+        while let Some((left, right)) = stack.pop() {
+            if visited == node_budget {
+                return None;
+            }
+            visited += 1;
+
+            // the same node is equal to itself, however large it is
+            if ptr::eq(left, right) {
+                continue;
+            }
+            if discriminant(left) != discriminant(right) {
+                return Some(false);
+            }
+
+            match (left, right) {
+                (Self::Concat(l1, r1), Self::Concat(l2, r2))
+                | (Self::Or(l1, r1), Self::Or(l2, r2))
+                | (Self::Shuffle(l1, r1), Self::Shuffle(l2, r2)) => {
+                    stack.push((r1, r2));
+                    stack.push((l1, l2));
+                }
+                (Self::Count(inner1, count1), Self::Count(inner2, count2)) => {
+                    if count1 != count2 {
+                        return Some(false);
+                    }
+                    stack.push((inner1, inner2));
+                }
+                (Self::Literal(a), Self::Literal(b)) if a != b => return Some(false),
+                (Self::Class(a), Self::Class(b)) if a != b => return Some(false),
+                (Self::Pred(a), Self::Pred(b)) if a != b => return Some(false),
+                (Self::Str(a), Self::Str(b)) if a != b => return Some(false),
+                _ => {}
+            }
+        }
+
+        Some(true)
+    }
+
+    /// Returns a hash of the regex's structure.
+    fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns whether the given character is matched if the regex matches exactly one character
+    /// (a literal, class or predicate), otherwise returns `None`.
+    pub(crate) fn matches_single_char(&self, c: char) -> Option<bool> {
+        match self {
+            Self::Literal(ch) => Some(*ch == c),
+            Self::Class(ranges) => Some(ranges.iter().any(|range| range.contains(c))),
+            Self::Pred(predicate) => Some(predicate.test(c)),
+            _ => None,
+        }
+    }
+
+    /// Returns the first factor of the regex and the rest of it (e.g., `a` and `bc` for `abc`).
+    fn split_first(&self) -> (Self, Self) {
+        ensure_stack(|| match self {
+            Self::Concat(left, right) => {
+                let (first, rest) = left.split_first();
+                (first, Self::concat(rest, right.as_ref().clone()))
             }
-        )
+            Self::Str(s) => {
+                let mut chars = s.chars();
+                let first = chars.next().map_or(Self::Epsilon, Self::Literal);
+                (first, Self::string(chars.as_str()))
+            }
+            other => (other.clone(), Self::Epsilon),
+        })
     }
-}
 
-impl Regex {
-    pub fn star(&self) -> Self {
-        Self::Count(Box::new(self.clone()), Count::AtLeast(0))
+    /// Returns the concatenation of `left` and `right`, joining a literal or string at the end of
+    /// `left` with one at the start of `right` (e.g., `a·b = ab`, `a·(bc)d = (abc)d`).
+    fn concat_joining(mut left: Self, mut right: Self) -> Self {
+        match (&mut left, &mut right) {
+            (Self::Literal(c), Self::Literal(d)) => Self::Str(String::from_iter([*c, *d])),
+            (Self::Literal(c), Self::Str(s)) => Self::Str(format!("{c}{s}")),
+            (Self::Str(s), Self::Literal(c)) => {
+                s.push(*c);
+                left
+            }
+            (Self::Str(s), Self::Str(t)) => {
+                s.push_str(t);
+                left
+            }
+            (Self::Literal(_) | Self::Str(_), Self::Concat(first, rest))
+                if matches!(**first, Self::Literal(_) | Self::Str(_)) =>
+            {
+                let rest = rest.take();
+                let first = Self::concat_joining(left, first.take());
+                Self::Concat(Node::new(first), Node::new(rest))
+            }
+            (Self::Concat(rest, last), Self::Literal(_) | Self::Str(_))
+                if matches!(**last, Self::Literal(_) | Self::Str(_)) =>
+            {
+                let rest = rest.take();
+                let last = Self::concat_joining(last.take(), right);
+                Self::Concat(Node::new(rest), Node::new(last))
+            }
+            _ => Self::Concat(Node::new(left), Node::new(right)),
+        }
     }
 
-    pub fn plus(&self) -> Self {
-        Self::Count(Box::new(self.clone()), Count::AtLeast(1))
+    /// Returns `true` if the regex has subexpressions (e.g., `ab` or `a*`), otherwise returns `false`.
+    const fn has_children(&self) -> bool {
+        matches!(
+            self,
+            Self::Concat(_, _) | Self::Or(_, _) | Self::Shuffle(_, _) | Self::Count(_, _)
+        )
     }
 
-    pub fn optional(&self) -> Self {
-        Self::Count(Box::new(self.clone()), Count::Range(0, 1))
+    /// Moves the regex out, leaving `∅` in its place.
+    fn take(&mut self) -> Self {
+        std::mem::replace(self, Self::Empty)
     }
 
-    fn is_nullable_(&self) -> bool {
-        match self {
-            Self::Empty => false,
-            Self::Epsilon => true,
-            Self::Literal(_) => false,
-            Self::Concat(left, right) => left.is_nullable_() && right.is_nullable_(),
-            Self::Or(left, right) => left.is_nullable_() || right.is_nullable_(),
-            Self::Class(_) => false,
-            Self::Count(_, quantifier) => match quantifier {
-                Count::Exact(n) => *n == 0,
-                Count::Range(min, _) | Count::AtLeast(min) => *min == 0,
-            },
+    /// Builds a sorted and deduplicated alternation from a flattened list of simplified
+    /// alternatives, also applying the algebraic rules if `algebraic` is `true`.
+    fn or_of_alternatives(mut alternatives: Vec<Self>, options: &SimplifyOptions) -> Self {
+        // (r ∪ s) ∪ t = r ∪ (s ∪ t), r ∪ s = s ∪ r, r ∪ r = r
+        alternatives.sort_unstable();
+        alternatives.dedup_by(|a, b| a.eq_for_simplify(b));
+        if !options.algebraic {
+            return Self::alternation(alternatives, options.rebalance);
         }
-    }
 
-    /// If the regex is nullable, returns `Regex::Epsilon`, otherwise returns `Regex::Empty`.
-    pub fn is_nullable(&self) -> Self {
-        if self.is_nullable_() {
-            Self::Epsilon
+        // r ∪ r* = r*, r ∪ r+ = r+
+        let repeated = alternatives
+            .iter()
+            .map(|alternative| {
+                alternatives.iter().any(|other| {
+                    matches!(other, Self::Count(inner, Count::AtLeast(0 | 1))
+                        if inner.eq_for_simplify(alternative))
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut repeated = repeated.into_iter();
+        alternatives.retain(|_| !repeated.next().unwrap_or(false));
+
+        // r{a,b} ∪ r{c,d} = r{min(a,c),max(b,d)} if the counts overlap or touch, so that the
+        // counters of repetitions of the same regex (e.g., in the derivatives of `(a{2,5})+`) are
+        // merged rather than multiplied
+        let alternatives = Self::merge_counts(alternatives);
+
+        // rs ∪ rt = r(s ∪ t)
+        let mut groups: Vec<(Self, Vec<Self>, &Self, usize)> = Vec::new();
+        for alternative in &alternatives {
+            let (first, rest) = alternative.split_first();
+            // the rests are flattened, since a rest may itself be an alternation (e.g., `s ∪ t` in
+            // `r(s ∪ t)`) whose alternatives should be deduplicated with the others
+            match groups.iter_mut().find(|(other, _, _, _)| *other == first) {
+                Some((_, rests, _, members)) => {
+                    rest.collect_alternatives(rests);
+                    *members += 1;
+                }
+                None => {
+                    let mut rests = Vec::new();
+                    rest.collect_alternatives(&mut rests);
+                    groups.push((first, rests, alternative, 1));
+                }
+            }
+        }
+        let mut factored = groups
+            .into_iter()
+            .map(|(first, rests, alternative, members)| {
+                if members == 1 {
+                    alternative.clone()
+                } else {
+                    Self::concat(first, Self::or_of_alternatives(rests, options))
+                }
+            })
+            .collect::<Vec<_>>();
+        factored.sort_unstable();
+        factored.dedup_by(|a, b| a.eq_for_simplify(b));
+
+        // ε ∪ r = r if r is nullable, otherwise ε ∪ r = r?
+        let optional = factored.first() == Some(&Self::Epsilon);
+        if optional {
+            factored.remove(0);
+            if factored.is_empty() {
+                return Self::Epsilon;
+            }
+        }
+        let needs_optional = optional && !factored.iter().any(Self::nullable);
+
+        let alternation = Self::alternation(factored, options.rebalance);
+        if needs_optional {
+            Self::Count(Node::new(alternation), Count::Range(0, 1))
         } else {
-            Self::Empty
+            alternation
         }
     }
 
-    /// Returns the Brzozowski derivative of the regex with respect to a given character.
-    pub fn derivative(&self, c: char) -> Self {
-        match self {
-            Self::Empty | Self::Epsilon => Self::Empty,
-            Self::Literal(ch) => {
-                if *ch == c {
-                    Self::Epsilon
-                } else {
-                    Self::Empty
+    /// Merges the counts of the same regex among the sorted alternatives wherever the numbers of
+    /// repetitions they allow overlap or touch (e.g., `a{2,4}` and `a{5}` into `a{2,5}`).
+    fn merge_counts(alternatives: Vec<Self>) -> Vec<Self> {
+        let mut merged = Vec::with_capacity(alternatives.len());
+        let mut alternatives = alternatives.into_iter().peekable();
+        while let Some(alternative) = alternatives.next() {
+            let Self::Count(inner, count) = &alternative else {
+                merged.push(alternative);
+                continue;
+            };
+
+            // the counts of the same regex are next to each other, since the alternatives are sorted
+            let mut counts = vec![*count];
+            while let Some(Self::Count(next_inner, next_count)) = alternatives.peek() {
+                if !next_inner.eq_for_simplify(inner) {
+                    break;
                 }
+                counts.push(*next_count);
+                alternatives.next();
             }
-            Self::Concat(left, right) => Self::Or(
-                Box::new(Self::Concat(Box::new(left.derivative(c)), right.clone()).simplify()),
-                Box::new(
-                    Self::Concat(Box::new(left.is_nullable()), Box::new(right.derivative(c)))
-                        .simplify(),
-                ),
-            ),
-            Self::Or(left, right) => {
-                Self::Or(Box::new(left.derivative(c)), Box::new(right.derivative(c)))
-            }
-            Self::Class(ranges) => {
-                for range in ranges {
-                    if range.contains(c) {
-                        return Self::Epsilon;
-                    }
-                }
-                Self::Empty
+            if counts.len() == 1 {
+                merged.push(alternative);
+                continue;
             }
-            Self::Count(inner, count) => {
-                let new_count = match count {
-                    Count::Exact(n) => Count::Exact(n.saturating_sub(1)),
-                    Count::Range(min, max) => {
-                        Count::Range(min.saturating_sub(1), max.saturating_sub(1))
-                    }
-                    Count::AtLeast(min) => Count::AtLeast(min.saturating_sub(1)),
-                };
 
-                Self::Concat(
-                    Box::new(inner.derivative(c)),
-                    Box::new(Self::Count(inner.clone(), new_count)),
-                )
+            for count in Count::merge(counts) {
+                merged.push(Self::Count(inner.clone(), count));
             }
         }
-        .simplify()
+        merged
     }
 
-    /// Simplifies the regex.
-    pub fn simplify(&self) -> Self {
-        match self {
-            Self::Empty => Self::Empty,
-            Self::Epsilon => Self::Epsilon,
-            Self::Literal(c) => Self::Literal(*c),
-            Self::Concat(left, right) => {
-                let left_simplified = left.simplify();
-                let right_simplified = right.simplify();
+    /// Returns the right-nested alternation of the given alternatives, or `Regex::Empty` if there
+    /// are none. If `balanced` is `true`, at least 8 alternatives are nested in a balanced tree
+    /// instead.
+    fn alternation(mut alternatives: Vec<Self>, balanced: bool) -> Self {
+        if balanced && alternatives.len() >= REBALANCE_MIN_LEN {
+            return Self::combine_balanced(alternatives, |left, right| {
+                Self::Or(Node::new(left), Node::new(right))
+            })
+            .unwrap();
+        }
 
-                // r∅ = ∅r = ∅
-                if left_simplified == Self::Empty || right_simplified == Self::Empty {
-                    return Self::Empty;
-                }
+        let last = alternatives.pop().unwrap_or(Self::Empty);
+        alternatives.into_iter().rev().fold(last, |right, left| {
+            Self::Or(Node::new(left), Node::new(right))
+        })
+    }
+
+    /// Adds the alternatives of a flattened alternation to `alternatives`, skipping `∅` (e.g., `a`,
+    /// `b` and `c` for `(a|b)|c`).
+    fn collect_alternatives(mut self, alternatives: &mut Vec<Self>) {
+        ensure_stack(|| match &mut self {
+            Self::Or(left, right) => {
+                left.take().collect_alternatives(alternatives);
+                right.take().collect_alternatives(alternatives);
+            }
+            Self::Empty => {}
+            _ => alternatives.push(self),
+        });
+    }
+
+    /// Checks equality for simplification rules, bailing out of the structural comparison early on
+    /// large regexes and checking their hashes before comparing them in full.
+    fn eq_for_simplify(&self, other: &Self) -> bool {
+        if ptr::eq(self, other) {
+            return true;
+        }
+        self.eq_bounded(other, SIMPLIFY_EQ_BUDGET)
+            .unwrap_or_else(|| self.structural_hash() == other.structural_hash() && self == other)
+    }
+
+    /// Returns one representative character for each set of characters that every literal and
+    /// class in the given regexes treats identically.
+    ///
+    /// Predicates are not taken into account, so each predicate is only tested on the representative
+    /// of each set.
+    pub(crate) fn representative_chars(regexes: &[&Self]) -> Vec<char> {
+        Minterms::new(regexes).representatives()
+    }
 
-                // εr = rε = r
-                if left_simplified == Self::Epsilon {
-                    return right_simplified;
+    /// Partitions the alphabet into sets of characters that have the same derivative, and returns
+    /// each set along with that derivative.
+    ///
+    /// The sets are disjoint and together cover every character, and each is a union of classes of
+    /// `alphabet_classes`, split further by which predicates accept each character, so only one
+    /// derivative is taken per class. Predicates are tested on every character, so this takes time
+    /// proportional to the size of the alphabet for regexes that contain them.
+    pub fn derivative_classes(&self) -> Vec<(CharSet, Self)> {
+        let mut predicates = Vec::new();
+        self.collect_predicates(&mut predicates);
+        let sets = split_by_predicates(Minterms::new(&[self]).sets(), &predicates);
+        let mut indices = HashMap::new();
+        let mut classes: Vec<(CharSet, Self)> = Vec::new();
+
+        for set in sets {
+            let Some(c) = set.iter_chars().next() else {
+                continue;
+            };
+            let derivative = self.derivative(c);
+            match indices.get(&derivative) {
+                Some(&index) => {
+                    let (chars, _): &mut (CharSet, Self) = &mut classes[index];
+                    *chars = chars.union(&set);
                 }
-                if right_simplified == Self::Epsilon {
-                    return left_simplified;
+                None => {
+                    indices.insert(derivative.clone(), classes.len());
+                    classes.push((set, derivative));
                 }
+            }
+        }
+
+        classes
+    }
 
-                Self::Concat(Box::new(left_simplified), Box::new(right_simplified))
+    /// Returns the left quotient of the regex by `prefix`, which matches every string `s` for which
+    /// some string `p` matched by `prefix` makes `ps` match the regex.
+    ///
+    /// This explores the product of the derivative automata of both regexes, so it only terminates
+    /// quickly for regexes with a modest number of distinct derivatives. Predicates are only tested on
+    /// one representative character per set of characters the literals and classes can tell apart.
+    pub fn quotient(&self, prefix: &Self) -> Self {
+        let alphabet = Self::representative_chars(&[self, prefix]);
+        let start = (self.clone(), prefix.clone());
+
+        let mut seen = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+        let mut result = Self::Empty;
+
+        while let Some((remainder, prefix_remainder)) = queue.pop_front() {
+            if prefix_remainder.nullable() {
+                result = Self::Or(Node::new(result), Node::new(remainder.clone())).simplify();
             }
-            Self::Or(left, right) => {
-                let left_simplified = left.simplify();
-                let right_simplified = right.simplify();
 
-                // r ∪ ∅ = ∅ ∪ r = r
-                if left_simplified == Self::Empty {
-                    return right_simplified;
+            for &c in &alphabet {
+                let next_prefix_remainder = prefix_remainder.derivative(c);
+                if next_prefix_remainder == Self::Empty {
+                    continue;
                 }
-                if right_simplified == Self::Empty {
-                    return left_simplified;
+                let next_remainder = remainder.derivative(c);
+                if next_remainder == Self::Empty {
+                    continue;
                 }
 
-                // r ∪ r = r
-                if left_simplified == right_simplified {
-                    return left_simplified;
+                let next = (next_remainder, next_prefix_remainder);
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
                 }
+            }
+        }
+
+        result
+    }
 
-                Self::Or(Box::new(left_simplified), Box::new(right_simplified))
+    /// Returns `true` if some string is matched by both the regex and `other`, otherwise returns
+    /// `false`.
+    ///
+    /// Like `quotient`, this explores the product of the derivative automata of both regexes, and
+    /// predicates are only tested on one representative character per set of characters the literals
+    /// and classes can tell apart.
+    pub fn intersects(&self, other: &Self) -> bool {
+        let alphabet = Self::representative_chars(&[self, other]);
+        let start = (self.clone(), other.clone());
+
+        let mut seen = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((remainder, other_remainder)) = queue.pop_front() {
+            if remainder.nullable() && other_remainder.nullable() {
+                return true;
             }
-            Self::Class(ranges) => {
-                let mut new_ranges = Vec::new();
-                let mut changed = false;
-                for range in ranges {
-                    if let CharRange::Range(start, end) = range {
-                        if start == end {
-                            new_ranges.push(CharRange::Single(*start));
-                            changed = true;
-                        } else {
-                            new_ranges.push(range.clone());
-                        }
-                    } else {
-                        new_ranges.push(range.clone());
-                    }
-                }
 
-                if changed {
-                    return Self::Class(new_ranges).simplify();
+            for &c in &alphabet {
+                let next_remainder = remainder.derivative(c);
+                if next_remainder == Self::Empty {
+                    continue;
                 }
-
-                if ranges.len() == 1 {
-                    if let CharRange::Single(c) = ranges[0] {
-                        return Self::Literal(c);
-                    }
+                let next_other_remainder = other_remainder.derivative(c);
+                if next_other_remainder == Self::Empty {
+                    continue;
                 }
 
-                let mut new_ranges = ranges.clone();
-                new_ranges.sort_unstable_by_key(|r| match r {
-                    CharRange::Single(c) => *c,
-                    CharRange::Range(start, _) => *start,
-                });
-                Self::Class(new_ranges)
+                let next = (next_remainder, next_other_remainder);
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
             }
-            Self::Count(inner, count) => {
-                let inner_simplified = inner.simplify();
+        }
 
-                // ∅* = ε* = ε
-                if let Count::AtLeast(0) = count {
-                    if inner_simplified == Self::Empty {
-                        return Self::Epsilon;
-                    }
-                }
+        false
+    }
 
-                // (r*)* = r*
-                if let Count::AtLeast(0) = count {
-                    if let Self::Count(_, Count::AtLeast(0)) = inner_simplified {
-                        return inner_simplified;
-                    }
+    /// Returns a regex matching the strings that are matched by both the regex and `other` (e.g.,
+    /// `a*b` for `[ab]*b` and `a*b*`).
+    ///
+    /// This explores the product of the derivative automata of both regexes, with edges labelled by
+    /// the intersections of their `derivative_classes`, so predicates are tested on every character.
+    /// The product is then turned back into a regex by eliminating its states one at a time, which
+    /// may give a regex much larger than either input.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let start = (self.clone(), other.clone());
+        let mut indices = HashMap::from([(start.clone(), 0)]);
+        let mut states = vec![start];
+        // `edges[i][j]` matches the characters leading from state `i` to state `j`
+        let mut edges: Vec<HashMap<usize, Self>> = Vec::new();
+
+        while let Some((remainder, other_remainder)) = states.get(edges.len()).cloned() {
+            let mut state_edges: HashMap<usize, Self> = HashMap::new();
+            let other_classes = other_remainder.derivative_classes();
+            for (set, next_remainder) in remainder.derivative_classes() {
+                if next_remainder == Self::Empty {
+                    continue;
                 }
-
-                // (ε)+ = ε
-                if let Count::AtLeast(1) = count {
-                    if inner_simplified == Self::Epsilon {
-                        return Self::Epsilon;
+                for (other_set, next_other_remainder) in &other_classes {
+                    let both = set.intersection(other_set);
+                    if *next_other_remainder == Self::Empty || both.is_empty() {
+                        continue;
                     }
-                }
 
-                // ∅{n,m} = ∅
-                if inner_simplified == Self::Empty {
-                    return Self::Empty;
+                    let next = (next_remainder.clone(), next_other_remainder.clone());
+                    let index = *indices.entry(next.clone()).or_insert_with(|| {
+                        states.push(next);
+                        states.len() - 1
+                    });
+                    let label = Self::class(both.ranges());
+                    let edge = state_edges.remove(&index).unwrap_or(Self::Empty);
+                    state_edges.insert(index, Self::or(edge, label));
                 }
-                // ε{n,m} = ε
-                if inner_simplified == Self::Epsilon {
-                    return Self::Epsilon;
+            }
+            edges.push(state_edges);
+        }
+
+        // each state matches `Σ edges[i][j] · state j`, plus `ε` if both remainders are nullable
+        let mut accepts = states
+            .iter()
+            .map(|(remainder, other_remainder)| {
+                if remainder.nullable() && other_remainder.nullable() {
+                    Self::Epsilon
+                } else {
+                    Self::Empty
                 }
+            })
+            .collect::<Vec<_>>();
+
+        // eliminate the states from the last to the first, so the first is left in terms of itself
+        for k in (0..states.len()).rev() {
+            let own = edges[k]
+                .remove(&k)
+                .map_or(Self::Epsilon, |edge| edge.star());
+            accepts[k] = Self::concat(own.clone(), accepts[k].clone());
+            for edge in edges[k].values_mut() {
+                *edge = Self::concat(own.clone(), edge.clone());
+            }
 
-                // r{n,n} = r{n}
-                if let Count::Range(min, max) = count {
-                    if min == max {
-                        return Self::Count(Box::new(inner_simplified), Count::Exact(*min))
-                            .simplify();
-                    }
+            let (earlier, rest) = edges.split_at_mut(k);
+            let eliminated = &rest[0];
+            for (i, state_edges) in earlier.iter_mut().enumerate() {
+                let Some(to_k) = state_edges.remove(&k) else {
+                    continue;
+                };
+                accepts[i] = Self::or(
+                    accepts[i].clone(),
+                    Self::concat(to_k.clone(), accepts[k].clone()),
+                );
+                for (&j, edge) in eliminated {
+                    let through_k = Self::concat(to_k.clone(), edge.clone());
+                    let existing = state_edges.remove(&j).unwrap_or(Self::Empty);
+                    state_edges.insert(j, Self::or(existing, through_k));
                 }
+            }
+        }
+
+        accepts.swap_remove(0).simplify()
+    }
+
+    /// Returns `true` if every string matched by the regex is also matched by `other`, otherwise
+    /// returns `false`.
+    ///
+    /// Like `intersects`, this explores the product of the derivative automata of both regexes, and
+    /// predicates are only tested on one representative character per set of characters the literals
+    /// and classes can tell apart.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        let alphabet = Self::representative_chars(&[self, other]);
+        let start = (self.clone(), other.clone());
+
+        let mut seen = HashSet::from([start.clone()]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((remainder, other_remainder)) = queue.pop_front() {
+            if remainder.nullable() && !other_remainder.nullable() {
+                return false;
+            }
 
-                // r{0} = ε
-                if let Count::Exact(0) = count {
-                    return Self::Epsilon;
+            for &c in &alphabet {
+                let next_remainder = remainder.derivative(c);
+                if next_remainder == Self::Empty {
+                    continue;
                 }
-                // r{1} = r
-                if let Count::Exact(1) = count {
-                    return inner_simplified;
+
+                let next = (next_remainder, other_remainder.derivative(c));
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
                 }
+            }
+        }
+
+        true
+    }
+
+    /// Returns the derivative of the regex with respect to each character of the given string in turn.
+    pub fn derivative_str(&self, s: &str) -> Self {
+        self.derivative_chars(s.chars())
+    }
+
+    /// Returns the derivative of the regex with respect to each of the given characters in turn,
+    /// stopping early once it is `∅`.
+    ///
+    /// Simplification turns every derivative that matches nothing into `∅` (e.g., the derivative of
+    /// `x*[]` with respect to `x`), so checking for `∅` after each character is enough to stop as
+    /// soon as the string can no longer match. The regex itself is not simplified, so it is checked
+    /// once with `matches_nothing` before starting.
+    pub(crate) fn derivative_chars(&self, chars: impl IntoIterator<Item = char>) -> Self {
+        if self.matches_nothing() {
+            return Self::Empty;
+        }
 
-                Self::Count(Box::new(inner_simplified), *count)
+        let mut chars = chars.into_iter();
+        let Some(first) = chars.next() else {
+            return self.clone();
+        };
+        let mut current = self.derivative(first);
+        for c in chars {
+            // no string can match ∅, so its derivatives are all ∅
+            if current == Self::Empty {
+                break;
             }
+            current = current.derivative_of_simplified(c);
         }
+        current
     }
 
-    /// Returns `true` if the regex matches the given string, otherwise returns `false`.
+    /// Returns `true` if the regex matches the whole of the given string, otherwise returns `false`.
+    ///
+    /// This is the same as `is_full_match`; unlike `is_match` in the `regex` crate, a match of part
+    /// of the string is not enough (see `is_partial_match`).
+    ///
+    /// Each distinct derivative's derivative with respect to each class of characters the regex
+    /// treats the same is only calculated once, so repetitive input (e.g., `(a*b*c*)*d+` on
+    /// `abcabc...d`) mostly reuses derivatives it has already taken, like a lazy DFA. The
+    /// derivatives are kept in a cache on each thread, so matching with the same regex again
+    /// reuses them too (see `matches_with` for a cache of your own). Short strings are matched
+    /// without the cache unless it already holds the regex, and strings whose derivatives are
+    /// mostly all different stop using it.
     pub fn matches(&self, s: &str) -> bool {
-        let mut current = self.clone();
-        for c in s.chars() {
-            current = current.derivative(c);
-        }
-        current.is_nullable_()
+        with_thread_cache(|cache| {
+            if s.len() < SHORT_INPUT_LEN && !cache.holds(self) {
+                return self.derivative_str(s).nullable();
+            }
+            self.matches_with(s, cache)
+        })
+    }
+
+    /// Like `matches`, but keeps the derivatives in the given cache rather than this thread's, so
+    /// a hot loop can reuse them (and the cache's allocations) across calls.
+    ///
+    /// The cache can be shared between regexes, but is emptied whenever it is given a different
+    /// regex from the last one, so it pays to keep one cache per regex.
+    pub fn matches_with(&self, s: &str, cache: &mut Cache) -> bool {
+        !self.matches_nothing() && cache.matcher(self).matches_once(s)
+    }
+
+    /// Returns `true` if the regex matches the string made of the given characters (e.g., a `Chars`
+    /// or characters decoded from a stream), otherwise returns `false`.
+    ///
+    /// No more characters are taken from the iterator once the string can no longer match, so it can
+    /// be used with iterators that never end.
+    pub fn matches_iter<I: IntoIterator<Item = char>>(&self, chars: I) -> bool {
+        self.derivative_chars(chars).nullable()
+    }
+
+    /// Returns `true` if some string starting with the given prefix matches the regex (including
+    /// the prefix itself), otherwise returns `false` (e.g., `\d{3}-\d{4}` could match `555-`, but
+    /// not `555x`).
+    ///
+    /// This checks that the derivative with respect to the prefix matches any string at all, not
+    /// just the empty string, so it tells whether input typed so far is still potentially valid.
+    pub fn could_match(&self, prefix: &str) -> bool {
+        !self.derivative_str(prefix).matches_nothing()
+    }
+
+    /// Returns the set of characters that some string matched by the regex starts with, i.e., the
+    /// characters after which it can still match (e.g., `[a-c]x|dy` gives `[a-d]`).
+    ///
+    /// Taken from the derivative with respect to the input so far, this is what may be typed next.
+    /// Like `derivative_classes`, predicates are tested on every character.
+    pub fn next_chars(&self) -> CharSet {
+        self.derivative_classes()
+            .into_iter()
+            .filter(|(_, derivative)| !derivative.matches_nothing())
+            .fold(CharSet::default(), |chars, (set, _)| chars.union(&set))
+    }
+
+    /// Returns `true` if the regex has a predicate, otherwise returns `false`.
+    pub(crate) fn has_predicate(&self) -> bool {
+        ensure_stack(|| match self {
+            Self::Pred(_) => true,
+            Self::Empty | Self::Epsilon | Self::Literal(_) | Self::Class(_) | Self::Str(_) => false,
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right) => {
+                left.has_predicate() || right.has_predicate()
+            }
+            Self::Count(inner, _) => inner.has_predicate(),
+        })
+    }
+
+    /// Adds each distinct predicate in the regex to `predicates`.
+    fn collect_predicates(&self, predicates: &mut Vec<Predicate>) {
+        ensure_stack(|| match self {
+            Self::Pred(predicate) => {
+                if !predicates.contains(predicate) {
+                    predicates.push(predicate.clone());
+                }
+            }
+            Self::Empty | Self::Epsilon | Self::Literal(_) | Self::Class(_) | Self::Str(_) => {}
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right) => {
+                left.collect_predicates(predicates);
+                right.collect_predicates(predicates);
+            }
+            Self::Count(inner, _) => inner.collect_predicates(predicates),
+        });
+    }
+
+    /// Returns the number of nodes in the regex (e.g., `ab|c*` has 4: the alternation, the string
+    /// `ab`, the count and the literal `c`), which grows as derivatives blow up.
+    pub fn node_count(&self) -> usize {
+        ensure_stack(|| match self {
+            Self::Empty
+            | Self::Epsilon
+            | Self::Literal(_)
+            | Self::Class(_)
+            | Self::Pred(_)
+            | Self::Str(_) => 1,
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right) => {
+                1 + left.node_count() + right.node_count()
+            }
+            Self::Count(inner, _) => 1 + inner.node_count(),
+        })
+    }
+
+    /// Returns an estimate of the number of bytes the regex takes on the heap: its boxed nodes, and
+    /// the ranges of its classes and the text of its strings and predicate names.
+    ///
+    /// This does not count the regex itself, which may be on the stack, or the functions of
+    /// predicates, which are shared by every copy of the regex.
+    pub fn heap_bytes_estimate(&self) -> usize {
+        ensure_stack(|| match self {
+            Self::Empty | Self::Epsilon | Self::Literal(_) => 0,
+            Self::Concat(left, right) | Self::Or(left, right) | Self::Shuffle(left, right) => {
+                2 * size_of::<NodeData>() + left.heap_bytes_estimate() + right.heap_bytes_estimate()
+            }
+            Self::Count(inner, _) => size_of::<NodeData>() + inner.heap_bytes_estimate(),
+            Self::Class(ranges) => ranges.capacity() * size_of::<CharRange>(),
+            Self::Pred(predicate) => predicate.name.capacity(),
+            Self::Str(s) => s.capacity(),
+        })
+    }
+
+    /// Like `matches`, but also returns statistics about the work done while matching.
+    pub fn matches_counted(&self, s: &str) -> (bool, EvalStats) {
+        let mut stats = EvalStats {
+            max_size: self.node_count(),
+            ..EvalStats::default()
+        };
+
+        let (matched, derivative_calls, simplify_calls) = count_calls(|| {
+            let mut current = if self.matches_nothing() {
+                Self::Empty
+            } else {
+                self.clone()
+            };
+            for c in s.chars() {
+                if current == Self::Empty {
+                    break;
+                }
+                current = current.derivative(c);
+
+                let size = current.node_count();
+                stats.derivative_steps += 1;
+                stats.nodes_allocated += size;
+                stats.max_size = stats.max_size.max(size);
+            }
+            current.nullable()
+        });
+        stats.derivative_calls = derivative_calls;
+        stats.simplify_calls = simplify_calls;
+
+        (matched, stats)
+    }
+
+    /// Like `matches_counted`, but also measures the time spent simplifying, and reuses the
+    /// derivative of each distinct derivative with respect to each character, counting how often
+    /// it could.
+    ///
+    /// Measuring and caching make this slower than `matches`, so it is meant for finding out which
+    /// patterns are expensive to match, not for matching.
+    pub fn matches_with_stats(&self, s: &str) -> (bool, MatchStats) {
+        let mut stats = MatchStats {
+            counts: EvalStats {
+                max_size: self.node_count(),
+                ..EvalStats::default()
+            },
+            ..MatchStats::default()
+        };
+        let mut cache: HashMap<(Self, char), Self> = HashMap::new();
+
+        let ((matched, derivative_calls, simplify_calls), simplify_time) =
+            measure_simplify_time(|| {
+                count_calls(|| {
+                    let mut current = if self.matches_nothing() {
+                        Self::Empty
+                    } else {
+                        self.clone()
+                    };
+                    for c in s.chars() {
+                        if current == Self::Empty {
+                            break;
+                        }
+                        current = if let Some(next) = cache.get(&(current.clone(), c)) {
+                            stats.cache_hits += 1;
+                            next.clone()
+                        } else {
+                            stats.cache_misses += 1;
+                            let next = current.derivative(c);
+                            cache.insert((current, c), next.clone());
+                            next
+                        };
+
+                        let size = current.node_count();
+                        stats.counts.derivative_steps += 1;
+                        stats.counts.nodes_allocated += size;
+                        stats.counts.max_size = stats.counts.max_size.max(size);
+                    }
+                    current.nullable()
+                })
+            });
+        stats.counts.derivative_calls = derivative_calls;
+        stats.counts.simplify_calls = simplify_calls;
+        stats.simplify_time = simplify_time;
+
+        (matched, stats)
     }
 
     /// Tries to parse a string into a `Regex`.
@@ -363,9 +2264,41 @@ impl Regex {
     }
 }
 
+/// Tries to parse a string into a `Regex`, like `Regex::new`.
+impl TryFrom<&str> for Regex {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+/// Tries to parse a string into a `Regex`, like `Regex::new`.
+impl TryFrom<String> for Regex {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(&s)
+    }
+}
+
 mod tests {
     #[allow(unused_imports)]
     use super::*;
+    #[allow(unused_imports)]
+    use std::collections::BTreeSet;
+
+    /// Returns how deeply the regex is nested.
+    #[cfg(test)]
+    fn depth(regex: &Regex) -> usize {
+        match regex {
+            Regex::Concat(left, right) | Regex::Or(left, right) | Regex::Shuffle(left, right) => {
+                1 + depth(left).max(depth(right))
+            }
+            Regex::Count(inner, _) => 1 + depth(inner),
+            _ => 1,
+        }
+    }
 
     // comprehensive derivative tests
     #[test]
@@ -394,25 +2327,53 @@ mod tests {
 
     #[test]
     fn test_derivative_concat_first_char() {
-        let regex = Regex::Concat(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Concat(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert_eq!(regex.derivative('a'), Regex::Literal('b'));
     }
 
+    #[test]
+    fn test_derivative_concat_single_char_fast_path() {
+        let regex = Regex::new("[a-c]x*").unwrap();
+        let (derivative, derivative_calls, _) = count_calls(|| regex.derivative('b'));
+        assert_eq!(derivative, Regex::new("x*").unwrap());
+        assert_eq!(derivative_calls, 1);
+
+        let (derivative, _, simplify_calls) = count_calls(|| regex.derivative('z'));
+        assert_eq!(derivative, Regex::Empty);
+        assert_eq!(simplify_calls, 0);
+
+        let regex = Regex::pred("digit", |c: char| c.is_ascii_digit()) + Regex::literal('a');
+        assert_eq!(regex.derivative('1'), Regex::Literal('a'));
+        assert_eq!(regex.derivative('a'), Regex::Empty);
+    }
+
     #[test]
     fn test_derivative_or_left_match() {
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert_eq!(regex.derivative('a'), Regex::Epsilon);
     }
 
     #[test]
     fn test_derivative_or_right_match() {
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert_eq!(regex.derivative('b'), Regex::Epsilon);
     }
 
     #[test]
     fn test_derivative_or_no_match() {
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert_eq!(regex.derivative('c'), Regex::Empty);
     }
 
@@ -432,30 +2393,109 @@ mod tests {
 
     #[test]
     fn test_derivative_count_match() {
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Range(2, 3));
         let result = regex.derivative('a');
         assert_eq!(
             result,
-            Regex::Count(Box::new(Regex::Literal('a')), Count::Range(1, 2),)
+            Regex::Count(Node::new(Regex::Literal('a')), Count::Range(1, 2),)
         );
     }
 
     #[test]
     fn test_derivative_count_no_match() {
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Range(2, 3));
         assert_eq!(regex.derivative('b'), Regex::Empty);
     }
 
+    #[test]
+    fn test_matches_iter() {
+        let regex = Regex::new(r"[a-z]+\d{2}").unwrap();
+        for s in ["ab12", "a1", "", "💕12"] {
+            assert_eq!(regex.matches_iter(s.chars()), regex.matches(s), "{s}");
+        }
+        assert!(regex.matches_iter(vec!['x', '4', '2']));
+        assert!(regex.matches_iter("x".chars().chain("42".chars())));
+
+        // an endless iterator is not read past the point where it can no longer match
+        assert!(!regex.matches_iter(std::iter::repeat('1')));
+    }
+
+    #[test]
+    fn test_derivative_str() {
+        let regex = Regex::new("ab(c|d)*e").unwrap();
+        assert_eq!(regex.derivative_str(""), regex);
+        assert_eq!(
+            regex.derivative_str("abcd"),
+            regex
+                .derivative('a')
+                .derivative('b')
+                .derivative('c')
+                .derivative('d')
+        );
+        assert_eq!(regex.derivative_str("abcde"), Regex::Epsilon);
+        assert_eq!(regex.derivative_str("abx"), Regex::Empty);
+        assert_eq!(regex.derivative_str("xabcde"), Regex::Empty);
+    }
+
+    #[test]
+    fn test_derivative_classes() {
+        let regex = Regex::new("[a-z]b|c").unwrap();
+        let classes = regex.derivative_classes();
+        assert_eq!(
+            classes,
+            vec![
+                (
+                    CharSet::new(&[
+                        CharRange::Range('\0', '`'),
+                        CharRange::Range('{', char::MAX),
+                    ]),
+                    Regex::Empty
+                ),
+                (
+                    CharSet::new(&[CharRange::Range('a', 'b'), CharRange::Range('d', 'z')]),
+                    Regex::Literal('b')
+                ),
+                (
+                    CharSet::new(&[CharRange::Single('c')]),
+                    Regex::Literal('b').optional()
+                ),
+            ]
+        );
+
+        for (set, derivative) in &classes {
+            for c in ['\0', 'a', 'c', 'q', 'z', '~', '💕'] {
+                if set.contains(c) {
+                    assert_eq!(&regex.derivative(c), derivative);
+                }
+            }
+        }
+
+        let regex = Regex::pred("alpha", char::is_alphabetic).plus();
+        let classes = regex.derivative_classes();
+        assert_eq!(classes.len(), 2);
+        for (set, derivative) in &classes {
+            for c in ['a', 'é', 'β', '1', ' ', '💕'] {
+                assert_eq!(set.contains(c), regex.derivative(c) == *derivative);
+            }
+        }
+        assert!(regex.next_chars().contains('β'));
+        assert!(!regex.next_chars().contains('1'));
+    }
+
     #[test]
     fn test_derivative_complex_pattern() {
         // Pattern: a(b|c)*d
         let regex = Regex::Concat(
-            Box::new(Regex::Literal('a')),
-            Box::new(Regex::Concat(
-                Box::new(
-                    Regex::Or(Box::new(Regex::Literal('b')), Box::new(Regex::Literal('c'))).star(),
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Concat(
+                Node::new(
+                    Regex::Or(
+                        Node::new(Regex::Literal('b')),
+                        Node::new(Regex::Literal('c')),
+                    )
+                    .star(),
                 ),
-                Box::new(Regex::Literal('d')),
+                Node::new(Regex::Literal('d')),
             )),
         );
 
@@ -464,10 +2504,14 @@ mod tests {
         assert_eq!(
             d1,
             Regex::Concat(
-                Box::new(
-                    Regex::Or(Box::new(Regex::Literal('b')), Box::new(Regex::Literal('c'))).star()
+                Node::new(
+                    Regex::Or(
+                        Node::new(Regex::Literal('b')),
+                        Node::new(Regex::Literal('c'))
+                    )
+                    .star()
                 ),
-                Box::new(Regex::Literal('d'))
+                Node::new(Regex::Literal('d'))
             )
         );
 
@@ -476,10 +2520,14 @@ mod tests {
         assert_eq!(
             d2,
             Regex::Concat(
-                Box::new(
-                    Regex::Or(Box::new(Regex::Literal('b')), Box::new(Regex::Literal('c'))).star()
+                Node::new(
+                    Regex::Or(
+                        Node::new(Regex::Literal('b')),
+                        Node::new(Regex::Literal('c'))
+                    )
+                    .star()
                 ),
-                Box::new(Regex::Literal('d'))
+                Node::new(Regex::Literal('d'))
             )
         );
 
@@ -510,41 +2558,221 @@ mod tests {
     #[test]
     fn test_simplify_concat_with_empty() {
         // r∅ = ∅
-        let regex = Regex::Concat(Box::new(Regex::Literal('a')), Box::new(Regex::Empty));
+        let regex = Regex::Concat(Node::new(Regex::Literal('a')), Node::new(Regex::Empty));
         assert_eq!(regex.simplify(), Regex::Empty);
 
         // ∅r = ∅
-        let regex = Regex::Concat(Box::new(Regex::Empty), Box::new(Regex::Literal('a')));
+        let regex = Regex::Concat(Node::new(Regex::Empty), Node::new(Regex::Literal('a')));
         assert_eq!(regex.simplify(), Regex::Empty);
     }
 
     #[test]
     fn test_simplify_concat_with_epsilon() {
         // rε = r
-        let regex = Regex::Concat(Box::new(Regex::Literal('a')), Box::new(Regex::Epsilon));
+        let regex = Regex::Concat(Node::new(Regex::Literal('a')), Node::new(Regex::Epsilon));
         assert_eq!(regex.simplify(), Regex::Literal('a'));
 
         // εr = r
-        let regex = Regex::Concat(Box::new(Regex::Epsilon), Box::new(Regex::Literal('a')));
+        let regex = Regex::Concat(Node::new(Regex::Epsilon), Node::new(Regex::Literal('a')));
+        assert_eq!(regex.simplify(), Regex::Literal('a'));
+    }
+
+    #[test]
+    fn test_simplify_or_with_empty() {
+        // r ∪ ∅ = r
+        let regex = Regex::Or(Node::new(Regex::Literal('a')), Node::new(Regex::Empty));
+        assert_eq!(regex.simplify(), Regex::Literal('a'));
+
+        // ∅ ∪ r = r
+        let regex = Regex::Or(Node::new(Regex::Empty), Node::new(Regex::Literal('a')));
+        assert_eq!(regex.simplify(), Regex::Literal('a'));
+    }
+
+    #[test]
+    fn test_simplify_or_with_same() {
+        // r ∪ r = r
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('a')),
+        );
         assert_eq!(regex.simplify(), Regex::Literal('a'));
     }
 
     #[test]
-    fn test_simplify_or_with_empty() {
-        // r ∪ ∅ = r
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Empty));
-        assert_eq!(regex.simplify(), Regex::Literal('a'));
+    fn test_simplify_or_canonical() {
+        // (r ∪ s) ∪ r = s ∪ r = r ∪ s
+        let a_or_b = Regex::new("a|b").unwrap();
+        assert_eq!(Regex::new("(a|b)|a").unwrap(), a_or_b);
+        assert_eq!(Regex::new("b|a").unwrap(), a_or_b);
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('b')),
+            Node::new(Regex::Or(
+                Node::new(Regex::Empty),
+                Node::new(a_or_b.clone()),
+            )),
+        );
+        assert_eq!(regex.simplify(), a_or_b);
+
+        // The derivatives of (a|aa)*b stay finite without any other rules
+        let regex = Regex::new("(a|aa)*b").unwrap();
+        let mut states = HashSet::new();
+        let mut current = regex;
+        for _ in 0..20 {
+            states.insert(current.clone());
+            current = current.derivative('a');
+        }
+        assert!(states.len() <= 4, "{}", states.len());
+    }
+
+    #[test]
+    fn test_simplify_algebraic_rules() {
+        let a = Regex::Literal('a');
+        let b = Regex::Literal('b');
+
+        // ε ∪ r = r?, ε ∪ r* = r*
+        assert_eq!((Regex::Epsilon | a.clone()).simplify(), a.optional());
+        assert_eq!((Regex::Epsilon | a.star()).simplify(), a.star());
+
+        // r ∪ r* = r*, r ∪ r+ = r+
+        assert_eq!((a.clone() | a.star()).simplify(), a.star());
+        assert_eq!((a.plus() | a.clone()).simplify(), a.plus());
+
+        // (r?)* = (r+)* = (r?)+ = (r*)+ = r*
+        assert_eq!(a.optional().star().simplify(), a.star());
+        assert_eq!(a.plus().star().simplify(), a.star());
+        assert_eq!(a.optional().plus().simplify(), a.star());
+        assert_eq!(a.star().plus().simplify(), a.star());
+
+        // r*r* = r*
+        let regex = Regex::Concat(Node::new(a.star()), Node::new(a.star()));
+        assert_eq!(regex.simplify(), a.star());
+        assert_eq!(Regex::new("ba*a*").unwrap(), Regex::new("ba*").unwrap());
+
+        // rs ∪ rt = r(s ∪ t), r ∪ rs = rs?
+        assert_eq!(Regex::new("ab|ac").unwrap(), Regex::new("a(b|c)").unwrap());
+        assert_eq!(Regex::new("a|ab").unwrap(), Regex::new("ab?").unwrap());
+        assert_eq!(
+            Regex::new("abc|abd|b").unwrap(),
+            Regex::new("b|a(b(c|d))").unwrap()
+        );
+
+        // The rules only apply at the aggressive level
+        let regex = Regex::Or(Node::new(Regex::Epsilon), Node::new(b.clone()));
+        assert_eq!(regex.simplify_with(&SimplifyLevel::Basic.into()), regex);
+    }
+
+    #[test]
+    fn test_intersects() {
+        let regex = Regex::new("a+b").unwrap();
+        assert!(regex.intersects(&Regex::new("aaab|c").unwrap()));
+        assert!(regex.intersects(&Regex::new("[a-c]*").unwrap()));
+        assert!(!regex.intersects(&Regex::new("a+").unwrap()));
+        assert!(!regex.intersects(&Regex::Empty));
+        assert!(Regex::Epsilon.intersects(&Regex::new("a*").unwrap()));
+
+        let digit = Regex::pred("digit", |c: char| c.is_ascii_digit());
+        assert!(digit.plus().intersects(&Regex::new("[0-9]{2}").unwrap()));
+        assert!(!digit.intersects(&Regex::new("[a-z]").unwrap()));
+    }
+
+    #[test]
+    fn test_rebalance() {
+        let letters = ('a'..='p').map(|c| Regex::Class(vec![CharRange::Single(c)]));
+        let spine = letters
+            .clone()
+            .reduce(|left, right| Regex::Concat(Node::new(left), Node::new(right)))
+            .unwrap();
+        let balanced = spine.rebalance();
+        assert_eq!(depth(&spine), 16);
+        assert_eq!(depth(&balanced), 5);
+        assert_eq!(balanced.to_string(), spine.to_string());
+
+        let alternation = letters
+            .reduce(|left, right| Regex::Or(Node::new(left), Node::new(right)))
+            .unwrap();
+        assert_eq!(depth(&alternation.rebalance()), 5);
+
+        // short spines are left as they are
+        let regex = Regex::new("(a|b|c)*d").unwrap();
+        assert_eq!(regex.rebalance(), regex);
+
+        // simplifying at the aggressive level rebalances, so parsing does too
+        assert_eq!(spine.simplify(), Regex::new("abcdefghijklmnop").unwrap());
+        let regex = Regex::new(&"a*b".repeat(16)).unwrap();
+        assert!(depth(&regex) < 16);
+        assert!(regex.matches(&"aab".repeat(16)));
+        let options = SimplifyOptions {
+            rebalance: false,
+            ..SimplifyOptions::default()
+        };
+        assert_eq!(depth(&spine.simplify_with(&options)), 1);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let regex = Regex::new("[ab]*b").unwrap();
+        let intersection = regex.intersection(&Regex::new("a*b*").unwrap());
+        for s in ["b", "ab", "aabbb", "bbb"] {
+            assert!(intersection.matches(s), "{s}");
+        }
+        for s in ["", "a", "ba", "abab"] {
+            assert!(!intersection.matches(s), "{s}");
+        }
+
+        let intersection = Regex::new("(ab)*")
+            .unwrap()
+            .intersection(&Regex::new("[a-z]{4}|a").unwrap());
+        assert_eq!(intersection, Regex::new("abab").unwrap());
+
+        assert_eq!(regex.intersection(&Regex::Empty), Regex::Empty);
+        assert_eq!(regex.intersection(&Regex::new("a+").unwrap()), Regex::Empty);
+        assert_eq!(
+            Regex::Epsilon.intersection(&Regex::new("a*").unwrap()),
+            Regex::Epsilon
+        );
+    }
+
+    #[test]
+    fn test_union_all() {
+        let regexes = ["a", "b", "cd", "ef", "g"].map(|s| Regex::new(s).unwrap());
+        let union = Regex::union_all(regexes.clone());
+        for s in ["a", "b", "cd", "ef", "g"] {
+            assert!(union.matches(s), "{s}");
+        }
+        assert!(!union.matches("c") && !union.matches("ab"));
+        assert_eq!(Regex::union_all([]), Regex::Empty);
+        assert_eq!(Regex::union_all([Regex::literal('a')]), Regex::literal('a'));
 
-        // ∅ ∪ r = r
-        let regex = Regex::Or(Box::new(Regex::Empty), Box::new(Regex::Literal('a')));
-        assert_eq!(regex.simplify(), Regex::Literal('a'));
+        // the alternation is nested logarithmically deep
+        let words = (0..64).map(|i| Regex::string(&format!("w{i}x")));
+        assert!(depth(&Regex::union_all(words.clone())) < depth(&Regex::or_all(words)));
     }
 
     #[test]
-    fn test_simplify_or_with_same() {
-        // r ∪ r = r
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('a')));
-        assert_eq!(regex.simplify(), Regex::Literal('a'));
+    fn test_intersect_all() {
+        let regexes = ["[a-c]*", "a*b*c*", "[ab]+c"].map(|s| Regex::new(s).unwrap());
+        let intersection = Regex::intersect_all(regexes);
+        assert!(intersection.matches("aabc") && intersection.matches("bc"));
+        assert!(!intersection.matches("c") && !intersection.matches("bac"));
+
+        let everything = Regex::intersect_all([]);
+        assert!(everything.matches("") && everything.matches("any 💕 string"));
+    }
+
+    #[test]
+    fn test_is_subset_of() {
+        let regex = Regex::new("a+b").unwrap();
+        assert!(regex.is_subset_of(&Regex::new("a*b").unwrap()));
+        assert!(regex.is_subset_of(&Regex::new("[a-c]*").unwrap()));
+        assert!(regex.is_subset_of(&regex));
+        assert!(!regex.is_subset_of(&Regex::new("ab").unwrap()));
+        assert!(!Regex::new("a*b").unwrap().is_subset_of(&regex));
+        assert!(Regex::Empty.is_subset_of(&regex));
+        assert!(!Regex::Epsilon.is_subset_of(&regex));
+
+        let digit = Regex::pred("digit", |c: char| c.is_ascii_digit());
+        assert!(Regex::new("[0-9]{2}").unwrap().is_subset_of(&digit.plus()));
+        assert!(!Regex::new("[0-9a]").unwrap().is_subset_of(&digit));
     }
 
     #[test]
@@ -588,82 +2816,623 @@ mod tests {
         ]);
         assert_eq!(
             regex.simplify(),
-            Regex::Class(vec![
-                CharRange::Single('a'),
-                CharRange::Single('c'),
-                CharRange::Range('d', 'f')
-            ])
+            Regex::Class(vec![CharRange::Single('a'), CharRange::Range('c', 'f')])
+        );
+
+        // Overlapping and adjacent ranges are merged
+        let regex = Regex::Class(vec![
+            CharRange::Range('a', 'c'),
+            CharRange::Range('b', 'd'),
+            CharRange::Single('e'),
+            CharRange::Single('x'),
+        ]);
+        assert_eq!(
+            regex.simplify(),
+            Regex::Class(vec![CharRange::Range('a', 'e'), CharRange::Single('x')])
+        );
+        assert_eq!(Regex::new("[a-cb-d]").unwrap().to_string(), "[a-d]");
+
+        // Ranges that merge into a single character become a literal
+        let regex = Regex::Class(vec![CharRange::Single('a'), CharRange::Range('a', 'a')]);
+        assert_eq!(regex.simplify(), Regex::Literal('a'));
+
+        // The empty class is left alone
+        assert_eq!(Regex::Class(vec![]).simplify(), Regex::Class(vec![]));
+    }
+
+    #[test]
+    fn test_simplify_dead() {
+        // anything that needs a part matching nothing matches nothing
+        let dead = Regex::Class(vec![]);
+        let x_star = Regex::Literal('x').star();
+        assert_eq!(
+            Regex::Concat(Node::new(x_star.clone()), Node::new(dead.clone())).simplify(),
+            Regex::Empty
+        );
+        assert_eq!(
+            Regex::Shuffle(Node::new(dead.clone()), Node::new(x_star.clone())).simplify(),
+            Regex::Empty
+        );
+        assert_eq!(
+            Regex::Count(Node::new(dead.clone()), Count::Range(2, 3)).simplify(),
+            Regex::Empty
+        );
+        assert_eq!(
+            Regex::Count(Node::new(dead.clone()), Count::AtLeast(0)).simplify(),
+            Regex::Epsilon
+        );
+        assert_eq!(
+            Regex::Count(Node::new(x_star.clone()), Count::Range(3, 2)).simplify(),
+            Regex::Empty
+        );
+        assert_eq!(
+            Regex::Or(Node::new(dead), Node::new(x_star.clone())).simplify(),
+            x_star
         );
     }
 
+    #[test]
+    fn test_matching_stops_when_dead() {
+        // `x*[]` is its own derivative with respect to `x` unless it is simplified to ∅
+        let regex = Regex::new("x*[]|y").unwrap();
+        let (matched, stats) = regex.matches_counted(&"x".repeat(100));
+        assert!(!matched);
+        assert_eq!(stats.derivative_steps, 1);
+
+        let (matched, stats) = Regex::new("x*[]").unwrap().matches_counted("xxx");
+        assert!(!matched);
+        assert_eq!(stats.derivative_steps, 0);
+    }
+
     #[test]
     fn test_simplify_count() {
         // ∅{n} = ∅
-        let regex = Regex::Count(Box::new(Regex::Empty), Count::Exact(2));
+        let regex = Regex::Count(Node::new(Regex::Empty), Count::Exact(2));
         assert_eq!(regex.simplify(), Regex::Empty);
 
         // ∅{n,m} = ∅
-        let regex = Regex::Count(Box::new(Regex::Empty), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Empty), Count::Range(2, 3));
         assert_eq!(regex.simplify(), Regex::Empty);
 
         // ∅{n,} = ∅
-        let regex = Regex::Count(Box::new(Regex::Empty), Count::AtLeast(2));
+        let regex = Regex::Count(Node::new(Regex::Empty), Count::AtLeast(2));
         assert_eq!(regex.simplify(), Regex::Empty);
 
+        // ∅{0,m} = ∅? = ε
+        let regex = Regex::Count(Node::new(Regex::Empty), Count::Range(0, 3));
+        assert_eq!(regex.simplify(), Regex::Epsilon);
+        let regex = Regex::Empty.optional();
+        assert_eq!(regex.simplify(), Regex::Epsilon);
+
         // ε{n} = ε
-        let regex = Regex::Count(Box::new(Regex::Epsilon), Count::Exact(2));
+        let regex = Regex::Count(Node::new(Regex::Epsilon), Count::Exact(2));
         assert_eq!(regex.simplify(), Regex::Epsilon);
 
         // ε{n,m} = ε
-        let regex = Regex::Count(Box::new(Regex::Epsilon), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Epsilon), Count::Range(2, 3));
         assert_eq!(regex.simplify(), Regex::Epsilon);
 
         // ε{n,} = ε
-        let regex = Regex::Count(Box::new(Regex::Epsilon), Count::AtLeast(2));
+        let regex = Regex::Count(Node::new(Regex::Epsilon), Count::AtLeast(2));
         assert_eq!(regex.simplify(), Regex::Epsilon);
 
         // r{n,n} = r{n}
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Range(2, 2));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Range(2, 2));
         assert_eq!(
             regex.simplify(),
-            Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(2),)
+            Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(2),)
         );
 
         // r{0} = ε
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(0));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(0));
         assert_eq!(regex.simplify(), Regex::Epsilon);
 
         // r{1} = r
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(1));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(1));
         assert_eq!(regex.simplify(), Regex::Literal('a'));
     }
 
+    #[test]
+    fn test_simplify_merges_counts() {
+        let a = || Node::new(Regex::Literal('a'));
+
+        // r{2,4} ∪ r{5} = r{2,5}
+        let regex = Regex::Or(
+            Node::new(Regex::Count(a(), Count::Range(2, 4))),
+            Node::new(Regex::Count(a(), Count::Exact(5))),
+        );
+        assert_eq!(regex.simplify(), Regex::Count(a(), Count::Range(2, 5)));
+
+        // r{1,3} ∪ r{2,} = r+
+        let regex = Regex::Or(
+            Node::new(Regex::Count(a(), Count::Range(1, 3))),
+            Node::new(Regex::Count(a(), Count::AtLeast(2))),
+        );
+        assert_eq!(regex.simplify(), Regex::Count(a(), Count::AtLeast(1)));
+
+        // counts with a gap between them are kept apart
+        let regex = Regex::Or(
+            Node::new(Regex::Count(a(), Count::Exact(2))),
+            Node::new(Regex::Count(a(), Count::Exact(4))),
+        );
+        assert_eq!(regex.clone().simplify(), regex);
+
+        // r{n} = r{0,n} and r{n,} = r* if r is nullable
+        let a_opt = Regex::Count(a(), Count::Range(0, 1));
+        let a_opt_b_opt = Regex::Concat(
+            Node::new(a_opt.clone()),
+            Node::new(Regex::Count(
+                Node::new(Regex::Literal('b')),
+                Count::Range(0, 1),
+            )),
+        );
+        let regex = Regex::Count(Node::new(a_opt_b_opt.clone()), Count::Exact(3));
+        assert_eq!(
+            regex.simplify(),
+            Regex::Count(Node::new(a_opt_b_opt), Count::Range(0, 3))
+        );
+        let regex = Regex::Count(Node::new(a_opt), Count::AtLeast(3));
+        assert_eq!(regex.simplify(), Regex::Count(a(), Count::AtLeast(0)));
+
+        // (r*){n,m} = r*
+        let regex = Regex::Count(Node::new(Regex::Literal('a').star()), Count::Range(2, 5));
+        assert_eq!(regex.simplify(), Regex::Literal('a').star());
+
+        // r{a,b}{c,d} = r{ac,bd} if every number of repetitions in between is allowed
+        for (pattern, flattened) in [
+            ("(a{2,5}){3,8}", "a{6,40}"),
+            ("(a{2,5})+", "a{2,}"),
+            ("(a?){3}", "a{0,3}"),
+            ("((((a){2,5}){3,8}){2,6})+", "a{12,}"),
+        ] {
+            assert_eq!(Regex::new(pattern), Regex::new(flattened), "{pattern}");
+        }
+        // but not if some are missing, such as 3 for `(a{2}){1,2}`
+        for pattern in ["(a{2}){1,2}", "(a{2,3}){0,2}", "(a{3,4}){1,5}"] {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(
+                matches!(&regex, Regex::Count(inner, _) if matches!(**inner, Regex::Count(..)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_counts_stay_small() {
+        let regex = Regex::new(r"((((\d){2,5}){3,8}){2,6})+").unwrap();
+        let derivative = regex.derivative_str(&"1".repeat(100));
+        assert!(derivative.nullable());
+        assert!(derivative.node_count() <= regex.node_count());
+        assert!(!regex.matches(&"1".repeat(11)));
+    }
+
+    #[test]
+    fn test_nested_counts_do_not_multiply() {
+        // the derivatives of these used to have one alternative per number of repetitions so far
+        for (pattern, c) in [
+            ("(a{2,5}){1,100000}", "a"),
+            ("(a?b?){100000}", "ab"),
+            (".{0,50}(.{0,50}x){1,100}", "x"),
+        ] {
+            // the derivatives stop growing once the inner counter has been used up
+            let mut derivative = Regex::new(pattern).unwrap();
+            let mut max_sizes = [0; 2];
+            for max_size in &mut max_sizes {
+                for c in c.repeat(100).chars() {
+                    derivative = derivative.derivative(c);
+                    *max_size = (*max_size).max(derivative.node_count());
+                }
+            }
+            assert!(derivative.nullable(), "{pattern}");
+            assert_eq!(max_sizes[0], max_sizes[1], "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_simplify_levels() {
+        let regex = Regex::Or(
+            Node::new(Regex::Class(vec![CharRange::Single('a')])),
+            Node::new(Regex::Concat(
+                Node::new(Regex::Epsilon),
+                Node::new(Regex::Class(vec![CharRange::Single('a')])),
+            )),
+        );
+
+        assert_eq!(regex.simplify_with(&SimplifyLevel::None.into()), regex);
+        assert_eq!(
+            regex.simplify_with(&SimplifyLevel::Basic.into()),
+            Regex::Or(
+                Node::new(Regex::Class(vec![CharRange::Single('a')])),
+                Node::new(Regex::Class(vec![CharRange::Single('a')])),
+            )
+        );
+        assert_eq!(
+            regex.simplify_with(&SimplifyLevel::Aggressive.into()),
+            Regex::Literal('a')
+        );
+    }
+
+    #[test]
+    fn test_simplify_options_rules() {
+        let a = Regex::Class(vec![CharRange::Single('a')]);
+        let regex = Regex::Or(
+            Node::new(Regex::Concat(
+                Node::new(a.clone()),
+                Node::new(Regex::Epsilon),
+            )),
+            Node::new(Regex::Or(Node::new(Regex::Empty), Node::new(a.clone()))),
+        );
+
+        // Classes are normalized, but ∅ and ε are left alone
+        let options = SimplifyOptions {
+            classes: true,
+            ..SimplifyLevel::None.into()
+        };
+        assert_eq!(
+            regex.simplify_with(&options),
+            Regex::Or(
+                Node::new(Regex::Concat(
+                    Node::new(Regex::Literal('a')),
+                    Node::new(Regex::Epsilon)
+                )),
+                Node::new(Regex::Or(
+                    Node::new(Regex::Empty),
+                    Node::new(Regex::Literal('a'))
+                )),
+            )
+        );
+
+        // Alternatives are deduplicated, but classes are left alone
+        let options = SimplifyOptions {
+            alternatives: true,
+            ..SimplifyLevel::Basic.into()
+        };
+        assert_eq!(regex.simplify_with(&options), a);
+
+        // Alternations are sorted, but not rewritten algebraically
+        let regex = Regex::new("b|a*|a").unwrap();
+        let options = SimplifyOptions {
+            algebraic: false,
+            ..SimplifyOptions::default()
+        };
+        assert_eq!(
+            Regex::Or(
+                Node::new(Regex::Literal('b')),
+                Node::new(Regex::Literal('a'))
+            )
+            .simplify_with(&options),
+            Regex::Or(
+                Node::new(Regex::Literal('a')),
+                Node::new(Regex::Literal('b'))
+            )
+        );
+        assert_eq!(regex.simplify_with(&options), regex);
+    }
+
+    #[test]
+    fn test_simplify_options_budgets() {
+        // ((aε)ε)ε
+        let with_epsilon = |r: Regex| Regex::Concat(Node::new(r), Node::new(Regex::Epsilon));
+        let regex = with_epsilon(with_epsilon(with_epsilon(Regex::Literal('a'))));
+
+        for options in [
+            SimplifyOptions {
+                max_depth: Some(1),
+                ..SimplifyOptions::default()
+            },
+            SimplifyOptions {
+                max_nodes: Some(2),
+                ..SimplifyOptions::default()
+            },
+        ] {
+            assert_eq!(
+                regex.simplify_with(&options),
+                with_epsilon(Regex::Literal('a')),
+                "{options:?}"
+            );
+        }
+
+        let options = SimplifyOptions {
+            max_nodes: Some(100),
+            ..SimplifyOptions::default()
+        };
+        assert_eq!(regex.simplify_with(&options), Regex::Literal('a'));
+    }
+
+    #[test]
+    fn test_derivative_with_levels() {
+        let regex = Regex::new("(a|b)*c{2}").unwrap();
+        for level in [
+            SimplifyLevel::None,
+            SimplifyLevel::Basic,
+            SimplifyLevel::Aggressive,
+        ] {
+            let accepts = |s: &str| {
+                s.chars()
+                    .fold(regex.clone(), |r, c| r.derivative_with(c, &level.into()))
+                    .nullable()
+            };
+            assert!(accepts("abcc"), "{level:?}");
+            assert!(accepts("cc"), "{level:?}");
+            assert!(!accepts("abc"), "{level:?}");
+            assert!(!accepts("abccc"), "{level:?}");
+        }
+    }
+
     #[test]
     fn test_complex_simplification() {
         // (a|∅)(ε|b*)
         let regex = Regex::Concat(
-            Box::new(Regex::Or(
-                Box::new(Regex::Literal('a')),
-                Box::new(Regex::Empty),
+            Node::new(Regex::Or(
+                Node::new(Regex::Literal('a')),
+                Node::new(Regex::Empty),
             )),
-            Box::new(Regex::Or(
-                Box::new(Regex::Epsilon),
-                Box::new(Regex::Literal('b').star()),
+            Node::new(Regex::Or(
+                Node::new(Regex::Epsilon),
+                Node::new(Regex::Literal('b').star()),
             )),
         );
 
-        // Should simplify to a(ε|b*) which further simplifies to a
+        // Should simplify to a(ε|b*) which further simplifies to ab*
         let simplified = regex.simplify();
         assert_eq!(
             simplified,
             Regex::Concat(
-                Box::new(Regex::Literal('a')),
-                Box::new(Regex::Or(
-                    Box::new(Regex::Epsilon),
-                    Box::new(Regex::Literal('b').star())
-                ))
+                Node::new(Regex::Literal('a')),
+                Node::new(Regex::Literal('b').star())
+            )
+        );
+    }
+
+    #[test]
+    fn test_nullable() {
+        assert!(!Regex::Empty.nullable());
+        assert!(Regex::Epsilon.nullable());
+        assert!(!Regex::Literal('a').nullable());
+        assert!(Regex::Literal('a').star().nullable());
+        assert!(!Regex::new("a*b").unwrap().nullable());
+        assert!(Regex::new("a|b*").unwrap().nullable());
+
+        // a count of nullable regexes is nullable, whatever its minimum
+        let regex = Regex::Count(Node::new(Regex::Literal('a').star()), Count::Exact(3));
+        assert!(regex.nullable());
+        assert!(regex.matches(""));
+    }
+
+    #[test]
+    fn test_node_caches_nullable_and_hash() {
+        // a concatenation deep enough that walking it for every query would be slow
+        let nested = |last: Regex| {
+            (0..100_000).fold(last, |regex, _| {
+                Regex::Concat(Node::new(Regex::Literal('a').star()), Node::new(regex))
+            })
+        };
+        let regex = nested(Regex::Epsilon);
+        let other = nested(Regex::Literal('b'));
+        assert!(regex.nullable() && !other.nullable());
+        for _ in 0..100_000 {
+            assert!(regex.nullable());
+        }
+
+        let node = Node::new(regex.clone());
+        assert_eq!(node.structural_hash(), regex.structural_hash());
+        assert_eq!(Node::new(regex.clone()), node);
+        assert_ne!(Node::new(other.clone()), node);
+        assert_ne!(regex.structural_hash(), other.structural_hash());
+        assert_eq!(node.into_inner(), regex);
+    }
+
+    #[test]
+    fn test_derivative_and_nullable() {
+        let options = SimplifyOptions::default();
+        for pattern in [
+            "a*b",
+            "a|b*",
+            "(a?b?)c?",
+            "x{0}",
+            "(ab|c*){2,3}",
+            "b(a?){3}",
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            for c in ['a', 'b', 'c', 'x'] {
+                let (derivative, nullable) = regex.derivative_and_nullable(c, &options, false);
+                assert_eq!(derivative, regex.derivative(c), "{pattern} {c}");
+                assert_eq!(nullable, regex.nullable(), "{pattern} {c}");
+            }
+        }
+
+        // a left-nested concatenation, whose prefixes would each be walked again to check whether
+        // they are nullable
+        let regex = (0..200).fold(Regex::Literal('b'), |regex, _| {
+            Regex::Concat(Node::new(regex), Node::new(Regex::Literal('a').optional()))
+        });
+        let (derivative, nullable) = regex.derivative_and_nullable('b', &options, false);
+        assert!(!nullable);
+        assert!(derivative.nullable());
+        assert!(derivative.matches("aaa"));
+    }
+
+    #[test]
+    fn test_derivative_of_simplified() {
+        for (pattern, s) in [
+            (r"(\w+\s)*\w+", "the quick brown fox"),
+            ("(a|b)*abb(a|b){2}", "abababbab"),
+            ("(a*b*c*)*d+", "abcabcdd"),
+            ("((a{1,3}){2,3}){2,}", "aaaaaaaaaa"),
+            ("(ab|a)(bc|c)*", "abcbcc"),
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            let mut full = regex.clone();
+            let mut local = regex.clone();
+            for c in s.chars() {
+                full = full.derivative(c);
+                local = local.derivative_of_simplified(c);
+                assert_eq!(local, full, "{pattern} {c}");
+            }
+        }
+
+        // only the new nodes are simplified, not the parts copied from the regex
+        let regex = Regex::new(&"(ab|cd)".repeat(50)).unwrap().derivative('a');
+        let (_, _, full_calls) = count_calls(|| regex.derivative('b'));
+        let (_, _, local_calls) = count_calls(|| regex.derivative_of_simplified('b'));
+        assert!(local_calls * 10 < full_calls, "{local_calls} {full_calls}");
+    }
+
+    #[test]
+    fn test_node_count_and_heap_bytes() {
+        let regex = Regex::new("ab|c*").unwrap();
+        assert_eq!(regex.node_count(), 4);
+        assert_eq!(Regex::literal('a').node_count(), 1);
+        assert_eq!(Regex::literal('a').heap_bytes_estimate(), 0);
+        assert_eq!(Regex::string("abc").heap_bytes_estimate(), 3);
+
+        // two boxed literals, with what their nodes keep about them
+        let concat = Regex::Concat(
+            Node::new(Regex::literal('a')),
+            Node::new(Regex::literal('b')),
+        );
+        assert_eq!(concat.heap_bytes_estimate(), 2 * size_of::<NodeData>());
+        let class = Regex::Class(vec![CharRange::Single('a'), CharRange::Range('0', '9')]);
+        assert!(class.heap_bytes_estimate() >= 2 * size_of::<CharRange>());
+
+        // the derivatives of `(a|b)*a(a|b){n}` grow with how many `a`s could have been the one
+        let regex = Regex::new("(a|b)*a(a|b){8}").unwrap();
+        let derivative = regex.derivative_str("aaaaaaaa");
+        assert!(derivative.node_count() > regex.node_count());
+        assert!(derivative.heap_bytes_estimate() > regex.heap_bytes_estimate());
+    }
+
+    #[test]
+    fn test_count_residuals() {
+        // each repetition starts with the same derivative of `(a|b)(c|d)`, which is only taken once
+        let regex = Regex::new("((a|b)(c|d)){1,100}").unwrap();
+        let (first, first_calls, _) = count_calls(|| regex.derivative_of_simplified('a'));
+        assert_eq!(first, regex.derivative('a'));
+
+        let mut current = first;
+        for c in "cbd".chars() {
+            current = current.derivative_of_simplified(c);
+        }
+        let (next, next_calls, _) = count_calls(|| current.derivative_of_simplified('a'));
+        assert_eq!(next, current.derivative('a'));
+        assert!(next_calls < first_calls, "{next_calls} {first_calls}");
+        assert!(regex.derivative_str(&"ac".repeat(100)).nullable());
+        assert!(!regex.derivative_str(&"ac".repeat(101)).nullable());
+    }
+
+    #[test]
+    fn test_eq_same_regex() {
+        let regex = Regex::concat_all((0..10_000).map(|i| {
+            Regex::or(
+                Regex::literal(char::from_u32(0x100 + i).unwrap()),
+                Regex::literal('x').star(),
             )
+        }));
+        let same = &regex;
+
+        // the same regex is equal to itself without visiting its nodes
+        assert_eq!(regex.eq_bounded(same, 1), Some(true));
+        assert!(regex.eq_for_simplify(same));
+        assert_eq!(regex.cmp(same), Ordering::Equal);
+
+        // a copy is still compared node by node
+        let copy = regex.clone();
+        assert_eq!(regex.eq_bounded(&copy, 1), None);
+        assert_eq!(regex, copy);
+    }
+
+    #[test]
+    fn test_nullability_regex() {
+        assert_eq!(
+            Regex::Literal('a').star().nullability_regex(),
+            Regex::Epsilon
+        );
+        assert_eq!(Regex::Literal('a').nullability_regex(), Regex::Empty);
+    }
+
+    #[test]
+    fn test_smart_constructors() {
+        let ab = Regex::concat(Regex::literal('a'), Regex::literal('b'));
+        assert_eq!(ab, Regex::Str("ab".to_string()));
+        assert_eq!(Regex::concat(Regex::Epsilon, ab.clone()), ab);
+        assert_eq!(Regex::concat(ab.clone(), Regex::Empty), Regex::Empty);
+
+        assert_eq!(Regex::or(ab.clone(), ab.clone()), ab);
+        assert_eq!(Regex::or(Regex::Empty, ab.clone()), ab);
+
+        assert_eq!(
+            Regex::class(vec![CharRange::Range('a', 'a')]),
+            Regex::Literal('a')
+        );
+
+        assert_eq!(
+            Regex::concat_all("abc".chars().map(Regex::literal)),
+            Regex::new("abc").unwrap()
         );
+        assert_eq!(Regex::concat_all([]), Regex::Epsilon);
+        assert_eq!(
+            Regex::or_all("abc".chars().map(Regex::literal)).simplify(),
+            Regex::new("a|b|c").unwrap()
+        );
+        assert_eq!(Regex::or_all([]), Regex::Empty);
+    }
+
+    #[test]
+    fn test_operators() {
+        let a = Regex::literal('a');
+        let b = Regex::literal('b');
+
+        assert_eq!(a.clone() | b.clone(), Regex::new("a|b").unwrap());
+        assert_eq!(a.clone() + b.clone(), Regex::new("ab").unwrap());
+        assert_eq!(a.clone() * b.clone(), Regex::new("ab").unwrap());
+
+        let regex = (a.clone() + b.clone()).star() + (a | b);
+        assert!(regex.matches("ababa"));
+        assert!(!regex.matches("abab"));
+    }
+
+    #[test]
+    fn test_ord() {
+        assert!(Regex::Empty < Regex::Epsilon);
+        assert!(Regex::Literal('a') < Regex::Literal('b'));
+        assert!(Count::Exact(5) < Count::Range(0, 1));
+        assert!(CharRange::Single('z') < CharRange::Range('a', 'b'));
+
+        let predicate = Predicate::new("digit", |c: char| c.is_ascii_digit());
+        let other = Predicate::new("digit", |c: char| c.is_ascii_digit());
+        assert_eq!(predicate.cmp(&predicate.clone()), Ordering::Equal);
+        assert_ne!(predicate.cmp(&other), Ordering::Equal);
+        assert_eq!(predicate.cmp(&other), other.cmp(&predicate).reverse());
+
+        // predicates with the same name are in the order they were created in, wherever their
+        // functions are allocated
+        assert_eq!(predicate.cmp(&other), Ordering::Less);
+        let alpha = Predicate::new("alpha", char::is_alphabetic);
+        assert_eq!(alpha.cmp(&predicate), Ordering::Less);
+        let mut predicates = vec![other.clone(), alpha.clone(), predicate.clone()];
+        predicates.sort();
+        assert_eq!(predicates, [alpha, predicate, other]);
+    }
+
+    #[test]
+    fn test_derivative_closure_in_collections() {
+        let regex = Regex::new("(ab|b)*").unwrap();
+
+        let mut hash_states = HashSet::from([regex.clone()]);
+        let mut ordered_states = BTreeSet::from([regex.clone()]);
+        let mut queue = vec![regex];
+        while let Some(state) = queue.pop() {
+            for c in ['a', 'b', 'c'] {
+                let derivative = state.derivative(c);
+                ordered_states.insert(derivative.clone());
+                if hash_states.insert(derivative.clone()) {
+                    queue.push(derivative);
+                }
+            }
+        }
+
+        assert_eq!(hash_states.len(), ordered_states.len());
+        assert!(ordered_states.contains(&Regex::Empty));
+        assert_eq!(ordered_states.first(), Some(&Regex::Empty));
     }
 
     // matches tests
@@ -676,7 +3445,10 @@ mod tests {
 
     #[test]
     fn test_matches_concat() {
-        let regex = Regex::Concat(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Concat(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert!(regex.matches("ab"));
         assert!(!regex.matches("a"));
         assert!(!regex.matches("b"));
@@ -684,7 +3456,10 @@ mod tests {
 
     #[test]
     fn test_matches_or() {
-        let regex = Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')));
+        let regex = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
         assert!(regex.matches("a"));
         assert!(regex.matches("b"));
         assert!(!regex.matches("c"));
@@ -702,8 +3477,8 @@ mod tests {
     #[test]
     fn test_matches_complex() {
         let regex = Regex::Concat(
-            Box::new(Regex::Literal('a')),
-            Box::new(Regex::Literal('b').star()),
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b').star()),
         ); // ab*
         assert!(regex.matches("a"));
         assert!(regex.matches("ab"));
@@ -714,7 +3489,7 @@ mod tests {
 
     #[test]
     fn test_matches_count_range() {
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Range(2, 3));
         assert!(!regex.matches(""));
         assert!(!regex.matches("a"));
         assert!(regex.matches("aa"));
@@ -724,7 +3499,7 @@ mod tests {
 
     #[test]
     fn test_matches_count_single() {
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(2));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(2));
 
         assert!(!regex.matches(""));
         assert!(!regex.matches("a"));
@@ -740,15 +3515,234 @@ mod tests {
         assert!(!regex.matches("c"));
     }
 
+    #[test]
+    fn test_matches_pred() {
+        let regex = Regex::pred("alphabetic", char::is_alphabetic).plus();
+        assert!(regex.matches("abcé"));
+        assert!(regex.matches("Ωμέγα"));
+        assert!(!regex.matches("ab1"));
+        assert!(!regex.matches(""));
+    }
+
+    #[test]
+    fn test_pred_equality() {
+        let predicate = Predicate::new("digit", |c: char| c.is_ascii_digit());
+        assert_eq!(Regex::Pred(predicate.clone()), Regex::Pred(predicate));
+        assert_ne!(
+            Regex::pred("digit", |c: char| c.is_ascii_digit()),
+            Regex::pred("digit", |c: char| c.is_ascii_digit())
+        );
+    }
+
+    #[test]
+    fn test_pred_print() {
+        let regex = Regex::pred("alphabetic", char::is_alphabetic);
+        assert_eq!(regex.to_string(), "<alphabetic>");
+        assert_eq!(format!("{regex:?}"), r#"Pred(Predicate("alphabetic"))"#);
+    }
+
+    #[test]
+    fn test_matches_nothing() {
+        assert!(Regex::Empty.matches_nothing());
+        assert!(Regex::new("a[]").unwrap().matches_nothing());
+        assert!(Regex::new("([])+").unwrap().matches_nothing());
+        assert!(!Regex::new("([])*").unwrap().matches_nothing());
+        assert!(!Regex::new("a|[]").unwrap().matches_nothing());
+        assert!(!Regex::Epsilon.matches_nothing());
+
+        // each node keeps whether it matches nothing, so a deep regex is not walked again
+        let nested = |last: Regex| {
+            (0..100_000).fold(last, |regex, _| {
+                Regex::Concat(Node::new(Regex::Literal('a')), Node::new(regex))
+            })
+        };
+        let regex = nested(Regex::Class(vec![]));
+        let other = nested(Regex::Literal('b'));
+        for _ in 0..100_000 {
+            assert!(regex.matches_nothing() && !other.matches_nothing());
+        }
+        assert!(!Regex::Or(Node::new(regex), Node::new(other)).matches_nothing());
+    }
+
+    #[test]
+    fn test_could_match() {
+        let regex = Regex::new(r"\d{3}-\d{4}").unwrap();
+        assert!(regex.could_match(""));
+        assert!(regex.could_match("555"));
+        assert!(regex.could_match("555-"));
+        assert!(regex.could_match("555-1234"));
+        assert!(!regex.could_match("555x"));
+        assert!(!regex.could_match("555-12345"));
+
+        // the prefix can be valid without matching, and the regex can end in a part matching nothing
+        let regex = Regex::new("ab|c[]").unwrap();
+        assert!(regex.could_match("a"));
+        assert!(!regex.could_match("c"));
+        assert!(!Regex::Empty.could_match(""));
+    }
+
+    #[test]
+    fn test_next_chars() {
+        let regex = Regex::new("[a-c]x|dy").unwrap();
+        assert_eq!(
+            regex.next_chars(),
+            CharSet::new(&[CharRange::Range('a', 'd')])
+        );
+        assert_eq!(
+            regex.derivative('d').next_chars(),
+            CharSet::new(&[CharRange::Single('y')])
+        );
+        assert!(regex.derivative_str("dy").next_chars().is_empty());
+
+        // characters leading only to parts that match nothing are not allowed
+        let regex = Regex::new("ab|c[]").unwrap();
+        assert_eq!(regex.next_chars(), CharSet::new(&[CharRange::Single('a')]));
+        assert_eq!(
+            Regex::new("a*").unwrap().next_chars(),
+            CharSet::new(&[CharRange::Single('a')])
+        );
+    }
+
+    #[test]
+    fn test_str() {
+        let regex = Regex::new("abc").unwrap();
+        assert_eq!(regex, Regex::string("abc"));
+        assert_eq!(regex.derivative('a'), Regex::string("bc"));
+        assert_eq!(regex.derivative_str("ab"), Regex::Literal('c'));
+        assert_eq!(regex.derivative('b'), Regex::Empty);
+        assert_eq!(regex.reverse(), Regex::string("cba"));
+        assert!(regex.matches("abc") && !regex.matches("ab"));
+
+        assert_eq!(Regex::string(""), Regex::Epsilon);
+        assert_eq!(Regex::string("a"), Regex::Literal('a'));
+        assert_eq!(Regex::string("💕?").to_string(), r"💕\?");
+
+        // literals next to strings are joined when simplifying
+        let regex = Regex::Concat(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Concat(
+                Node::new(Regex::string("bc")),
+                Node::new(Regex::Literal('d').star()),
+            )),
+        );
+        assert_eq!(regex.simplify(), Regex::new("abcd*").unwrap());
+        assert_eq!(
+            Regex::new("abc|abd").unwrap(),
+            Regex::string("ab") + Regex::new("c|d").unwrap()
+        );
+        assert_eq!(
+            Regex::new("(?i)ab1").unwrap(),
+            Regex::new("[Aa][Bb]1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_derivative_shuffle() {
+        let ab = Regex::Concat(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
+        let regex = ab.shuffle(&Regex::Literal('c'));
+        assert_eq!(regex.derivative('c'), Regex::Str("ab".to_string()));
+        assert_eq!(
+            regex.derivative('a'),
+            Regex::Shuffle(
+                Node::new(Regex::Literal('b')),
+                Node::new(Regex::Literal('c'))
+            )
+        );
+        assert_eq!(regex.derivative('b'), Regex::Empty);
+    }
+
+    #[test]
+    fn test_simplify_shuffle() {
+        // r⧢∅ = ∅
+        let regex = Regex::Literal('a').shuffle(&Regex::Empty);
+        assert_eq!(regex.simplify(), Regex::Empty);
+
+        // ε⧢r = r
+        let regex = Regex::Epsilon.shuffle(&Regex::Literal('a'));
+        assert_eq!(regex.simplify(), Regex::Literal('a'));
+    }
+
+    #[test]
+    fn test_matches_shuffle() {
+        let ab = Regex::Concat(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        );
+        let cd = Regex::Concat(
+            Node::new(Regex::Literal('c')),
+            Node::new(Regex::Literal('d')),
+        );
+        let regex = ab.shuffle(&cd);
+        for s in ["abcd", "acbd", "acdb", "cabd", "cadb", "cdab"] {
+            assert!(regex.matches(s), "{s}");
+        }
+        assert!(!regex.matches("bacd"));
+        assert!(!regex.matches("abdc"));
+        assert!(!regex.matches("abc"));
+
+        let regex = Regex::Literal('a').star().shuffle(&Regex::Literal('b'));
+        assert!(regex.matches("b"));
+        assert!(regex.matches("aaba"));
+        assert!(!regex.matches("abab"));
+    }
+
+    #[test]
+    fn test_shuffle_print() {
+        let regex = Regex::Literal('a').shuffle(&Regex::Literal('b'));
+        assert_eq!(regex.to_string(), "(a⧢b)");
+    }
+
+    #[test]
+    fn test_matches_counted() {
+        let regex = Regex::new("ab*c").unwrap();
+        let (matched, stats) = regex.matches_counted("abbc");
+        assert!(matched);
+        assert_eq!(stats.derivative_steps, 4);
+        assert_eq!(stats.max_size, regex.node_count());
+        assert!(stats.derivative_calls >= stats.derivative_steps);
+        assert!(stats.simplify_calls > 0);
+        assert!(stats.nodes_allocated > 0);
+
+        // matching stops once the derivative is ∅
+        let (matched, stats) = regex.matches_counted("xabbc");
+        assert!(!matched);
+        assert_eq!(stats.derivative_steps, 1);
+        assert_eq!(stats.nodes_allocated, 1);
+    }
+
+    #[test]
+    fn test_matches_with_stats() {
+        let regex = Regex::new("(ab)*c").unwrap();
+        let (matched, stats) = regex.matches_with_stats("ababababc");
+        assert!(matched);
+        assert_eq!(stats.counts.derivative_steps, 9);
+        assert_eq!(
+            stats.counts.max_size,
+            regex.matches_counted("ababababc").1.max_size
+        );
+
+        // `(ab)*c` has two derivatives with respect to `ab…`, which are each taken once
+        assert_eq!((stats.cache_misses, stats.cache_hits), (3, 6));
+        assert!((stats.cache_hit_rate() - 6.0 / 9.0).abs() < 1e-9);
+
+        let (matched, stats) = regex.matches_with_stats("");
+        assert!(!matched);
+        assert_eq!(stats.cache_hit_rate(), 0.0);
+        assert_eq!(stats.simplify_time, std::time::Duration::ZERO);
+    }
+
     #[test]
     fn test_count_print() {
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Range(2, 3));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Range(2, 3));
         assert_eq!(regex.to_string(), "(a){2,3}");
 
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(2));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(2));
         assert_eq!(regex.to_string(), "(a){2}");
 
-        let regex = Regex::Count(Box::new(Regex::Literal('a')), Count::AtLeast(2));
+        let regex = Regex::Count(Node::new(Regex::Literal('a')), Count::AtLeast(2));
         assert_eq!(regex.to_string(), "(a){2,}");
 
         let regex = Regex::Literal('a').star();
@@ -760,4 +3754,25 @@ mod tests {
         let regex = Regex::Literal('a').optional();
         assert_eq!(regex.to_string(), "(a)?");
     }
+
+    #[test]
+    fn test_print() {
+        let regex = Regex::Concat(
+            Node::new(Regex::Or(
+                Node::new(Regex::Str("a.b".to_string())),
+                Node::new(Regex::Epsilon),
+            )),
+            Node::new(Regex::Class(vec![
+                CharRange::Single('-'),
+                CharRange::Range('a', ']'),
+            ])),
+        );
+        assert_eq!(regex.to_string(), r"(a\.b|ε)[\-a-\]]");
+        assert_eq!(Regex::Empty.to_string(), "∅");
+
+        let long = Regex::concat_all(
+            (0..100_000).map(|i| Regex::Literal(if i % 2 == 0 { 'a' } else { '*' })),
+        );
+        assert_eq!(long.to_string(), r"a\*".repeat(50_000));
+    }
 }
@@ -0,0 +1,304 @@
+//! Compiling a regex ahead of time into a deterministic finite automaton (DFA), for matching many
+//! strings as fast as possible once the compile time has been paid.
+//!
+//! The states of the DFA are the distinct derivatives of the regex. The alphabet is split into
+//! classes of characters that every literal and class in the regex treats the same (see
+//! `Regex::alphabet_classes`), and since the derivatives of a regex only contain its own literals
+//! and classes, every character of a class leads from each state to the same next state. So the
+//! DFA is built by taking the derivative of each state with respect to one character per class, and
+//! matching only looks up transitions in a table.
+
+mod codegen;
+mod serialize;
+
+use crate::derivatives::Regex;
+use crate::minterms::Minterms;
+use std::collections::{HashMap, VecDeque};
+
+pub use serialize::DfaRef;
+
+/// The number of states `Regex::compile` allows a DFA to have.
+const DEFAULT_MAX_STATES: usize = 10_000;
+
+/// The number of nodes a state of a DFA may have, unless the regex itself has more than half as
+/// many, so that derivatives that keep growing are given up on before they take too long.
+const MAX_STATE_NODES: usize = 1_000;
+
+/// A regex compiled into a DFA by `Regex::compile`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dfa {
+    /// The classes of characters.
+    minterms: Minterms,
+    /// The state reached from each state by each class, at `state * class_count + class`.
+    transitions: Vec<u32>,
+    /// Whether each state matches the end of the input.
+    accepting: Vec<bool>,
+    /// The state of `∅`, from which no input is accepted, if it is reachable.
+    dead: Option<u32>,
+}
+
+impl Dfa {
+    /// The state of the regex itself.
+    const START: u32 = 0;
+
+    /// Returns the number of states of the DFA, which are the distinct derivatives of the regex.
+    pub fn state_count(&self) -> usize {
+        self.accepting.len()
+    }
+
+    /// Returns the number of classes the alphabet is split into.
+    pub const fn class_count(&self) -> usize {
+        self.minterms.len()
+    }
+
+    /// Returns the approximate number of bytes the DFA takes, including its tables, which grow with
+    /// the number of states times the number of classes.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.minterms.heap_bytes()
+            + self.transitions.capacity() * size_of::<u32>()
+            + self.accepting.capacity() * size_of::<bool>()
+    }
+
+    /// Returns the DFA with the fewest states that matches the same strings, which merges the
+    /// states whose derivatives match the same strings but were not simplified to the same regex
+    /// (e.g., `a*|aa*` compiles to 3 states, which minimize to 2).
+    ///
+    /// States are split apart while two states in a group lead to different groups on some class,
+    /// starting from the accepting and the other states, so this takes up to `states` passes over
+    /// the transitions.
+    pub fn minimize(&self) -> Self {
+        let class_count = self.class_count();
+        // the group of each state, numbered in the order of their first state so that the start
+        // state stays first
+        let mut groups: Vec<u32> = self
+            .accepting
+            .iter()
+            .map(|&accepting| u32::from(accepting))
+            .collect();
+        let mut group_count = 0;
+        loop {
+            let mut ids: HashMap<(u32, Vec<u32>), u32> = HashMap::new();
+            let refined: Vec<u32> = (0..self.state_count())
+                .map(|state| {
+                    let next = &self.transitions[state * class_count..(state + 1) * class_count];
+                    let key = (
+                        groups[state],
+                        next.iter().map(|&next| groups[next as usize]).collect(),
+                    );
+                    let id = u32::try_from(ids.len()).unwrap_or(u32::MAX);
+                    *ids.entry(key).or_insert(id)
+                })
+                .collect();
+            // each pass only splits groups, so the groups are final once none is split
+            let done = ids.len() == group_count;
+            group_count = ids.len();
+            groups = refined;
+            if done {
+                break;
+            }
+        }
+
+        let mut transitions = vec![0; group_count * class_count];
+        let mut accepting = vec![false; group_count];
+        for (state, &group) in groups.iter().enumerate() {
+            let group = group as usize;
+            transitions[group * class_count..(group + 1) * class_count]
+                .copy_from_slice(&self.transitions[state * class_count..(state + 1) * class_count]);
+            accepting[group] = self.accepting[state];
+        }
+        for next in &mut transitions {
+            *next = groups[*next as usize];
+        }
+
+        Self {
+            minterms: self.minterms.clone(),
+            transitions,
+            accepting,
+            dead: self.dead.map(|dead| groups[dead as usize]),
+        }
+    }
+
+    /// Returns `true` if the DFA matches the given string, otherwise returns `false`.
+    pub fn matches(&self, s: &str) -> bool {
+        let class_count = self.class_count();
+        let dead = self.dead.unwrap_or(u32::MAX);
+        let mut state = Self::START;
+        for c in s.chars() {
+            state = self.transitions[state as usize * class_count + self.minterms.class_of(c)];
+            if state == dead {
+                return false;
+            }
+        }
+        self.accepting[state as usize]
+    }
+}
+
+impl Regex {
+    /// Tries to compile the regex into a DFA, whose `matches` only looks up one transition per
+    /// character.
+    ///
+    /// Returns an error if the regex has predicates, which can tell apart characters the DFA treats
+    /// the same, or if it has more than 10,000 distinct derivatives or one with too many nodes (see
+    /// `compile_with_max_states`).
+    pub fn compile(&self) -> Result<Dfa, String> {
+        self.compile_with_max_states(DEFAULT_MAX_STATES)
+    }
+
+    /// Like `compile`, but with a limit on the number of states of the DFA. Each state takes a
+    /// transition for every class, so a DFA takes about `4 * states * classes` bytes.
+    ///
+    /// Also returns an error if a state has more than 1,000 nodes (see `node_count`), or more than
+    /// twice as many as the regex if that is larger, since taking the derivatives of states that
+    /// keep growing (e.g., for nested counts) can take far longer than the limit on states suggests.
+    pub fn compile_with_max_states(&self, max_states: usize) -> Result<Dfa, String> {
+        if self.has_predicate() {
+            return Err("Cannot compile a regex with predicates into a DFA".to_string());
+        }
+        let max_nodes = MAX_STATE_NODES.max(2 * self.node_count());
+
+        let minterms = Minterms::new(&[self]);
+        let representatives = minterms.representatives();
+
+        let mut ids: HashMap<Self, u32> = HashMap::from([(self.clone(), Dfa::START)]);
+        let mut queue = VecDeque::from([self.clone()]);
+        let mut transitions = Vec::new();
+        let mut accepting = Vec::new();
+        let mut dead = (*self == Self::Empty).then_some(Dfa::START);
+
+        // states are numbered in the order they are found, which is the order they are explored
+        while let Some(state) = queue.pop_front() {
+            accepting.push(state.nullable());
+            for &c in &representatives {
+                let derivative = state.derivative(c);
+                let next = match ids.get(&derivative) {
+                    Some(&next) => next,
+                    None => {
+                        if ids.len() >= max_states {
+                            return Err(format!("The DFA has more than {max_states} states"));
+                        }
+                        if derivative.node_count() > max_nodes {
+                            return Err(format!(
+                                "A state of the DFA has more than {max_nodes} nodes"
+                            ));
+                        }
+                        let next = u32::try_from(ids.len())
+                            .map_err(|_| "The DFA has too many states".to_string())?;
+                        if derivative == Self::Empty {
+                            dead = Some(next);
+                        }
+                        ids.insert(derivative.clone(), next);
+                        queue.push_back(derivative);
+                        next
+                    }
+                };
+                transitions.push(next);
+            }
+        }
+
+        Ok(Dfa {
+            minterms,
+            transitions,
+            accepting,
+            dead,
+        })
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Dfa, Regex};
+
+    #[test]
+    fn test_dfa_agrees_with_regex() {
+        let patterns = [
+            "abc",
+            "a*",
+            "(a|b)*abb",
+            r"[a-z]+\d{2}",
+            "(a*b*c*)*d+",
+            "(a{2,5}b{3,7}c{1,9}){2,4}",
+            "é+|[α-ω]x",
+            "a?b?",
+        ];
+        let inputs = [
+            "", "abc", "aaa", "abb", "babb", "ab12", "ab1", "abcd", "aaabbbc", "ééé", "βx", "x",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            let dfa = regex.compile().unwrap();
+            for s in inputs {
+                assert_eq!(dfa.matches(s), regex.matches(s), "{pattern} {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_dfa_states() {
+        let dfa = Regex::new("(a|b)*abb").unwrap().compile().unwrap();
+        // the four states of the textbook DFA, and the dead state for other characters
+        assert_eq!(dfa.state_count(), 5);
+        assert!(dfa.matches(&format!("{}abb", "ab".repeat(1000))));
+        assert!(!dfa.matches("abbc"));
+        // `a`, `b`, and every other character
+        assert_eq!(dfa.class_count(), 3);
+
+        let dfa = Regex::Empty.compile().unwrap();
+        assert_eq!(dfa.state_count(), 1);
+        assert!(!dfa.matches("") && !dfa.matches("a"));
+    }
+
+    #[test]
+    fn test_dfa_minimize() {
+        for (pattern, states, minimized) in [
+            ("a*|aa*", 3, 2),
+            ("(ab|a)*|a(ba)*", 5, 3),
+            ("(a|b)*abb", 5, 5),
+            ("[]", 2, 1),
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            let dfa = regex.compile().unwrap();
+            assert_eq!(dfa.state_count(), states, "{pattern}");
+            let minimal = dfa.minimize();
+            assert_eq!(minimal.state_count(), minimized, "{pattern}");
+            assert_eq!(minimal.minimize(), minimal, "{pattern}");
+            for s in ["", "a", "aa", "ab", "aba", "abab", "abb", "babb", "c"] {
+                assert_eq!(minimal.matches(s), regex.matches(s), "{pattern} {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_dfa_memory_usage() {
+        let small = Regex::new("(a|b)*abb").unwrap().compile().unwrap();
+        // at least the 5 × 3 transitions and the 5 accepting flags
+        assert!(small.memory_usage() >= size_of::<Dfa>() + 15 * 4 + 5);
+
+        let large = Regex::new("(a|b)*a(a|b){8}").unwrap().compile().unwrap();
+        assert!(large.state_count() > 500);
+        assert!(large.memory_usage() >= large.state_count() * large.class_count() * 4);
+        assert!(large.memory_usage() > small.memory_usage());
+    }
+
+    #[test]
+    fn test_dfa_errors() {
+        let regex = Regex::pred("vowel", |c| "aeiou".contains(c));
+        assert_eq!(
+            regex.compile(),
+            Err("Cannot compile a regex with predicates into a DFA".to_string())
+        );
+
+        let regex = Regex::new("a{1,100}").unwrap();
+        assert_eq!(
+            regex.compile_with_max_states(50),
+            Err("The DFA has more than 50 states".to_string())
+        );
+        assert!(regex.compile_with_max_states(102).is_ok());
+
+        let regex = Regex::new("((a{2,5}b?){3,8}c?)+").unwrap();
+        assert_eq!(
+            regex.compile(),
+            Err("A state of the DFA has more than 1000 nodes".to_string())
+        );
+    }
+}
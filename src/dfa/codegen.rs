@@ -0,0 +1,142 @@
+//! Generating Rust source code for a standalone function that matches like a compiled DFA, so that
+//! a hot pattern can be compiled into the program (e.g., by a build script) without depending on
+//! this crate at run time.
+
+use super::Dfa;
+use std::fmt::{Display, Write};
+
+/// The number of numbers written on each line of a table.
+const NUMBERS_PER_LINE: usize = 16;
+
+/// Writes a `static` array with the given name, element type and elements.
+fn write_table<T: Display>(code: &mut String, name: &str, element: &str, items: &[T]) {
+    let _ = writeln!(code, "    static {name}: [{element}; {}] = [", items.len());
+    for line in items.chunks(NUMBERS_PER_LINE) {
+        let line: Vec<String> = line.iter().map(ToString::to_string).collect();
+        let _ = writeln!(code, "        {},", line.join(", "));
+    }
+    code.push_str("    ];\n");
+}
+
+/// The keywords of Rust (2021), which cannot be the names of functions.
+const KEYWORDS: [&str; 51] = [
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while",
+];
+
+/// Returns `true` if the name can be used as the name of a function.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
+}
+
+impl Dfa {
+    /// Returns the Rust source code of a function `pub fn name(s: &str) -> bool` that returns
+    /// whether the DFA matches the string, for writing to a file from a build script and including
+    /// in a program that then does not need this crate to match.
+    ///
+    /// The function holds the tables of the DFA in `static` arrays and looks up one transition per
+    /// character, like `Dfa::matches`. Returns an error if `name` cannot be the name of a function.
+    pub fn to_rust(&self, name: &str) -> Result<String, String> {
+        let mut code = String::new();
+        code.push_str("// Generated by rzozowski from a compiled DFA.\n\n");
+        self.write_rust(&mut code, name)?;
+        Ok(code)
+    }
+
+    /// Writes the function returned by `to_rust`, without the comment at the top of the file.
+    pub(crate) fn write_rust(&self, code: &mut String, name: &str) -> Result<(), String> {
+        if !is_identifier(name) {
+            return Err(format!("`{name}` is not a valid function name"));
+        }
+
+        let boundaries: Vec<u32> = self
+            .minterms
+            .boundaries()
+            .iter()
+            .map(|&c| c as u32)
+            .collect();
+        let dead = self
+            .dead
+            .map_or_else(|| "u32::MAX".to_string(), |dead| dead.to_string());
+
+        code.push_str(
+            "/// Returns `true` if the string matches the regex this was generated from.\n",
+        );
+        let _ = writeln!(code, "pub fn {name}(s: &str) -> bool {{");
+        write_table(code, "ASCII_CLASSES", "u32", self.minterms.ascii_classes());
+        write_table(code, "BOUNDARIES", "u32", &boundaries);
+        write_table(
+            code,
+            "INTERVAL_CLASSES",
+            "u32",
+            self.minterms.interval_classes(),
+        );
+        write_table(code, "TRANSITIONS", "u32", &self.transitions);
+        write_table(code, "ACCEPTING", "bool", &self.accepting);
+        let _ = writeln!(
+            code,
+            "    const CLASS_COUNT: usize = {};",
+            self.class_count()
+        );
+        let _ = writeln!(code, "    const DEAD: u32 = {dead};");
+        code.push_str(
+            "
+    let mut state: u32 = 0;
+    for c in s.chars() {
+        let class = if c.is_ascii() {
+            ASCII_CLASSES[c as usize]
+        } else {
+            INTERVAL_CLASSES[BOUNDARIES.partition_point(|&boundary| boundary <= c as u32) - 1]
+        };
+        state = TRANSITIONS[state as usize * CLASS_COUNT + class as usize];
+        if state == DEAD {
+            return false;
+        }
+    }
+    ACCEPTING[state as usize]
+}
+",
+        );
+        Ok(())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use crate::derivatives::{CharRange, Regex};
+
+    #[test]
+    fn test_dfa_to_rust() {
+        let dfa = Regex::new("(a|b)*abb").unwrap().compile().unwrap();
+        let code = dfa.to_rust("ends_with_abb").unwrap();
+        assert!(code.contains("pub fn ends_with_abb(s: &str) -> bool {"));
+        assert!(code.contains("static TRANSITIONS: [u32; 15] = ["));
+        assert!(code.contains("static ACCEPTING: [bool; 5] = ["));
+        assert!(code.contains("const CLASS_COUNT: usize = 3;"));
+
+        // every string matches, so there is no dead state
+        let any = Regex::Class(vec![CharRange::Range('\0', char::MAX)]).star();
+        let code = any.compile().unwrap().to_rust("any").unwrap();
+        assert!(code.contains("const DEAD: u32 = u32::MAX;"));
+    }
+
+    #[test]
+    fn test_dfa_to_rust_name() {
+        let dfa = Regex::new("a").unwrap().compile().unwrap();
+        assert!(dfa.to_rust("_private2").is_ok());
+        for name in ["", "_", "2a", "a-b", "é", "fn", "match"] {
+            assert_eq!(
+                dfa.to_rust(name),
+                Err(format!("`{name}` is not a valid function name"))
+            );
+        }
+    }
+}
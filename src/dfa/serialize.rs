@@ -0,0 +1,328 @@
+//! Writing a compiled DFA to bytes and matching with it straight from them, so that a DFA compiled
+//! at build time (e.g., in a build script, then included with `include_bytes!` or memory-mapped)
+//! costs nothing to compile when the program starts.
+//!
+//! The format is a header followed by the tables of the DFA, every number a little-endian `u32`:
+//!
+//! - the magic bytes `RZDF`, the format version, the number of states, the number of classes, the
+//!   number of intervals of the alphabet, and the dead state (or `u32::MAX` if there is none)
+//! - the class of each ASCII character
+//! - the first character of each interval, and the class of each interval
+//! - the transitions, at `state * class_count + class`
+//! - whether each state is accepting, one byte per state
+//!
+//! Numbers are read a byte at a time, so the bytes do not need to be aligned.
+
+use super::Dfa;
+use crate::minterms::Minterms;
+
+/// The bytes every serialized DFA starts with.
+const MAGIC: &[u8; 4] = b"RZDF";
+
+/// The version of the format, which changes whenever the format does.
+const VERSION: u32 = 1;
+
+/// Returns the `index`th little-endian `u32` of the bytes.
+fn word(words: &[u8], index: usize) -> u32 {
+    let start = index * 4;
+    u32::from_le_bytes([
+        words[start],
+        words[start + 1],
+        words[start + 2],
+        words[start + 3],
+    ])
+}
+
+/// Returns the little-endian `u32`s of the bytes.
+fn words(words: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    (0..words.len() / 4).map(|index| word(words, index))
+}
+
+/// Splits `len` bytes off the front of the bytes, or returns an error if there are not enough.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if bytes.len() < len {
+        return Err("The DFA bytes end too early".to_string());
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+/// Splits `count` little-endian `u32`s off the front of the bytes, or returns an error if there
+/// are not enough.
+fn take_words<'a>(bytes: &mut &'a [u8], count: usize) -> Result<&'a [u8], String> {
+    let len = count
+        .checked_mul(4)
+        .ok_or_else(|| "The DFA bytes are too long".to_string())?;
+    take(bytes, len)
+}
+
+/// A DFA read from the bytes written by `Dfa::to_bytes`, which matches by looking up its
+/// transitions in those bytes, without copying them.
+///
+/// Reading checks that every table has the right length and every number in it is in range, which
+/// takes one pass over the bytes and no allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DfaRef<'a> {
+    state_count: usize,
+    class_count: usize,
+    /// The dead state, or `u32::MAX` if there is none.
+    dead: u32,
+    ascii_classes: &'a [u8],
+    boundaries: &'a [u8],
+    interval_classes: &'a [u8],
+    transitions: &'a [u8],
+    accepting: &'a [u8],
+}
+
+impl<'a> DfaRef<'a> {
+    /// Reads a DFA from the bytes written by `Dfa::to_bytes`.
+    ///
+    /// Returns an error if the bytes are not a DFA written by this version of the crate's format.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, String> {
+        let mut rest = bytes;
+        if take(&mut rest, MAGIC.len())? != MAGIC {
+            return Err("The bytes are not a DFA".to_string());
+        }
+        let header = take_words(&mut rest, 5)?;
+        let version = word(header, 0);
+        if version != VERSION {
+            return Err(format!(
+                "The DFA was written in version {version} of the format, not {VERSION}"
+            ));
+        }
+        let [state_count, class_count, interval_count] =
+            [1, 2, 3].map(|index| word(header, index) as usize);
+        let dead = word(header, 4);
+
+        let dfa = Self {
+            state_count,
+            class_count,
+            dead,
+            ascii_classes: take_words(&mut rest, 128)?,
+            boundaries: take_words(&mut rest, interval_count)?,
+            interval_classes: take_words(&mut rest, interval_count)?,
+            transitions: take_words(
+                &mut rest,
+                state_count
+                    .checked_mul(class_count)
+                    .ok_or_else(|| "The DFA bytes are too long".to_string())?,
+            )?,
+            accepting: take(&mut rest, state_count)?,
+        };
+        if !rest.is_empty() {
+            return Err("The DFA bytes go on after the DFA".to_string());
+        }
+        dfa.validate()?;
+        Ok(dfa)
+    }
+
+    /// Returns an error if a number in the tables is out of range, so that matching cannot index
+    /// out of bounds.
+    fn validate(&self) -> Result<(), String> {
+        if self.state_count == 0 || self.class_count == 0 {
+            return Err("The DFA has no states or no classes".to_string());
+        }
+        if self.dead != u32::MAX && self.dead as usize >= self.state_count {
+            return Err("The dead state of the DFA is not one of its states".to_string());
+        }
+
+        let mut previous = None;
+        for boundary in words(self.boundaries) {
+            let valid = char::from_u32(boundary).is_some()
+                && previous.map_or(boundary == 0, |previous| boundary > previous);
+            if !valid {
+                return Err("The intervals of the DFA are not in order from `\\0`".to_string());
+            }
+            previous = Some(boundary);
+        }
+        if previous.is_none() {
+            return Err("The DFA has no intervals".to_string());
+        }
+
+        if words(self.interval_classes).any(|class| class as usize >= self.class_count) {
+            return Err("An interval of the DFA is in a class that does not exist".to_string());
+        }
+        for c in 0..128_u8 {
+            if word(self.ascii_classes, usize::from(c)) as usize != self.search_class(char::from(c))
+            {
+                return Err("The ASCII classes of the DFA do not match its intervals".to_string());
+            }
+        }
+        if words(self.transitions).any(|state| state as usize >= self.state_count) {
+            return Err("A transition of the DFA leads to a state that does not exist".to_string());
+        }
+        if self.accepting.iter().any(|&accepting| accepting > 1) {
+            return Err("A state of the DFA is neither accepting nor not".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the number of states of the DFA.
+    pub const fn state_count(&self) -> usize {
+        self.state_count
+    }
+
+    /// Returns the number of classes the alphabet is split into.
+    pub const fn class_count(&self) -> usize {
+        self.class_count
+    }
+
+    /// Returns the class of the character, searching the intervals for it.
+    fn search_class(&self, c: char) -> usize {
+        // the first boundary is `\0`, so the interval is the last one starting at or before `c`
+        let (mut low, mut high) = (0, self.boundaries.len() / 4);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if word(self.boundaries, middle) <= c as u32 {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        word(self.interval_classes, low) as usize
+    }
+
+    /// Returns the class of the character.
+    fn class_of(&self, c: char) -> usize {
+        if c.is_ascii() {
+            return word(self.ascii_classes, c as usize) as usize;
+        }
+        self.search_class(c)
+    }
+
+    /// Returns `true` if the DFA matches the given string, otherwise returns `false`.
+    pub fn matches(&self, s: &str) -> bool {
+        let mut state = Dfa::START;
+        for c in s.chars() {
+            state = word(
+                self.transitions,
+                state as usize * self.class_count + self.class_of(c),
+            );
+            if state == self.dead {
+                return false;
+            }
+        }
+        self.accepting[state as usize] == 1
+    }
+
+    /// Copies the DFA out of the bytes into a `Dfa`.
+    pub fn to_dfa(&self) -> Dfa {
+        let boundaries = words(self.boundaries)
+            .map(|boundary| char::from_u32(boundary).unwrap_or_default())
+            .collect();
+        let minterms = Minterms::with_ascii_classes(
+            boundaries,
+            words(self.interval_classes).collect(),
+            self.class_count,
+        );
+        Dfa {
+            minterms,
+            transitions: words(self.transitions).collect(),
+            accepting: self
+                .accepting
+                .iter()
+                .map(|&accepting| accepting == 1)
+                .collect(),
+            dead: (self.dead != u32::MAX).then_some(self.dead),
+        }
+    }
+}
+
+impl Dfa {
+    /// Writes the DFA to bytes, which `Dfa::from_bytes` reads back and `DfaRef` matches with
+    /// directly.
+    ///
+    /// The bytes are the same on every platform, so they can be written by a build script and
+    /// included in the program (e.g., with `include_bytes!`).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut words: Vec<u32> = vec![
+            VERSION,
+            u32::try_from(self.state_count()).unwrap_or(u32::MAX),
+            u32::try_from(self.class_count()).unwrap_or(u32::MAX),
+            u32::try_from(self.minterms.boundaries().len()).unwrap_or(u32::MAX),
+            self.dead.unwrap_or(u32::MAX),
+        ];
+        words.extend(self.minterms.ascii_classes());
+        words.extend(self.minterms.boundaries().iter().map(|&c| c as u32));
+        words.extend(self.minterms.interval_classes());
+        words.extend(&self.transitions);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(words.into_iter().flat_map(u32::to_le_bytes));
+        bytes.extend(self.accepting.iter().map(|&accepting| u8::from(accepting)));
+        bytes
+    }
+
+    /// Reads a DFA from the bytes written by `Dfa::to_bytes`, copying its tables.
+    ///
+    /// Returns an error if the bytes are not a DFA written by this version of the crate's format.
+    /// Use `DfaRef::from_bytes` to match with the bytes without copying them.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        DfaRef::from_bytes(bytes).map(|dfa| dfa.to_dfa())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Dfa, DfaRef, MAGIC};
+    #[allow(unused_imports)]
+    use crate::derivatives::Regex;
+
+    #[test]
+    fn test_dfa_bytes_round_trip() {
+        let inputs = ["", "abc", "abb", "babb", "ab12", "ééé", "βx", "x", "zz99"];
+        for pattern in ["(a|b)*abb", r"[a-z]+\d{2}", "é+|[α-ω]x", "a?b?", "[]"] {
+            let regex = Regex::new(pattern).unwrap();
+            let dfa = regex.compile().unwrap();
+            let bytes = dfa.to_bytes();
+            assert_eq!(Dfa::from_bytes(&bytes), Ok(dfa.clone()), "{pattern}");
+
+            let borrowed = DfaRef::from_bytes(&bytes).unwrap();
+            assert_eq!(borrowed.state_count(), dfa.state_count());
+            assert_eq!(borrowed.class_count(), dfa.class_count());
+            for s in inputs {
+                assert_eq!(borrowed.matches(s), regex.matches(s), "{pattern} {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_dfa_bytes_errors() {
+        let bytes = Regex::new("(a|b)*abb")
+            .unwrap()
+            .compile()
+            .unwrap()
+            .to_bytes();
+        assert!(DfaRef::from_bytes(&bytes).is_ok());
+
+        assert_eq!(
+            DfaRef::from_bytes(b"nope"),
+            Err("The bytes are not a DFA".to_string())
+        );
+        assert_eq!(
+            DfaRef::from_bytes(&bytes[..bytes.len() - 1]),
+            Err("The DFA bytes end too early".to_string())
+        );
+        assert_eq!(
+            DfaRef::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err("The DFA bytes go on after the DFA".to_string())
+        );
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = 2;
+        assert_eq!(
+            DfaRef::from_bytes(&newer),
+            Err("The DFA was written in version 2 of the format, not 1".to_string())
+        );
+
+        // the last transition leads to a state that does not exist
+        let mut corrupt = bytes.clone();
+        let last_transition = bytes.len() - 5 - 4;
+        corrupt[last_transition] = 200;
+        assert_eq!(
+            DfaRef::from_bytes(&corrupt),
+            Err("A transition of the DFA leads to a state that does not exist".to_string())
+        );
+    }
+}
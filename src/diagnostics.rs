@@ -0,0 +1,417 @@
+//! Explanations of why a string does not match a regex, and of which parts of a pattern can never
+//! match.
+
+use crate::charset::CharSet;
+use crate::derivatives::{Count, Regex};
+use crate::parser::dead_branch_spans;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+/// A counted repetition (e.g., `\d{3,6}`) that a string did not match, returned by
+/// `Regex::explain_repetition`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepetitionFailure {
+    /// The regex being repeated (e.g., `[0-9]`).
+    pub regex: Regex,
+    /// The number of times the regex should have been repeated.
+    pub count: Count,
+    /// The number of repetitions that were matched in full.
+    pub completed: usize,
+    /// The (1-based) index of the repetition that was being attempted when matching failed, which is
+    /// `completed + 1`.
+    pub attempted: usize,
+    /// The byte offset at which matching failed.
+    pub offset: usize,
+}
+
+impl RepetitionFailure {
+    /// Returns whether the string had more repetitions than the count allows.
+    fn too_many(&self) -> bool {
+        self.count
+            .bounds()
+            .1
+            .is_some_and(|max| self.attempted > max)
+    }
+}
+
+impl Display for RepetitionFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let expected = match self.count {
+            Count::Exact(n) => n.to_string(),
+            Count::Range(min, max) => format!("{min} to {max}"),
+            Count::AtLeast(min) => format!("at least {min}"),
+        };
+
+        if self.too_many() {
+            write!(
+                f,
+                "expected {expected} repetitions of {}, got at least {}",
+                self.regex, self.attempted
+            )
+        } else {
+            write!(
+                f,
+                "expected {expected} repetitions of {}, got {}",
+                self.regex, self.completed
+            )
+        }
+    }
+}
+
+/// Whether a string matched a regex, and if not, where and why it stopped being able to match,
+/// returned by `Regex::explain`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchExplanation {
+    /// The string matched.
+    Matched,
+    /// No string starting with the string up to the character at the byte offset `offset` matches,
+    /// although one starting with the string before it does.
+    UnexpectedChar {
+        /// The byte offset of the character.
+        offset: usize,
+        /// The character.
+        found: char,
+        /// The characters that would have kept the string able to match.
+        expected: CharSet,
+    },
+    /// The string ended before it matched, although some longer string starting with it matches
+    /// (or the regex matches nothing at all, in which case `expected` is empty).
+    UnexpectedEnd {
+        /// The characters that could have come next.
+        expected: CharSet,
+    },
+}
+
+impl MatchExplanation {
+    /// Returns `true` if the string matched, otherwise returns `false`.
+    pub const fn is_match(&self) -> bool {
+        matches!(self, Self::Matched)
+    }
+}
+
+impl Display for MatchExplanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Matched => write!(f, "matched"),
+            Self::UnexpectedChar {
+                offset,
+                found,
+                expected,
+            } if expected.is_empty() => {
+                write!(
+                    f,
+                    "unexpected {found:?} at position {offset}, expected the end"
+                )
+            }
+            Self::UnexpectedChar {
+                offset,
+                found,
+                expected,
+            } => write!(
+                f,
+                "unexpected {found:?} at position {offset}, expected {expected}"
+            ),
+            Self::UnexpectedEnd { expected } => {
+                write!(f, "unexpected end, expected {expected}")
+            }
+        }
+    }
+}
+
+/// Appends the factors of the regex's top-level concatenation to `factors`.
+fn collect_factors<'a>(regex: &'a Regex, factors: &mut Vec<&'a Regex>) {
+    if let Regex::Concat(left, right) = regex {
+        collect_factors(left, factors);
+        collect_factors(right, factors);
+    } else {
+        factors.push(regex);
+    }
+}
+
+/// Returns the largest offset `a` for which the factors before `index` match `s[..a]` and the
+/// factor at `index` can still match a string starting with `s[a..]`.
+fn active_start(factors: &[&Regex], index: usize, s: &str) -> Option<usize> {
+    let mut prefix = Regex::concat_all(factors[..index].iter().map(|&factor| factor.clone()));
+    let mut starts = Vec::new();
+    for (i, c) in s.char_indices() {
+        if prefix.nullable() {
+            starts.push(i);
+        }
+        prefix = prefix.derivative(c);
+        if prefix == Regex::Empty {
+            break;
+        }
+    }
+    if prefix.nullable() {
+        starts.push(s.len());
+    }
+
+    starts
+        .into_iter()
+        .rev()
+        .find(|&start| factors[index].derivative_str(&s[start..]) != Regex::Empty)
+}
+
+/// Counts the repetitions of `inner` in `s`, returning the number of complete repetitions and the
+/// remainder of the repetition in progress, if any.
+fn count_repetitions(inner: &Regex, s: &str) -> (usize, Option<Regex>) {
+    let mut completed = 0;
+    let mut current: Option<Regex> = None;
+    for c in s.chars() {
+        let next = current.as_ref().map(|current| current.derivative(c));
+        current = match next {
+            Some(next) if next != Regex::Empty => Some(next),
+            _ => {
+                // the repetition in progress is complete, so start the next one
+                if current.is_some() {
+                    completed += 1;
+                }
+                Some(inner.derivative(c))
+            }
+        };
+    }
+
+    match current {
+        Some(current) if current.nullable() => (completed + 1, None),
+        current => (completed, current),
+    }
+}
+
+/// Returns the byte ranges of the parts of a pattern that can never be part of a match (e.g.,
+/// `b[]` in `a|b[]`, or `[a--[a]]` in `x[a--[a]]*`), or an error if the pattern is invalid.
+///
+/// A part can never be part of a match if no string matches it, such as an empty class or an
+/// alternative containing one. Only the outermost such part is reported, and a repeated part is
+/// reported on its own, since its repetition still matches the empty string.
+pub fn dead_branches(pattern: &str) -> Result<Vec<Range<usize>>, String> {
+    dead_branch_spans(pattern)
+}
+
+impl Regex {
+    /// Explains whether the string matches the regex, and if not, the first character after which
+    /// it could no longer match along with the characters that would have been accepted there, or
+    /// the characters that could have followed if the string ended too early (e.g., `\d{3}-\d{4}`
+    /// against `555-12x4` expected `[0-9]` instead of `x` at position 6).
+    pub fn explain(&self, s: &str) -> MatchExplanation {
+        let mut current = self.clone();
+        for (offset, found) in s.char_indices() {
+            let next = current.derivative(found);
+            if next.matches_nothing() {
+                return MatchExplanation::UnexpectedChar {
+                    offset,
+                    found,
+                    expected: current.next_chars(),
+                };
+            }
+            current = next;
+        }
+
+        if current.nullable() {
+            MatchExplanation::Matched
+        } else {
+            MatchExplanation::UnexpectedEnd {
+                expected: current.next_chars(),
+            }
+        }
+    }
+
+    /// Explains why the string does not match the regex if matching stopped inside one of the
+    /// counted repetitions in its top-level concatenation (e.g., `\d{3,6}` matching `12`), by
+    /// reporting how many repetitions were completed and which one was being attempted.
+    ///
+    /// Returns `None` if the string matches or matching stopped somewhere else. When a string can be
+    /// split between repetitions in more than one way, each repetition is taken to end as late as
+    /// possible.
+    pub fn explain_repetition(&self, s: &str) -> Option<RepetitionFailure> {
+        let mut current = self.clone();
+        let mut offset = s.len();
+        let mut rejected = None;
+        for (i, c) in s.char_indices() {
+            let next = current.derivative(c);
+            if next == Self::Empty {
+                offset = i;
+                rejected = Some(c);
+                break;
+            }
+            current = next;
+        }
+        if rejected.is_none() && current.nullable() {
+            return None;
+        }
+
+        let consumed = &s[..offset];
+        let mut factors = Vec::new();
+        collect_factors(self, &mut factors);
+
+        for index in (0..factors.len()).rev() {
+            let Some(start) = active_start(&factors, index, consumed) else {
+                continue;
+            };
+
+            if let Self::Count(inner, count) = factors[index] {
+                let (completed, in_progress) = count_repetitions(inner, &consumed[start..]);
+                let (min, max) = count.bounds();
+                // whether the rejected character would have started another repetition
+                let continues = in_progress.is_none()
+                    && rejected.is_some_and(|c| inner.derivative(c) != Self::Empty);
+
+                if in_progress.is_some()
+                    || completed < min
+                    || (continues && max.is_some_and(|max| completed >= max))
+                {
+                    return Some(RepetitionFailure {
+                        regex: inner.as_ref().clone(),
+                        count: *count,
+                        completed,
+                        attempted: completed + 1,
+                        offset,
+                    });
+                }
+            }
+
+            // a factor that consumed some of the string is where matching stopped
+            if start < consumed.len() {
+                return None;
+            }
+        }
+
+        None
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{dead_branches, CharSet, Count, MatchExplanation, Regex, RepetitionFailure};
+    #[allow(unused_imports)]
+    use crate::CharRange;
+
+    #[test]
+    fn test_explain() {
+        let regex = Regex::new(r"\d{3}-\d{4}").unwrap();
+        let digits = CharSet::new(&[CharRange::Range('0', '9')]);
+        assert_eq!(regex.explain("555-1234"), MatchExplanation::Matched);
+        assert!(regex.explain("555-1234").is_match());
+
+        let explanation = regex.explain("555-12x4");
+        assert_eq!(
+            explanation,
+            MatchExplanation::UnexpectedChar {
+                offset: 6,
+                found: 'x',
+                expected: digits.clone(),
+            }
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "unexpected 'x' at position 6, expected [0-9]"
+        );
+
+        let explanation = regex.explain("555");
+        assert_eq!(
+            explanation,
+            MatchExplanation::UnexpectedEnd {
+                expected: CharSet::new(&[CharRange::Single('-')]),
+            }
+        );
+        assert_eq!(explanation.to_string(), r"unexpected end, expected [\-]");
+
+        assert_eq!(
+            regex.explain("555-12345").to_string(),
+            "unexpected '5' at position 8, expected the end"
+        );
+    }
+
+    #[test]
+    fn test_explain_unicode() {
+        // offsets are bytes
+        let regex = Regex::new("é+x").unwrap();
+        let explanation = regex.explain("ééy");
+        assert!(matches!(
+            explanation,
+            MatchExplanation::UnexpectedChar {
+                offset: 4,
+                found: 'y',
+                ..
+            }
+        ));
+        assert!(!explanation.is_match());
+    }
+
+    #[test]
+    fn test_explain_repetition_too_few() {
+        let regex = Regex::new(r"\d{3,6}").unwrap();
+        let failure = regex.explain_repetition("12").unwrap();
+        assert_eq!(
+            failure,
+            RepetitionFailure {
+                regex: Regex::new(r"\d").unwrap(),
+                count: Count::Range(3, 6),
+                completed: 2,
+                attempted: 3,
+                offset: 2,
+            }
+        );
+        assert_eq!(
+            failure.to_string(),
+            "expected 3 to 6 repetitions of [0-9], got 2"
+        );
+
+        let regex = Regex::new(r"id-\d{3}-x").unwrap();
+        let failure = regex.explain_repetition("id-12-x").unwrap();
+        assert_eq!((failure.completed, failure.offset), (2, 5));
+        assert_eq!(
+            failure.to_string(),
+            "expected 3 repetitions of [0-9], got 2"
+        );
+    }
+
+    #[test]
+    fn test_explain_repetition_too_many() {
+        let regex = Regex::new(r"\d{3,6}x").unwrap();
+        let failure = regex.explain_repetition("1234567x").unwrap();
+        assert_eq!((failure.completed, failure.attempted), (6, 7));
+        assert_eq!(failure.offset, 6);
+        assert_eq!(
+            failure.to_string(),
+            "expected 3 to 6 repetitions of [0-9], got at least 7"
+        );
+    }
+
+    #[test]
+    fn test_explain_repetition_in_progress() {
+        let regex = Regex::new("(ab){3}").unwrap();
+        let failure = regex.explain_repetition("abac").unwrap();
+        assert_eq!((failure.completed, failure.attempted), (1, 2));
+        assert_eq!(failure.offset, 3);
+    }
+
+    #[test]
+    fn test_explain_repetition_elsewhere() {
+        let regex = Regex::new(r"\d{3,6}x").unwrap();
+        assert_eq!(regex.explain_repetition("123x"), None);
+        // the repetition is complete, and `y` is not a digit
+        assert_eq!(regex.explain_repetition("123y"), None);
+        // the first repetition fails when the string does not start with a digit
+        let failure = regex.explain_repetition("y123x").unwrap();
+        assert_eq!((failure.completed, failure.offset), (0, 0));
+
+        let regex = Regex::new(r"a\d{2}").unwrap();
+        assert_eq!(regex.explain_repetition("b"), None);
+        assert_eq!(regex.explain_repetition("a1").unwrap().completed, 1);
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        assert_eq!(dead_branches("a|b*").unwrap(), vec![]);
+        assert_eq!(dead_branches("a|b[]").unwrap(), vec![2..5]);
+        assert_eq!(dead_branches("(x|[a-c&&[d-f]])y").unwrap(), vec![3..15]);
+        assert_eq!(dead_branches("x[a--[a]]*").unwrap(), vec![1..9]);
+        assert_eq!(dead_branches("x([])+").unwrap(), vec![0..6]);
+        assert_eq!(dead_branches("[]").unwrap(), vec![0..2]);
+
+        // spans are byte ranges into the whole pattern
+        assert_eq!(dead_branches("(?i)💕|(é[])?").unwrap(), vec![9..15]);
+
+        assert!(dead_branches("a|(").is_err());
+    }
+}
@@ -0,0 +1,284 @@
+//! A C ABI for embedding the derivative engine in other languages.
+//!
+//! Regexes are passed around as opaque `RzRegex` handles, and strings as NUL-terminated UTF-8. Every
+//! handle and string returned by these functions is owned by the caller, and must be freed with
+//! `rz_regex_free` or `rz_string_free` respectively.
+//!
+//! With the `ffi` feature, the build script writes a matching C header to `include/rzozowski.h` in
+//! Cargo's `OUT_DIR`, and a C library can be built with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+#![allow(unsafe_code)]
+
+use crate::derivatives::Regex;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// An opaque handle to a compiled regex.
+#[derive(Debug)]
+pub struct RzRegex(Regex);
+
+/// Runs `f`, returning `on_panic` if it panics, so that a panic in the library (e.g., running out
+/// of memory for a derivative that blows up) never unwinds into the caller, which is undefined
+/// behaviour across `extern "C"`.
+fn catch_panic<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Returns the string behind a pointer, or `None` if the pointer is null or the string is not
+/// valid UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string that is valid for the returned lifetime.
+unsafe fn str_from_ptr<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+
+    // SAFETY: `s` is not null, and the caller guarantees that it points to a NUL-terminated string.
+    let s = unsafe { CStr::from_ptr(s) };
+    s.to_str().ok()
+}
+
+/// Returns an owned C string with the given contents, replacing any NUL characters with U+FFFD.
+fn string_to_ptr(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', "\u{FFFD}")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Compiles a pattern into a regex, returning null if it is null, not valid UTF-8, or cannot be
+/// parsed, or if compiling it panics.
+///
+/// If compilation fails and `error` is not null, `*error` is set to a message that must be freed
+/// with `rz_string_free`.
+///
+/// # Safety
+///
+/// `pattern` must be null or a NUL-terminated string, and `error` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_new(
+    pattern: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut RzRegex {
+    let result = catch_panic(Err("Compiling the pattern panicked".to_string()), || {
+        // SAFETY: the caller guarantees that `pattern` is null or a NUL-terminated string.
+        unsafe { str_from_ptr(pattern) }
+            .ok_or_else(|| "Pattern is null or not valid UTF-8".to_string())
+            .and_then(Regex::new)
+    });
+
+    match result {
+        Ok(regex) => Box::into_raw(Box::new(RzRegex(regex))),
+        Err(message) => {
+            if !error.is_null() {
+                // SAFETY: `error` is not null, and the caller guarantees that it is valid for writes.
+                unsafe { *error = string_to_ptr(&message) };
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns 1 if the regex matches the string, 0 if it does not, or -1 if either is null, the
+/// string is not valid UTF-8, or matching panics.
+///
+/// # Safety
+///
+/// `regex` must be null or a handle returned by this library that has not been freed, and `s`
+/// must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_matches(regex: *const RzRegex, s: *const c_char) -> c_int {
+    catch_panic(-1, || {
+        // SAFETY: the caller guarantees that `regex` is null or a live handle.
+        let Some(regex) = (unsafe { regex.as_ref() }) else {
+            return -1;
+        };
+        // SAFETY: the caller guarantees that `s` is null or a NUL-terminated string.
+        let Some(s) = (unsafe { str_from_ptr(s) }) else {
+            return -1;
+        };
+
+        c_int::from(regex.0.matches(s))
+    })
+}
+
+/// Returns the derivative of the regex with respect to the given Unicode scalar value, or null if
+/// the regex is null, `c` is not a valid `char`, or taking the derivative panics.
+///
+/// # Safety
+///
+/// `regex` must be null or a handle returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_derivative(regex: *const RzRegex, c: u32) -> *mut RzRegex {
+    catch_panic(ptr::null_mut(), || {
+        // SAFETY: the caller guarantees that `regex` is null or a live handle.
+        let Some(regex) = (unsafe { regex.as_ref() }) else {
+            return ptr::null_mut();
+        };
+        let Some(c) = char::from_u32(c) else {
+            return ptr::null_mut();
+        };
+
+        Box::into_raw(Box::new(RzRegex(regex.0.derivative(c))))
+    })
+}
+
+/// Returns the regex as a string (e.g., `a(b)*`), or null if the regex is null or formatting it
+/// panics. The string must be freed with `rz_string_free`.
+///
+/// # Safety
+///
+/// `regex` must be null or a handle returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_to_string(regex: *const RzRegex) -> *mut c_char {
+    catch_panic(ptr::null_mut(), || {
+        // SAFETY: the caller guarantees that `regex` is null or a live handle.
+        unsafe { regex.as_ref() }
+            .map_or(ptr::null_mut(), |regex| string_to_ptr(&regex.0.to_string()))
+    })
+}
+
+/// Returns the derivative of the regex with respect to the given Unicode scalar value as a string,
+/// or null if the regex is null, `c` is not a valid `char`, or taking the derivative panics. The
+/// string must be freed with `rz_string_free`.
+///
+/// # Safety
+///
+/// `regex` must be null or a handle returned by this library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_derivative_string(regex: *const RzRegex, c: u32) -> *mut c_char {
+    catch_panic(ptr::null_mut(), || {
+        // SAFETY: the caller guarantees that `regex` is null or a live handle.
+        let Some(regex) = (unsafe { regex.as_ref() }) else {
+            return ptr::null_mut();
+        };
+        let Some(c) = char::from_u32(c) else {
+            return ptr::null_mut();
+        };
+
+        string_to_ptr(&regex.0.derivative(c).to_string())
+    })
+}
+
+/// Frees a regex handle. Does nothing if `regex` is null.
+///
+/// # Safety
+///
+/// `regex` must be null or a handle returned by this library that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rz_regex_free(regex: *mut RzRegex) {
+    if !regex.is_null() {
+        // SAFETY: the caller guarantees that `regex` was returned by `Box::into_raw` in this
+        // library and has not been freed.
+        catch_panic((), || drop(unsafe { Box::from_raw(regex) }));
+    }
+}
+
+/// Frees a string returned by this library. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rz_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: the caller guarantees that `s` was returned by `CString::into_raw` in this
+        // library and has not been freed.
+        catch_panic((), || drop(unsafe { CString::from_raw(s) }));
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{
+        c_char, catch_panic, ptr, rz_regex_derivative, rz_regex_derivative_string, rz_regex_free,
+        rz_regex_matches, rz_regex_new, rz_regex_to_string, rz_string_free, CStr, Regex, RzRegex,
+    };
+
+    /// Compiles a pattern, returning null if it fails.
+    #[allow(dead_code)]
+    fn compile(pattern: &CStr) -> *mut RzRegex {
+        // SAFETY: `pattern` is a NUL-terminated string, and a null `error` is allowed.
+        unsafe { rz_regex_new(pattern.as_ptr(), ptr::null_mut()) }
+    }
+
+    /// Copies a string returned by the library and frees it.
+    #[allow(dead_code)]
+    fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        // SAFETY: `s` is a live NUL-terminated string returned by the library.
+        let owned = unsafe { CStr::from_ptr(s) }.to_str().unwrap().to_string();
+        // SAFETY: `s` was returned by the library and is not used again.
+        unsafe { rz_string_free(s) };
+        owned
+    }
+
+    #[test]
+    fn test_ffi_compile_and_match() {
+        let regex = compile(c"ca+b");
+        assert!(!regex.is_null());
+
+        let matches = |regex: *const RzRegex, s: *const c_char| {
+            // SAFETY: `regex` is null or a live handle, and `s` is null or NUL-terminated.
+            unsafe { rz_regex_matches(regex, s) }
+        };
+        let results = [
+            matches(regex, c"caab".as_ptr()),
+            matches(regex, c"cb".as_ptr()),
+            matches(regex, ptr::null()),
+            matches(ptr::null(), c"cb".as_ptr()),
+        ];
+        assert_eq!(results, [1, 0, -1, -1]);
+
+        // SAFETY: `regex` is a live handle that is not used again.
+        unsafe { rz_regex_free(regex) };
+    }
+
+    #[test]
+    fn test_ffi_compile_error() {
+        let mut error = ptr::null_mut();
+        // SAFETY: the pattern is NUL-terminated and `error` is valid for writes.
+        let regex = unsafe { rz_regex_new(c"a{".as_ptr(), &mut error) };
+        assert!(regex.is_null());
+        assert!(!take_string(error).is_empty());
+
+        // SAFETY: a null pattern and a null `error` are allowed.
+        let regex = unsafe { rz_regex_new(ptr::null(), ptr::null_mut()) };
+        assert!(regex.is_null());
+    }
+
+    #[test]
+    fn test_ffi_derivative() {
+        let regex = compile(c"ca+b");
+        let expected = Regex::new("ca+b").unwrap().derivative('c').to_string();
+
+        // SAFETY: `regex` is a live handle.
+        let derivative = unsafe { rz_regex_derivative(regex, u32::from('c')) };
+        // SAFETY: `derivative` is a live handle and the string is NUL-terminated.
+        let matched = unsafe { rz_regex_matches(derivative, c"ab".as_ptr()) };
+        assert_eq!(matched, 1);
+
+        // SAFETY: `regex` is a live handle.
+        let s = unsafe { rz_regex_derivative_string(regex, u32::from('c')) };
+        assert_eq!(take_string(s), expected);
+        // SAFETY: `derivative` is a live handle.
+        let s = unsafe { rz_regex_to_string(derivative) };
+        assert_eq!(take_string(s), expected);
+
+        // SAFETY: `regex` is a live handle.
+        let invalid = unsafe { rz_regex_derivative(regex, 0xD800) };
+        assert!(invalid.is_null());
+
+        // SAFETY: both handles are live and not used again.
+        unsafe { rz_regex_free(derivative) };
+        // SAFETY: as above.
+        unsafe { rz_regex_free(regex) };
+    }
+
+    #[test]
+    fn test_ffi_catch_panic() {
+        assert_eq!(catch_panic(-1, || 1), 1);
+        assert_eq!(catch_panic(-1, || panic!("in the library")), -1);
+        assert!(catch_panic(ptr::null_mut::<RzRegex>(), || panic!("in the library")).is_null());
+    }
+}
@@ -0,0 +1,419 @@
+//! Generation of inputs that stress a regex, for benchmarking and fuzzing.
+
+use crate::derivatives::{CharRange, Count, Regex};
+
+/// The characters tried, after some random ones, when looking for one that satisfies a predicate.
+const PREDICATE_CANDIDATES: &[char] = &['a', 'Z', '0', '_', ' ', '\n', '-', '.', 'é', 'Ω', '💕'];
+
+/// How many random characters are tried when looking for one that satisfies a predicate.
+const PREDICATE_ATTEMPTS: usize = 64;
+
+/// How many repetitions are chosen for a counted repetition.
+#[derive(Debug, Clone, Copy)]
+enum Repetitions {
+    /// A random number between the minimum and maximum, at most `max_repeat` above the minimum.
+    Random,
+    /// As many as possible, up to the given number.
+    Long(usize),
+    /// Exactly the minimum, minus one if `under` is `true`.
+    Min { under: bool },
+    /// Exactly the maximum (or the minimum if unbounded), plus one if `over` is `true`.
+    Max { over: bool },
+}
+
+/// A seeded generator of strings that stress a regex (e.g., near misses, long runs, and counts at
+/// their boundaries).
+///
+/// The same seed always generates the same strings.
+#[derive(Debug, Clone)]
+pub struct InputGenerator {
+    state: u64,
+    max_repeat: usize,
+}
+
+impl InputGenerator {
+    /// Creates a generator with the given seed, which repeats unbounded repetitions (e.g., `a*`) at
+    /// most 8 times beyond their minimum.
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: seed,
+            max_repeat: 8,
+        }
+    }
+
+    /// Sets how many times unbounded repetitions are repeated at most beyond their minimum.
+    #[must_use]
+    pub const fn max_repeat(mut self, max_repeat: usize) -> Self {
+        self.max_repeat = max_repeat;
+        self
+    }
+
+    /// Returns the next pseudo-random number (using `SplitMix64`).
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a pseudo-random number in `min..=max`.
+    pub(crate) fn next_in(&mut self, min: u64, max: u64) -> u64 {
+        match (max - min).checked_add(1) {
+            Some(span) => min + self.next_u64() % span,
+            None => self.next_u64(),
+        }
+    }
+
+    /// Returns a pseudo-random character in `start..=end`.
+    fn next_char_in(&mut self, start: char, end: char) -> char {
+        char::from_u32(self.next_in(start as u64, end as u64) as u32).unwrap_or(start)
+    }
+
+    /// Returns a pseudo-random character satisfying `matches`, if one can be found.
+    fn next_char_where(&mut self, matches: impl Fn(char) -> bool) -> Option<char> {
+        (0..PREDICATE_ATTEMPTS)
+            .map(|_| self.next_char_in(' ', '~'))
+            .chain(PREDICATE_CANDIDATES.iter().copied())
+            .find(|c| matches(*c))
+    }
+
+    /// Appends a string matched by the regex to `out`, returning `None` if none could be generated.
+    fn generate(
+        &mut self,
+        regex: &Regex,
+        repetitions: Repetitions,
+        out: &mut String,
+    ) -> Option<()> {
+        match regex {
+            Regex::Empty => return None,
+            Regex::Epsilon => {}
+            Regex::Literal(c) => out.push(*c),
+            Regex::Str(s) => out.push_str(s),
+            Regex::Class(ranges) => {
+                if ranges.is_empty() {
+                    return None;
+                }
+                let c = match &ranges[self.next_in(0, ranges.len() as u64 - 1) as usize] {
+                    CharRange::Single(c) => *c,
+                    CharRange::Range(start, end) => self.next_char_in(*start, *end),
+                };
+                out.push(c);
+            }
+            Regex::Pred(predicate) => out.push(self.next_char_where(|c| predicate.test(c))?),
+            Regex::Concat(left, right) => {
+                self.generate(left, repetitions, out)?;
+                self.generate(right, repetitions, out)?;
+            }
+            Regex::Or(left, right) => {
+                let (first, second) = if self.next_u64() % 2 == 0 {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                let len = out.len();
+                if self.generate(first, repetitions, out).is_none() {
+                    out.truncate(len);
+                    self.generate(second, repetitions, out)?;
+                }
+            }
+            Regex::Shuffle(left, right) => {
+                let mut left_str = String::new();
+                let mut right_str = String::new();
+                self.generate(left, repetitions, &mut left_str)?;
+                self.generate(right, repetitions, &mut right_str)?;
+
+                let mut left_chars = left_str.chars().peekable();
+                let mut right_chars = right_str.chars().peekable();
+                while left_chars.peek().is_some() || right_chars.peek().is_some() {
+                    let from_left = right_chars.peek().is_none()
+                        || (left_chars.peek().is_some() && self.next_u64() % 2 == 0);
+                    let next = if from_left {
+                        left_chars.next()
+                    } else {
+                        right_chars.next()
+                    };
+                    out.extend(next);
+                }
+            }
+            Regex::Count(inner, count) => {
+                let (min, max) = match count {
+                    Count::Exact(n) => (*n, Some(*n)),
+                    Count::Range(min, max) => (*min, Some(*max)),
+                    Count::AtLeast(min) => (*min, None),
+                };
+                let n = match repetitions {
+                    Repetitions::Random => {
+                        let max = max.unwrap_or(min + self.max_repeat);
+                        self.next_in(min as u64, max as u64) as usize
+                    }
+                    Repetitions::Long(n) => max.map_or(n.max(min), |max| max.min(n.max(min))),
+                    Repetitions::Min { under } => min.saturating_sub(usize::from(under)),
+                    Repetitions::Max { over } => max.unwrap_or(min) + usize::from(over),
+                };
+                for _ in 0..n {
+                    self.generate(inner, repetitions, out)?;
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// Generates a string with the given repetitions.
+    fn generate_string(&mut self, regex: &Regex, repetitions: Repetitions) -> Option<String> {
+        let mut out = String::new();
+        self.generate(regex, repetitions, &mut out)?;
+        Some(out)
+    }
+
+    /// Returns a random string matched by the regex, or `None` if none could be generated.
+    pub fn matching(&mut self, regex: &Regex) -> Option<String> {
+        self.generate_string(regex, Repetitions::Random)
+    }
+
+    /// Returns a string matched by the regex except for its last character, which is replaced with
+    /// one that makes the match fail at that point. Returns `None` if no such string could be found.
+    pub fn near_miss(&mut self, regex: &Regex) -> Option<String> {
+        let mut prefix = self.matching(regex)?;
+        prefix.pop();
+
+        let remainder = regex.derivative_str(&prefix);
+        let (set, _) = remainder
+            .derivative_classes()
+            .into_iter()
+            .find(|(_, derivative)| *derivative == Regex::Empty)?;
+        let c = match &set.ranges()[0] {
+            CharRange::Single(c) => *c,
+            CharRange::Range(start, end) => self.next_char_in(*start, *end),
+        };
+
+        prefix.push(c);
+        Some(prefix)
+    }
+
+    /// Returns a string matched by the regex in which every repetition is repeated as close to
+    /// `len` times as its count allows (e.g., a long run inside `[a-z]*`).
+    pub fn long_runs(&mut self, regex: &Regex, len: usize) -> Option<String> {
+        self.generate_string(regex, Repetitions::Long(len))
+    }
+
+    /// Returns a random regex over the characters `a` to `c`, nested at most `depth` deep, for
+    /// checking properties that should hold for every regex.
+    #[cfg(any(test, feature = "testkit"))]
+    pub fn regex(&mut self, depth: usize) -> Regex {
+        if depth == 0 || self.next_in(0, 3) == 0 {
+            return match self.next_in(0, 5) {
+                0 => Regex::Epsilon,
+                1 => Regex::Empty,
+                2 => Regex::Class(vec![CharRange::Range('a', 'b')]),
+                3 => Regex::string("ab"),
+                _ => Regex::Literal(self.next_char_in('a', 'c')),
+            };
+        }
+
+        let depth = depth - 1;
+        match self.next_in(0, 4) {
+            0 => Regex::Concat(self.regex(depth).into(), self.regex(depth).into()),
+            1 => Regex::Or(self.regex(depth).into(), self.regex(depth).into()),
+            2 => Regex::Shuffle(self.regex(depth).into(), self.regex(depth).into()),
+            _ => {
+                let min = self.next_in(0, 2) as usize;
+                let count = match self.next_in(0, 2) {
+                    0 => Count::Exact(min),
+                    1 => Count::Range(min, min + self.next_in(0, 2) as usize),
+                    _ => Count::AtLeast(min),
+                };
+                Regex::Count(self.regex(depth).into(), count)
+            }
+        }
+    }
+
+    /// Returns a random regex that can be written as a pattern (so without `∅`, `ε`, interleavings
+    /// or predicates), nested at most `depth` deep, for checking that printing and parsing agree.
+    ///
+    /// Its characters include ones that must be escaped in patterns or classes (e.g., `*` and `-`).
+    #[cfg(any(test, feature = "testkit"))]
+    pub fn pattern_regex(&mut self, depth: usize) -> Regex {
+        if depth == 0 || self.next_in(0, 3) == 0 {
+            return match self.next_in(0, 3) {
+                0 => {
+                    let start = self.next_pattern_char();
+                    let end = self.next_pattern_char();
+                    Regex::Class(vec![
+                        CharRange::Range(start.min(end), start.max(end)),
+                        CharRange::Single(self.next_pattern_char()),
+                    ])
+                }
+                1 => Regex::Str(
+                    (0..self.next_in(2, 4))
+                        .map(|_| self.next_pattern_char())
+                        .collect(),
+                ),
+                _ => Regex::Literal(self.next_pattern_char()),
+            };
+        }
+
+        let depth = depth - 1;
+        match self.next_in(0, 2) {
+            0 => Regex::Concat(
+                self.pattern_regex(depth).into(),
+                self.pattern_regex(depth).into(),
+            ),
+            1 => Regex::Or(
+                self.pattern_regex(depth).into(),
+                self.pattern_regex(depth).into(),
+            ),
+            _ => {
+                let min = self.next_in(0, 2) as usize;
+                let count = match self.next_in(0, 2) {
+                    0 => Count::Exact(min),
+                    1 => Count::Range(min, min + self.next_in(0, 2) as usize),
+                    _ => Count::AtLeast(min),
+                };
+                Regex::Count(self.pattern_regex(depth).into(), count)
+            }
+        }
+    }
+
+    /// Returns a random character for `pattern_regex`.
+    #[cfg(any(test, feature = "testkit"))]
+    fn next_pattern_char(&mut self) -> char {
+        const CHARS: &[char] = &[
+            'a', 'b', 'c', '.', '*', '+', '?', '|', '(', ')', '[', ']', '{', '}', '-', ',', '\\',
+            '%', '@', ' ', 'é',
+        ];
+        CHARS[self.next_in(0, CHARS.len() as u64 - 1) as usize]
+    }
+
+    /// Returns strings in which every counted repetition is at, just under, or just over the
+    /// boundaries of its count, paired with whether the regex is expected to match them.
+    ///
+    /// Strings that are at the boundaries always match, while strings that are just under or just
+    /// over them usually do not, though another part of the regex may still match them.
+    pub fn count_boundaries(&mut self, regex: &Regex) -> Vec<(String, bool)> {
+        [
+            (Repetitions::Min { under: false }, true),
+            (Repetitions::Max { over: false }, true),
+            (Repetitions::Min { under: true }, false),
+            (Repetitions::Max { over: true }, false),
+        ]
+        .into_iter()
+        .filter_map(|(repetitions, at_boundary)| {
+            let s = self.generate_string(regex, repetitions)?;
+            Some((s, at_boundary))
+        })
+        .collect()
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[allow(dead_code)]
+    const PATTERNS: &[&str] = &[
+        "abcdef",
+        "a|b",
+        "[a-z]*",
+        r"\d{3,6}[a-z_]+",
+        "(a*b*c*)*d+",
+        "((a|b|c)(d|e|f)(g|h|i))+",
+        r"[a-zA-Z0-9._%+\-]+@[a-zA-Z0-9.\-]+\.[a-zA-Z]{2,}",
+    ];
+
+    #[test]
+    fn test_generate_matching() {
+        let mut generator = InputGenerator::new(42);
+        for pattern in PATTERNS {
+            let regex = Regex::new(pattern).unwrap();
+            for _ in 0..20 {
+                let s = generator.matching(&regex).unwrap();
+                assert!(regex.matches(&s), "{pattern}: {s:?}");
+            }
+        }
+
+        assert_eq!(generator.matching(&Regex::Empty), None);
+        assert_eq!(generator.matching(&Regex::Class(vec![])), None);
+
+        let regex = Regex::pred("alphabetic", char::is_alphabetic).plus();
+        assert!(regex.matches(&generator.matching(&regex).unwrap()));
+    }
+
+    #[test]
+    fn test_generate_is_deterministic() {
+        let regex = Regex::new("[a-z]{2,10}").unwrap();
+        let first = InputGenerator::new(7).matching(&regex);
+        let second = InputGenerator::new(7).matching(&regex);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_generate_near_miss() {
+        let mut generator = InputGenerator::new(42);
+        for pattern in PATTERNS {
+            let regex = Regex::new(pattern).unwrap();
+            let s = generator.near_miss(&regex).unwrap();
+            assert!(!regex.matches(&s), "{pattern}: {s:?}");
+
+            let mut prefix = s.clone();
+            prefix.pop();
+            assert_ne!(
+                regex.derivative_str(&prefix),
+                Regex::Empty,
+                "{pattern}: {s:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_long_runs() {
+        let mut generator = InputGenerator::new(42);
+        let regex = Regex::new("[a-z]*x{2,5}").unwrap();
+        let s = generator.long_runs(&regex, 100).unwrap();
+        assert_eq!(s.chars().count(), 105);
+        assert!(regex.matches(&s));
+    }
+
+    #[test]
+    fn test_generate_regex() {
+        let mut generator = InputGenerator::new(42);
+        for _ in 0..100 {
+            let regex = generator.regex(4);
+            // the regex only uses the characters `a` to `c`
+            assert_eq!(regex.derivative('d'), Regex::Empty, "{regex}");
+            if let Some(s) = generator.matching(&regex) {
+                assert!(regex.matches(&s), "{regex}: {s:?}");
+            }
+        }
+
+        assert_eq!(
+            InputGenerator::new(7).regex(3),
+            InputGenerator::new(7).regex(3)
+        );
+    }
+
+    #[test]
+    fn test_generate_count_boundaries() {
+        let mut generator = InputGenerator::new(42);
+        let regex = Regex::new("a{2,4}b").unwrap();
+        assert_eq!(
+            generator.count_boundaries(&regex),
+            vec![
+                ("aab".to_string(), true),
+                ("aaaab".to_string(), true),
+                ("ab".to_string(), false),
+                ("aaaaab".to_string(), false),
+            ]
+        );
+
+        for pattern in PATTERNS {
+            let regex = Regex::new(pattern).unwrap();
+            for (s, at_boundary) in generator.count_boundaries(&regex) {
+                if at_boundary {
+                    assert!(regex.matches(&s), "{pattern}: {s:?}");
+                }
+            }
+        }
+    }
+}
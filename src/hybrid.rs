@@ -0,0 +1,211 @@
+//! Choosing how to match a regex from how much it has been used, so that a pattern matched once
+//! does not pay to build a DFA, and a pattern matched against a lot of input ends up with one.
+
+use crate::cache::LazyDfa;
+use crate::derivatives::Regex;
+use crate::dfa::Dfa;
+use std::fmt::{Display, Formatter};
+
+/// The number of bytes of input after which a `HybridMatcher` switches from taking derivatives to a
+/// lazy DFA, which keeps the derivatives it finds for later strings.
+const LAZY_DFA_AFTER: usize = 1024;
+
+/// The number of bytes of input after which a `HybridMatcher` tries to compile a full DFA.
+const DFA_AFTER: usize = 1024 * 1024;
+
+/// The largest regex, in nodes, that a `HybridMatcher` tries to compile a full DFA for, since
+/// larger ones tend to have too many states.
+const MAX_DFA_PATTERN_SIZE: usize = 1000;
+
+/// The number of states a `HybridMatcher` allows the full DFA it compiles to have.
+const MAX_DFA_STATES: usize = 10_000;
+
+/// A way of matching a regex, from the cheapest to set up to the fastest to match with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Engine {
+    /// The derivative of the regex is taken with respect to each character, as with
+    /// `Regex::matches`, and nothing is kept between strings.
+    Derivatives,
+    /// The derivatives are kept between strings in a `LazyDfa`, so each is only taken once.
+    LazyDfa,
+    /// The regex is compiled into a `Dfa` up front, so matching only looks up transitions.
+    Dfa,
+}
+
+impl Display for Engine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Derivatives => "derivatives",
+            Self::LazyDfa => "lazy DFA",
+            Self::Dfa => "DFA",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The engine a `HybridMatcher` is matching with, along with what it needs.
+#[derive(Debug, Clone)]
+enum State {
+    Derivatives,
+    LazyDfa(Box<LazyDfa>),
+    Dfa(Box<Dfa>),
+}
+
+/// A matcher that picks its engine from how much input it has matched, or uses the one it was
+/// told to (see `RegexBuilder::engine`).
+///
+/// It starts by taking derivatives, which costs nothing to set up. After about a kilobyte of
+/// input, it keeps its derivatives between strings in a `LazyDfa`. After about a megabyte, it
+/// compiles the regex into a full `Dfa` if the regex has fewer than 1,000 nodes and the DFA has at
+/// most 10,000 states, and otherwise stays with the lazy DFA.
+#[derive(Debug, Clone)]
+pub struct HybridMatcher {
+    regex: Regex,
+    state: State,
+    /// Whether the engine was chosen by the user, so it is never changed.
+    forced: bool,
+    /// Whether compiling a full DFA has been tried and failed, so it is not tried again.
+    dfa_failed: bool,
+    /// The number of bytes of input matched so far.
+    input_len: usize,
+}
+
+impl HybridMatcher {
+    /// Creates a matcher that picks its engine as it is used.
+    pub fn new(regex: &Regex) -> Self {
+        Self {
+            regex: regex.clone(),
+            state: State::Derivatives,
+            forced: false,
+            dfa_failed: false,
+            input_len: 0,
+        }
+    }
+
+    /// Creates a matcher that always uses the given engine.
+    ///
+    /// Returns an error if the engine is `Engine::Dfa` and the regex cannot be compiled into a DFA
+    /// (see `Regex::compile`).
+    pub fn with_engine(regex: &Regex, engine: Engine) -> Result<Self, String> {
+        let state = match engine {
+            Engine::Derivatives => State::Derivatives,
+            Engine::LazyDfa => State::LazyDfa(Box::new(LazyDfa::new(regex))),
+            Engine::Dfa => State::Dfa(Box::new(regex.compile()?)),
+        };
+        Ok(Self {
+            regex: regex.clone(),
+            state,
+            forced: true,
+            dfa_failed: false,
+            input_len: 0,
+        })
+    }
+
+    /// Returns the regex being matched.
+    pub const fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the engine the next string will be matched with.
+    pub const fn engine(&self) -> Engine {
+        match self.state {
+            State::Derivatives => Engine::Derivatives,
+            State::LazyDfa(_) => Engine::LazyDfa,
+            State::Dfa(_) => Engine::Dfa,
+        }
+    }
+
+    /// Returns `true` if the regex matches the given string, otherwise returns `false`.
+    pub fn matches(&mut self, s: &str) -> bool {
+        self.input_len = self.input_len.saturating_add(s.len());
+        let matched = match &mut self.state {
+            State::Derivatives => self.regex.matches(s),
+            State::LazyDfa(dfa) => dfa.matches(s),
+            State::Dfa(dfa) => dfa.matches(s),
+        };
+        if !self.forced {
+            self.upgrade();
+        }
+        matched
+    }
+
+    /// Switches to a faster engine if enough input has been matched to pay for it.
+    fn upgrade(&mut self) {
+        match self.state {
+            State::Derivatives if self.input_len >= LAZY_DFA_AFTER => {
+                self.state = State::LazyDfa(Box::new(LazyDfa::new(&self.regex)));
+            }
+            State::LazyDfa(_) if self.input_len >= DFA_AFTER && !self.dfa_failed => {
+                let dfa = (self.regex.node_count() <= MAX_DFA_PATTERN_SIZE)
+                    .then(|| self.regex.compile_with_max_states(MAX_DFA_STATES).ok())
+                    .flatten();
+                match dfa {
+                    Some(dfa) => self.state = State::Dfa(Box::new(dfa)),
+                    None => self.dfa_failed = true,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Engine, HybridMatcher, Regex};
+
+    #[test]
+    fn test_hybrid_upgrades() {
+        let regex = Regex::new(r"(\w+\s)*\w+").unwrap();
+        let mut matcher = HybridMatcher::new(&regex);
+        assert_eq!(matcher.engine(), Engine::Derivatives);
+        assert!(matcher.matches("the quick brown fox"));
+        assert!(!matcher.matches("the quick brown fox "));
+        assert_eq!(matcher.engine(), Engine::Derivatives);
+
+        let input = "the quick brown fox ".repeat(100) + "jumps";
+        assert!(matcher.matches(&input));
+        assert_eq!(matcher.engine(), Engine::LazyDfa);
+
+        for _ in 0..600 {
+            assert!(matcher.matches(&input));
+        }
+        assert_eq!(matcher.engine(), Engine::Dfa);
+        assert!(matcher.matches("jumps over"));
+        assert!(!matcher.matches("jumps  over"));
+    }
+
+    #[test]
+    fn test_hybrid_without_dfa() {
+        // a regex with predicates cannot be compiled into a DFA, so it stays with the lazy DFA
+        let vowel = Regex::pred("vowel", |c| "aeiou".contains(c));
+        let regex = vowel.star();
+        let mut matcher = HybridMatcher::new(&regex);
+        let input = "aeiou".repeat(1000);
+        for _ in 0..300 {
+            assert!(matcher.matches(&input));
+        }
+        assert_eq!(matcher.engine(), Engine::LazyDfa);
+        assert!(!matcher.matches("aeb"));
+    }
+
+    #[test]
+    fn test_hybrid_forced() {
+        let regex = Regex::new(r"[a-z]+\d").unwrap();
+        for engine in [Engine::Derivatives, Engine::LazyDfa, Engine::Dfa] {
+            let mut matcher = HybridMatcher::with_engine(&regex, engine).unwrap();
+            let input = "abc".repeat(1000) + "1";
+            for _ in 0..400 {
+                assert!(matcher.matches(&input));
+            }
+            assert!(!matcher.matches("abc"));
+            assert_eq!(matcher.engine(), engine, "{engine}");
+        }
+
+        let vowel = Regex::pred("vowel", |c| "aeiou".contains(c));
+        assert_eq!(
+            HybridMatcher::with_engine(&vowel, Engine::Dfa).unwrap_err(),
+            "Cannot compile a regex with predicates into a DFA"
+        );
+    }
+}
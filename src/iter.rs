@@ -0,0 +1,129 @@
+//! Iterator adaptors for matching many strings against the same regex.
+//!
+//! The strings share one cache of derivatives, so each derivative is only calculated the first time
+//! it is needed, which makes matching a batch of similar strings much faster than calling
+//! `Regex::matches` on each of them.
+
+use crate::cache::CachedMatcher;
+use crate::derivatives::Regex;
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+impl Regex {
+    /// Returns whether the regex matches each of the strings, in order.
+    ///
+    /// The strings share one cache of derivatives. With the `rayon` feature, the strings are
+    /// matched in parallel, with one cache per thread.
+    pub fn matches_batch(&self, inputs: &[&str]) -> Vec<bool> {
+        #[cfg(feature = "rayon")]
+        {
+            inputs
+                .par_iter()
+                .map_init(|| CachedMatcher::new(self), |matcher, s| matcher.matches(s))
+                .collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut matcher = CachedMatcher::new(self);
+            inputs.iter().map(|s| matcher.matches(s)).collect()
+        }
+    }
+}
+
+/// An iterator over the items of another iterator that match a regex, created by
+/// `RegexIteratorExt::filter_matches`.
+#[derive(Debug, Clone)]
+pub struct FilterMatches<I> {
+    iter: I,
+    matcher: CachedMatcher,
+}
+
+impl<I> Iterator for FilterMatches<I>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let matcher = &mut self.matcher;
+        self.iter.find(|item| matcher.matches(item.as_ref()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Extends iterators over strings (e.g., `&str` or `String`) with methods for matching every item
+/// against a regex.
+pub trait RegexIteratorExt: Iterator + Sized
+where
+    Self::Item: AsRef<str>,
+{
+    /// Returns an iterator over the items that match the regex.
+    fn filter_matches(self, regex: &Regex) -> FilterMatches<Self> {
+        FilterMatches {
+            iter: self,
+            matcher: CachedMatcher::new(regex),
+        }
+    }
+
+    /// Splits the items into those that match the regex and those that do not, keeping their order.
+    fn partition_matches(self, regex: &Regex) -> (Vec<Self::Item>, Vec<Self::Item>) {
+        let mut matcher = CachedMatcher::new(regex);
+        self.partition(|item| matcher.matches(item.as_ref()))
+    }
+}
+
+impl<I> RegexIteratorExt for I
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Regex, RegexIteratorExt};
+
+    #[test]
+    fn test_filter_matches() {
+        let regex = Regex::new("a+b").unwrap();
+        let matched = ["ab", "b", "aab", "aa", "aaab"]
+            .into_iter()
+            .filter_matches(&regex)
+            .collect::<Vec<_>>();
+        assert_eq!(matched, vec!["ab", "aab", "aaab"]);
+
+        let lines = vec!["ab".to_string(), "ba".to_string()];
+        let matched = lines.into_iter().filter_matches(&regex).collect::<Vec<_>>();
+        assert_eq!(matched, vec!["ab".to_string()]);
+    }
+
+    #[test]
+    fn test_matches_batch() {
+        let regex = Regex::new(r"[a-z]+\d{2}").unwrap();
+        let inputs = ["ab12", "a1", "", "zz99", "zz999"];
+        assert_eq!(
+            regex.matches_batch(&inputs),
+            vec![true, false, false, true, false]
+        );
+        assert_eq!(regex.matches_batch(&[]), Vec::<bool>::new());
+
+        let inputs = (0..1000).map(|i| format!("x{i}")).collect::<Vec<_>>();
+        let inputs = inputs.iter().map(String::as_str).collect::<Vec<_>>();
+        let expected = inputs.iter().map(|s| regex.matches(s)).collect::<Vec<_>>();
+        assert_eq!(regex.matches_batch(&inputs), expected);
+    }
+
+    #[test]
+    fn test_partition_matches() {
+        let regex = Regex::new(r"\d+").unwrap();
+        let (numbers, words) = ["1", "one", "22", "two", ""]
+            .into_iter()
+            .partition_matches(&regex);
+        assert_eq!(numbers, vec!["1", "22"]);
+        assert_eq!(words, vec!["one", "two", ""]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Splitting a string into tokens with maximal munch, for replacing hand-written scanners.
+//!
+//! At each position, every rule's regex is matched against the rest of the string at once, and the
+//! longest match wins. A tie between rules matching the same length is won by the rule that comes
+//! first, so keywords should be listed before identifiers.
+
+use crate::derivatives::Regex;
+use crate::set::{initial_states, next_states};
+use std::ops::Range;
+
+/// A lexer built from an ordered list of rules, each a regex and the token it produces.
+///
+/// Rules that only match the empty string never produce a token, since they would not make
+/// progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexer<T> {
+    rules: Vec<(Regex, T)>,
+}
+
+impl<T: Clone> Lexer<T> {
+    /// Creates a lexer from its rules, in order of priority.
+    pub fn new(rules: impl IntoIterator<Item = (Regex, T)>) -> Self {
+        Self {
+            rules: rules.into_iter().collect(),
+        }
+    }
+
+    /// Returns the rules, in order of priority.
+    pub fn rules(&self) -> &[(Regex, T)] {
+        &self.rules
+    }
+
+    /// Returns the token at the byte offset `start` of the input and its byte range, or `None` if
+    /// no rule matches a non-empty prefix of the input there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a character boundary of the input.
+    pub fn token_at(&self, input: &str, start: usize) -> Option<(T, Range<usize>)> {
+        // the distinct derivatives of the rules that can still match, each with the indices of the
+        // rules that reached it
+        let mut states = initial_states(self.rules.iter().map(|(regex, _)| regex));
+        let mut longest: Option<(usize, usize)> = None;
+        for (i, c) in input[start..].char_indices() {
+            states = next_states(states, c);
+            if states.is_empty() {
+                break;
+            }
+
+            let end = start + i + c.len_utf8();
+            let winner = states
+                .iter()
+                .filter(|(state, _)| state.nullable())
+                .flat_map(|(_, indices)| indices.iter().copied())
+                .min();
+            if let Some(winner) = winner {
+                longest = Some((winner, end));
+            }
+        }
+
+        longest.map(|(index, end)| (self.rules[index].1.clone(), start..end))
+    }
+
+    /// Returns an iterator over the tokens of the input and their byte ranges, in order.
+    ///
+    /// If no rule matches at some position, the iterator yields an error and ends.
+    pub const fn tokens<'l, 's>(&'l self, input: &'s str) -> Tokens<'l, 's, T> {
+        Tokens {
+            lexer: self,
+            input,
+            position: 0,
+            failed: false,
+        }
+    }
+}
+
+/// An iterator over the tokens of a string, returned by `Lexer::tokens`.
+#[derive(Debug, Clone)]
+pub struct Tokens<'l, 's, T> {
+    lexer: &'l Lexer<T>,
+    input: &'s str,
+    /// The byte offset of the next token.
+    position: usize,
+    /// Whether no rule matched at `position`, so the iterator has ended.
+    failed: bool,
+}
+
+impl<T: Clone> Iterator for Tokens<'_, '_, T> {
+    type Item = Result<(T, Range<usize>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.position >= self.input.len() {
+            return None;
+        }
+
+        match self.lexer.token_at(self.input, self.position) {
+            Some((token, span)) => {
+                self.position = span.end;
+                Some(Ok((token, span)))
+            }
+            None => {
+                self.failed = true;
+                Some(Err(format!(
+                    "No token matches at position {}",
+                    self.position
+                )))
+            }
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Lexer, Regex};
+
+    #[allow(dead_code)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Token {
+        If,
+        Ident,
+        Number,
+        Op,
+        Space,
+    }
+
+    #[allow(dead_code)]
+    fn lexer() -> Lexer<Token> {
+        Lexer::new([
+            (Regex::new("if").unwrap(), Token::If),
+            (Regex::new("[a-z][a-z0-9]*").unwrap(), Token::Ident),
+            (Regex::new(r"\d+").unwrap(), Token::Number),
+            (Regex::new("=|==|<|<=").unwrap(), Token::Op),
+            (Regex::new(" +").unwrap(), Token::Space),
+        ])
+    }
+
+    #[test]
+    fn test_lexer_maximal_munch() {
+        let tokens = lexer()
+            .tokens("if iffy <= 10")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                // `if` ties with an identifier, and the earlier rule wins
+                (Token::If, 0..2),
+                (Token::Space, 2..3),
+                // `iffy` is longer than `if`
+                (Token::Ident, 3..7),
+                (Token::Space, 7..8),
+                (Token::Op, 8..10),
+                (Token::Space, 10..11),
+                (Token::Number, 11..13),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_error() {
+        let tokens = lexer().tokens("x = ?y").collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Ok((Token::Ident, 0..1)),
+                Ok((Token::Space, 1..2)),
+                Ok((Token::Op, 2..3)),
+                Ok((Token::Space, 3..4)),
+                Err("No token matches at position 4".to_string()),
+            ]
+        );
+        assert_eq!(lexer().tokens("").count(), 0);
+    }
+
+    #[test]
+    fn test_lexer_token_at() {
+        let lexer = lexer();
+        assert_eq!(lexer.token_at("a==b", 1), Some((Token::Op, 1..3)));
+        assert_eq!(lexer.token_at("a==b", 4), None);
+
+        // empty matches are not tokens
+        let lexer = Lexer::new([
+            (Regex::new("a*").unwrap(), 0),
+            (Regex::new("é").unwrap(), 1),
+        ]);
+        assert_eq!(lexer.token_at("bé", 0), None);
+        assert_eq!(lexer.token_at("bé", 1), Some((1, 1..3)));
+    }
+}
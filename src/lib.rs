@@ -47,10 +47,73 @@
     unused_extern_crates,
     unused_import_braces,
 )]
+// The dev-dependencies are only used by the benchmarks.
+#![cfg_attr(test, allow(unused_crate_dependencies))]
 
 //! *rzozowski* (ruh-zov-ski) is a Rust crate for reasoning about regular expressions in terms of Brzozowski derivatives.
 
+pub mod alphabet;
+mod arena;
+mod budget;
+pub mod build;
+mod builder;
+pub mod bytes;
+mod cache;
+mod case;
+mod charset;
+pub mod coverage;
 mod derivatives;
+mod dfa;
+pub mod diagnostics;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod generate;
+mod hybrid;
+pub mod iter;
+pub mod lexer;
+mod matcher;
+mod minterms;
+#[cfg(feature = "unicode-normalization")]
+pub mod normalize;
+mod os;
+pub mod pack;
 mod parser;
+pub mod peg;
+mod prefilter;
+mod reader;
+mod search;
+mod searcher;
+pub mod segments;
+#[cfg(feature = "serde")]
+pub mod serde_pattern;
+mod set;
+#[cfg(feature = "concurrent-cache")]
+mod shared;
+pub mod specificity;
+mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod template;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
 
-pub use derivatives::{CharRange, Count, Regex};
+pub use arena::Arena;
+pub use budget::{MatchBudget, MatchError};
+pub use builder::RegexBuilder;
+pub use cache::{Cache, LazyDfa};
+pub use charset::{CharSet, CharSetChars};
+pub use derivatives::{CharRange, Count, Node, Predicate, Regex, SimplifyLevel, SimplifyOptions};
+pub use dfa::{Dfa, DfaRef};
+pub use hybrid::{Engine, HybridMatcher};
+pub use iter::RegexIteratorExt;
+pub use matcher::{Checkpoint, Matcher, Strategy, StrategyReason, StrategyReport};
+pub use parser::{ControlCharPolicy, ParseError, ParseErrorKind};
+pub use reader::{ScanLines, ScannedLine};
+pub use search::{MatchMode, Matches};
+pub use searcher::{MatchEnds, StreamSearcher};
+pub use set::{RegexSet, SetMatches};
+#[cfg(feature = "concurrent-cache")]
+pub use shared::SharedMatcher;
+pub use stats::{EvalStats, MatchStats};
+#[cfg(feature = "tokio")]
+pub use stream::StreamMatch;
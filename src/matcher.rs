@@ -0,0 +1,351 @@
+//! Matching a regex one character at a time, for input that arrives gradually (e.g., keystrokes in
+//! a text field).
+
+use crate::charset::CharSet;
+use crate::derivatives::{CharRange, Regex};
+use std::fmt::{Display, Formatter};
+
+/// How a `Matcher` matches its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Strategy {
+    /// The regex only matches one string, so the input is compared with it character by character.
+    Literal,
+    /// The derivative of the regex is taken with respect to each character.
+    Derivatives,
+}
+
+/// Why a `Strategy` was chosen over a faster one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StrategyReason {
+    /// The regex only matches one string (e.g., `abc`).
+    SingleString,
+    /// The regex matches more than one string (e.g., `ab*`), so it cannot be compared as a literal.
+    NotLiteral,
+    /// The regex matches no strings at all.
+    MatchesNothing,
+}
+
+/// The strategy a `Matcher` uses and why it was chosen, returned by `Matcher::strategy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrategyReport {
+    /// The strategy used.
+    pub strategy: Strategy,
+    /// Why it was chosen.
+    pub reason: StrategyReason,
+}
+
+impl Display for StrategyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let strategy = match self.strategy {
+            Strategy::Literal => "literal comparison",
+            Strategy::Derivatives => "derivatives",
+        };
+        let reason = match self.reason {
+            StrategyReason::SingleString => "the regex only matches one string",
+            StrategyReason::NotLiteral => "the regex matches more than one string",
+            StrategyReason::MatchesNothing => "the regex matches no strings",
+        };
+        write!(f, "{strategy}, because {reason}")
+    }
+}
+
+/// The state of a `Matcher` part way through its input.
+#[derive(Debug, Clone)]
+enum State {
+    /// The number of bytes of the literal matched so far, or `None` once the input has diverged from
+    /// it.
+    Literal(Option<usize>),
+    /// The derivative of the regex with respect to the input so far.
+    Derivative(Regex),
+}
+
+/// A regex part way through matching some input, which is fed to it one character at a time.
+///
+/// Each character only takes one derivative, so checking the input after every keystroke does not
+/// need to match the whole input again. A regex that only matches one string is compared with the
+/// input directly instead; `Matcher::strategy` reports which is used.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    regex: Regex,
+    /// The only string the regex matches, if there is exactly one.
+    literal: Option<String>,
+    state: State,
+}
+
+/// Returns the only string the regex matches if it is a literal, string or `ε`.
+fn single_string(regex: &Regex) -> Option<String> {
+    match regex {
+        Regex::Epsilon => Some(String::new()),
+        Regex::Literal(c) => Some(c.to_string()),
+        Regex::Str(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+impl Matcher {
+    /// Creates a matcher for the regex that has not been fed any input.
+    pub fn new(regex: &Regex) -> Self {
+        let literal = single_string(regex);
+        let state = Self::start(regex, literal.as_ref());
+        Self {
+            regex: regex.clone(),
+            literal,
+            state,
+        }
+    }
+
+    /// Returns the state before any input has been fed.
+    fn start(regex: &Regex, literal: Option<&String>) -> State {
+        if literal.is_some() {
+            State::Literal(Some(0))
+        } else {
+            State::Derivative(regex.clone())
+        }
+    }
+
+    /// Returns the strategy the matcher uses and why it was chosen.
+    pub fn strategy(&self) -> StrategyReport {
+        let (strategy, reason) = if self.literal.is_some() {
+            (Strategy::Literal, StrategyReason::SingleString)
+        } else if self.regex == Regex::Empty {
+            (Strategy::Derivatives, StrategyReason::MatchesNothing)
+        } else {
+            (Strategy::Derivatives, StrategyReason::NotLiteral)
+        };
+        StrategyReport { strategy, reason }
+    }
+
+    /// Feeds the next character of the input to the matcher.
+    pub fn push(&mut self, c: char) {
+        match &mut self.state {
+            State::Literal(matched) => {
+                let literal = self.literal.as_deref().unwrap_or_default();
+                *matched = matched.filter(|&matched| literal[matched..].starts_with(c));
+                if let Some(matched) = matched {
+                    *matched += c.len_utf8();
+                }
+            }
+            // every derivative of ∅ is ∅
+            State::Derivative(current) if *current != Regex::Empty => {
+                *current = current.derivative(c);
+            }
+            State::Derivative(_) => {}
+        }
+    }
+
+    /// Returns `true` if the regex matches the input fed so far, otherwise returns `false`.
+    pub fn is_match(&self) -> bool {
+        match &self.state {
+            State::Literal(matched) => *matched == self.literal.as_ref().map(String::len),
+            State::Derivative(current) => current.nullable(),
+        }
+    }
+
+    /// Returns `true` if the regex cannot match any input starting with the input fed so far, so
+    /// feeding it more characters is pointless, otherwise returns `false`.
+    pub fn is_dead(&self) -> bool {
+        match &self.state {
+            State::Literal(matched) => matched.is_none(),
+            State::Derivative(current) => *current == Regex::Empty,
+        }
+    }
+
+    /// Returns the set of characters that can be fed next without making the matcher dead, e.g.,
+    /// for suggesting what to type next. See `Regex::next_chars`.
+    pub fn allowed_next(&self) -> CharSet {
+        match &self.state {
+            State::Literal(matched) => {
+                let literal = self.literal.as_deref().unwrap_or_default();
+                matched
+                    .and_then(|matched| literal[matched..].chars().next())
+                    .map_or_else(CharSet::default, |c| CharSet::new(&[CharRange::Single(c)]))
+            }
+            State::Derivative(current) => current.next_chars(),
+        }
+    }
+
+    /// Forgets the input fed so far, as if the matcher had just been created.
+    pub fn reset(&mut self) {
+        self.state = Self::start(&self.regex, self.literal.as_ref());
+    }
+
+    /// Returns a checkpoint of the input fed so far, which `restore` goes back to (e.g., before
+    /// feeding input that may be undone).
+    pub fn save(&self) -> Checkpoint {
+        Checkpoint {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Goes back to the checkpoint, as if the input fed since it was saved had never been fed,
+    /// without taking any derivatives again.
+    ///
+    /// The checkpoint must have been saved by this matcher or a clone of it; the matcher is
+    /// meaningless (and may panic) after restoring a checkpoint of a matcher for another regex.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.state = checkpoint.state;
+    }
+}
+
+/// The state of a `Matcher` after some input, returned by `Matcher::save`.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    state: State,
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{CharRange, CharSet, Matcher, Regex, Strategy, StrategyReason};
+
+    #[test]
+    fn test_matcher() {
+        let regex = Regex::new(r"\d{3}-\d{4}").unwrap();
+        let mut matcher = Matcher::new(&regex);
+        assert!(!matcher.is_match() && !matcher.is_dead());
+
+        for c in "555-12".chars() {
+            matcher.push(c);
+            assert!(!matcher.is_dead());
+        }
+        assert!(!matcher.is_match());
+
+        matcher.push('3');
+        matcher.push('4');
+        assert!(matcher.is_match());
+
+        matcher.push('5');
+        assert!(!matcher.is_match() && matcher.is_dead());
+        matcher.push('6');
+        assert!(matcher.is_dead());
+    }
+
+    #[test]
+    fn test_matcher_reset() {
+        let regex = Regex::new("ab*").unwrap();
+        let mut matcher = Matcher::new(&regex);
+        matcher.push('b');
+        assert!(matcher.is_dead());
+
+        matcher.reset();
+        assert!(!matcher.is_dead());
+        matcher.push('a');
+        assert!(matcher.is_match());
+        matcher.push('b');
+        assert!(matcher.is_match());
+    }
+
+    #[test]
+    fn test_matcher_literal() {
+        let regex = Regex::new("héllo").unwrap();
+        let mut matcher = Matcher::new(&regex);
+        assert_eq!(matcher.strategy().strategy, Strategy::Literal);
+
+        for c in "hél".chars() {
+            matcher.push(c);
+        }
+        assert!(!matcher.is_match() && !matcher.is_dead());
+        matcher.push('l');
+        matcher.push('o');
+        assert!(matcher.is_match());
+        matcher.push('o');
+        assert!(!matcher.is_match() && matcher.is_dead());
+
+        matcher.reset();
+        matcher.push('x');
+        assert!(matcher.is_dead());
+
+        let mut matcher = Matcher::new(&Regex::Epsilon);
+        assert!(matcher.is_match());
+        matcher.push('a');
+        assert!(matcher.is_dead());
+    }
+
+    #[test]
+    fn test_matcher_allowed_next() {
+        let mut matcher = Matcher::new(&Regex::new(r"\d+(\.\d)?").unwrap());
+        let digits = CharSet::new(&[CharRange::Range('0', '9')]);
+        assert_eq!(matcher.allowed_next(), digits);
+        matcher.push('1');
+        assert_eq!(
+            matcher.allowed_next(),
+            digits.union(&CharSet::new(&[CharRange::Single('.')]))
+        );
+        matcher.push('.');
+        assert_eq!(matcher.allowed_next(), digits);
+        matcher.push('5');
+        assert!(matcher.allowed_next().is_empty());
+
+        let mut matcher = Matcher::new(&Regex::new("hé").unwrap());
+        assert_eq!(
+            matcher.allowed_next(),
+            CharSet::new(&[CharRange::Single('h')])
+        );
+        matcher.push('h');
+        assert_eq!(
+            matcher.allowed_next(),
+            CharSet::new(&[CharRange::Single('é')])
+        );
+        matcher.push('x');
+        assert!(matcher.allowed_next().is_empty());
+    }
+
+    #[test]
+    fn test_matcher_strategy() {
+        let report = Matcher::new(&Regex::new("abc").unwrap()).strategy();
+        assert_eq!(report.reason, StrategyReason::SingleString);
+        assert_eq!(
+            report.to_string(),
+            "literal comparison, because the regex only matches one string"
+        );
+
+        let report = Matcher::new(&Regex::new("ab*").unwrap()).strategy();
+        assert_eq!(
+            (report.strategy, report.reason),
+            (Strategy::Derivatives, StrategyReason::NotLiteral)
+        );
+
+        let report = Matcher::new(&Regex::Empty).strategy();
+        assert_eq!(report.reason, StrategyReason::MatchesNothing);
+        assert_eq!(
+            report.to_string(),
+            "derivatives, because the regex matches no strings"
+        );
+    }
+
+    #[test]
+    fn test_matcher_checkpoint() {
+        let regex = Regex::new("(ab)+c").unwrap();
+        let mut matcher = Matcher::new(&regex);
+        for c in "ab".chars() {
+            matcher.push(c);
+        }
+        let checkpoint = matcher.save();
+
+        // speculative input that kills the matcher is rolled back
+        matcher.push('x');
+        assert!(matcher.is_dead());
+        matcher.restore(checkpoint.clone());
+        assert!(!matcher.is_dead());
+        matcher.push('c');
+        assert!(matcher.is_match());
+
+        // a checkpoint can be restored more than once
+        matcher.restore(checkpoint);
+        for c in "abc".chars() {
+            matcher.push(c);
+        }
+        assert!(matcher.is_match());
+
+        let mut matcher = Matcher::new(&Regex::new("abc").unwrap());
+        matcher.push('a');
+        let checkpoint = matcher.save();
+        matcher.push('c');
+        assert!(matcher.is_dead());
+        matcher.restore(checkpoint);
+        matcher.push('b');
+        matcher.push('c');
+        assert!(matcher.is_match());
+    }
+}
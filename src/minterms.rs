@@ -0,0 +1,291 @@
+//! Splitting the alphabet into the classes of characters (minterms) that every literal and class in
+//! a regex treats the same.
+//!
+//! The derivatives of a regex only contain its own literals and classes, so every character of a
+//! class has the same derivative, both for the regex and for every derivative of it. Analyses and
+//! automata can then take one derivative per class rather than per character.
+
+use crate::charset::{next_char, prev_char, CharSet};
+use crate::derivatives::{ensure_stack, CharRange, Regex};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The classes of characters that every literal and class in some regexes treat the same.
+///
+/// The alphabet is first cut into intervals at every boundary of a literal or class, and then the
+/// intervals that are in exactly the same literals and classes are put in the same class (e.g.,
+/// `[a-z]+\d` has three classes: `[a-z]`, `[0-9]`, and every other character). If any of the
+/// regexes has a predicate, each interval is its own class instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Minterms {
+    /// The first character of each interval, in order, starting with `\0`.
+    boundaries: Vec<char>,
+    /// The class of each interval, numbered in order of their first character.
+    interval_classes: Vec<u32>,
+    /// The number of classes.
+    len: usize,
+    /// The class of each ASCII character, so that ASCII input does not search the intervals.
+    ascii_classes: [u32; 128],
+}
+
+/// Adds the set of characters of each literal and class in the regex to `atoms`.
+fn collect_atoms(regex: &Regex, atoms: &mut HashSet<CharSet>) {
+    ensure_stack(|| match regex {
+        Regex::Empty | Regex::Epsilon | Regex::Pred(_) => {}
+        Regex::Literal(c) => {
+            atoms.insert(CharSet::new(&[CharRange::Single(*c)]));
+        }
+        Regex::Str(s) => {
+            for c in s.chars() {
+                atoms.insert(CharSet::new(&[CharRange::Single(c)]));
+            }
+        }
+        Regex::Class(ranges) => {
+            atoms.insert(CharSet::new(ranges));
+        }
+        Regex::Concat(left, right) | Regex::Or(left, right) | Regex::Shuffle(left, right) => {
+            collect_atoms(left, atoms);
+            collect_atoms(right, atoms);
+        }
+        Regex::Count(inner, _) => collect_atoms(inner, atoms),
+    });
+}
+
+impl Minterms {
+    /// Returns the classes of characters that every literal and class in the regexes treat the
+    /// same.
+    pub(crate) fn new(regexes: &[&Regex]) -> Self {
+        let mut atoms = HashSet::new();
+        for regex in regexes {
+            collect_atoms(regex, &mut atoms);
+        }
+        let atoms: Vec<CharSet> = atoms.into_iter().collect();
+
+        // every interval starts where some atom starts or ends
+        let mut starts = BTreeSet::from(['\0']);
+        for atom in &atoms {
+            for &(start, end) in atom.intervals() {
+                starts.insert(start);
+                if let Some(next) = next_char(end) {
+                    starts.insert(next);
+                }
+            }
+        }
+        let boundaries: Vec<char> = starts.into_iter().collect();
+
+        // predicates can tell apart the characters of a class, so with predicates each interval is
+        // its own class, and they are tested on one character per interval
+        if regexes.iter().any(|regex| regex.has_predicate()) {
+            let interval_classes = (0..boundaries.len())
+                .map(|interval| u32::try_from(interval).unwrap_or(u32::MAX))
+                .collect();
+            let len = boundaries.len();
+            return Self::with_ascii_classes(boundaries, interval_classes, len);
+        }
+
+        let mut ids: HashMap<Vec<bool>, u32> = HashMap::new();
+        let interval_classes = boundaries
+            .iter()
+            .map(|&start| {
+                let signature = atoms.iter().map(|atom| atom.contains(start)).collect();
+                let next_id = u32::try_from(ids.len()).unwrap_or(u32::MAX);
+                *ids.entry(signature).or_insert(next_id)
+            })
+            .collect();
+
+        Self::with_ascii_classes(boundaries, interval_classes, ids.len())
+    }
+
+    /// Creates the classes from the intervals and their classes, filling in the class of each ASCII
+    /// character.
+    pub(crate) fn with_ascii_classes(
+        boundaries: Vec<char>,
+        interval_classes: Vec<u32>,
+        len: usize,
+    ) -> Self {
+        let mut minterms = Self {
+            boundaries,
+            interval_classes,
+            len,
+            ascii_classes: [0; 128],
+        };
+        for c in 0..128 {
+            let class = minterms.search_class(char::from(c));
+            minterms.ascii_classes[usize::from(c)] = u32::try_from(class).unwrap_or(u32::MAX);
+        }
+        minterms
+    }
+
+    /// Returns the number of classes.
+    pub(crate) const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the first character of each interval, in order, starting with `\0`.
+    pub(crate) fn boundaries(&self) -> &[char] {
+        &self.boundaries
+    }
+
+    /// Returns the class of each interval.
+    pub(crate) fn interval_classes(&self) -> &[u32] {
+        &self.interval_classes
+    }
+
+    /// Returns the class of each ASCII character.
+    pub(crate) const fn ascii_classes(&self) -> &[u32; 128] {
+        &self.ascii_classes
+    }
+
+    /// Returns the number of bytes the intervals take on the heap.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.boundaries.capacity() * size_of::<char>()
+            + self.interval_classes.capacity() * size_of::<u32>()
+    }
+
+    /// Returns the class of the character.
+    pub(crate) fn class_of(&self, c: char) -> usize {
+        if c.is_ascii() {
+            return self.ascii_classes[c as usize] as usize;
+        }
+        self.search_class(c)
+    }
+
+    /// Returns the class of the character, searching the intervals for it.
+    fn search_class(&self, c: char) -> usize {
+        // the first boundary is `\0`, so every character is in some interval
+        let interval = self.boundaries.partition_point(|&boundary| boundary <= c) - 1;
+        self.interval_classes[interval] as usize
+    }
+
+    /// Returns the first character of each class, in order of the classes.
+    pub(crate) fn representatives(&self) -> Vec<char> {
+        let mut representatives = Vec::with_capacity(self.len);
+        for (&start, &class) in self.boundaries.iter().zip(&self.interval_classes) {
+            // classes are numbered in order of their first character
+            if class as usize == representatives.len() {
+                representatives.push(start);
+            }
+        }
+        representatives
+    }
+
+    /// Returns the characters of each class, in order of the classes.
+    pub(crate) fn sets(&self) -> Vec<CharSet> {
+        let mut intervals = vec![Vec::new(); self.len];
+        for (i, (&start, &class)) in self
+            .boundaries
+            .iter()
+            .zip(&self.interval_classes)
+            .enumerate()
+        {
+            let end = self
+                .boundaries
+                .get(i + 1)
+                .map_or(char::MAX, |&next| prev_char(next).unwrap());
+            intervals[class as usize].push((start, end));
+        }
+        intervals.into_iter().map(CharSet::from_intervals).collect()
+    }
+}
+
+impl Regex {
+    /// Partitions the alphabet into the classes of characters that every literal and class in the
+    /// regex treats the same, in order of their first character (e.g., `[a-z]+\d` gives `[a-z]`,
+    /// `[0-9]`, and every other character).
+    ///
+    /// Every character of a class has the same derivative, for the regex and for all of its
+    /// derivatives, so an automaton for the regex only needs one transition per class. This is the
+    /// coarsest partition that respects every literal and class, although the regex itself may
+    /// treat characters of different classes the same (see `derivative_classes`). A predicate can
+    /// tell apart any characters, so if the regex has one, the alphabet is only cut at the
+    /// boundaries of its literals and classes, without putting the pieces back together.
+    pub fn alphabet_classes(&self) -> Vec<CharSet> {
+        Minterms::new(&[self]).sets()
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{CharRange, CharSet, Minterms, Regex};
+
+    #[test]
+    fn test_alphabet_classes() {
+        let regex = Regex::new(r"[a-z]+\d").unwrap();
+        assert_eq!(
+            regex.alphabet_classes(),
+            vec![
+                CharSet::new(&[
+                    CharRange::Range('\0', '/'),
+                    CharRange::Range(':', '`'),
+                    CharRange::Range('{', char::MAX),
+                ]),
+                CharSet::new(&[CharRange::Range('0', '9')]),
+                CharSet::new(&[CharRange::Range('a', 'z')]),
+            ]
+        );
+
+        // overlapping classes are split where they overlap
+        let regex = Regex::new("[a-m]x|[h-z]y").unwrap();
+        let classes = regex.alphabet_classes();
+        assert_eq!(classes.len(), 6);
+        assert!(classes.contains(&CharSet::new(&[CharRange::Range('h', 'm')])));
+
+        // the classes cover every character exactly once
+        let total: usize = classes.iter().map(CharSet::len).sum();
+        assert_eq!(total, CharSet::default().complement().len());
+
+        assert_eq!(
+            Regex::Epsilon.alphabet_classes(),
+            vec![CharSet::default().complement()]
+        );
+    }
+
+    #[test]
+    fn test_alphabet_classes_have_equal_derivatives() {
+        let regex = Regex::new("(ab|[a-c]d)*[^b]").unwrap();
+        let derivatives = [regex.clone(), regex.derivative('a'), regex.derivative('c')];
+        for class in regex.alphabet_classes() {
+            let chars = class.iter_chars().take(50).collect::<Vec<_>>();
+            for derivative in &derivatives {
+                for &c in &chars {
+                    assert_eq!(derivative.derivative(c), derivative.derivative(chars[0]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_minterms_class_of() {
+        let regex = Regex::new(r"[a-z]+\d").unwrap();
+        let minterms = Minterms::new(&[&regex]);
+        assert_eq!(minterms.len(), 3);
+        assert_eq!(minterms.representatives(), vec!['\0', '0', 'a']);
+        assert_eq!(minterms.class_of('q'), minterms.class_of('a'));
+        assert_eq!(minterms.class_of('~'), minterms.class_of(' '));
+        assert_ne!(minterms.class_of('5'), minterms.class_of('~'));
+    }
+
+    #[test]
+    fn test_minterms_class_of_ascii_matches_search() {
+        let regex = Regex::new("[a-z\x7F-\u{100}]+[0-9~]").unwrap();
+        let minterms = Minterms::new(&[&regex]);
+        for c in '\0'..='\u{200}' {
+            assert_eq!(minterms.class_of(c), minterms.search_class(c), "{c:?}");
+        }
+    }
+
+    #[test]
+    fn test_minterms_with_predicates() {
+        let digit = Regex::pred("digit", |c: char| c.is_ascii_digit());
+        let regex = Regex::concat(Regex::new("[0-9a]").unwrap(), digit);
+        assert_eq!(
+            regex.alphabet_classes(),
+            vec![
+                CharSet::new(&[CharRange::Range('\0', '/')]),
+                CharSet::new(&[CharRange::Range('0', '9')]),
+                CharSet::new(&[CharRange::Range(':', '`')]),
+                CharSet::new(&[CharRange::Single('a')]),
+                CharSet::new(&[CharRange::Range('b', char::MAX)]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Unicode normalization of patterns and inputs, so that canonically equivalent strings (e.g., `é`
+//! as one character and `e` followed by a combining acute accent) match the same regexes.
+//!
+//! Normalize the pattern with `RegexBuilder::normalization`, and match inputs with
+//! `Regex::matches_normalized` using the same form.
+
+use crate::derivatives::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+/// A Unicode normalization form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// Canonical decomposition followed by canonical composition (e.g., `e` followed by U+0301
+    /// becomes `é`).
+    Nfc,
+    /// Compatibility decomposition followed by canonical composition, which also replaces
+    /// compatibility characters (e.g., `ﬁ` becomes `fi`).
+    Nfkc,
+}
+
+impl Normalization {
+    /// Returns the string in this normalization form.
+    pub fn normalize(self, s: &str) -> String {
+        match self {
+            Self::Nfc => s.nfc().collect(),
+            Self::Nfkc => s.nfkc().collect(),
+        }
+    }
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches the given string once it is in the given normalization
+    /// form, otherwise returns `false`.
+    pub fn matches_normalized(&self, s: &str, form: Normalization) -> bool {
+        self.matches(&form.normalize(s))
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Normalization, Regex};
+    #[allow(unused_imports)]
+    use crate::RegexBuilder;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(Normalization::Nfc.normalize("e\u{301}"), "é");
+        assert_eq!(Normalization::Nfc.normalize("ﬁ"), "ﬁ");
+        assert_eq!(Normalization::Nfkc.normalize("ﬁ"), "fi");
+    }
+
+    #[test]
+    fn test_matches_normalized() {
+        let regex = RegexBuilder::new("caf\u{65}\u{301}+")
+            .normalization(Normalization::Nfc)
+            .build()
+            .unwrap();
+        assert_eq!(regex, Regex::new("café+").unwrap());
+
+        assert!(regex.matches_normalized("café", Normalization::Nfc));
+        assert!(regex.matches_normalized("cafe\u{301}", Normalization::Nfc));
+        assert!(!regex.matches("cafe\u{301}"));
+
+        let regex = RegexBuilder::new("ﬁne")
+            .normalization(Normalization::Nfkc)
+            .build()
+            .unwrap();
+        assert!(regex.matches_normalized("ﬁne", Normalization::Nfkc));
+        assert!(regex.matches_normalized("fine", Normalization::Nfkc));
+    }
+}
@@ -0,0 +1,50 @@
+//! Matching OS strings and paths, such as file names found while scanning a directory, which may
+//! not be valid UTF-8.
+
+use crate::derivatives::Regex;
+use std::ffi::OsStr;
+use std::path::Path;
+
+impl Regex {
+    /// Returns `true` if the regex matches the OS string, otherwise returns `false`.
+    ///
+    /// Any part of it that is not valid UTF-8 is replaced with `U+FFFD`, as by
+    /// `OsStr::to_string_lossy`, so it can be matched by `�` or `.`. To match the bytes
+    /// themselves instead, use `bytes::Regex::matches_os`.
+    pub fn matches_os(&self, s: &OsStr) -> bool {
+        self.matches(&s.to_string_lossy())
+    }
+
+    /// Returns `true` if the regex matches the whole path, otherwise returns `false`. The path is
+    /// converted like in `matches_os`, and its separators are matched as they are written (e.g.,
+    /// `/` on Unix).
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.matches_os(path.as_os_str())
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{OsStr, Path, Regex};
+
+    #[test]
+    fn test_matches_path() {
+        let regex = Regex::new(r"src/\w+\.rs").unwrap();
+        assert!(regex.matches_path(Path::new("src/lib.rs")));
+        assert!(!regex.matches_path(Path::new("src/lib.rs.bak")));
+        assert!(regex.matches_os(OsStr::new("src/os.rs")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_matches_os_lossy() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"report-\xff.txt");
+        assert!(Regex::new("report-\u{FFFD}\\.txt")
+            .unwrap()
+            .matches_os(name));
+        assert!(Regex::new(r"report-.\.txt").unwrap().matches_os(name));
+        assert!(!Regex::new(r"report-\.txt").unwrap().matches_os(name));
+    }
+}
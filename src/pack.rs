@@ -0,0 +1,427 @@
+//! Pattern packs: versioned collections of named patterns, each with strings it must and must not
+//! match, so a team can distribute validated patterns as a single file.
+//!
+//! A pack is a text file of `key: value` lines, where the value is everything after the first `: `
+//! (so patterns may contain `:` and spaces). Blank lines and lines starting with `#` are ignored.
+//!
+//! ```text
+//! rzozowski-pack: 1
+//! name: identifiers
+//! meta.owner: platform team
+//!
+//! pattern: snake_case
+//! regex: [a-z_][a-z0-9_]*
+//! match: user_id
+//! reject: 2fast
+//! ```
+//!
+//! The first line gives the version of the format. `pattern` starts a new pattern, and the lines
+//! after it up to the next `pattern` describe it: its `regex`, the `dialect` it is written in
+//! (`rzozowski`, the default), the strings it must `match` and `reject`, and any `meta.` keys.
+//! `name` and `meta.` keys before the first pattern describe the whole pack.
+
+use crate::derivatives::Regex;
+use std::fmt::{Display, Formatter};
+
+/// The version of the pattern pack format written by `PatternPack`'s `Display`, and the only one
+/// that can be parsed.
+pub const PACK_VERSION: u32 = 1;
+
+/// The key of the line giving the version of the format.
+const VERSION_KEY: &str = "rzozowski-pack";
+
+/// The syntax a pattern in a pack is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// The syntax parsed by `Regex::new`.
+    #[default]
+    Rzozowski,
+}
+
+impl Dialect {
+    /// Returns the name of the dialect as written in a pack.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Rzozowski => "rzozowski",
+        }
+    }
+
+    /// Returns the dialect with the given name, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rzozowski" => Some(Self::Rzozowski),
+            _ => None,
+        }
+    }
+}
+
+/// A named pattern in a pack, with the strings it must and must not match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedPattern {
+    /// The name of the pattern, which is unique in its pack.
+    pub name: String,
+    /// The syntax the pattern is written in.
+    pub dialect: Dialect,
+    /// The pattern.
+    pub pattern: String,
+    /// Strings the pattern must match.
+    pub matches: Vec<String>,
+    /// Strings the pattern must not match.
+    pub rejects: Vec<String>,
+    /// Other information about the pattern (e.g., its owner), as key and value pairs.
+    pub metadata: Vec<(String, String)>,
+}
+
+impl PackedPattern {
+    /// Tries to compile the pattern and checks it against its self-tests, returning the compiled
+    /// regex.
+    pub fn compile(&self) -> Result<Regex, String> {
+        let regex = match self.dialect {
+            Dialect::Rzozowski => Regex::new(&self.pattern),
+        }
+        .map_err(|error| {
+            format!(
+                "Pattern {} does not compile: {}",
+                self.name,
+                error.trim_end()
+            )
+        })?;
+
+        if let Some(s) = self.matches.iter().find(|s| !regex.matches(s)) {
+            return Err(format!("Pattern {} does not match {s:?}", self.name));
+        }
+        if let Some(s) = self.rejects.iter().find(|s| regex.matches(s)) {
+            return Err(format!(
+                "Pattern {} matches {s:?}, which it must reject",
+                self.name
+            ));
+        }
+
+        Ok(regex)
+    }
+}
+
+/// A versioned collection of named patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatternPack {
+    /// The name of the pack.
+    pub name: String,
+    /// Other information about the pack (e.g., its owner), as key and value pairs.
+    pub metadata: Vec<(String, String)>,
+    /// The patterns in the pack.
+    pub patterns: Vec<PackedPattern>,
+}
+
+impl PatternPack {
+    /// Tries to parse a pack from its text, without compiling its patterns.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'));
+
+        let (number, line) = lines.next().ok_or("Empty pattern pack")?;
+        let version = split_line(number, line)?;
+        if version.0 != VERSION_KEY {
+            return Err(format!(
+                "Line {number}: expected the pack to start with `{VERSION_KEY}: {PACK_VERSION}`"
+            ));
+        }
+        if version.1.parse::<u32>() != Ok(PACK_VERSION) {
+            return Err(format!(
+                "Line {number}: unsupported pattern pack version {}, expected {PACK_VERSION}",
+                version.1
+            ));
+        }
+
+        let mut pack = Self::default();
+        let mut has_regex = Vec::new();
+        for (number, line) in lines {
+            let (key, value) = split_line(number, line)?;
+            let value = value.to_string();
+
+            if key == "pattern" {
+                if pack.patterns.iter().any(|pattern| pattern.name == value) {
+                    return Err(format!("Line {number}: duplicate pattern {value}"));
+                }
+                pack.patterns.push(PackedPattern {
+                    name: value,
+                    ..PackedPattern::default()
+                });
+                has_regex.push(false);
+                continue;
+            }
+
+            let Some(pattern) = pack.patterns.last_mut() else {
+                match key.strip_prefix("meta.") {
+                    Some(meta_key) => pack.metadata.push((meta_key.to_string(), value)),
+                    None if key == "name" => pack.name = value,
+                    None => return Err(format!("Line {number}: unknown pack key {key}")),
+                }
+                continue;
+            };
+
+            match key {
+                "regex" => {
+                    pattern.pattern = value;
+                    *has_regex.last_mut().unwrap() = true;
+                }
+                "dialect" => {
+                    pattern.dialect = Dialect::from_name(&value)
+                        .ok_or_else(|| format!("Line {number}: unknown dialect {value}"))?;
+                }
+                "match" => pattern.matches.push(value),
+                "reject" => pattern.rejects.push(value),
+                _ => match key.strip_prefix("meta.") {
+                    Some(meta_key) => pattern.metadata.push((meta_key.to_string(), value)),
+                    None => return Err(format!("Line {number}: unknown pattern key {key}")),
+                },
+            }
+        }
+
+        if let Some(index) = has_regex.iter().position(|has_regex| !has_regex) {
+            return Err(format!(
+                "Pattern {} has no regex",
+                pack.patterns[index].name
+            ));
+        }
+
+        Ok(pack)
+    }
+
+    /// Tries to parse a pack from its text, compile every pattern, and run every self-test.
+    pub fn load(text: &str) -> Result<CompiledPack, String> {
+        Self::parse(text)?.compile()
+    }
+
+    /// Checks that every pattern compiles and passes its self-tests, returning a description of
+    /// each failure.
+    pub fn validate(&self) -> Vec<String> {
+        self.patterns
+            .iter()
+            .filter_map(|pattern| pattern.compile().err())
+            .collect()
+    }
+
+    /// Tries to compile every pattern and run every self-test, returning the first failure.
+    pub fn compile(self) -> Result<CompiledPack, String> {
+        let regexes = self
+            .patterns
+            .iter()
+            .map(PackedPattern::compile)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CompiledPack {
+            pack: self,
+            regexes,
+        })
+    }
+}
+
+/// Splits a line into its key and value, which are separated by `:` and an optional space.
+fn split_line(number: usize, line: &str) -> Result<(&str, &str), String> {
+    let (key, value) = line
+        .split_once(':')
+        .ok_or_else(|| format!("Line {number}: expected `key: value`"))?;
+    Ok((key.trim(), value.strip_prefix(' ').unwrap_or(value)))
+}
+
+impl Display for PatternPack {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{VERSION_KEY}: {PACK_VERSION}")?;
+        if !self.name.is_empty() {
+            writeln!(f, "name: {}", self.name)?;
+        }
+        for (key, value) in &self.metadata {
+            writeln!(f, "meta.{key}: {value}")?;
+        }
+
+        for pattern in &self.patterns {
+            writeln!(f)?;
+            writeln!(f, "pattern: {}", pattern.name)?;
+            if pattern.dialect != Dialect::default() {
+                writeln!(f, "dialect: {}", pattern.dialect.name())?;
+            }
+            writeln!(f, "regex: {}", pattern.pattern)?;
+            for s in &pattern.matches {
+                writeln!(f, "match: {s}")?;
+            }
+            for s in &pattern.rejects {
+                writeln!(f, "reject: {s}")?;
+            }
+            for (key, value) in &pattern.metadata {
+                writeln!(f, "meta.{key}: {value}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A pack whose patterns have all compiled and passed their self-tests, returned by
+/// `PatternPack::load`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledPack {
+    pack: PatternPack,
+    regexes: Vec<Regex>,
+}
+
+impl CompiledPack {
+    /// Returns the pack the regexes were compiled from.
+    pub const fn pack(&self) -> &PatternPack {
+        &self.pack
+    }
+
+    /// Returns the regex compiled from the pattern with the given name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Regex> {
+        self.pack
+            .patterns
+            .iter()
+            .position(|pattern| pattern.name == name)
+            .map(|index| &self.regexes[index])
+    }
+
+    /// Returns the name of each pattern and the regex compiled from it, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Regex)> {
+        self.pack
+            .patterns
+            .iter()
+            .map(|pattern| pattern.name.as_str())
+            .zip(&self.regexes)
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Dialect, PackedPattern, PatternPack, Regex};
+
+    #[allow(dead_code)]
+    const PACK: &str = "\
+# identifiers used across services
+rzozowski-pack: 1
+name: identifiers
+meta.owner: platform team
+
+pattern: snake_case
+regex: [a-z_][a-z0-9_]*
+match: user_id
+match: _
+reject: 2fast
+reject: Camel
+meta.since: 0.2
+
+pattern: key_value
+dialect: rzozowski
+regex: [a-z]+: .*
+match: name: rzozowski
+match: path: /a:b
+";
+
+    #[test]
+    fn test_parse_pack() {
+        let pack = PatternPack::parse(PACK).unwrap();
+        assert_eq!(pack.name, "identifiers");
+        assert_eq!(
+            pack.metadata,
+            vec![("owner".to_string(), "platform team".to_string())]
+        );
+        assert_eq!(
+            pack.patterns[0],
+            PackedPattern {
+                name: "snake_case".to_string(),
+                dialect: Dialect::Rzozowski,
+                pattern: "[a-z_][a-z0-9_]*".to_string(),
+                matches: vec!["user_id".to_string(), "_".to_string()],
+                rejects: vec!["2fast".to_string(), "Camel".to_string()],
+                metadata: vec![("since".to_string(), "0.2".to_string())],
+            }
+        );
+        // values keep everything after the first `: `
+        assert_eq!(pack.patterns[1].pattern, "[a-z]+: .*");
+        assert_eq!(
+            pack.patterns[1].matches,
+            vec!["name: rzozowski", "path: /a:b"]
+        );
+
+        // packs are written in the same format
+        assert_eq!(PatternPack::parse(&pack.to_string()).unwrap(), pack);
+    }
+
+    #[test]
+    fn test_load_pack() {
+        let pack = PatternPack::load(PACK).unwrap();
+        assert_eq!(
+            pack.get("snake_case"),
+            Some(&Regex::new("[a-z_][a-z0-9_]*").unwrap())
+        );
+        assert_eq!(pack.get("missing"), None);
+        assert_eq!(
+            pack.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["snake_case", "key_value"]
+        );
+        assert_eq!(pack.pack().name, "identifiers");
+    }
+
+    #[test]
+    fn test_pack_self_tests() {
+        let pack = PatternPack::parse(&PACK.replace("reject: Camel", "reject: user")).unwrap();
+        assert_eq!(
+            pack.validate(),
+            vec![r#"Pattern snake_case matches "user", which it must reject"#]
+        );
+        assert_eq!(
+            pack.compile().unwrap_err(),
+            r#"Pattern snake_case matches "user", which it must reject"#
+        );
+
+        let pack = PatternPack::parse(&PACK.replace("match: _", "match: A")).unwrap();
+        assert_eq!(
+            pack.validate(),
+            vec![r#"Pattern snake_case does not match "A""#]
+        );
+
+        let pack = PatternPack::parse(&PACK.replace("[a-z]+: .*", "[a-z")).unwrap();
+        let errors = pack.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Pattern key_value does not compile: "));
+    }
+
+    #[allow(dead_code)]
+    fn pack_error(text: &str) -> String {
+        PatternPack::load(text).unwrap_err()
+    }
+
+    #[test]
+    fn test_parse_pack_errors() {
+        assert_eq!(PatternPack::parse("").unwrap_err(), "Empty pattern pack");
+        assert_eq!(
+            pack_error("rzozowski-pack: 2"),
+            "Line 1: unsupported pattern pack version 2, expected 1"
+        );
+        assert_eq!(
+            pack_error("name: x"),
+            "Line 1: expected the pack to start with `rzozowski-pack: 1`"
+        );
+        assert_eq!(
+            pack_error("rzozowski-pack: 1\npattern: a\nregex: a\npattern: a"),
+            "Line 4: duplicate pattern a"
+        );
+        assert_eq!(
+            pack_error("rzozowski-pack: 1\npattern: a\ndialect: pcre"),
+            "Line 3: unknown dialect pcre"
+        );
+        assert_eq!(
+            pack_error("rzozowski-pack: 1\npattern: a\nmatch: a"),
+            "Pattern a has no regex"
+        );
+        assert_eq!(
+            pack_error("rzozowski-pack: 1\nregex: a"),
+            "Line 2: unknown pack key regex"
+        );
+        assert_eq!(
+            pack_error("rzozowski-pack: 1\npattern: a\nregex a"),
+            "Line 3: expected `key: value`"
+        );
+    }
+}
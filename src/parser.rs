@@ -1,44 +1,129 @@
 mod lexer;
 
-use crate::derivatives::{CharRange, Count, Regex, CLASS_ESCAPE_CHARS, NON_CLASS_ESCAPE_CHARS};
+use crate::charset::CharSet;
+use crate::derivatives::{
+    ensure_stack, grow_stack_if_low, CharRange, Count, Node, Predicate, Regex, SimplifyOptions,
+    CLASS_ESCAPE_CHARS, NON_CLASS_ESCAPE_CHARS,
+};
 use chumsky::{
+    error::RichReason,
     input::{Stream, ValueInput},
     prelude::*,
 };
 use lexer::Token;
 use logos::Logos;
-use std::fmt::Write as _;
+use std::fmt::{Display, Formatter, Write as _};
+use std::ops::Range;
 use std::{collections::HashMap, sync::LazyLock};
 
 /// Represents a regex in a more convenient format for parsing. This is an intermediate representation before converting to the final `Regex` type.
 #[derive(Clone)]
 enum RegexRepresentation {
     Literal(char),
-    Concat(Box<Self>, Box<Self>),
-    Or(Box<Self>, Box<Self>),
+    Str(String),
+    Concat(Vec<Self>),
+    Or(Vec<Self>),
     Optional(Box<Self>),
     Star(Box<Self>),
     Plus(Box<Self>),
     Class(Vec<CharRange>),
     Count(Box<Self>, Count),
+    Pred(Predicate),
+    /// A subexpression with the span of tokens it was parsed from, kept for `dead_branch_spans`.
+    Spanned(Box<Self>, SimpleSpan),
+}
+
+impl Drop for RegexRepresentation {
+    // dropped by hand so that dropping deeply nested groups does not overflow the stack
+    fn drop(&mut self) {
+        ensure_stack(|| match self {
+            Self::Concat(items) | Self::Or(items) => drop(std::mem::take(items)),
+            Self::Optional(inner)
+            | Self::Star(inner)
+            | Self::Plus(inner)
+            | Self::Count(inner, _)
+            | Self::Spanned(inner, _) => {
+                drop(std::mem::replace(inner.as_mut(), Self::Concat(Vec::new())));
+            }
+            _ => {}
+        });
+    }
 }
 
 impl RegexRepresentation {
+    /// Concatenates the items, joining adjacent literals into strings.
+    fn concat_all(items: Vec<Self>) -> Self {
+        let mut joined: Vec<Self> = Vec::new();
+        for item in items {
+            if let (Some(last), Self::Literal(c)) = (joined.last_mut(), &item) {
+                match last {
+                    Self::Literal(previous) => {
+                        let s = String::from_iter([*previous, *c]);
+                        *last = Self::Str(s);
+                        continue;
+                    }
+                    Self::Str(s) => {
+                        s.push(*c);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            joined.push(item);
+        }
+
+        if joined.len() == 1 {
+            joined.remove(0)
+        } else {
+            Self::Concat(joined)
+        }
+    }
+
     fn to_regex(&self) -> Regex {
-        match self {
+        ensure_stack(|| match self {
             Self::Literal(c) => Regex::Literal(*c),
-            Self::Concat(left, right) => {
-                Regex::Concat(Box::new(left.to_regex()), Box::new(right.to_regex()))
-            }
-            Self::Or(left, right) => {
-                Regex::Or(Box::new(left.to_regex()), Box::new(right.to_regex()))
-            }
+            Self::Str(s) => Regex::string(s),
+            Self::Concat(items) => items
+                .iter()
+                .map(Self::to_regex)
+                .reduce(|acc, regex| Regex::Concat(Node::new(acc), Node::new(regex)))
+                .unwrap_or(Regex::Epsilon),
+            Self::Or(alternatives) => alternatives
+                .iter()
+                .map(Self::to_regex)
+                .reduce(|acc, regex| Regex::Or(Node::new(acc), Node::new(regex)))
+                .unwrap_or(Regex::Empty),
             Self::Optional(inner) => inner.to_regex().optional(),
             Self::Star(inner) => inner.to_regex().star(),
             Self::Plus(inner) => inner.to_regex().plus(),
             Self::Class(ranges) => Regex::Class(ranges.clone()),
-            Self::Count(inner, count) => Regex::Count(Box::new(inner.to_regex()), *count),
-        }
+            Self::Count(inner, count) => Regex::Count(Node::new(inner.to_regex()), *count),
+            Self::Pred(predicate) => Regex::Pred(predicate.clone()),
+            Self::Spanned(inner, _) => inner.to_regex(),
+        })
+    }
+
+    /// Adds the spans of the outermost spanned subexpressions that match nothing to `spans`.
+    fn collect_dead_spans(&self, spans: &mut Vec<SimpleSpan>) {
+        ensure_stack(|| match self {
+            Self::Spanned(inner, span) => {
+                if inner.to_regex().matches_nothing() {
+                    spans.push(*span);
+                } else {
+                    inner.collect_dead_spans(spans);
+                }
+            }
+            Self::Concat(items) | Self::Or(items) => {
+                for item in items {
+                    item.collect_dead_spans(spans);
+                }
+            }
+            Self::Optional(inner)
+            | Self::Star(inner)
+            | Self::Plus(inner)
+            | Self::Count(inner, _) => inner.collect_dead_spans(spans),
+            Self::Literal(_) | Self::Str(_) | Self::Class(_) | Self::Pred(_) => {}
+        });
     }
 }
 
@@ -73,6 +158,130 @@ static SPECIAL_CHAR_SEQUENCES: LazyLock<HashMap<char, RegexRepresentation>> = La
     ])
 });
 
+/// The Unicode versions of the special character sequences, used instead of the ASCII ones when
+/// `ParseFlags::unicode` is set. They are created once so that the same sequence always gives an
+/// equal predicate.
+static UNICODE_CHAR_SEQUENCES: LazyLock<HashMap<char, RegexRepresentation>> = LazyLock::new(|| {
+    HashMap::from([
+        // "\d" => any numeric character
+        (
+            'd',
+            RegexRepresentation::Pred(Predicate::new("numeric", char::is_numeric)),
+        ),
+        // "\w" => any alphanumeric character or `_`
+        (
+            'w',
+            RegexRepresentation::Pred(Predicate::new("word", |c: char| {
+                c.is_alphanumeric() || c == '_'
+            })),
+        ),
+        // "\s" => any whitespace character
+        (
+            's',
+            RegexRepresentation::Pred(Predicate::new("whitespace", char::is_whitespace)),
+        ),
+    ])
+});
+
+/// Options that change how a pattern is parsed, set with `RegexBuilder`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseFlags {
+    /// Whether literals and classes match regardless of case (also set by a leading `(?i)`).
+    pub case_insensitive: bool,
+    /// Whether `.` matches `\n`.
+    pub dot_matches_new_line: bool,
+    /// Whether `\d`, `\w` and `\s` match Unicode characters rather than only ASCII ones.
+    pub unicode: bool,
+    /// The maximum depth of nested groups, if any.
+    pub nest_limit: Option<usize>,
+    /// The largest number allowed in a count (e.g., `5` in `a{2,5}`), if any.
+    pub count_limit: Option<usize>,
+    /// How control characters and unusual whitespace in the pattern are treated.
+    pub control_chars: ControlCharPolicy,
+}
+
+/// How a pattern may contain control characters (e.g., NUL, tab or newline) and whitespace other than
+/// the ASCII space (e.g., U+3000 IDEOGRAPHIC SPACE), set with `RegexBuilder::control_chars`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ControlCharPolicy {
+    /// They may be written as they are and match themselves, like any other character, or written as
+    /// hexadecimal escapes (e.g., `\x09`).
+    #[default]
+    Literal,
+    /// They must be written as hexadecimal escapes (e.g., `\x09` for a tab), both inside and outside
+    /// classes. Characters above `\xff` cannot be escaped, so they cannot be written at all.
+    RequireEscape,
+    /// They cannot be written in the pattern at all, even as escapes, though they can still be
+    /// matched by `.`, `\s` or a class range spanning them (e.g., `[!-ÿ]`).
+    Reject,
+}
+
+/// What made a pattern fail to parse, for callers that handle some errors differently (e.g., telling
+/// the user to raise a limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A count is larger than `usize::MAX` (e.g., `a{99999999999999999999}`).
+    CountTooLarge,
+    /// A count is larger than the limit set with `RegexBuilder::count_limit`.
+    CountOverLimit,
+    /// Any other error, such as invalid syntax or another limit being exceeded.
+    Other,
+}
+
+/// An error from parsing a pattern, with its kind and a message describing it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    message: String,
+}
+
+impl ParseError {
+    /// Creates an error of the given kind.
+    pub(crate) fn new(kind: ParseErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Returns what made the pattern fail to parse.
+    pub const fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// Returns the message describing the error, which is what `RegexBuilder::build` returns.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<String> for ParseError {
+    fn from(message: String) -> Self {
+        Self::new(ParseErrorKind::Other, message)
+    }
+}
+
+impl From<ParseError> for String {
+    fn from(error: ParseError) -> Self {
+        error.message
+    }
+}
+
+/// Returns whether the character is a control character or whitespace other than the ASCII space,
+/// which `ControlCharPolicy` applies to.
+fn is_control_or_unusual_whitespace(c: char) -> bool {
+    c.is_control() || (c.is_whitespace() && c != ' ')
+}
+
 fn tokenize_string(input: &str) -> Result<Vec<Token>, String> {
     let lexer = Token::lexer(input);
     let tokens = lexer
@@ -115,46 +324,76 @@ where
 
 /// Parses a special character sequence (e.g., `\d`).
 fn special_char_sequence<'a, I>(
+    unicode: bool,
 ) -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
+    let sequences = if unicode {
+        &UNICODE_CHAR_SEQUENCES
+    } else {
+        &SPECIAL_CHAR_SEQUENCES
+    };
+
     just(Token::Backslash)
         .then(any().filter(|token| matches!(token, Token::Literal(_))))
-        .filter(|(_, token)| {
+        .filter(move |(_, token)| {
             let c = token.as_char();
-            SPECIAL_CHAR_SEQUENCES.contains_key(&c)
+            sequences.contains_key(&c)
         })
-        .map(|(_, token)| {
+        .map(move |(_, token)| {
             let c = token.as_char();
-            SPECIAL_CHAR_SEQUENCES[&c].clone()
+            sequences[&c].clone()
         })
 }
 
-/// Parses a literal (e.g., `a`, `\[`, `\d`).
-fn literal<'a, I>() -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
+/// Parses a hexadecimal digit (e.g., `f`).
+fn hex_digit<'a, I>() -> impl Parser<'a, I, u32, extra::Err<Rich<'a, Token>>>
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    any()
+        .filter(|token| matches!(token, Token::Literal(_)))
+        .filter(|token: &Token| token.as_char().is_ascii_hexdigit())
+        .map(|token| token.as_char().to_digit(16).unwrap())
+}
+
+/// Parses a two-digit hexadecimal escape (e.g., `\x1f`) into the character with that code point.
+fn hex_escape<'a, I>() -> impl Parser<'a, I, char, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    special_char_sequence()
+    just(Token::Backslash)
+        .ignore_then(just(Token::Literal('x')))
+        .ignore_then(hex_digit())
+        .then(hex_digit())
+        .map(|(high, low)| char::from_u32(high * 16 + low).unwrap())
+}
+
+/// Parses a literal (e.g., `a`, `\[`, `\d`, `\x41`).
+fn literal<'a, I>(
+    flags: ParseFlags,
+) -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    special_char_sequence(flags.unicode)
         .boxed()
+        .or(hex_escape().map(RegexRepresentation::Literal))
         .or(escaped_char().map(RegexRepresentation::Literal))
         .or(unescaped_char().map(RegexRepresentation::Literal))
 }
 
-/// Parses an unescaped character that is not a special character sequence (e.g., `a`, `0`, `_`).
+/// Parses an unescaped character that is not a special character sequence (e.g., `a`, `0`, `*`).
+///
+/// Only the class escape characters must be escaped in a class, so characters that are special
+/// outside classes (e.g., `*` and `(`) stand for themselves, as `Display` writes them.
 fn class_unescaped_char<'a, I>() -> impl Parser<'a, I, char, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
     any()
-        .filter(|token| {
-            matches!(
-                token,
-                Token::Literal(_) | Token::Percent | Token::Plus | Token::Dot | Token::At
-            )
-        })
-        .filter(|token| !CLASS_ESCAPE_CHARS.contains(&token.as_char()))
+        .filter(|token: &Token| !CLASS_ESCAPE_CHARS.contains(&token.as_char()))
         .map(|token| token.as_char())
 }
 
@@ -172,12 +411,14 @@ where
         .map(|(_, token)| token.as_char())
 }
 
-/// Parses a class character.
+/// Parses a class character (e.g., `a`, `\-`, `\x1f`).
 fn class_char<'a, I>() -> impl Parser<'a, I, char, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    class_escaped_char().or(class_unescaped_char())
+    hex_escape()
+        .or(class_escaped_char())
+        .or(class_unescaped_char())
 }
 
 /// Parses a single class character into a `CharRange`.
@@ -204,19 +445,129 @@ fn class_range<'a, I>() -> impl Parser<'a, I, CharRange, extra::Err<Rich<'a, Tok
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    class_range_range().or(class_range_single())
+    class_set_operation()
+        .not()
+        .ignore_then(class_range_range().or(class_range_single()))
 }
 
-/// Parses a character class (e.g., `[a-z]`, `[a-zA-Z0-9]`, `[a-zA]`, `[\--0]`).
+/// Parses an unescaped hyphen at the start or end of a character class (e.g., the `-` in `[a-z-]`).
+fn class_edge_hyphen<'a, I>() -> impl Parser<'a, I, Option<CharRange>, extra::Err<Rich<'a, Token>>>
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    class_set_operation()
+        .not()
+        .ignore_then(just(Token::Hyphen).to(CharRange::Single('-')))
+        .or_not()
+}
+
+#[derive(Clone)]
+enum ClassSetOperation {
+    Difference,
+    Intersection,
+}
+
+/// Parses a character class set operation (e.g., `--` or `&&`).
+fn class_set_operation<'a, I>(
+) -> impl Parser<'a, I, ClassSetOperation, extra::Err<Rich<'a, Token>>> + Clone
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    let difference = just(Token::Hyphen)
+        .then(just(Token::Hyphen))
+        .to(ClassSetOperation::Difference);
+    let intersection = just(Token::Literal('&'))
+        .then(just(Token::Literal('&')))
+        .to(ClassSetOperation::Intersection);
+
+    difference.or(intersection)
+}
+
+/// Parses the members of a character class (e.g., the `a-z-` in `[a-z-]`).
+fn class_members<'a, I>() -> impl Parser<'a, I, Vec<CharRange>, extra::Err<Rich<'a, Token>>>
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    class_edge_hyphen()
+        .then(class_range().repeated().collect::<Vec<_>>())
+        .then(class_edge_hyphen())
+        .map(|((leading, ranges), trailing)| {
+            leading.into_iter().chain(ranges).chain(trailing).collect()
+        })
+}
+
+/// Applies class set operations to the members of a class from left to right.
+fn apply_class_set_operations(
+    members: Vec<CharRange>,
+    operations: Vec<(ClassSetOperation, Vec<CharRange>)>,
+) -> Vec<CharRange> {
+    operations
+        .into_iter()
+        .fold(members, |ranges, (operation, operand)| {
+            let (set, operand) = (CharSet::new(&ranges), CharSet::new(&operand));
+            match operation {
+                ClassSetOperation::Difference => set.difference(&operand),
+                ClassSetOperation::Intersection => set.intersection(&operand),
+            }
+            .ranges()
+        })
+}
+
+/// Parses a character class (e.g., `[a-z]`, `[a-zA-Z0-9]`, `[a-zA]`, `[\--0]`, `[-az]`, `[a-z-]`).
+///
+/// Following POSIX, a `]` immediately after the opening `[` is a literal member (e.g., `[]a]`), unless
+/// it would leave the class unterminated, in which case `[]` is the empty class.
+///
+/// The members may be followed by set operations with nested classes, applied from left to right:
+/// `--` for difference (e.g., `[a-z--[aeiou]]`) and `&&` for intersection (e.g., `[a-z&&[x-~]]`).
 fn class<'a, I>() -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    class_range()
-        .repeated()
-        .collect::<Vec<_>>()
-        .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket))
-        .map(RegexRepresentation::Class)
+    recursive(|class| {
+        let leading_bracket_members = just(Token::CloseBracket)
+            .to(CharRange::Single(']'))
+            .then(class_members())
+            .map(|(bracket, members)| std::iter::once(bracket).chain(members).collect());
+
+        let operations = class_set_operation()
+            .then(class)
+            .repeated()
+            .collect::<Vec<_>>();
+
+        let leading_bracket_class = just(Token::OpenBracket)
+            .ignore_then(leading_bracket_members)
+            .then(operations.clone())
+            .then_ignore(just(Token::CloseBracket));
+
+        leading_bracket_class
+            .or(class_members()
+                .then(operations)
+                .delimited_by(just(Token::OpenBracket), just(Token::CloseBracket)))
+            .map(|(members, operations)| apply_class_set_operations(members, operations))
+            .boxed()
+    })
+    .map(RegexRepresentation::Class)
+}
+
+/// Parses a `.`, which matches any character except `\n`, or any character at all if
+/// `dot_matches_new_line` is set.
+fn dot<'a, I>(
+    dot_matches_new_line: bool,
+) -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    let ranges = if dot_matches_new_line {
+        vec![CharRange::Range('\0', char::MAX)]
+    } else {
+        vec![
+            CharRange::Range('\0', '\t'),
+            CharRange::Range('\u{b}', char::MAX),
+        ]
+    };
+
+    just(Token::Dot).to(RegexRepresentation::Class(ranges))
 }
 
 /// Parses a parenthesized expression (e.g., `(a)`, `(a|b)`, `(a*)`, `(a+)`, `(a?)`).
@@ -226,7 +577,10 @@ fn parenthesized<'a, I>(
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    regex.delimited_by(just(Token::OpenParen), just(Token::CloseParen))
+    let parenthesized = regex.delimited_by(just(Token::OpenParen), just(Token::CloseParen));
+
+    // groups are parsed recursively, so deeply nested groups may need more stack
+    custom(move |input| grow_stack_if_low(|| input.parse(&parenthesized)))
 }
 
 #[derive(Clone)]
@@ -251,8 +605,10 @@ where
         .map(|token| token.as_char())
 }
 
-/// Parses a number (e.g., `3`, `42`).
-fn parse_number<'a, I>() -> impl Parser<'a, I, usize, extra::Err<Rich<'a, Token>>>
+/// Parses a number (e.g., `3`, `42`) in a count, which must be at most `limit` if given.
+fn parse_number<'a, I>(
+    limit: Option<usize>,
+) -> impl Parser<'a, I, usize, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
@@ -260,57 +616,83 @@ where
         .repeated()
         .at_least(1)
         .collect::<Vec<_>>()
-        .map(|digits| digits.iter().collect::<String>().parse::<usize>().unwrap())
+        .try_map(move |digits, span| {
+            let digits = digits.iter().collect::<String>();
+            // the digits are all ASCII digits, so parsing can only fail if the number is too large
+            let number = digits.parse::<usize>().map_err(|_| {
+                Rich::custom(
+                    span,
+                    format!("Count {digits} is too large, the maximum is {}", usize::MAX),
+                )
+            })?;
+
+            match limit {
+                Some(limit) if number > limit => Err(Rich::custom(
+                    span,
+                    format!("Count {number} is more than the limit of {limit}"),
+                )),
+                _ => Ok(number),
+            }
+        })
 }
 
 /// Parses a `Count::Exact` (e.g., `{3}`).
-fn parse_count_exact<'a, I>() -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
+fn parse_count_exact<'a, I>(
+    limit: Option<usize>,
+) -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
     just(Token::OpenCurly)
-        .ignore_then(parse_number())
+        .ignore_then(parse_number(limit))
         .then_ignore(just(Token::CloseCurly))
         .map(Count::Exact)
 }
 
 /// Parses a `Count::Range` (e.g., `{3,5}`).
-fn parse_count_range<'a, I>() -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
+fn parse_count_range<'a, I>(
+    limit: Option<usize>,
+) -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
     just(Token::OpenCurly)
-        .ignore_then(parse_number())
+        .ignore_then(parse_number(limit))
         .then_ignore(just(Token::Comma))
-        .then(parse_number())
+        .then(parse_number(limit))
         .then_ignore(just(Token::CloseCurly))
         .map(|(min, max)| Count::Range(min, max))
 }
 
 /// Parses a `Count::AtLeast` (e.g., `{3,}`).
-fn parse_count_at_least<'a, I>() -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
+fn parse_count_at_least<'a, I>(
+    limit: Option<usize>,
+) -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
     just(Token::OpenCurly)
-        .ignore_then(parse_number())
+        .ignore_then(parse_number(limit))
         .then_ignore(just(Token::Comma))
         .then_ignore(just(Token::CloseCurly))
         .map(Count::AtLeast)
 }
 
-/// Parses a count (e.g., `{3}`, `{3,5}`, `{3,}`).
-fn parse_count<'a, I>() -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
+/// Parses a count (e.g., `{3}`, `{3,5}`, `{3,}`) whose numbers are at most `limit`, if given.
+fn parse_count<'a, I>(
+    limit: Option<usize>,
+) -> impl Parser<'a, I, Count, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    parse_count_range()
-        .or(parse_count_at_least())
-        .or(parse_count_exact())
+    parse_count_range(limit)
+        .or(parse_count_at_least(limit))
+        .or(parse_count_exact(limit))
 }
 
 /// Parses an optional repetition operation (e.g., `*`, `+`, `?`, `{3}`, `{3,5}`, or nothing).
 fn parse_repetition<'a, I>(
+    count_limit: Option<usize>,
 ) -> impl Parser<'a, I, Option<RepetitionKind>, extra::Err<Rich<'a, Token>>> + Clone
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
@@ -321,7 +703,9 @@ where
         Token::Question => Some(RepetitionKind::ZeroOrOne),
     };
 
-    let count_repetition = parse_count().map(RepetitionKind::Count).map(Some);
+    let count_repetition = parse_count(count_limit)
+        .map(RepetitionKind::Count)
+        .map(Some);
 
     count_repetition
         .or(simple_repetition)
@@ -329,73 +713,273 @@ where
         .boxed()
 }
 
-fn parser<'a, I>() -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
+fn parser<'a, I>(
+    flags: ParseFlags,
+) -> impl Parser<'a, I, RegexRepresentation, extra::Err<Rich<'a, Token>>>
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    recursive(|regex| {
-        let atom = literal()
+    recursive(move |regex| {
+        let atom = literal(flags)
             .boxed()
+            .or(dot(flags.dot_matches_new_line).boxed())
             .or(class().boxed())
             .or(parenthesized(regex).boxed());
 
         let repetition = atom
-            .then(parse_repetition())
-            .map(|(atom, repetition)| match repetition {
-                Some(RepetitionKind::ZeroOrOne) => RegexRepresentation::Optional(Box::new(atom)),
-                Some(RepetitionKind::ZeroOrMore) => RegexRepresentation::Star(Box::new(atom)),
-                Some(RepetitionKind::OneOrMore) => RegexRepresentation::Plus(Box::new(atom)),
-                Some(RepetitionKind::Count(count)) => {
-                    RegexRepresentation::Count(Box::new(atom), count)
+            .map_with(|atom, e| (atom, e.span()))
+            .then(parse_repetition(flags.count_limit))
+            .map(|((atom, span), repetition)| {
+                let Some(repetition) = repetition else {
+                    return atom;
+                };
+
+                // repeated atoms keep their span, so a repeated part that matches nothing can be
+                // reported even though the repetition itself matches the empty string
+                let atom = Box::new(RegexRepresentation::Spanned(Box::new(atom), span));
+                match repetition {
+                    RepetitionKind::ZeroOrOne => RegexRepresentation::Optional(atom),
+                    RepetitionKind::ZeroOrMore => RegexRepresentation::Star(atom),
+                    RepetitionKind::OneOrMore => RegexRepresentation::Plus(atom),
+                    RepetitionKind::Count(count) => RegexRepresentation::Count(atom, count),
                 }
-                None => atom,
             });
 
         let concatenation = repetition
             .repeated()
             .at_least(1)
             .collect::<Vec<_>>()
-            .map(|regexes| {
-                regexes
-                    .into_iter()
-                    .reduce(|acc, regex| {
-                        RegexRepresentation::Concat(Box::new(acc), Box::new(regex))
-                    })
-                    .unwrap()
-            });
+            .map(RegexRepresentation::concat_all)
+            .map_with(|regex, e| RegexRepresentation::Spanned(Box::new(regex), e.span()));
 
         #[allow(clippy::let_and_return)]
         let alternation = concatenation
             .separated_by(just(Token::Pipe))
             .at_least(1)
             .collect::<Vec<_>>()
-            .map(|regexes| {
-                regexes
-                    .into_iter()
-                    .reduce(|acc, regex| RegexRepresentation::Or(Box::new(acc), Box::new(regex)))
-                    .unwrap()
+            .map(|mut alternatives| {
+                if alternatives.len() == 1 {
+                    alternatives.remove(0)
+                } else {
+                    RegexRepresentation::Or(alternatives)
+                }
             });
 
         alternation
     })
 }
 
+/// Returns an error if groups are nested more than `limit` deep.
+///
+/// Parentheses inside character classes (e.g., `[((]`) are members of the class, not groups.
+fn check_nest_limit(tokens: &[Token], limit: usize) -> Result<(), String> {
+    let mut depth: usize = 0;
+    // how many classes the scan is inside, counting nested classes after `--` and `&&`
+    let mut class_depth: usize = 0;
+    let mut escaped = false;
+    let mut leading_bracket = false;
+    for (i, token) in tokens.iter().enumerate() {
+        if std::mem::take(&mut leading_bracket) && *token == Token::CloseBracket {
+            continue;
+        }
+        match token {
+            _ if escaped => escaped = false,
+            Token::Backslash => escaped = true,
+            Token::OpenBracket if class_depth == 0 || ends_with_set_operation(&tokens[..i]) => {
+                class_depth += 1;
+                // a `]` straight after the `[` is a member, unless it would leave the class
+                // unterminated (see `class`)
+                leading_bracket = tokens[i + 1..]
+                    .iter()
+                    .skip(1)
+                    .any(|token| *token == Token::CloseBracket);
+            }
+            Token::CloseBracket if class_depth > 0 => class_depth -= 1,
+            Token::OpenParen if class_depth == 0 => {
+                depth += 1;
+                if depth > limit {
+                    return Err(format!("Groups are nested more than {limit} deep"));
+                }
+            }
+            Token::CloseParen if class_depth == 0 => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the tokens end with a class set operation (`--` or `&&`), which a nested class
+/// follows.
+fn ends_with_set_operation(tokens: &[Token]) -> bool {
+    matches!(
+        tokens,
+        [.., Token::Hyphen, Token::Hyphen] | [.., Token::Literal('&'), Token::Literal('&')]
+    )
+}
+
+/// Returns the character written by the hexadecimal escape that the tokens start with after its
+/// backslash (e.g., `x1f`), if they start with one.
+fn hex_escape_char(tokens: &[Token]) -> Option<char> {
+    let [Token::Literal('x'), Token::Literal(high), Token::Literal(low), ..] = tokens else {
+        return None;
+    };
+    let code = high.to_digit(16)? * 16 + low.to_digit(16)?;
+    char::from_u32(code)
+}
+
+/// Returns an error if the tokens write a control character or unusual whitespace in a way the
+/// policy does not allow.
+fn check_control_chars(tokens: &[Token], policy: ControlCharPolicy) -> Result<(), String> {
+    if policy == ControlCharPolicy::Literal {
+        return Ok(());
+    }
+
+    let mut escaped = false;
+    for (i, token) in tokens.iter().enumerate() {
+        match *token {
+            Token::Literal(c) if is_control_or_unusual_whitespace(c) => {
+                let code = u32::from(c);
+                return Err(
+                    if policy == ControlCharPolicy::RequireEscape && code <= 0xff {
+                        format!(
+                            "Error at position {i}: U+{code:04X} must be written as \\x{code:02x}"
+                        )
+                    } else {
+                        format!("Error at position {i}: U+{code:04X} is not allowed")
+                    },
+                );
+            }
+            _ if escaped => escaped = false,
+            Token::Backslash => {
+                escaped = true;
+                if policy == ControlCharPolicy::Reject {
+                    if let Some(c) = hex_escape_char(&tokens[i + 1..])
+                        .filter(|&c| is_control_or_unusual_whitespace(c))
+                    {
+                        let code = u32::from(c);
+                        return Err(format!(
+                            "Error at position {i}: U+{code:04X} is not allowed, even as \\x{code:02x}"
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
 /// Tries to parse a given string into a `Regex` object.
+///
+/// A leading `(?i)` flag makes the whole regex case-insensitive (e.g., `(?i)ab` matches `AB`).
 pub fn parse_string_to_regex(input: &str) -> Result<Regex, String> {
+    parse_string_to_regex_with(input, ParseFlags::default())
+}
+
+/// Like `parse_string_to_regex`, but with the given flags.
+pub fn parse_string_to_regex_with(input: &str, flags: ParseFlags) -> Result<Regex, String> {
+    Ok(parse_string_to_regex_simplified_with(
+        input,
+        flags,
+        &SimplifyOptions::default(),
+    )?)
+}
+
+/// Like `parse_string_to_regex_with`, but simplifies the regex with the given options.
+pub fn parse_string_to_regex_simplified_with(
+    input: &str,
+    flags: ParseFlags,
+    options: &SimplifyOptions,
+) -> Result<Regex, ParseError> {
+    if let Some(input) = input.strip_prefix("(?i)") {
+        let flags = ParseFlags {
+            case_insensitive: true,
+            ..flags
+        };
+        return parse_string_to_regex_simplified_with(input, flags, options);
+    }
+
+    let regex = parse_to_representation(input, flags)?;
+    if flags.case_insensitive {
+        Ok(regex.to_regex().case_insensitive().simplify_with(options))
+    } else {
+        Ok(regex.to_regex().simplify_with(options))
+    }
+}
+
+/// Returns the byte ranges of the parts of the pattern that can never be part of a match, either
+/// because no string matches them (e.g., `[]` in `a|[]`) or because they are followed or preceded
+/// by such a part (e.g., `a[]` in `a[]|b`).
+///
+/// The outermost part is reported for each, so `b[]` in `a|b[]` is reported instead of `[]`.
+/// Repeated parts are reported separately from their repetition, which still matches the empty
+/// string (e.g., `([])` in `a([])*`).
+pub(crate) fn dead_branch_spans(input: &str) -> Result<Vec<Range<usize>>, String> {
+    let (offset, pattern) = input
+        .strip_prefix("(?i)")
+        .map_or((0, input), |pattern| ("(?i)".len(), pattern));
+    let regex = parse_to_representation(pattern, ParseFlags::default())?;
+
+    let mut spans = Vec::new();
+    regex.collect_dead_spans(&mut spans);
+
+    // every token is one character, so token indices are character indices
+    let boundaries = pattern
+        .char_indices()
+        .map(|(i, _)| offset + i)
+        .chain(std::iter::once(input.len()))
+        .collect::<Vec<_>>();
+    Ok(spans
+        .into_iter()
+        .map(|span| boundaries[span.start]..boundaries[span.end])
+        .collect())
+}
+
+/// Tokenizes and parses the input into the intermediate representation.
+fn parse_to_representation(
+    input: &str,
+    flags: ParseFlags,
+) -> Result<RegexRepresentation, ParseError> {
     let tokens = tokenize_string(input).map_err(|_| "Failed to tokenize input".to_string())?;
 
     if tokens.is_empty() {
-        return Err("Empty input not allowed".to_string());
+        return Err("Empty input not allowed".to_string().into());
     }
+    if let Some(limit) = flags.nest_limit {
+        check_nest_limit(&tokens, limit)?;
+    }
+    check_control_chars(&tokens, flags.control_chars)?;
 
-    let result = parser().parse(Stream::from_iter(tokens)).into_result();
+    let result = grow_stack_if_low(|| {
+        parser(flags)
+            .parse(Stream::from_iter(tokens.iter().cloned()))
+            .into_result()
+    });
 
     match result {
-        Ok(regex) => Ok(regex.to_regex().simplify()),
+        Ok(regex) => Ok(regex),
         Err(errors) => {
+            let mut kind = ParseErrorKind::Other;
             let mut error_message = String::new();
             for error in errors {
                 let span = error.span();
+                if let RichReason::Custom(message) = error.reason() {
+                    // the only custom errors are from `parse_number`, whose span covers the digits
+                    let digits = tokens[span.start..span.end]
+                        .iter()
+                        .map(Token::as_char)
+                        .collect::<String>();
+                    kind = if digits.parse::<usize>().is_err() {
+                        ParseErrorKind::CountTooLarge
+                    } else {
+                        ParseErrorKind::CountOverLimit
+                    };
+                    let _ = writeln!(error_message, "Error at position {}: {message}", span.start);
+                    continue;
+                }
+
                 let found = error
                     .found()
                     .map(|t| t.to_string())
@@ -411,7 +995,7 @@ pub fn parse_string_to_regex(input: &str) -> Result<Regex, String> {
                 );
             }
 
-            Err(error_message)
+            Err(ParseError::new(kind, error_message))
         }
     }
 }
@@ -441,6 +1025,15 @@ mod tests {
         assert_eq!(regex, Regex::Literal('a'));
     }
 
+    #[test]
+    fn parse_literal_hex_escape() {
+        let regex = parse_string_to_regex(r"\x41").unwrap();
+        assert_eq!(regex, Regex::Literal('A'));
+
+        let regex = parse_string_to_regex(r"[\x00-\x1f]").unwrap();
+        assert_eq!(regex, Regex::Class(vec![CharRange::Range('\0', '\x1f')]));
+    }
+
     #[test]
     fn parse_character_class_simple() {
         let regex = parse_string_to_regex("[a-z]").unwrap();
@@ -482,6 +1075,64 @@ mod tests {
         assert_eq!(regex, Regex::Class(vec![CharRange::Range('-', '0')]));
     }
 
+    #[test]
+    fn parse_character_class_special_characters() {
+        // characters that are special outside classes need no escaping inside them
+        let regex = parse_string_to_regex("[*-/(|]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![
+                CharRange::Single('('),
+                CharRange::Range('*', '/'),
+                CharRange::Single('|'),
+            ])
+        );
+        assert_eq!(regex.to_string(), "[(*-/|]");
+        assert_eq!(
+            parse_string_to_regex("[{}?]").unwrap(),
+            Regex::Class(vec![
+                CharRange::Single('?'),
+                CharRange::Single('{'),
+                CharRange::Single('}')
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_character_class_edge_hyphens() {
+        // trailing hyphen, like `regex`'s `[a-z-]`
+        let regex = parse_string_to_regex("[a-z-]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![CharRange::Single('-'), CharRange::Range('a', 'z')])
+        );
+
+        // leading hyphen, like `regex`'s `[-az]`
+        let regex = parse_string_to_regex("[-az]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![
+                CharRange::Single('-'),
+                CharRange::Single('a'),
+                CharRange::Single('z'),
+            ])
+        );
+
+        // lone hyphen, like `regex`'s `[-]`
+        let regex = parse_string_to_regex("[-]").unwrap();
+        assert_eq!(regex, Regex::Literal('-'));
+
+        // a hyphen with no range end is a literal, like `regex`'s `[a-]`
+        let regex = parse_string_to_regex("[a-]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![CharRange::Single('-'), CharRange::Single('a')])
+        );
+
+        // hyphens in the middle of a class must still be escaped
+        assert!(parse_string_to_regex("[a-c-e]").is_err());
+    }
+
     #[test]
     fn parse_repetition_star() {
         let regex = parse_string_to_regex("a*").unwrap();
@@ -505,7 +1156,7 @@ mod tests {
         let regex = parse_string_to_regex("a{3}").unwrap();
         assert_eq!(
             regex,
-            Regex::Count(Box::new(Regex::Literal('a')), Count::Exact(3))
+            Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(3))
         );
     }
 
@@ -514,7 +1165,7 @@ mod tests {
         let regex = parse_string_to_regex("a{3,5}").unwrap();
         assert_eq!(
             regex,
-            Regex::Count(Box::new(Regex::Literal('a')), Count::Range(3, 5))
+            Regex::Count(Node::new(Regex::Literal('a')), Count::Range(3, 5))
         );
     }
 
@@ -523,30 +1174,55 @@ mod tests {
         let regex = parse_string_to_regex("a{3,}").unwrap();
         assert_eq!(
             regex,
-            Regex::Count(Box::new(Regex::Literal('a')), Count::AtLeast(3))
+            Regex::Count(Node::new(Regex::Literal('a')), Count::AtLeast(3))
         );
     }
 
     #[test]
-    fn parse_concatenation() {
-        let regex = parse_string_to_regex("ab").unwrap();
+    fn parse_repetition_count_overflow() {
+        let too_large = "9".repeat(30);
+        for pattern in [
+            format!("a{{{too_large}}}"),
+            format!("a{{3,{too_large}}}"),
+            format!("a{{{too_large},}}"),
+            format!("(b|a{{{too_large}}})c"),
+        ] {
+            let error = parse_string_to_regex(&pattern).unwrap_err();
+            assert!(
+                error.contains(&format!("Count {too_large} is too large")),
+                "{pattern}: {error}"
+            );
+        }
+
+        // the largest count is still accepted
+        let regex = parse_string_to_regex(&format!("a{{{}}}", usize::MAX)).unwrap();
         assert_eq!(
             regex,
-            Regex::Concat(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')))
+            Regex::Count(Node::new(Regex::Literal('a')), Count::Exact(usize::MAX))
         );
     }
 
+    #[test]
+    fn parse_concatenation() {
+        let regex = parse_string_to_regex("ab").unwrap();
+        assert_eq!(regex, Regex::Str("ab".to_string()));
+    }
+
     #[test]
     fn parse_concatenation_three() {
         let regex = parse_string_to_regex("abc").unwrap();
+        assert_eq!(regex, Regex::Str("abc".to_string()));
+
+        // literals on either side of other regexes are joined separately
+        let regex = parse_string_to_regex("ab+cd").unwrap();
         assert_eq!(
             regex,
             Regex::Concat(
-                Box::new(Regex::Concat(
-                    Box::new(Regex::Literal('a')),
-                    Box::new(Regex::Literal('b')),
+                Node::new(Regex::Concat(
+                    Node::new(Regex::Literal('a')),
+                    Node::new(Regex::Literal('b').plus()),
                 )),
-                Box::new(Regex::Literal('c')),
+                Node::new(Regex::Str("cd".to_string())),
             )
         );
     }
@@ -555,12 +1231,12 @@ mod tests {
     fn parse_concatenation_complex() {
         let regex = parse_string_to_regex("a(bc)*d[a-z]").unwrap();
 
-        let bc = Regex::Concat(Box::new(Regex::Literal('b')), Box::new(Regex::Literal('c')));
+        let bc = Regex::Str("bc".to_string());
         let star = bc.star();
-        let a_bc_star = Regex::Concat(Box::new(Regex::Literal('a')), Box::new(star));
-        let a_bc_star_d = Regex::Concat(Box::new(a_bc_star), Box::new(Regex::Literal('d')));
+        let a_bc_star = Regex::Concat(Node::new(Regex::Literal('a')), Node::new(star));
+        let a_bc_star_d = Regex::Concat(Node::new(a_bc_star), Node::new(Regex::Literal('d')));
         let class = Regex::Class(vec![CharRange::Range('a', 'z')]);
-        let a_bc_star_d_class = Regex::Concat(Box::new(a_bc_star_d), Box::new(class));
+        let a_bc_star_d_class = Regex::Concat(Node::new(a_bc_star_d), Node::new(class));
 
         assert_eq!(regex, a_bc_star_d_class);
     }
@@ -570,7 +1246,10 @@ mod tests {
         let regex = parse_string_to_regex("a|b").unwrap();
         assert_eq!(
             regex,
-            Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b')))
+            Regex::Or(
+                Node::new(Regex::Literal('a')),
+                Node::new(Regex::Literal('b'))
+            )
         );
     }
 
@@ -581,11 +1260,11 @@ mod tests {
         assert_eq!(
             regex,
             Regex::Or(
-                Box::new(Regex::Or(
-                    Box::new(Regex::Literal('a')),
-                    Box::new(Regex::Literal('b')),
+                Node::new(Regex::Literal('a')),
+                Node::new(Regex::Or(
+                    Node::new(Regex::Literal('b')),
+                    Node::new(Regex::Literal('c')),
                 )),
-                Box::new(Regex::Literal('c')),
             )
         );
     }
@@ -595,9 +1274,9 @@ mod tests {
         let regex = parse_string_to_regex("a*|(bc)?").unwrap();
 
         let a_star = Regex::Literal('a').star();
-        let bc = Regex::Concat(Box::new(Regex::Literal('b')), Box::new(Regex::Literal('c')));
+        let bc = Regex::Str("bc".to_string());
         let bc_optional = bc.optional();
-        let a_star_or_bc_optional = Regex::Or(Box::new(a_star), Box::new(bc_optional));
+        let a_star_or_bc_optional = Regex::Or(Node::new(a_star), Node::new(bc_optional));
 
         assert_eq!(regex, a_star_or_bc_optional);
     }
@@ -608,12 +1287,70 @@ mod tests {
         assert_eq!(regex, Regex::Class(vec![]));
     }
 
+    #[test]
+    fn parse_character_class_leading_bracket() {
+        let regex = parse_string_to_regex("[]a]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![CharRange::Single(']'), CharRange::Single('a')])
+        );
+
+        let regex = parse_string_to_regex("[]]").unwrap();
+        assert_eq!(regex, Regex::Literal(']'));
+
+        // an empty class followed by `[a]`, which matches nothing
+        let regex = parse_string_to_regex("[][a]").unwrap();
+        assert_eq!(regex, Regex::Empty);
+    }
+
+    #[test]
+    fn parse_character_class_set_operations() {
+        // consonants
+        let regex = parse_string_to_regex("[a-z--[aeiou]]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![
+                CharRange::Range('b', 'd'),
+                CharRange::Range('f', 'h'),
+                CharRange::Range('j', 'n'),
+                CharRange::Range('p', 't'),
+                CharRange::Range('v', 'z'),
+            ])
+        );
+
+        let regex = parse_string_to_regex("[a-z&&[x-~]]").unwrap();
+        assert_eq!(regex, Regex::Class(vec![CharRange::Range('x', 'z')]));
+
+        // operations apply from left to right and nest
+        let regex = parse_string_to_regex("[a-z--[b-y]&&[a-c]]").unwrap();
+        assert_eq!(regex, Regex::Literal('a'));
+
+        let regex = parse_string_to_regex("[a-z--[a-y--[b]]]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![CharRange::Single('b'), CharRange::Single('z')])
+        );
+
+        // a lone `&` is still a literal
+        let regex = parse_string_to_regex("[a&]").unwrap();
+        assert_eq!(
+            regex,
+            Regex::Class(vec![CharRange::Single('&'), CharRange::Single('a')])
+        );
+
+        assert!(parse_string_to_regex("[a-z--]").is_err());
+        assert!(parse_string_to_regex("[a-z&&b]").is_err());
+    }
+
     #[test]
     fn parse_nested_parentheses() {
         let regex = parse_string_to_regex("((a|b)*c)+").unwrap();
-        let a_or_b_star =
-            Regex::Or(Box::new(Regex::Literal('a')), Box::new(Regex::Literal('b'))).star();
-        let a_or_b_star_c = Regex::Concat(Box::new(a_or_b_star), Box::new(Regex::Literal('c')));
+        let a_or_b_star = Regex::Or(
+            Node::new(Regex::Literal('a')),
+            Node::new(Regex::Literal('b')),
+        )
+        .star();
+        let a_or_b_star_c = Regex::Concat(Node::new(a_or_b_star), Node::new(Regex::Literal('c')));
         let a_or_b_star_c_plus = a_or_b_star_c.plus();
 
         assert_eq!(regex, a_or_b_star_c_plus);
@@ -628,16 +1365,7 @@ mod tests {
     #[test]
     fn parse_hyphen() {
         let regex = parse_string_to_regex("a-z").unwrap();
-        assert_eq!(
-            regex,
-            Regex::Concat(
-                Box::new(Regex::Concat(
-                    Box::new(Regex::Literal('a')),
-                    Box::new(Regex::Literal('-')),
-                )),
-                Box::new(Regex::Literal('z')),
-            )
-        );
+        assert_eq!(regex, Regex::Str("a-z".to_string()));
     }
 
     #[test]
@@ -667,7 +1395,6 @@ mod tests {
     fn parse_email() {
         let pattern = r"[a-zA-Z0-9._%+\-]+@[a-zA-Z0-9.\-]+\.[a-zA-Z]{2,}";
         let regex = parse_string_to_regex(pattern);
-        println!("Error: {:?}", regex);
         assert!(regex.is_ok());
     }
 }
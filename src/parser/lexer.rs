@@ -92,4 +92,16 @@ mod tests {
         assert_eq!(lexer.next(), Some(Ok(Token::Backslash)));
         assert_eq!(lexer.next(), Some(Ok(Token::OpenBracket)));
     }
+
+    #[test]
+    fn lex_control_literal() {
+        // control characters and unusual whitespace are lexed as literals, and `ControlCharPolicy`
+        // decides whether they are allowed
+        let input = "\t\0\u{3000}";
+        let mut lexer = Token::lexer(input);
+
+        assert_eq!(lexer.next(), Some(Ok(Token::Literal('\t'))));
+        assert_eq!(lexer.next(), Some(Ok(Token::Literal('\0'))));
+        assert_eq!(lexer.next(), Some(Ok(Token::Literal('\u{3000}'))));
+    }
 }
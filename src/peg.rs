@@ -0,0 +1,161 @@
+//! Matching with ordered alternation, as in parsing expression grammars (PEGs), for porting
+//! grammars that rely on earlier alternatives winning.
+//!
+//! In a `Regex`, `a|ab` matches both `a` and `ab`, and the alternatives are a set. With ordered
+//! alternation, the first alternative that matches at a position is taken and never reconsidered,
+//! and repetitions take as many repetitions as they can without giving any back. So `a|ab` only
+//! matches `a` in `ab`, and `(a|ab)c` does not match `abc` at all, since `a` is taken and `c` then
+//! fails on `b`.
+//!
+//! Ordered matching walks the regex directly rather than taking derivatives, since which strings
+//! it matches depends on the order the alternatives were written in.
+
+use crate::derivatives::{ensure_stack, Regex};
+use std::ops::Range;
+
+/// A regex whose alternations are ordered, compiled with `RegexBuilder::build_ordered`.
+///
+/// Its alternatives are kept in the order they were written, so it is only simplified with the
+/// rules of `SimplifyLevel::Basic`, which do not reorder them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderedRegex {
+    regex: Regex,
+}
+
+impl OrderedRegex {
+    /// Creates an ordered regex from a regex, keeping the order of its alternatives as they are.
+    pub const fn new(regex: Regex) -> Self {
+        Self { regex }
+    }
+
+    /// Returns the regex, with its alternatives in order.
+    pub const fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Returns the length in bytes of the prefix of the string that the regex matches with ordered
+    /// alternation, or `None` if it fails (e.g., `a|ab` matches a prefix of length 1 of `abc`).
+    ///
+    /// Unlike `Regex::match_prefix`, there is at most one such prefix, so this is not necessarily
+    /// the longest prefix the regex could match.
+    pub fn match_prefix(&self, s: &str) -> Option<usize> {
+        match_at(&self.regex, s, 0)
+    }
+
+    /// Returns `true` if the regex matches the whole string with ordered alternation, otherwise
+    /// returns `false`.
+    pub fn matches(&self, s: &str) -> bool {
+        self.match_prefix(s) == Some(s.len())
+    }
+
+    /// Returns the byte range of the first match of the regex in the haystack with ordered
+    /// alternation, or `None` if it does not match anywhere.
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        haystack
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(haystack.len()))
+            .find_map(|start| match_at(&self.regex, haystack, start).map(|end| start..end))
+    }
+}
+
+/// Returns the byte offset where the match of the regex starting at byte offset `start` of `s`
+/// ends, with ordered alternation and repetitions that never give back what they took.
+fn match_at(regex: &Regex, s: &str, start: usize) -> Option<usize> {
+    ensure_stack(|| match regex {
+        Regex::Empty => None,
+        Regex::Epsilon => Some(start),
+        Regex::Literal(_) | Regex::Class(_) | Regex::Pred(_) => {
+            let c = s[start..].chars().next()?;
+            (regex.matches_single_char(c) == Some(true)).then(|| start + c.len_utf8())
+        }
+        Regex::Str(literal) => s[start..]
+            .starts_with(literal.as_str())
+            .then(|| start + literal.len()),
+        Regex::Concat(left, right) => match_at(right, s, match_at(left, s, start)?),
+        Regex::Or(left, right) => match_at(left, s, start).or_else(|| match_at(right, s, start)),
+        Regex::Count(inner, count) => {
+            let (min, max) = count.bounds();
+            let mut end = start;
+            let mut repetitions = 0;
+            while max.map_or(true, |max| repetitions < max) {
+                let Some(next) = match_at(inner, s, end) else {
+                    break;
+                };
+                if next == end {
+                    // every further repetition matches the empty string too
+                    repetitions = repetitions.max(min);
+                    break;
+                }
+                end = next;
+                repetitions += 1;
+            }
+            (repetitions >= min).then_some(end)
+        }
+        // interleavings have no order to follow, so their longest match is taken
+        Regex::Shuffle(_, _) => regex.match_prefix(&s[start..]).map(|len| start + len),
+    })
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{OrderedRegex, Regex};
+    #[allow(unused_imports)]
+    use crate::RegexBuilder;
+
+    #[allow(dead_code)]
+    fn ordered(pattern: &str) -> OrderedRegex {
+        RegexBuilder::new(pattern).build_ordered().unwrap()
+    }
+
+    #[test]
+    fn test_ordered_alternation() {
+        let regex = ordered("a|ab");
+        assert_eq!(regex.match_prefix("abc"), Some(1));
+        assert!(regex.matches("a"));
+        assert!(!regex.matches("ab"));
+
+        // later alternatives are still tried when earlier ones fail
+        let regex = ordered("ab|a");
+        assert_eq!(regex.match_prefix("abc"), Some(2));
+        assert_eq!(regex.match_prefix("ac"), Some(1));
+        assert!(regex.matches("ab") && regex.matches("a"));
+
+        // an alternative that matched is not reconsidered when what follows fails
+        let regex = ordered("(a|ab)c");
+        assert!(regex.matches("ac"));
+        assert!(!regex.matches("abc"));
+        assert!(Regex::new("(a|ab)c").unwrap().matches("abc"));
+    }
+
+    #[test]
+    fn test_ordered_repetition() {
+        // repetitions never give back what they took
+        let regex = ordered("a*a");
+        assert!(!regex.matches("aaa"));
+        assert_eq!(ordered("a*b").match_prefix("aab"), Some(3));
+
+        let regex = ordered("(a|b){2,3}");
+        assert_eq!(regex.match_prefix("abab"), Some(3));
+        assert_eq!(regex.match_prefix("a"), None);
+
+        // a repetition of something matching the empty string stops
+        assert!(ordered("(a?)*b").matches("aab"));
+        assert!(ordered("(a?){3}").matches(""));
+    }
+
+    #[test]
+    fn test_ordered_find() {
+        let regex = ordered(r"\d|\d\d");
+        assert_eq!(regex.find("ab12"), Some(2..3));
+        assert_eq!(regex.find("ab"), None);
+        assert_eq!(ordered("x*").find("ab"), Some(0..0));
+        assert_eq!(ordered("é|ab").find("xé"), Some(1..3));
+    }
+
+    #[test]
+    fn test_ordered_keeps_order() {
+        assert_eq!(ordered("b|a").regex().to_string(), "(b|a)");
+        assert_eq!(Regex::new("b|a").unwrap().to_string(), "(a|b)");
+    }
+}
@@ -0,0 +1,190 @@
+//! Skipping ahead in a haystack to where a match can start, using the literal text every match of
+//! a regex begins and ends with, so that searching does not take derivatives at every position.
+//!
+//! The literals are found with `memchr::memmem`, which is much faster than stepping through the
+//! haystack one character at a time (e.g., `error: \d+` only starts a match where `error: ` is).
+
+use crate::derivatives::{grow_stack_if_low, CharRange, Count, Regex};
+use memchr::memmem::Finder;
+
+/// The longest literal a count is repeated into (e.g., `(ab){3}` gives `ababab`), since a longer
+/// one does not skip any more of the haystack.
+const MAX_REPEATED_LEN: usize = 64;
+
+/// Returns the characters that every string the regex matches begins with, or ends with if
+/// `backwards` (in reverse order), and whether the regex matches nothing but those characters.
+fn affix(regex: &Regex, backwards: bool) -> (Vec<char>, bool) {
+    if let Some(c) = single_char(regex) {
+        return (vec![c], true);
+    }
+    grow_stack_if_low(|| match regex {
+        Regex::Epsilon => (Vec::new(), true),
+        Regex::Str(s) if backwards => (s.chars().rev().collect(), true),
+        Regex::Str(s) => (s.chars().collect(), true),
+        Regex::Concat(left, right) => {
+            let (first, second) = if backwards {
+                (right, left)
+            } else {
+                (left, right)
+            };
+            let (mut chars, complete) = affix(first, backwards);
+            if !complete {
+                return (chars, false);
+            }
+            let (rest, complete) = affix(second, backwards);
+            chars.extend(rest);
+            (chars, complete)
+        }
+        Regex::Or(left, right) => {
+            let (mut chars, left_complete) = affix(left, backwards);
+            let (other, right_complete) = affix(right, backwards);
+            let complete = left_complete && right_complete && chars == other;
+            let common = chars.iter().zip(&other).take_while(|(a, b)| a == b).count();
+            chars.truncate(common);
+            (chars, complete)
+        }
+        Regex::Count(inner, count) => {
+            let (min, max) = count.bounds();
+            if min == 0 {
+                return (Vec::new(), false);
+            }
+            let (chars, complete) = affix(inner, backwards);
+            match count {
+                Count::Exact(n)
+                    if complete
+                        && chars
+                            .len()
+                            .checked_mul(*n)
+                            .is_some_and(|len| len <= MAX_REPEATED_LEN) =>
+                {
+                    (chars.repeat(*n), true)
+                }
+                _ => (chars, complete && max == Some(1)),
+            }
+        }
+        _ => (Vec::new(), false),
+    })
+}
+
+/// Returns the only character a literal or a class of one character matches.
+fn single_char(regex: &Regex) -> Option<char> {
+    match regex {
+        Regex::Literal(c) => Some(*c),
+        Regex::Class(ranges) => match ranges.as_slice() {
+            [CharRange::Single(c)] => Some(*c),
+            [CharRange::Range(start, end)] if start == end => Some(*start),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns a finder for the characters, or `None` if there are none.
+fn finder(chars: impl Iterator<Item = char>) -> Option<Finder<'static>> {
+    let needle: String = chars.collect();
+    (!needle.is_empty()).then(|| Finder::new(&needle).into_owned())
+}
+
+/// The literal text every match of a regex begins and ends with, used to skip over the parts of a
+/// haystack where no match can be.
+#[derive(Debug, Clone)]
+pub(crate) struct Prefilter {
+    /// Finds the text every match begins with, if there is any.
+    prefix: Option<Finder<'static>>,
+    /// Finds the text every match ends with, if there is any.
+    suffix: Option<Finder<'static>>,
+}
+
+impl Prefilter {
+    pub(crate) fn new(regex: &Regex) -> Self {
+        let (prefix, _) = affix(regex, false);
+        let (suffix, _) = affix(regex, true);
+        Self {
+            prefix: finder(prefix.into_iter()),
+            suffix: finder(suffix.into_iter().rev()),
+        }
+    }
+
+    /// Returns `false` if no match can lie in the haystack from the byte offset `start` onwards,
+    /// because the text every match ends with is not there.
+    pub(crate) fn may_match(&self, haystack: &str, start: usize) -> bool {
+        self.suffix.as_ref().map_or(true, |suffix| {
+            suffix.find(&haystack.as_bytes()[start..]).is_some()
+        })
+    }
+
+    /// Returns the first byte offset at or after `start` where a match can begin, or `None` if
+    /// none can.
+    pub(crate) fn next_start(&self, haystack: &str, start: usize) -> Option<usize> {
+        self.prefix.as_ref().map_or(Some(start), |prefix| {
+            prefix
+                .find(&haystack.as_bytes()[start..])
+                .map(|offset| start + offset)
+        })
+    }
+
+    /// Returns `true` if a match can begin at the byte offset `position`, otherwise returns
+    /// `false`.
+    pub(crate) fn can_start_at(&self, haystack: &str, position: usize) -> bool {
+        self.prefix.as_ref().map_or(true, |prefix| {
+            haystack.as_bytes()[position..].starts_with(prefix.needle())
+        })
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Finder, Prefilter, Regex};
+
+    #[allow(dead_code)]
+    fn affixes(pattern: &str) -> (String, String) {
+        let prefilter = Prefilter::new(&Regex::new(pattern).unwrap());
+        let needle = |finder: Option<Finder>| {
+            finder.map_or_else(String::new, |finder| {
+                String::from_utf8(finder.needle().to_vec()).unwrap()
+            })
+        };
+        (needle(prefilter.prefix), needle(prefilter.suffix))
+    }
+
+    #[test]
+    fn test_prefilter_affixes() {
+        assert_eq!(affixes("abc"), ("abc".into(), "abc".into()));
+        assert_eq!(affixes(r"error: \d+"), ("error: ".into(), String::new()));
+        assert_eq!(affixes(r"\d+px"), (String::new(), "px".into()));
+        assert_eq!(affixes("ab(c|d)ef"), ("ab".into(), "ef".into()));
+        assert_eq!(affixes("abc|abd"), ("ab".into(), String::new()));
+        assert_eq!(affixes("(ab){3}x"), ("abababx".into(), "abababx".into()));
+        assert_eq!(affixes("(ab)+x"), ("ab".into(), "abx".into()));
+        assert_eq!(affixes("é+ü"), ("é".into(), "éü".into()));
+        // too many repetitions to spell out, without overflowing
+        assert_eq!(
+            affixes("(ab){18446744073709551615}"),
+            ("ab".into(), "ab".into())
+        );
+
+        // a regex that matches the empty string can match anywhere
+        assert_eq!(affixes("(abc)?"), (String::new(), String::new()));
+        assert_eq!(affixes("a*b*"), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn test_prefilter_positions() {
+        let prefilter = Prefilter::new(&Regex::new(r"ab\d").unwrap());
+        let haystack = "xxabyab1";
+        assert_eq!(prefilter.next_start(haystack, 0), Some(2));
+        assert_eq!(prefilter.next_start(haystack, 3), Some(5));
+        assert_eq!(prefilter.next_start(haystack, 6), None);
+        assert!(prefilter.can_start_at(haystack, 5));
+        assert!(!prefilter.can_start_at(haystack, 4));
+        assert!(prefilter.may_match(haystack, 0));
+
+        let prefilter = Prefilter::new(&Regex::new(r"\d+px").unwrap());
+        assert!(prefilter.may_match("12px", 0));
+        assert!(!prefilter.may_match("12pt", 0));
+        assert!(!prefilter.may_match("12px", 3));
+
+        let regex = Regex::new("(ab){18446744073709551615}").unwrap();
+        assert_eq!(regex.find("xab"), None);
+    }
+}
@@ -0,0 +1,333 @@
+//! Matching a regex against a `std::io::Read` (e.g., a file), without reading it all into memory.
+//!
+//! The bytes are read in chunks and decoded as UTF-8 (a character may be split across chunks), and
+//! each character is fed to the derivative as soon as it is decoded. The same decoding is used by
+//! `Regex::matches_stream` for asynchronous streams.
+
+use crate::derivatives::Regex;
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+
+/// The number of bytes read from a reader at a time.
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Returns the length of the UTF-8 encoded character that starts with the given byte.
+const fn utf8_len(first_byte: u8) -> usize {
+    match first_byte {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    }
+}
+
+/// Returns the error for a stream that is not valid UTF-8.
+fn invalid_utf8(offset: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Stream is not valid UTF-8 at byte {offset}"),
+    )
+}
+
+/// The state of a regex part way through matching a stream of bytes that arrive in chunks.
+#[derive(Debug)]
+pub(crate) struct ChunkMatcher {
+    pub(crate) current: Regex,
+    /// The bytes at the end of the last chunk that start a character split across chunks.
+    pending: Vec<u8>,
+    /// The number of bytes fed to the regex so far, excluding `pending`.
+    pub(crate) offset: usize,
+}
+
+impl ChunkMatcher {
+    pub(crate) const fn new(regex: Regex) -> Self {
+        Self {
+            current: regex,
+            pending: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Feeds each character of `s` to the regex, returning `false` as soon as it reaches `∅`.
+    fn push_str(&mut self, s: &str) -> bool {
+        for c in s.chars() {
+            self.current = self.current.derivative(c);
+            if self.current == Regex::Empty {
+                return false;
+            }
+            self.offset += c.len_utf8();
+        }
+        true
+    }
+
+    /// Feeds a chunk of the stream to the regex, returning `false` as soon as it reaches `∅`.
+    pub(crate) fn push_chunk(&mut self, mut chunk: &[u8]) -> io::Result<bool> {
+        if let Some(&first_byte) = self.pending.first() {
+            // complete the character split across chunks before decoding the rest
+            let missing = (utf8_len(first_byte) - self.pending.len()).min(chunk.len());
+            self.pending.extend_from_slice(&chunk[..missing]);
+            chunk = &chunk[missing..];
+            if self.pending.len() < utf8_len(first_byte) {
+                return Ok(true);
+            }
+
+            let pending = std::mem::take(&mut self.pending);
+            let s = std::str::from_utf8(&pending).map_err(|_| invalid_utf8(self.offset))?;
+            if !self.push_str(s) {
+                return Ok(false);
+            }
+        }
+
+        match std::str::from_utf8(chunk) {
+            Ok(s) => Ok(self.push_str(s)),
+            Err(error) => {
+                let (valid, rest) = chunk.split_at(error.valid_up_to());
+                // `valid_up_to` is always on a character boundary
+                if !self.push_str(std::str::from_utf8(valid).unwrap()) {
+                    return Ok(false);
+                }
+                if error.error_len().is_some() {
+                    return Err(invalid_utf8(self.offset));
+                }
+
+                self.pending = rest.to_vec();
+                Ok(true)
+            }
+        }
+    }
+
+    /// Returns whether the regex matches the stream once it has ended.
+    pub(crate) fn finish(self) -> io::Result<bool> {
+        if !self.pending.is_empty() {
+            return Err(invalid_utf8(self.offset));
+        }
+
+        Ok(self.current.nullable())
+    }
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches everything read from the reader, otherwise returns
+    /// `false`.
+    ///
+    /// The input is read in chunks of a few kilobytes and never held in memory all at once. Reading
+    /// stops as soon as no continuation of the input could match. Returns an error if the reader
+    /// does, or if the input is not valid UTF-8 (with kind `io::ErrorKind::InvalidData`).
+    pub fn matches_reader<R: Read>(&self, mut reader: R) -> io::Result<bool> {
+        let mut matcher = ChunkMatcher::new(self.clone());
+        if matcher.current == Self::Empty {
+            return Ok(false);
+        }
+
+        let mut buffer = vec![0; READ_CHUNK_SIZE];
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => return matcher.finish(),
+                Ok(read) => read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            };
+
+            if !matcher.push_chunk(&buffer[..read])? {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// A line read by `Regex::scan_lines`, with the matches of the regex in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedLine {
+    /// The (1-based) number of the line.
+    pub number: usize,
+    /// The line, without its `\n` or `\r\n` terminator.
+    pub line: String,
+    /// The byte ranges of the non-overlapping matches of the regex in the line, as returned by
+    /// `Regex::find_iter`.
+    pub matches: Vec<Range<usize>>,
+}
+
+impl ScannedLine {
+    /// Returns `true` if the regex matched anywhere in the line, otherwise returns `false`.
+    pub fn is_match(&self) -> bool {
+        !self.matches.is_empty()
+    }
+}
+
+/// An iterator over the lines of a reader and the matches of a regex in each, returned by
+/// `Regex::scan_lines`.
+#[derive(Debug)]
+pub struct ScanLines<'r, R> {
+    regex: &'r Regex,
+    reader: R,
+    /// The number of lines read so far.
+    number: usize,
+}
+
+impl<R: BufRead> Iterator for ScanLines<'_, R> {
+    type Item = io::Result<ScannedLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => return None,
+            Ok(_) => {}
+            Err(error) => return Some(Err(error)),
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+
+        self.number += 1;
+        let matches = self.regex.find_iter(&line).collect();
+        Some(Ok(ScannedLine {
+            number: self.number,
+            line,
+            matches,
+        }))
+    }
+}
+
+impl Regex {
+    /// Returns an iterator over the lines of the reader, each with the matches of the regex in it,
+    /// reading one line at a time (e.g., for filtering a log as it is written).
+    ///
+    /// Yields an error if the reader does, or if a line is not valid UTF-8 (with kind
+    /// `io::ErrorKind::InvalidData`).
+    pub const fn scan_lines<R: BufRead>(&self, reader: R) -> ScanLines<'_, R> {
+        ScanLines {
+            regex: self,
+            reader,
+            number: 0,
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{io, Read, Regex, ScannedLine};
+
+    /// A reader that returns the given chunks one `read` at a time, then `error` once if it is set.
+    #[allow(dead_code)]
+    struct ChunkReader<'a> {
+        chunks: std::slice::Iter<'a, &'a [u8]>,
+        error: Option<io::ErrorKind>,
+    }
+
+    impl Read for ChunkReader<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some(chunk) = self.chunks.next() else {
+                return self.error.take().map_or(Ok(0), |kind| Err(kind.into()));
+            };
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[allow(dead_code)]
+    fn read_chunks<'a>(chunks: &'a [&'a [u8]]) -> ChunkReader<'a> {
+        ChunkReader {
+            chunks: chunks.iter(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_matches_reader() {
+        let regex = Regex::new("a+b").unwrap();
+        assert!(regex.matches_reader("aaab".as_bytes()).unwrap());
+        assert!(!regex.matches_reader("aaa".as_bytes()).unwrap());
+        assert!(!regex.matches_reader(io::empty()).unwrap());
+        assert!(!regex.matches_reader("acb".as_bytes()).unwrap());
+
+        // more than one buffer's worth of input
+        let input = format!("{}b", "a".repeat(20_000));
+        assert!(regex.matches_reader(input.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_matches_reader_split_characters() {
+        let regex = Regex::new("é💕+").unwrap();
+        let bytes = "é💕💕".as_bytes();
+        for split in 1..bytes.len() {
+            let (first, second) = bytes.split_at(split);
+            let chunks = [first, second];
+            assert!(
+                regex.matches_reader(read_chunks(&chunks)).unwrap(),
+                "{split}"
+            );
+        }
+
+        let error = regex
+            .matches_reader(read_chunks(&[b"\xC3\xA9\xF0\x9F"]))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_matches_reader_errors() {
+        let regex = Regex::new("a*").unwrap();
+        let chunks: [&[u8]; 2] = [b"aa", b"a"];
+
+        // interrupted reads are retried
+        let reader = ChunkReader {
+            chunks: chunks.iter(),
+            error: Some(io::ErrorKind::Interrupted),
+        };
+        assert!(regex.matches_reader(reader).unwrap());
+
+        let reader = ChunkReader {
+            chunks: chunks.iter(),
+            error: Some(io::ErrorKind::ConnectionReset),
+        };
+        let error = regex.matches_reader(reader).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+
+        // reading stops before the error once the input can no longer match
+        let chunks: [&[u8]; 1] = [b"ab"];
+        let reader = ChunkReader {
+            chunks: chunks.iter(),
+            error: Some(io::ErrorKind::ConnectionReset),
+        };
+        assert!(!regex.matches_reader(reader).unwrap());
+    }
+
+    #[test]
+    fn test_scan_lines() {
+        let regex = Regex::new("error: [a-z]+").unwrap();
+        let log = "ok\r\nerror: disk, error: net\nok\n\nerror: cpu";
+        let lines = regex
+            .scan_lines(log.as_bytes())
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[1],
+            ScannedLine {
+                number: 2,
+                line: "error: disk, error: net".to_string(),
+                matches: vec![0..11, 13..23],
+            }
+        );
+        assert_eq!(lines[0].line, "ok");
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|line| line.is_match())
+                .map(|line| line.number)
+                .collect::<Vec<_>>(),
+            vec![2, 5]
+        );
+
+        assert_eq!(regex.scan_lines(io::empty()).count(), 0);
+        let error = regex
+            .scan_lines(&b"ok\n\xff\n"[..])
+            .nth(1)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
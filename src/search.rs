@@ -0,0 +1,511 @@
+//! Searching for matches of a regex inside a longer string, rather than matching the whole string.
+
+use crate::derivatives::Regex;
+use crate::prefilter::Prefilter;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Panics if `start` is not on a character boundary of the haystack (including if it is past the
+/// end), before anything slices the haystack there.
+fn assert_boundary(haystack: &str, start: usize) {
+    assert!(
+        haystack.is_char_boundary(start),
+        "byte offset {start} is not on a character boundary of the haystack"
+    );
+}
+
+/// How much of a string a regex must match, chosen for each call to `Regex::is_match_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchMode {
+    /// The regex must match the whole string, as with `Regex::is_full_match`.
+    Full,
+    /// The regex must match a prefix of the string, as with `Regex::match_prefix`.
+    Prefix,
+    /// The regex must match some part of the string, as with `Regex::is_partial_match`.
+    Partial,
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches the whole string, otherwise returns `false` (e.g., `\d+`
+    /// matches `123` but not `a123`). This is the same as `matches`.
+    pub fn is_full_match(&self, s: &str) -> bool {
+        self.matches(s)
+    }
+
+    /// Returns `true` if the regex matches any part of the string, including all of it or an empty
+    /// part of it, otherwise returns `false` (e.g., `\d+` matches `a123`). This is what `is_match`
+    /// in the `regex` crate checks, and is the same as `is_match_anywhere`.
+    pub fn is_partial_match(&self, s: &str) -> bool {
+        self.is_match_anywhere(s)
+    }
+
+    /// Returns `true` if the regex matches the string in the given mode, otherwise returns
+    /// `false`, for choosing between full and partial matching at run time.
+    pub fn is_match_with(&self, s: &str, mode: MatchMode) -> bool {
+        match mode {
+            MatchMode::Full => self.is_full_match(s),
+            MatchMode::Prefix => self.match_shortest_prefix(s).is_some(),
+            MatchMode::Partial => self.is_partial_match(s),
+        }
+    }
+
+    /// Returns the byte range of the leftmost match of the regex in the haystack, or `None` if no
+    /// part of it matches.
+    ///
+    /// Of the matches starting at the leftmost position, the longest one is returned (e.g., `a|ab`
+    /// finds `ab` in `xab`). A regex that matches the empty string always finds a match, which may
+    /// be empty.
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        self.find_at(haystack, 0)
+    }
+
+    /// Returns an iterator over the byte ranges of the successive non-overlapping matches of the
+    /// regex in the haystack, each found like `find` from where the previous one ended.
+    ///
+    /// An empty match is not reported right where the previous match ended, and searching moves
+    /// forward by one character after it, so every match makes progress.
+    pub const fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches {
+            regex: self,
+            haystack,
+            position: 0,
+            last_end: None,
+            prefilter: None,
+        }
+    }
+
+    /// Returns `true` if the regex matches any part of the haystack, otherwise returns `false`.
+    ///
+    /// Like `is_match_at`, this returns as soon as a match has been found, without looking for
+    /// where it starts or ends, so it is cheaper than checking whether `find` returns a match.
+    pub fn is_match_anywhere(&self, haystack: &str) -> bool {
+        self.is_match_at(haystack, 0)
+    }
+
+    /// Returns the number of non-overlapping matches of the regex in the haystack, which are the
+    /// matches `find_iter` would return.
+    ///
+    /// Each match must still be searched to its end, since the next search starts there.
+    pub fn count_matches(&self, haystack: &str) -> usize {
+        self.find_iter(haystack).count()
+    }
+
+    /// Returns the length in bytes of the longest prefix of the string that the regex matches, or
+    /// `None` if it matches no prefix (e.g., `[a-z]+` matches a prefix of length 3 of `abc123`).
+    ///
+    /// Matching stops as soon as no longer prefix can match, so the rest of the string is not read.
+    pub fn match_prefix(&self, s: &str) -> Option<usize> {
+        let mut current = self.clone();
+        let mut longest = None;
+        for (i, c) in s.char_indices() {
+            if current.nullable() {
+                longest = Some(i);
+            }
+            current = current.derivative(c);
+            if current == Self::Empty {
+                return longest;
+            }
+        }
+
+        if current.nullable() {
+            Some(s.len())
+        } else {
+            longest
+        }
+    }
+
+    /// Returns the length in bytes of the shortest prefix of the string that the regex matches, or
+    /// `None` if it matches no prefix (e.g., `[a-z]+` matches a prefix of length 1 of `abc123`).
+    pub fn match_shortest_prefix(&self, s: &str) -> Option<usize> {
+        let mut current = self.clone();
+        for (i, c) in s.char_indices() {
+            if current.nullable() {
+                return Some(i);
+            }
+            current = current.derivative(c);
+            if current == Self::Empty {
+                return None;
+            }
+        }
+
+        current.nullable().then_some(s.len())
+    }
+
+    /// Returns `true` if the regex matches any part of the haystack starting at or after the byte
+    /// offset `start`, otherwise returns `false`.
+    ///
+    /// Returns as soon as a match has been found, without looking for where it ends.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a character boundary of the haystack.
+    pub fn is_match_at(&self, haystack: &str, start: usize) -> bool {
+        assert_boundary(haystack, start);
+        let prefilter = Prefilter::new(self);
+        if !prefilter.may_match(haystack, start) {
+            return false;
+        }
+
+        let mut states: Vec<Self> = Vec::new();
+        let mut i = start;
+        loop {
+            // with no match in progress, none can start before the text every match begins with
+            if states.is_empty() {
+                let Some(next) = prefilter.next_start(haystack, i) else {
+                    return false;
+                };
+                i = next;
+            }
+            if self != &Self::Empty && prefilter.can_start_at(haystack, i) {
+                states.push(self.clone());
+            }
+            if states.iter().any(Self::nullable) {
+                return true;
+            }
+
+            let Some(c) = haystack[i..].chars().next() else {
+                return false;
+            };
+            let mut seen = HashSet::new();
+            states = states
+                .into_iter()
+                .map(|state| state.derivative(c))
+                .filter(|state| *state != Self::Empty && seen.insert(state.clone()))
+                .collect();
+            i += c.len_utf8();
+        }
+    }
+
+    /// Returns the byte range of the longest match of the regex that starts exactly at the byte
+    /// offset `start` of the haystack, or `None` if no match starts there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a character boundary of the haystack.
+    pub fn find_anchored_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        self.match_prefix(&haystack[start..])
+            .map(|len| start..start + len)
+    }
+
+    /// Like `find`, but only considers matches starting at or after the byte offset `start`, so
+    /// the range is still relative to the start of the whole haystack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not on a character boundary of the haystack.
+    pub fn find_at(&self, haystack: &str, start: usize) -> Option<Range<usize>> {
+        assert_boundary(haystack, start);
+        self.find_at_with(haystack, start, &Prefilter::new(self))
+    }
+
+    /// Like `find_at`, but skips to where matches can start with the given prefilter of the regex.
+    ///
+    /// Only the positions where the text every match begins with is found are tried as starts, and
+    /// while no match is in progress, `memchr` skips straight to the next of them.
+    fn find_at_with(
+        &self,
+        haystack: &str,
+        start: usize,
+        prefilter: &Prefilter,
+    ) -> Option<Range<usize>> {
+        if !prefilter.may_match(haystack, start) {
+            return None;
+        }
+
+        // the derivatives of the regex with respect to the haystack since each start that can still
+        // match, from the earliest start onwards
+        let mut states: Vec<(Self, usize)> = Vec::new();
+        let mut found: Option<Range<usize>> = None;
+
+        let mut i = start;
+        loop {
+            if states.is_empty() && found.is_none() {
+                i = prefilter.next_start(haystack, i)?;
+            }
+            // once a match is found, later starts cannot give the leftmost match
+            if found.is_none() && self != &Self::Empty && prefilter.can_start_at(haystack, i) {
+                states.push((self.clone(), i));
+            }
+
+            for (state, start) in &states {
+                if state.nullable() {
+                    // the states are ordered by start, so the first nullable one is the leftmost
+                    if found.as_ref().map_or(true, |found| *start <= found.start) {
+                        found = Some(*start..i);
+                    }
+                    break;
+                }
+            }
+            if let Some(found) = &found {
+                states.retain(|(_, start)| *start <= found.start);
+            }
+
+            let Some(c) = haystack[i..].chars().next() else {
+                break;
+            };
+            // a state reached from a later start matches the same continuations, so only the
+            // earliest start is kept for each state
+            let mut seen = HashSet::new();
+            states = states
+                .into_iter()
+                .map(|(state, start)| (state.derivative(c), start))
+                .filter(|(state, _)| *state != Self::Empty && seen.insert(state.clone()))
+                .collect();
+
+            if states.is_empty() && found.is_some() {
+                break;
+            }
+            i += c.len_utf8();
+        }
+
+        found
+    }
+}
+
+/// An iterator over the non-overlapping matches of a regex in a string, returned by
+/// `Regex::find_iter`.
+#[derive(Debug, Clone)]
+pub struct Matches<'r, 'h> {
+    regex: &'r Regex,
+    haystack: &'h str,
+    /// The byte offset the next search starts from.
+    position: usize,
+    /// The byte offset where the previous match ended, if there was one.
+    last_end: Option<usize>,
+    /// The prefilter of the regex, made at the first search and shared by the rest.
+    prefilter: Option<Prefilter>,
+}
+
+impl Iterator for Matches<'_, '_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.position > self.haystack.len() {
+                return None;
+            }
+            let prefilter = self
+                .prefilter
+                .get_or_insert_with(|| Prefilter::new(self.regex));
+            let found = self
+                .regex
+                .find_at_with(self.haystack, self.position, prefilter)?;
+
+            if found.is_empty() {
+                // move past the empty match, so the next search makes progress
+                self.position = found.end
+                    + self.haystack[found.end..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                if self.last_end == Some(found.end) {
+                    continue;
+                }
+            } else {
+                self.position = found.end;
+            }
+
+            self.last_end = Some(found.end);
+            return Some(found);
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{MatchMode, Regex};
+
+    #[allow(dead_code)]
+    fn find_all(pattern: &str, haystack: &str) -> Vec<std::ops::Range<usize>> {
+        Regex::new(pattern).unwrap().find_iter(haystack).collect()
+    }
+
+    #[test]
+    fn test_find() {
+        let regex = Regex::new(r"\d+").unwrap();
+        assert_eq!(regex.find("abc 123 45"), Some(4..7));
+        assert_eq!(regex.find("9"), Some(0..1));
+        assert_eq!(regex.find("abc"), None);
+        assert_eq!(regex.find(""), None);
+
+        // the longest of the leftmost matches
+        let regex = Regex::new("a|ab|abcd").unwrap();
+        assert_eq!(regex.find("xxabcx"), Some(2..4));
+        assert_eq!(regex.find("xxabcdx"), Some(2..6));
+
+        // an earlier start wins even if its match ends later
+        let regex = Regex::new("abcd|bc").unwrap();
+        assert_eq!(regex.find("abcd"), Some(0..4));
+        assert_eq!(regex.find("abce"), Some(1..3));
+    }
+
+    #[test]
+    fn test_find_empty() {
+        let regex = Regex::new("b*").unwrap();
+        assert_eq!(regex.find("aab"), Some(0..0));
+        assert_eq!(regex.find("bba"), Some(0..2));
+        assert_eq!(regex.find(""), Some(0..0));
+
+        assert_eq!(Regex::Empty.find("abc"), None);
+    }
+
+    #[test]
+    fn test_find_unicode() {
+        // ranges are byte offsets
+        let regex = Regex::new("é+").unwrap();
+        assert_eq!(regex.find("💕aéé!"), Some(5..9));
+    }
+
+    #[test]
+    fn test_find_with_literals() {
+        // matches only start where the text every match begins with is
+        let regex = Regex::new(r"id=\d+;").unwrap();
+        let haystack = format!("{}id=x; id=12; id=3;", "id".repeat(50));
+        assert_eq!(
+            regex.find(&haystack),
+            Some(haystack.find("id=12").unwrap()..haystack.len() - 6)
+        );
+        assert_eq!(regex.find_iter(&haystack).count(), 2);
+        assert!(regex.is_match_anywhere(&haystack));
+        assert!(!regex.is_match_anywhere("id=12"));
+
+        // the match must end with `px`
+        let regex = Regex::new(r"\d+px").unwrap();
+        assert_eq!(regex.find("1 22px 3px"), Some(2..6));
+        assert_eq!(regex.find("1 22pt 3pt"), None);
+        assert!(!regex.is_match_at("12px", 3));
+        assert!(!regex.is_match_at("12px", 4));
+
+        let regex = Regex::new("ab(c|d)+é").unwrap();
+        for haystack in ["abcé", "xabdcé", "ababcdé", "abé", "aéé", "ab", "abcdabcé"] {
+            let expected = (0..=haystack.len())
+                .filter(|&start| haystack.is_char_boundary(start))
+                .find_map(|start| regex.find_anchored_at(haystack, start));
+            assert_eq!(regex.find(haystack), expected, "{haystack}");
+        }
+    }
+
+    #[test]
+    fn test_match_prefix() {
+        let regex = Regex::new("[a-z]+").unwrap();
+        assert_eq!(regex.match_prefix("abc123"), Some(3));
+        assert_eq!(regex.match_shortest_prefix("abc123"), Some(1));
+        assert_eq!(regex.match_prefix("abc"), Some(3));
+        assert_eq!(regex.match_prefix("1abc"), None);
+        assert_eq!(regex.match_shortest_prefix("1abc"), None);
+        assert_eq!(regex.match_prefix(""), None);
+
+        // a longer prefix that only begins a match does not count
+        let regex = Regex::new("ab|abcd").unwrap();
+        assert_eq!(regex.match_prefix("abcx"), Some(2));
+        assert_eq!(regex.match_prefix("abcde"), Some(4));
+
+        let regex = Regex::new("é*").unwrap();
+        assert_eq!(regex.match_prefix("ééa"), Some(4));
+        assert_eq!(regex.match_shortest_prefix("ééa"), Some(0));
+    }
+
+    #[test]
+    fn test_find_at() {
+        let regex = Regex::new(r"\d+").unwrap();
+        let haystack = "12 ab 345";
+        assert_eq!(regex.find_at(haystack, 0), Some(0..2));
+        assert_eq!(regex.find_at(haystack, 1), Some(1..2));
+        assert_eq!(regex.find_at(haystack, 2), Some(6..9));
+        assert_eq!(regex.find_at(haystack, 9), None);
+
+        assert!(regex.is_match_at(haystack, 3));
+        assert!(!regex.is_match_at("12 ab", 2));
+        assert!(!regex.is_match_at("12", 2));
+        assert!(Regex::new("x*").unwrap().is_match_at("12", 2));
+
+        assert_eq!(regex.find_anchored_at(haystack, 6), Some(6..9));
+        assert_eq!(regex.find_anchored_at(haystack, 7), Some(7..9));
+        assert_eq!(regex.find_anchored_at(haystack, 2), None);
+
+        // offsets are bytes
+        let regex = Regex::new("é+").unwrap();
+        assert_eq!(regex.find_at("éaéé", 2), Some(3..7));
+        assert_eq!(regex.find_anchored_at("éaéé", 0), Some(0..2));
+    }
+
+    #[test]
+    fn test_find_iter() {
+        assert_eq!(find_all(r"\d+", "a1 23 456b"), vec![1..2, 3..5, 6..9]);
+        assert_eq!(find_all(r"\d+", "abc"), vec![]);
+        // matches do not overlap
+        assert_eq!(find_all("aba", "ababababa"), vec![0..3, 4..7]);
+        assert_eq!(find_all("é|💕", "aé💕b"), vec![1..3, 3..7]);
+
+        let regex = Regex::new("error: [a-z]+").unwrap();
+        let log = "ok\nerror: disk\nok\nerror: net\n";
+        let messages = regex
+            .find_iter(log)
+            .map(|found| &log[found])
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["error: disk", "error: net"]);
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let regex = Regex::new(r"\d+").unwrap();
+        assert_eq!(regex.count_matches("a1 23 456b"), 3);
+        assert_eq!(regex.count_matches("abc"), 0);
+        assert_eq!(Regex::new("aba").unwrap().count_matches("ababababa"), 2);
+        assert_eq!(Regex::new("b*").unwrap().count_matches("abba"), 3);
+
+        assert!(regex.is_match_anywhere("abc 1"));
+        assert!(!regex.is_match_anywhere("abc"));
+        assert!(!regex.is_match_anywhere(""));
+        assert!(Regex::new("x*").unwrap().is_match_anywhere(""));
+    }
+
+    #[test]
+    fn test_find_iter_empty() {
+        assert_eq!(find_all("b*", "abba"), vec![0..0, 1..3, 4..4]);
+        assert_eq!(find_all("b*", ""), vec![0..0]);
+        assert_eq!(find_all("b*", "é"), vec![0..0, 2..2]);
+        assert_eq!(
+            Regex::Epsilon.find_iter("ab").collect::<Vec<_>>(),
+            vec![0..0, 1..1, 2..2]
+        );
+    }
+
+    #[test]
+    fn test_full_and_partial_match() {
+        let regex = Regex::new(r"\d+").unwrap();
+        assert!(regex.is_full_match("123"));
+        assert!(!regex.is_full_match("a123"));
+        assert!(regex.is_partial_match("a123"));
+        assert!(!regex.is_partial_match("abc"));
+
+        let cases = [
+            ("123", [true, true, true]),
+            ("12a", [false, true, true]),
+            ("a12", [false, false, true]),
+            ("", [false, false, false]),
+        ];
+        for (s, expected) in cases {
+            let modes = [MatchMode::Full, MatchMode::Prefix, MatchMode::Partial];
+            for (mode, expected) in modes.into_iter().zip(expected) {
+                assert_eq!(regex.is_match_with(s, mode), expected, "{s} {mode:?}");
+            }
+        }
+
+        // a regex matching the empty string matches part of every string
+        assert!(Regex::new("x*").unwrap().is_partial_match("abc"));
+        assert!(!Regex::new("x*").unwrap().is_full_match("abc"));
+    }
+
+    #[test]
+    #[should_panic(expected = "byte offset 5 is not on a character boundary of the haystack")]
+    fn test_is_match_at_past_end() {
+        Regex::new(r"\d+px").unwrap().is_match_at("12px", 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "byte offset 1 is not on a character boundary of the haystack")]
+    fn test_find_at_inside_char() {
+        Regex::new("a").unwrap().find_at("éa", 1);
+    }
+}
@@ -0,0 +1,172 @@
+//! Searching an unbounded stream of characters for matches as it arrives, for detecting patterns
+//! online (e.g., signatures in network traffic).
+//!
+//! A match can start anywhere, so the searcher takes derivatives of `Σ*r` rather than `r`: its
+//! derivative with respect to the stream so far is nullable exactly when a match of `r` ends at the
+//! current position. Simplification merges the derivatives of the matches still in progress, so
+//! the memory used depends on the regex but not on how much of the stream has been read.
+
+use crate::derivatives::{CharRange, Regex};
+
+/// A search for the matches of a regex in a stream of characters, which is fed to it one
+/// character at a time and reports where matches end.
+///
+/// Matches may overlap, and every position where one ends is reported (e.g., `aa` ends at 2 and 3
+/// in `aaa`). Where they start is not tracked, since that would need memory for every match in
+/// progress.
+#[derive(Debug, Clone)]
+pub struct StreamSearcher {
+    /// `Σ*r`, the regex matching every string that ends with a match of `r`.
+    unanchored: Regex,
+    /// The derivative of `unanchored` with respect to the stream so far.
+    current: Regex,
+    /// The number of bytes fed so far.
+    offset: usize,
+}
+
+impl StreamSearcher {
+    /// Creates a searcher for the regex that has not been fed any input.
+    pub fn new(regex: &Regex) -> Self {
+        let any = Regex::Class(vec![CharRange::Range('\0', char::MAX)]);
+        let unanchored = Regex::concat(any.star(), regex.clone());
+        Self {
+            current: unanchored.clone(),
+            unanchored,
+            offset: 0,
+        }
+    }
+
+    /// Feeds the next character of the stream to the searcher, returning `true` if a match ends
+    /// right after it, otherwise returning `false`.
+    pub fn push(&mut self, c: char) -> bool {
+        // every derivative of ∅ is ∅
+        if self.current != Regex::Empty {
+            self.current = self.current.derivative(c);
+        }
+        self.offset += c.len_utf8();
+        self.is_match()
+    }
+
+    /// Returns `true` if a match ends at the current position (for an empty stream, if the regex
+    /// matches the empty string), otherwise returns `false`.
+    pub fn is_match(&self) -> bool {
+        self.current.nullable()
+    }
+
+    /// Returns the number of bytes of the stream fed so far, which is the byte offset of the end
+    /// of a match reported by `push`.
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Forgets the stream fed so far, as if the searcher had just been created.
+    pub fn reset(&mut self) {
+        self.current = self.unanchored.clone();
+        self.offset = 0;
+    }
+}
+
+/// An iterator over the byte offsets where matches end in a stream of characters, returned by
+/// `Regex::match_ends`.
+#[derive(Debug, Clone)]
+pub struct MatchEnds<I> {
+    searcher: StreamSearcher,
+    chars: I,
+    /// Whether the empty match at the start of the stream has been reported, if there is one.
+    started: bool,
+}
+
+impl<I: Iterator<Item = char>> Iterator for MatchEnds<I> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if self.searcher.is_match() {
+                return Some(0);
+            }
+        }
+
+        for c in self.chars.by_ref() {
+            if self.searcher.push(c) {
+                return Some(self.searcher.offset());
+            }
+        }
+        None
+    }
+}
+
+impl Regex {
+    /// Returns an iterator over the byte offsets where matches of the regex end in the stream of
+    /// characters, which may be unbounded. Each offset is yielded as soon as the character before
+    /// it has been read. See `StreamSearcher`.
+    pub fn match_ends<I: IntoIterator<Item = char>>(&self, chars: I) -> MatchEnds<I::IntoIter> {
+        MatchEnds {
+            searcher: StreamSearcher::new(self),
+            chars: chars.into_iter(),
+            started: false,
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Regex, StreamSearcher};
+
+    #[test]
+    fn test_stream_searcher() {
+        let regex = Regex::new(r"GET /\w+").unwrap();
+        let mut searcher = StreamSearcher::new(&regex);
+        assert!(!searcher.is_match());
+
+        let mut ends = Vec::new();
+        for c in "xGET /a GET /bc".chars() {
+            if searcher.push(c) {
+                ends.push(searcher.offset());
+            }
+        }
+        assert_eq!(ends, vec![7, 14, 15]);
+        assert_eq!(searcher.offset(), 15);
+
+        searcher.reset();
+        assert_eq!(searcher.offset(), 0);
+        assert!(!searcher.push('/'));
+    }
+
+    #[test]
+    fn test_match_ends() {
+        let regex = Regex::new("aa").unwrap();
+        assert_eq!(
+            regex.match_ends("aaa".chars()).collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert_eq!(regex.match_ends("".chars()).count(), 0);
+
+        let regex = Regex::new("é?").unwrap();
+        assert_eq!(
+            regex.match_ends("aé".chars()).collect::<Vec<_>>(),
+            vec![0, 1, 3]
+        );
+
+        assert_eq!(Regex::Empty.match_ends("abc".chars()).count(), 0);
+    }
+
+    #[test]
+    fn test_stream_searcher_memory_is_bounded() {
+        let regex = Regex::new("ab(c|d)*e").unwrap();
+        let mut searcher = StreamSearcher::new(&regex);
+        let mut largest = 0;
+        for c in "xabcdcab".chars().cycle().take(4000) {
+            searcher.push(c);
+            largest = largest.max(searcher.current.node_count());
+        }
+        assert!(largest < 50, "{largest}");
+
+        // an infinite stream is searched lazily
+        let stream = "..abe".chars().cycle();
+        assert_eq!(
+            regex.match_ends(stream).take(3).collect::<Vec<_>>(),
+            vec![5, 10, 15]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Regexes over keys made of segments (e.g., config paths or message topics like `sensor/1/temp`),
+//! where each segment is treated as a single character.
+//!
+//! A `SegmentAlphabet` assigns a private-use character to every segment that appears in a pattern,
+//! so the usual derivative engine can match keys against patterns and compare patterns with each
+//! other, as long as all of them are built with the same alphabet.
+//!
+//! MQTT topic filters (e.g., `sensor/+/temp/#`) can be converted with `Regex::from_mqtt_filter` and
+//! checked for overlap with `filters_overlap`.
+
+use crate::derivatives::{CharRange, Regex};
+use std::collections::BTreeMap;
+
+/// The character assigned to the first segment.
+const FIRST_SEGMENT_CHAR: char = '\u{F0000}';
+
+/// The last character that can be assigned to a segment.
+const LAST_SEGMENT_CHAR: char = '\u{FFFFF}';
+
+/// The character assigned to the first system segment (one starting with `$`).
+const FIRST_SYSTEM_SEGMENT_CHAR: char = '\u{100000}';
+
+/// The last character that can be assigned to a system segment.
+const LAST_SYSTEM_SEGMENT_CHAR: char = '\u{10FFFE}';
+
+/// The character that stands for any segment that does not appear in a pattern.
+const OTHER_SEGMENT_CHAR: char = '\0';
+
+/// The character that stands for any system segment that does not appear in a pattern.
+const OTHER_SYSTEM_SEGMENT_CHAR: char = char::MAX;
+
+/// The MQTT wildcard that matches any single topic level.
+const MQTT_SINGLE_LEVEL: &str = "+";
+
+/// The MQTT wildcard that matches the parent level and any number of levels below it.
+const MQTT_MULTI_LEVEL: &str = "#";
+
+/// The pattern segment that matches any single segment.
+pub const ANY_SEGMENT: &str = "*";
+
+/// The pattern segment that matches any number of segments, including none.
+pub const ANY_SEGMENTS: &str = "**";
+
+/// Returns `true` if the segment is a system segment (e.g., `$SYS`), which MQTT wildcards at the
+/// start of a filter do not match, otherwise returns `false`.
+fn is_system_segment(segment: &str) -> bool {
+    segment.starts_with('$')
+}
+
+/// A mapping from segments to the characters that stand for them in segment regexes.
+///
+/// System segments (those starting with `$`) are assigned characters from a separate range, so
+/// that patterns can match any segment except them.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentAlphabet {
+    chars: BTreeMap<String, char>,
+    system_segments: usize,
+}
+
+impl SegmentAlphabet {
+    /// Creates an empty alphabet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct segments in the alphabet.
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    /// Returns `true` if no segments have been added to the alphabet, otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Returns the character for the given segment, adding it to the alphabet if needed.
+    fn intern(&mut self, segment: &str) -> Result<char, String> {
+        if let Some(c) = self.chars.get(segment) {
+            return Ok(*c);
+        }
+
+        let system = is_system_segment(segment);
+        let (first, last, offset) = if system {
+            (
+                FIRST_SYSTEM_SEGMENT_CHAR,
+                LAST_SYSTEM_SEGMENT_CHAR,
+                self.system_segments,
+            )
+        } else {
+            (
+                FIRST_SEGMENT_CHAR,
+                LAST_SEGMENT_CHAR,
+                self.chars.len() - self.system_segments,
+            )
+        };
+        let c = u32::try_from(offset)
+            .ok()
+            .and_then(|offset| char::from_u32(first as u32 + offset))
+            .filter(|c| *c <= last)
+            .ok_or_else(|| "Too many distinct segments in the alphabet".to_string())?;
+
+        self.chars.insert(segment.to_string(), c);
+        if system {
+            self.system_segments += 1;
+        }
+        Ok(c)
+    }
+
+    /// Returns a regex that matches any single segment.
+    pub fn any_segment() -> Regex {
+        Regex::Class(vec![CharRange::Range('\0', char::MAX)])
+    }
+
+    /// Returns a regex that matches any single segment except system segments (those starting with
+    /// `$`).
+    pub fn any_non_system_segment() -> Regex {
+        Regex::Class(vec![CharRange::Range('\0', LAST_SEGMENT_CHAR)])
+    }
+
+    /// Returns a regex that matches a key of the given segments, where `*` matches any single
+    /// segment and `**` matches any number of segments (e.g., `["config", "*", "port"]`).
+    pub fn pattern(&mut self, segments: &[&str]) -> Result<Regex, String> {
+        let factors = segments
+            .iter()
+            .map(|segment| match *segment {
+                ANY_SEGMENT => Ok(Self::any_segment()),
+                ANY_SEGMENTS => Ok(Self::any_segment().star()),
+                literal => self.intern(literal).map(Regex::literal),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Regex::concat_all(factors))
+    }
+
+    /// Returns the string that stands for the given key, in which segments that are not in the
+    /// alphabet are only matched by wildcards.
+    pub fn key(&self, segments: &[&str]) -> String {
+        segments
+            .iter()
+            .map(|segment| {
+                self.chars.get(*segment).copied().unwrap_or_else(|| {
+                    if is_system_segment(segment) {
+                        OTHER_SYSTEM_SEGMENT_CHAR
+                    } else {
+                        OTHER_SEGMENT_CHAR
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the regex, which must have been built with this alphabet, matches the
+    /// given key, otherwise returns `false`.
+    pub fn matches(&self, regex: &Regex, segments: &[&str]) -> bool {
+        regex.matches(&self.key(segments))
+    }
+
+    /// Returns `true` if the regex, which must have been built with this alphabet, matches the
+    /// given MQTT topic (e.g., `sensor/kitchen/temp`), otherwise returns `false`.
+    pub fn matches_topic(&self, regex: &Regex, topic: &str) -> bool {
+        self.matches(regex, &topic.split('/').collect::<Vec<_>>())
+    }
+}
+
+impl Regex {
+    /// Converts an MQTT topic filter (e.g., `sensor/+/temp/#`) into a regex over its levels, using
+    /// the given alphabet for the levels it names.
+    ///
+    /// As in MQTT, `+` matches any single level, `#` matches the parent level and any number of
+    /// levels below it, and a wildcard at the start of a filter does not match system levels (those
+    /// starting with `$`, e.g., `$SYS`).
+    pub fn from_mqtt_filter(filter: &str, alphabet: &mut SegmentAlphabet) -> Result<Self, String> {
+        if filter.is_empty() {
+            return Err("Empty MQTT filter not allowed".to_string());
+        }
+
+        let levels = filter.split('/').collect::<Vec<_>>();
+        let mut factors = Vec::new();
+        for (i, level) in levels.iter().enumerate() {
+            let any_level = if i == 0 {
+                SegmentAlphabet::any_non_system_segment()
+            } else {
+                SegmentAlphabet::any_segment()
+            };
+
+            let factor = match *level {
+                MQTT_MULTI_LEVEL if i + 1 == levels.len() => {
+                    // The parent level is already matched, so `#` may also match nothing
+                    (any_level + SegmentAlphabet::any_segment().star()).optional()
+                }
+                MQTT_SINGLE_LEVEL => any_level,
+                level if level.contains(MQTT_MULTI_LEVEL) || level.contains(MQTT_SINGLE_LEVEL) => {
+                    return Err(format!(
+                        "Invalid MQTT filter {filter:?}: wildcards must take up a whole level, and `#` must be the last level"
+                    ));
+                }
+                level => Self::literal(alphabet.intern(level)?),
+            };
+            factors.push(factor);
+        }
+
+        Ok(Self::concat_all(factors))
+    }
+}
+
+/// Returns `true` if some topic is matched by both MQTT filters (e.g., `sensor/+/temp` and
+/// `sensor/kitchen/#`), otherwise returns `false`.
+pub fn filters_overlap(a: &str, b: &str) -> Result<bool, String> {
+    let mut alphabet = SegmentAlphabet::new();
+    let a = Regex::from_mqtt_filter(a, &mut alphabet)?;
+    let b = Regex::from_mqtt_filter(b, &mut alphabet)?;
+    Ok(a.intersects(&b))
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[test]
+    fn test_segment_pattern_literals() {
+        let mut alphabet = SegmentAlphabet::new();
+        let regex = alphabet.pattern(&["config", "server", "port"]).unwrap();
+        assert_eq!(alphabet.len(), 3);
+
+        assert!(alphabet.matches(&regex, &["config", "server", "port"]));
+        assert!(!alphabet.matches(&regex, &["config", "server"]));
+        assert!(!alphabet.matches(&regex, &["config", "client", "port"]));
+        assert!(!alphabet.matches(&regex, &["configserverport"]));
+    }
+
+    #[test]
+    fn test_segment_pattern_wildcards() {
+        let mut alphabet = SegmentAlphabet::new();
+        let one = alphabet.pattern(&["sensor", "*", "temp"]).unwrap();
+        let many = alphabet.pattern(&["sensor", "**"]).unwrap();
+
+        assert!(alphabet.matches(&one, &["sensor", "kitchen", "temp"]));
+        assert!(alphabet.matches(&one, &["sensor", "sensor", "temp"]));
+        assert!(!alphabet.matches(&one, &["sensor", "temp"]));
+        assert!(!alphabet.matches(&one, &["sensor", "a", "b", "temp"]));
+
+        assert!(alphabet.matches(&many, &["sensor"]));
+        assert!(alphabet.matches(&many, &["sensor", "a", "b", "temp"]));
+        assert!(!alphabet.matches(&many, &["actuator", "a"]));
+    }
+
+    #[test]
+    fn test_segment_pattern_shared_alphabet() {
+        let mut alphabet = SegmentAlphabet::new();
+        let first = alphabet.pattern(&["a", "*"]).unwrap();
+        let second = alphabet.pattern(&["*", "b"]).unwrap();
+        assert_eq!(alphabet.len(), 2);
+
+        // Both patterns match a/b, so the derivatives by its key are both nullable
+        let key = alphabet.key(&["a", "b"]);
+        assert!(first.derivative_str(&key).nullable());
+        assert!(second.derivative_str(&key).nullable());
+    }
+
+    #[test]
+    fn test_mqtt_filter() {
+        let mut alphabet = SegmentAlphabet::new();
+        let regex = Regex::from_mqtt_filter("sensor/+/temp/#", &mut alphabet).unwrap();
+
+        assert!(alphabet.matches_topic(&regex, "sensor/kitchen/temp"));
+        assert!(alphabet.matches_topic(&regex, "sensor/kitchen/temp/celsius"));
+        assert!(alphabet.matches_topic(&regex, "sensor/kitchen/temp/a/b"));
+        assert!(alphabet.matches_topic(&regex, "sensor//temp"));
+        assert!(!alphabet.matches_topic(&regex, "sensor/temp"));
+        assert!(!alphabet.matches_topic(&regex, "sensor/kitchen/humidity"));
+        assert!(!alphabet.matches_topic(&regex, "actuator/kitchen/temp"));
+    }
+
+    #[test]
+    fn test_mqtt_filter_system_topics() {
+        let mut alphabet = SegmentAlphabet::new();
+        let everything = Regex::from_mqtt_filter("#", &mut alphabet).unwrap();
+        let any_first = Regex::from_mqtt_filter("+/info", &mut alphabet).unwrap();
+        let system = Regex::from_mqtt_filter("$SYS/#", &mut alphabet).unwrap();
+
+        assert!(alphabet.matches_topic(&everything, "a/b"));
+        assert!(!alphabet.matches_topic(&everything, "$SYS/info"));
+        assert!(!alphabet.matches_topic(&any_first, "$SYS/info"));
+        assert!(!alphabet.matches_topic(&any_first, "$other/info"));
+        assert!(alphabet.matches_topic(&any_first, "broker/info"));
+        assert!(alphabet.matches_topic(&system, "$SYS/info"));
+    }
+
+    #[test]
+    fn test_mqtt_filter_invalid() {
+        let mut alphabet = SegmentAlphabet::new();
+        for filter in ["", "a/#/b", "a/b#", "a+/b"] {
+            assert!(
+                Regex::from_mqtt_filter(filter, &mut alphabet).is_err(),
+                "{filter}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_filters_overlap() {
+        assert_eq!(
+            filters_overlap("sensor/+/temp", "sensor/kitchen/#"),
+            Ok(true)
+        );
+        assert_eq!(filters_overlap("sensor/#", "sensor"), Ok(true));
+        assert_eq!(filters_overlap("+/+", "a/b/c"), Ok(false));
+        assert_eq!(
+            filters_overlap("sensor/+/temp", "sensor/+/humidity"),
+            Ok(false)
+        );
+        assert_eq!(filters_overlap("#", "$SYS/#"), Ok(false));
+        assert_eq!(filters_overlap("$SYS/+", "$SYS/#"), Ok(true));
+        assert!(filters_overlap("a/#/b", "a").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Serializes a `Regex` as its pattern string and deserializes it by parsing, for configs that are
+//! edited by hand. Use it with `#[serde(with = "rzozowski::serde_pattern")]`.
+//!
+//! Only regexes that can be written as a pattern can be serialized. `∅`, `ε`, predicates and shuffles
+//! have no pattern syntax, so serializing a regex that contains any of them fails. The parser never
+//! produces them, so any regex created with `Regex::new` can be serialized and deserializes to an
+//! equal regex.
+
+use crate::derivatives::Regex;
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde::ser::{Error as _, Serializer};
+
+/// Returns a description of the first construct in the regex that has no pattern syntax, if any.
+fn first_ast_only(regex: &Regex) -> Option<&'static str> {
+    match regex {
+        Regex::Empty => Some("∅"),
+        Regex::Epsilon => Some("ε"),
+        Regex::Pred(_) => Some("a predicate"),
+        Regex::Shuffle(_, _) => Some("a shuffle"),
+        Regex::Literal(_) | Regex::Str(_) | Regex::Class(_) => None,
+        Regex::Concat(left, right) | Regex::Or(left, right) => {
+            first_ast_only(left).or_else(|| first_ast_only(right))
+        }
+        Regex::Count(inner, _) => first_ast_only(inner),
+    }
+}
+
+/// Serializes the regex as its pattern string.
+pub fn serialize<S: Serializer>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error> {
+    if let Some(construct) = first_ast_only(regex) {
+        return Err(S::Error::custom(format!(
+            "Regex {regex} cannot be serialized as a pattern because it contains {construct}"
+        )));
+    }
+
+    serializer.collect_str(regex)
+}
+
+/// Deserializes a regex by parsing a pattern string.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(D::Error::custom)
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::Regex;
+    #[allow(unused_imports)]
+    use crate::derivatives::Node;
+
+    // serde's derive macros are only enabled in the dev-dependencies
+    #[cfg(test)]
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Config {
+        #[serde(with = "crate::serde_pattern")]
+        pattern: Regex,
+    }
+
+    #[test]
+    fn test_serde_pattern_round_trip() {
+        for pattern in [
+            "abc",
+            "a|b",
+            r"[a-z\-]+",
+            r"\d{3,6}",
+            "(ab)*c?",
+            r"a\.b\(c\)",
+            r"[a-zA-Z0-9._%+\-]+@[a-zA-Z0-9.\-]+\.[a-zA-Z]{2,}",
+        ] {
+            let config = Config {
+                pattern: Regex::new(pattern).unwrap(),
+            };
+            let json = serde_json::to_string(&config).unwrap();
+            let deserialized: Config = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized.pattern, config.pattern, "{pattern}: {json}");
+        }
+    }
+
+    #[test]
+    fn test_serde_pattern_deserialize() {
+        let config: Config = serde_json::from_str(r#"{"pattern": "a+b"}"#).unwrap();
+        assert!(config.pattern.matches("aab"));
+
+        assert!(serde_json::from_str::<Config>(r#"{"pattern": "a{"}"#).is_err());
+        assert!(serde_json::from_str::<Config>(r#"{"pattern": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_serde_pattern_rejects_ast_only_constructs() {
+        for regex in [
+            Regex::Empty,
+            Regex::Concat(Node::new(Regex::literal('a')), Node::new(Regex::Epsilon)),
+            Regex::pred("digit", |c: char| c.is_ascii_digit()),
+            Regex::literal('a').shuffle(&Regex::literal('b')),
+        ] {
+            assert!(serde_json::to_string(&Config { pattern: regex }).is_err());
+        }
+    }
+}
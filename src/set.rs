@@ -0,0 +1,238 @@
+//! Matching a string against many regexes at once.
+
+use crate::derivatives::Regex;
+use std::collections::HashMap;
+
+/// A collection of regexes that a string is matched against in a single pass, finding which of
+/// them match it.
+///
+/// Matching takes the derivatives of every regex that can still match with respect to each
+/// character in turn, so the string is only read once. Regexes whose derivatives are the same (e.g.,
+/// `ab` and `(a)b`, or `a[bc]` and `a[bd]` after `ab`) share the work, and a regex is dropped as
+/// soon as its derivative is `∅`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexSet {
+    regexes: Vec<Regex>,
+}
+
+impl RegexSet {
+    /// Tries to parse each pattern into a `Regex`, in order.
+    pub fn new<I, S>(patterns: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let regexes = patterns
+            .into_iter()
+            .enumerate()
+            .map(|(i, pattern)| {
+                Regex::new(pattern.as_ref())
+                    .map_err(|error| format!("Invalid pattern {i}: {error}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { regexes })
+    }
+
+    /// Returns a set of the given regexes, in order.
+    pub fn from_regexes(regexes: impl IntoIterator<Item = Regex>) -> Self {
+        Self {
+            regexes: regexes.into_iter().collect(),
+        }
+    }
+
+    /// Returns the regexes in the set, in order.
+    pub fn regexes(&self) -> &[Regex] {
+        &self.regexes
+    }
+
+    /// Returns the number of regexes in the set.
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    /// Returns `true` if the set has no regexes, otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// Returns which regexes in the set match the whole string.
+    pub fn matches(&self, s: &str) -> SetMatches {
+        let mut matched = vec![false; self.regexes.len()];
+        for index in self.matching_indices(s) {
+            matched[index] = true;
+        }
+        SetMatches { matched }
+    }
+
+    /// Returns `true` if any regex in the set matches the whole string, otherwise returns `false`.
+    pub fn is_match(&self, s: &str) -> bool {
+        !self.matching_indices(s).is_empty()
+    }
+
+    /// Returns the indices of the regexes that match the whole string, in order.
+    ///
+    /// The derivatives of the regexes that can still match are kept as a list of distinct
+    /// derivatives, each with the indices of the regexes that reached it, and reading stops once
+    /// the list is empty.
+    fn matching_indices(&self, s: &str) -> Vec<usize> {
+        let mut states = initial_states(&self.regexes);
+        for c in s.chars() {
+            if states.is_empty() {
+                return Vec::new();
+            }
+            states = next_states(states, c);
+        }
+
+        let mut matched: Vec<usize> = states
+            .into_iter()
+            .filter(|(state, _)| state.nullable())
+            .flat_map(|(_, indices)| indices)
+            .collect();
+        matched.sort_unstable();
+        matched
+    }
+}
+
+/// Returns the distinct regexes that are not `∅`, each with the indices of the regexes equal to it.
+pub(crate) fn initial_states<'a>(
+    regexes: impl IntoIterator<Item = &'a Regex>,
+) -> Vec<(Regex, Vec<usize>)> {
+    let mut states: Vec<(Regex, Vec<usize>)> = Vec::new();
+    let mut ids: HashMap<&Regex, usize> = HashMap::new();
+    for (index, regex) in regexes.into_iter().enumerate() {
+        if *regex == Regex::Empty {
+            continue;
+        }
+        match ids.get(regex) {
+            Some(&id) => states[id].1.push(index),
+            None => {
+                ids.insert(regex, states.len());
+                states.push((regex.clone(), vec![index]));
+            }
+        }
+    }
+    states
+}
+
+/// Returns the distinct derivatives of the states with respect to the character that are not `∅`,
+/// each with the indices of the regexes that reached it.
+pub(crate) fn next_states(states: Vec<(Regex, Vec<usize>)>, c: char) -> Vec<(Regex, Vec<usize>)> {
+    let mut next: Vec<(Regex, Vec<usize>)> = Vec::new();
+    let mut ids: HashMap<Regex, usize> = HashMap::new();
+    for (state, indices) in states {
+        let derivative = state.derivative(c);
+        if derivative == Regex::Empty {
+            continue;
+        }
+        match ids.get(&derivative) {
+            Some(&id) => next[id].1.extend(indices),
+            None => {
+                ids.insert(derivative.clone(), next.len());
+                next.push((derivative, indices));
+            }
+        }
+    }
+    next
+}
+
+/// Which regexes of a `RegexSet` matched a string, returned by `RegexSet::matches`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetMatches {
+    matched: Vec<bool>,
+}
+
+impl SetMatches {
+    /// Returns `true` if any regex matched, otherwise returns `false`.
+    pub fn matched_any(&self) -> bool {
+        self.matched.contains(&true)
+    }
+
+    /// Returns `true` if the regex at the given index matched, otherwise returns `false`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is not less than the number of regexes in the set.
+    pub fn matched(&self, index: usize) -> bool {
+        self.matched[index]
+    }
+
+    /// Returns the indices of the regexes that matched, in order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.matched
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &matched)| matched.then_some(index))
+    }
+
+    /// Returns the number of regexes that matched.
+    pub fn len(&self) -> usize {
+        self.matched.iter().filter(|&&matched| matched).count()
+    }
+
+    /// Returns `true` if no regex matched, otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        !self.matched_any()
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Regex, RegexSet};
+
+    #[test]
+    fn test_regex_set_matches() {
+        let set = RegexSet::new([r"\d+", r"[a-z]+", r"[a-z0-9]+", "abc", "(a)bc"]).unwrap();
+        assert_eq!(set.len(), 5);
+
+        let matches = set.matches("abc");
+        assert_eq!(matches.iter().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert!(matches.matched(3));
+        assert!(!matches.matched(0));
+        assert_eq!(matches.len(), 4);
+
+        assert_eq!(set.matches("123").iter().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(set.matches("a1").iter().collect::<Vec<_>>(), vec![2]);
+        assert!(set.matches("A").is_empty());
+        assert!(!set.matches("").matched_any());
+    }
+
+    #[test]
+    fn test_regex_set_agrees_with_regexes() {
+        let patterns = ["a*", "(ab)*", "a|b", "[ab]{2,3}", "b.*", ".*a"];
+        let set = RegexSet::new(patterns).unwrap();
+        for s in ["", "a", "b", "ab", "aa", "abab", "ba", "bba", "abc", "aaaa"] {
+            let expected = set
+                .regexes()
+                .iter()
+                .enumerate()
+                .filter_map(|(index, regex)| regex.matches(s).then_some(index))
+                .collect::<Vec<_>>();
+            assert_eq!(set.matches(s).iter().collect::<Vec<_>>(), expected, "{s}");
+            assert_eq!(set.is_match(s), !expected.is_empty(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_regex_set_is_match() {
+        let set = RegexSet::new(["a.*", "ab"]).unwrap();
+        assert!(set.is_match("abc"));
+        assert!(set.is_match("ab"));
+        assert!(!set.is_match("b"));
+
+        let set = RegexSet::from_regexes([Regex::Empty, Regex::Epsilon]);
+        assert!(set.is_match(""));
+        assert!(!set.is_match("a"));
+        assert!(!RegexSet::from_regexes([]).is_match(""));
+    }
+
+    #[test]
+    fn test_regex_set_errors() {
+        assert_eq!(
+            RegexSet::new(["a", "b"]).unwrap().regexes(),
+            &[Regex::literal('a'), Regex::literal('b')]
+        );
+        assert!(RegexSet::new(["a", "(b"])
+            .unwrap_err()
+            .starts_with("Invalid pattern 1: "));
+    }
+}
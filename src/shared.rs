@@ -0,0 +1,170 @@
+//! Caching the derivatives of a regex in one cache that many threads match with at once, so that
+//! each transition is only calculated once for the whole process rather than once per thread.
+
+use crate::derivatives::Regex;
+use crate::minterms::Minterms;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// A matcher for a regex whose cache of derivatives is shared by every thread that uses it (with the
+/// `concurrent-cache` feature).
+///
+/// Like `Regex::matches`, each distinct derivative (a state) is numbered as it is found and the
+/// transitions between them are cached per class of characters the regex treats the same, but the
+/// cache lives as long as the matcher and is `Sync`, so it can be put in a `static` or an `Arc` and
+/// shared by threads matching with the same pattern. Transitions are looked up under read locks,
+/// which do not block each other, and only adding a new state takes a write lock.
+#[derive(Debug)]
+pub struct SharedMatcher {
+    /// The states in the order they were found, and whether each is nullable.
+    states: RwLock<Vec<(Arc<Regex>, bool)>>,
+    /// The number of each state.
+    ids: DashMap<Arc<Regex>, usize>,
+    /// The classes of characters, or `None` if the regex has predicates.
+    minterms: Option<Minterms>,
+    /// The state reached from a state by a class (or a character, without classes).
+    transitions: DashMap<(usize, u32), usize>,
+}
+
+impl SharedMatcher {
+    /// The state of the regex itself.
+    const START: usize = 0;
+
+    /// Creates a matcher for the regex with an empty cache.
+    pub fn new(regex: &Regex) -> Self {
+        let minterms = (!regex.has_predicate()).then(|| Minterms::new(&[regex]));
+        let start = Arc::new(regex.clone());
+        Self {
+            states: RwLock::new(vec![(Arc::clone(&start), regex.nullable())]),
+            ids: DashMap::from_iter([(start, Self::START)]),
+            minterms,
+            transitions: DashMap::new(),
+        }
+    }
+
+    /// Returns the state with the given number and whether it is nullable.
+    fn state(&self, id: usize) -> (Arc<Regex>, bool) {
+        let states = self.states.read().unwrap_or_else(PoisonError::into_inner);
+        let (state, nullable) = &states[id];
+        (Arc::clone(state), *nullable)
+    }
+
+    /// Returns the number of the given state, adding it if it is new.
+    fn state_id(&self, regex: Regex) -> usize {
+        if let Some(id) = self.ids.get(&regex) {
+            return *id;
+        }
+
+        // another thread may have added the same state since, so it is checked again while no other
+        // state can be added
+        let mut states = self.states.write().unwrap_or_else(PoisonError::into_inner);
+        match self.ids.entry(Arc::new(regex)) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let id = states.len();
+                states.push((Arc::clone(entry.key()), entry.key().nullable()));
+                entry.insert(id);
+                id
+            }
+        }
+    }
+
+    /// Returns the state reached from `state` by the character `c`.
+    fn next_state(&self, state: usize, c: char) -> usize {
+        let key = self.minterms.as_ref().map_or(u32::from(c), |minterms| {
+            u32::try_from(minterms.class_of(c)).unwrap_or(u32::MAX)
+        });
+        if let Some(next) = self.transitions.get(&(state, key)) {
+            return *next;
+        }
+
+        // the derivative is taken without holding any lock, so other threads are not kept waiting
+        // (two threads may take the same one, and both get the same state for it)
+        let (regex, _) = self.state(state);
+        let derivative = if state == Self::START {
+            regex.derivative(c)
+        } else {
+            regex.derivative_of_simplified(c)
+        };
+        let next = self.state_id(derivative);
+        self.transitions.insert((state, key), next);
+        next
+    }
+
+    /// Returns `true` if the regex matches the given string, otherwise returns `false`.
+    pub fn matches(&self, s: &str) -> bool {
+        let mut state = Self::START;
+        let (mut regex, mut nullable) = self.state(state);
+        for c in s.chars() {
+            if *regex == Regex::Empty {
+                return false;
+            }
+            let next = self.next_state(state, c);
+            if next != state {
+                state = next;
+                (regex, nullable) = self.state(state);
+            }
+        }
+        nullable
+    }
+
+    /// Returns the number of distinct derivatives found so far by every thread.
+    pub fn state_count(&self) -> usize {
+        self.states
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Regex, SharedMatcher};
+    #[allow(unused_imports)]
+    use std::thread;
+
+    #[test]
+    fn test_shared_matcher() {
+        let regex = Regex::new(r"[a-z]+\d{2}").unwrap();
+        let matcher = SharedMatcher::new(&regex);
+        for s in ["ab12", "abc12", "a1", "12", "", "zz99", "zz999", "a b12"] {
+            assert_eq!(matcher.matches(s), regex.matches(s), "{s}");
+        }
+
+        // the derivatives are shared between strings
+        let states = matcher.state_count();
+        assert!(matcher.matches("ba21"));
+        assert_eq!(matcher.state_count(), states);
+
+        let vowel = Regex::pred("vowel", |c| "aeiou".contains(c));
+        let matcher = SharedMatcher::new(&Regex::concat(vowel.star(), Regex::literal('x')));
+        assert!(matcher.matches("aeix"));
+        assert!(!matcher.matches("abx"));
+    }
+
+    #[test]
+    fn test_shared_matcher_threads() {
+        let regex = Regex::new(r"(\w+\s)*\w+|\d{3}-\d{4}").unwrap();
+        let matcher = SharedMatcher::new(&regex);
+        let inputs = ["the quick brown fox", "555-1234", "555-12345", "a b  c", ""];
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        for s in inputs {
+                            assert_eq!(matcher.matches(s), regex.matches(s), "{s}");
+                        }
+                    }
+                });
+            }
+        });
+
+        // every thread found the same states, and each was only added once
+        let alone = SharedMatcher::new(&regex);
+        for s in inputs {
+            alone.matches(s);
+        }
+        assert_eq!(matcher.state_count(), alone.state_count());
+    }
+}
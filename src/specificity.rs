@@ -0,0 +1,87 @@
+//! Ordering patterns from most to least specific, for rule engines that apply the most specific
+//! matching rule first.
+//!
+//! A pattern is more specific than another if every string it matches is also matched by the other
+//! one (e.g., `abc` is more specific than `ab.`, which is more specific than `a.*`).
+
+use crate::derivatives::Regex;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Returns the indices of the patterns ordered so that each pattern comes before every pattern that
+/// matches a strict superset of what it matches.
+///
+/// Patterns that are not ordered by inclusion, including patterns that match exactly the same
+/// strings, are kept in the order they are given, so the result is deterministic.
+pub fn sort_by_specificity(patterns: &[Regex]) -> Vec<usize> {
+    let n = patterns.len();
+    // `subsets[i][j]` is whether pattern `i` matches a subset of what pattern `j` matches
+    let subsets = patterns
+        .iter()
+        .map(|pattern| {
+            patterns
+                .iter()
+                .map(|other| pattern.is_subset_of(other))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // pattern `j` must wait for every pattern `i` that matches a strict subset of what it matches
+    let mut successors = vec![Vec::new(); n];
+    let mut waiting_for = vec![0; n];
+    for i in 0..n {
+        for j in 0..n {
+            if subsets[i][j] && !subsets[j][i] {
+                successors[i].push(j);
+                waiting_for[j] += 1;
+            }
+        }
+    }
+
+    // inclusion is transitive, so this never leaves a pattern waiting forever
+    let mut ready = (0..n)
+        .filter(|&i| waiting_for[i] == 0)
+        .map(Reverse)
+        .collect::<BinaryHeap<_>>();
+    let mut order = Vec::with_capacity(n);
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(i);
+        for &j in &successors[i] {
+            waiting_for[j] -= 1;
+            if waiting_for[j] == 0 {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+
+    order
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{sort_by_specificity, Regex};
+
+    #[allow(dead_code)]
+    fn sort(patterns: &[&str]) -> Vec<usize> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect::<Vec<_>>();
+        sort_by_specificity(&patterns)
+    }
+
+    #[test]
+    fn test_sort_by_specificity() {
+        assert_eq!(sort(&["a.*", "abc", ".*", "ab."]), vec![1, 3, 0, 2]);
+        assert_eq!(sort(&["[0-9]+", "x", "42"]), vec![1, 2, 0]);
+        assert_eq!(sort(&[]), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_sort_by_specificity_unordered() {
+        // unrelated and equivalent patterns keep their order
+        assert_eq!(sort(&["b", "a"]), vec![0, 1]);
+        assert_eq!(sort(&["a+", "aa*", "a"]), vec![2, 0, 1]);
+        assert_eq!(sort(&["x|y", "[a-z]", "y|x"]), vec![0, 2, 1]);
+    }
+}
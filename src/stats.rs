@@ -0,0 +1,186 @@
+//! Counters for the work done while taking derivatives and simplifying, which unlike wall time are
+//! stable across machines.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Statistics about the work done while matching a string, returned by `Regex::matches_counted`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalStats {
+    /// The number of characters the regex was differentiated with respect to.
+    pub derivative_steps: usize,
+    /// The number of calls to the derivative, including recursive calls on subexpressions.
+    pub derivative_calls: usize,
+    /// The number of calls to simplification, including recursive calls on subexpressions.
+    pub simplify_calls: usize,
+    /// The total number of nodes in the derivatives built at each step.
+    pub nodes_allocated: usize,
+    /// The largest number of nodes in the regex or any of its derivatives.
+    pub max_size: usize,
+}
+
+/// Statistics about matching a string, including timings and how often derivatives were reused,
+/// returned by `Regex::matches_with_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// The counts of the work done, as returned by `Regex::matches_counted`.
+    pub counts: EvalStats,
+    /// The time spent simplifying derivatives.
+    pub simplify_time: Duration,
+    /// The number of steps whose derivative had already been taken earlier in the string (e.g.,
+    /// every `a` after the first for `a*`).
+    pub cache_hits: usize,
+    /// The number of steps whose derivative had to be taken.
+    pub cache_misses: usize,
+}
+
+impl MatchStats {
+    /// Returns the fraction of steps whose derivative was reused, or 0 if no steps were taken.
+    pub fn cache_hit_rate(&self) -> f64 {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            0.0
+        } else {
+            self.cache_hits as f64 / lookups as f64
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Calls {
+    derivative: usize,
+    simplify: usize,
+}
+
+thread_local! {
+    static CALLS: Cell<Calls> = const {
+        Cell::new(Calls {
+            derivative: 0,
+            simplify: 0,
+        })
+    };
+}
+
+thread_local! {
+    /// The time spent simplifying so far, if it is being measured.
+    static SIMPLIFY_TIME: Cell<Option<Duration>> = const { Cell::new(None) };
+    /// Whether a simplification is being timed, so nested ones are not counted twice.
+    static TIMING_SIMPLIFY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs the simplification `f`, adding the time it takes to the total if simplification is being
+/// timed on this thread.
+pub fn time_simplify<T>(f: impl FnOnce() -> T) -> T {
+    if TIMING_SIMPLIFY.get() || SIMPLIFY_TIME.get().is_none() {
+        return f();
+    }
+
+    TIMING_SIMPLIFY.set(true);
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    TIMING_SIMPLIFY.set(false);
+    SIMPLIFY_TIME.set(SIMPLIFY_TIME.get().map(|total| total + elapsed));
+    result
+}
+
+/// Runs `f` and returns its result along with the time spent simplifying on this thread.
+pub fn measure_simplify_time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let outer = SIMPLIFY_TIME.replace(Some(Duration::ZERO));
+    let result = f();
+    let time = SIMPLIFY_TIME.replace(outer).unwrap_or_default();
+    if let Some(outer) = outer {
+        // the outer measurement includes this one
+        SIMPLIFY_TIME.set(Some(outer + time));
+    }
+    (result, time)
+}
+
+/// Records one call to `derivative_with`.
+pub fn record_derivative_call() {
+    let mut calls = CALLS.get();
+    calls.derivative += 1;
+    CALLS.set(calls);
+}
+
+/// Records one call to `simplify_with`.
+pub fn record_simplify_call() {
+    let mut calls = CALLS.get();
+    calls.simplify += 1;
+    CALLS.set(calls);
+}
+
+/// Runs `f` and returns its result along with the number of derivative and simplify calls it made
+/// on this thread.
+pub fn count_calls<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+    let before = CALLS.get();
+    let result = f();
+    let after = CALLS.get();
+    (
+        result,
+        after.derivative - before.derivative,
+        after.simplify - before.simplify,
+    )
+}
+
+/// Runs `f` and returns its result along with the number of steps (i.e., derivative and simplify
+/// calls) it made on this thread.
+#[cfg(test)]
+pub fn count_steps<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let (result, derivative_calls, simplify_calls) = count_calls(f);
+    (result, derivative_calls + simplify_calls)
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::Regex;
+
+    /// How far above its baseline a step count may rise before the regression test fails.
+    #[allow(dead_code)]
+    const REGRESSION_THRESHOLD: f64 = 1.1;
+
+    #[test]
+    fn test_count_steps() {
+        let regex = Regex::new("ab").unwrap();
+        let (matched, steps) = count_steps(|| regex.matches("ab"));
+        assert!(matched);
+        assert!(steps > 0);
+
+        let ((), steps) = count_steps(|| ());
+        assert_eq!(steps, 0);
+    }
+
+    #[test]
+    fn test_pathological_step_counts() {
+        let gallery: serde_json::Value =
+            serde_json::from_str(include_str!("../benches/pathological.json")).unwrap();
+
+        let mut regressions = Vec::new();
+        for entry in gallery.as_array().unwrap() {
+            let name = entry["name"].as_str().unwrap();
+            let pattern = entry["pattern"].as_str().unwrap();
+            let input = entry["input"].as_str().unwrap();
+
+            let (regex, compile_steps) = count_steps(|| Regex::new(pattern).unwrap());
+            let (matched, match_steps) = count_steps(|| regex.matches(input));
+            assert_eq!(matched, entry["matches"].as_bool().unwrap(), "{name}");
+
+            for (kind, steps) in [("compile", compile_steps), ("match", match_steps)] {
+                let baseline = entry[format!("{kind}_steps")].as_u64().unwrap();
+                if steps as f64 > baseline as f64 * REGRESSION_THRESHOLD {
+                    regressions.push(format!(
+                        "{name}: {kind} took {steps} steps (baseline {baseline})"
+                    ));
+                }
+            }
+        }
+
+        assert!(
+            regressions.is_empty(),
+            "{}\nUpdate benches/pathological.json if the new counts are expected.",
+            regressions.join("\n")
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Matching a regex against an asynchronous stream of characters, or of byte chunks such as a
+//! request body, without waiting for the whole stream to arrive.
+//!
+//! Byte chunks (with the `tokio` feature) are decoded as UTF-8 (a character may be split across
+//! chunks). Each character is fed to the derivative as soon as it arrives. Once the derivative is
+//! `∅`, no continuation of the stream can match, so matching stops without reading the rest of the
+//! stream.
+
+use crate::derivatives::Regex;
+#[cfg(feature = "tokio")]
+use crate::reader::ChunkMatcher;
+#[cfg(feature = "tokio")]
+use bytes::Bytes;
+use futures_core::Stream;
+use std::future::poll_fn;
+#[cfg(feature = "tokio")]
+use std::io;
+use std::pin::pin;
+
+/// The result of matching a stream of byte chunks.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamMatch {
+    /// The stream ended and the regex matched it.
+    Match,
+    /// The stream ended and the regex did not match it, although a longer stream might have matched.
+    NoMatch,
+    /// No stream that starts with the bytes read so far can match, so the rest of the stream was not
+    /// read. `offset` is the byte offset of the first character that could not be matched.
+    DeadEnd { offset: usize },
+}
+
+impl Regex {
+    /// Returns `true` if the regex matches the characters of the stream, otherwise returns `false`.
+    ///
+    /// Returns as soon as no continuation of the stream could match, without waiting for the rest
+    /// of it.
+    pub async fn matches_char_stream<S>(&self, stream: S) -> bool
+    where
+        S: Stream<Item = char>,
+    {
+        let mut stream = pin!(stream);
+        let mut current = self.clone();
+        while current != Self::Empty {
+            let Some(c) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await else {
+                return current.nullable();
+            };
+            current = current.derivative(c);
+        }
+
+        false
+    }
+
+    /// Matches the regex against a stream of byte chunks, returning as soon as no continuation of
+    /// the stream could match.
+    ///
+    /// Returns an error if the stream yields one, or if it is not valid UTF-8 (with kind
+    /// `io::ErrorKind::InvalidData`).
+    #[cfg(feature = "tokio")]
+    pub async fn matches_stream<S>(&self, stream: S) -> io::Result<StreamMatch>
+    where
+        S: Stream<Item = io::Result<Bytes>>,
+    {
+        let mut stream = pin!(stream);
+        let mut matcher = ChunkMatcher::new(self.clone());
+        if matcher.current == Self::Empty {
+            return Ok(StreamMatch::DeadEnd { offset: 0 });
+        }
+
+        while let Some(chunk) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            if !matcher.push_chunk(&chunk?)? {
+                return Ok(StreamMatch::DeadEnd {
+                    offset: matcher.offset,
+                });
+            }
+        }
+
+        if matcher.finish()? {
+            Ok(StreamMatch::Match)
+        } else {
+            Ok(StreamMatch::NoMatch)
+        }
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::Regex;
+    #[cfg(feature = "tokio")]
+    #[allow(unused_imports)]
+    use super::{io, Bytes, StreamMatch};
+
+    /// Runs the future to completion.
+    #[cfg(test)]
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_matches_char_stream() {
+        let regex = Regex::new("a+b").unwrap();
+        let matches = |s: &str| block_on(regex.matches_char_stream(tokio_stream::iter(s.chars())));
+        assert!(matches("aaab"));
+        assert!(!matches("aaa"));
+        assert!(!matches(""));
+        assert!(!matches("ba"));
+
+        // an endless stream is not read past the point where it can no longer match
+        let endless = tokio_stream::iter(['a', 'c'].into_iter().chain(std::iter::repeat('b')));
+        assert!(!block_on(regex.matches_char_stream(endless)));
+    }
+
+    /// Matches the regex against a stream of the given items.
+    #[cfg(all(test, feature = "tokio"))]
+    fn matches_items(regex: &Regex, items: Vec<io::Result<Bytes>>) -> io::Result<StreamMatch> {
+        block_on(regex.matches_stream(tokio_stream::iter(items)))
+    }
+
+    /// Matches the regex against a stream of the given chunks.
+    #[cfg(all(test, feature = "tokio"))]
+    fn matches_chunks(regex: &Regex, chunks: &[&[u8]]) -> io::Result<StreamMatch> {
+        let items = chunks
+            .iter()
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        matches_items(regex, items)
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_matches_stream() {
+        let regex = Regex::new("a+b").unwrap();
+        assert_eq!(
+            matches_chunks(&regex, &[b"aa", b"a", b"b"]).unwrap(),
+            StreamMatch::Match
+        );
+        assert_eq!(
+            matches_chunks(&regex, &[b"aa", b"a"]).unwrap(),
+            StreamMatch::NoMatch
+        );
+        assert_eq!(matches_chunks(&regex, &[]).unwrap(), StreamMatch::NoMatch);
+        assert_eq!(
+            matches_chunks(&regex, &[b"aa", b"ca", b"b"]).unwrap(),
+            StreamMatch::DeadEnd { offset: 2 }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_matches_stream_stops_early() {
+        let regex = Regex::new("a*").unwrap();
+        let items = vec![
+            Ok(Bytes::from_static(b"ab")),
+            Err(io::Error::other("never read")),
+        ];
+        assert_eq!(
+            matches_items(&regex, items).unwrap(),
+            StreamMatch::DeadEnd { offset: 1 }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_matches_stream_split_characters() {
+        let regex = Regex::new("é💕+").unwrap();
+        let bytes = "é💕💕".as_bytes();
+        for split in 0..=bytes.len() {
+            let (first, second) = bytes.split_at(split);
+            assert_eq!(
+                matches_chunks(&regex, &[first, second]).unwrap(),
+                StreamMatch::Match,
+                "{split}"
+            );
+        }
+
+        let chunks = bytes.iter().map(std::slice::from_ref).collect::<Vec<_>>();
+        assert_eq!(matches_chunks(&regex, &chunks).unwrap(), StreamMatch::Match);
+
+        let regex = Regex::new("é+").unwrap();
+        assert_eq!(
+            matches_chunks(&regex, &[&[0xC3], &[0xA9, 0xC3], b"\xA9x"]).unwrap(),
+            StreamMatch::DeadEnd { offset: 4 }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_matches_stream_invalid_utf8() {
+        let regex = Regex::new("[a-z]*").unwrap();
+        for chunks in [
+            &[b"a\xFFb".as_slice()][..],
+            &[b"a\xC3", b"b"],
+            &[b"a", b"\xF0\x9F"],
+        ] {
+            let error = matches_chunks(&regex, chunks).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_matches_stream_error() {
+        let regex = Regex::new("a*").unwrap();
+        let items = vec![
+            Ok(Bytes::from_static(b"aa")),
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "reset")),
+        ];
+        let error = matches_items(&regex, items).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+    }
+}
@@ -0,0 +1,297 @@
+//! Templates: patterns with named holes (e.g., `id={id:\d+}&name={name:[a-z]+}`) that both match
+//! a string and extract the part matched by each hole.
+//!
+//! A hole is written `{name:pattern}`, where the name is an identifier. Everything outside the holes
+//! is an ordinary pattern, so an alternation cannot span a hole. Counts such as `a{2}` are not holes,
+//! since they do not start with an identifier followed by `:`.
+//!
+//! When a string can be split between the holes in more than one way, each hole takes the longest
+//! part it can, from left to right.
+
+use crate::derivatives::Regex;
+use std::ops::Range;
+
+/// A part of a template, which is a hole if it has a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment {
+    name: Option<String>,
+    regex: Regex,
+}
+
+/// A compiled template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+/// The parts of a string matched by the holes of a template, returned by `Template::captures`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateMatch<'t, 's> {
+    haystack: &'s str,
+    holes: Vec<(&'t str, Range<usize>)>,
+}
+
+impl<'t, 's> TemplateMatch<'t, 's> {
+    /// Returns the part of the string matched by the hole with the given name, if there is one.
+    pub fn get(&self, name: &str) -> Option<&'s str> {
+        self.span(name).map(|span| &self.haystack[span])
+    }
+
+    /// Returns the byte range of the string matched by the hole with the given name, if there is one.
+    pub fn span(&self, name: &str) -> Option<Range<usize>> {
+        self.holes
+            .iter()
+            .find(|(hole, _)| *hole == name)
+            .map(|(_, span)| span.clone())
+    }
+
+    /// Returns the name of each hole and the part of the string it matched, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'t str, &'s str)> + '_ {
+        self.holes
+            .iter()
+            .map(|(name, span)| (*name, &self.haystack[span.clone()]))
+    }
+}
+
+/// Returns the length of the hole name at the start of `s` if it is followed by `:`.
+fn hole_name_len(s: &str) -> Option<usize> {
+    let len = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    let starts_with_digit = s.starts_with(|c: char| c.is_ascii_digit());
+
+    (len > 0 && !starts_with_digit && s[len..].starts_with(':')).then_some(len)
+}
+
+/// Returns the length of the hole pattern at the start of `s`, up to its closing `}`.
+fn hole_pattern_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Splits a template into the patterns outside holes (without a name) and inside holes.
+fn split_template(template: &str) -> Result<Vec<(Option<&str>, &str)>, String> {
+    let mut parts = Vec::new();
+    let mut text_start = 0;
+    let mut escaped = false;
+
+    let mut i = 0;
+    while let Some(c) = template[i..].chars().next() {
+        let hole_name_len = match c {
+            _ if escaped => {
+                escaped = false;
+                None
+            }
+            '\\' => {
+                escaped = true;
+                None
+            }
+            '{' => hole_name_len(&template[i + 1..]),
+            _ => None,
+        };
+        let Some(name_len) = hole_name_len else {
+            i += c.len_utf8();
+            continue;
+        };
+
+        let name = &template[i + 1..i + 1 + name_len];
+        let pattern_start = i + 1 + name_len + 1;
+        let pattern_len = hole_pattern_len(&template[pattern_start..])
+            .ok_or_else(|| format!("Hole `{name}` is not closed"))?;
+
+        parts.push((None, &template[text_start..i]));
+        parts.push((
+            Some(name),
+            &template[pattern_start..pattern_start + pattern_len],
+        ));
+
+        // continue after the closing `}`
+        text_start = pattern_start + pattern_len + 1;
+        i = text_start;
+    }
+    parts.push((None, &template[text_start..]));
+
+    Ok(parts)
+}
+
+impl Template {
+    /// Tries to compile a template (e.g., `{year:\d{4}}-{month:\d{2}}`).
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut segments: Vec<Segment> = Vec::new();
+
+        for (name, pattern) in split_template(template)? {
+            let regex = match name {
+                Some(name) => {
+                    if segments
+                        .iter()
+                        .any(|segment| segment.name.as_deref() == Some(name))
+                    {
+                        return Err(format!("Hole `{name}` appears more than once"));
+                    }
+                    Regex::new(pattern)
+                        .map_err(|error| format!("Invalid pattern in hole `{name}`: {error}"))?
+                }
+                None if pattern.is_empty() => continue,
+                None => Regex::new(pattern)?,
+            };
+
+            segments.push(Segment {
+                name: name.map(str::to_string),
+                regex,
+            });
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Returns the names of the holes, in order.
+    pub fn hole_names(&self) -> impl Iterator<Item = &str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| segment.name.as_deref())
+    }
+
+    /// Returns the template as a single regex, without its holes.
+    pub fn to_regex(&self) -> Regex {
+        Regex::concat_all(self.segments.iter().map(|segment| segment.regex.clone()))
+    }
+
+    /// Returns `true` if the template matches the given string, otherwise returns `false`.
+    pub fn matches(&self, s: &str) -> bool {
+        self.to_regex().matches(s)
+    }
+
+    /// Matches the template against the given string, returning the part matched by each hole, or
+    /// `None` if it does not match.
+    pub fn captures<'s>(&self, s: &'s str) -> Option<TemplateMatch<'_, 's>> {
+        let boundaries = s
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(s.len()))
+            .collect::<Vec<_>>();
+
+        // completes[i][k] is whether the segments from `i` onwards match the string after the
+        // `k`th character, found by matching the reversed segments against the reversed string
+        let completes = (0..=self.segments.len())
+            .map(|i| {
+                let mut current = Regex::concat_all(
+                    self.segments[i..]
+                        .iter()
+                        .map(|segment| segment.regex.clone()),
+                )
+                .reverse();
+                let mut completes = vec![false; boundaries.len()];
+                for (k, c) in s.chars().rev().enumerate() {
+                    completes[boundaries.len() - 1 - k] = current.nullable();
+                    current = current.derivative(c);
+                }
+                completes[0] = current.nullable();
+                completes
+            })
+            .collect::<Vec<_>>();
+
+        if !completes[0][0] {
+            return None;
+        }
+
+        let mut holes = Vec::new();
+        let mut start = 0;
+        for (i, segment) in self.segments.iter().enumerate() {
+            // the longest part that still lets the rest of the template match
+            let mut current = segment.regex.clone();
+            let mut end = None;
+            for k in start..boundaries.len() {
+                if current.nullable() && completes[i + 1][k] {
+                    end = Some(k);
+                }
+                if k + 1 == boundaries.len() || current == Regex::Empty {
+                    break;
+                }
+                current = current.derivative(s[boundaries[k]..].chars().next().unwrap());
+            }
+
+            let end = end.expect("the rest of the template matches");
+            if let Some(name) = &segment.name {
+                holes.push((name.as_str(), boundaries[start]..boundaries[end]));
+            }
+            start = end;
+        }
+
+        Some(TemplateMatch { haystack: s, holes })
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{Regex, Template};
+
+    #[test]
+    fn test_template_captures() {
+        let template = Template::new(r"{year:\d{4}}-{month:\d{2}}").unwrap();
+        assert_eq!(
+            template.hole_names().collect::<Vec<_>>(),
+            vec!["year", "month"]
+        );
+
+        let captures = template.captures("2024-05").unwrap();
+        assert_eq!(captures.get("year"), Some("2024"));
+        assert_eq!(captures.get("month"), Some("05"));
+        assert_eq!(captures.span("month"), Some(5..7));
+        assert_eq!(captures.get("day"), None);
+        assert_eq!(
+            captures.iter().collect::<Vec<_>>(),
+            vec![("year", "2024"), ("month", "05")]
+        );
+
+        assert!(template.captures("2024-5").is_none());
+        assert!(!template.matches("24-05"));
+        assert!(template.matches("2024-05"));
+    }
+
+    #[test]
+    fn test_template_longest_first() {
+        let template = Template::new("{first:[a-z]+}{second:[a-z]+}x?").unwrap();
+        let captures = template.captures("abcx").unwrap();
+        assert_eq!(captures.get("first"), Some("abc"));
+        assert_eq!(captures.get("second"), Some("x"));
+
+        let template = Template::new("{key:[a-z=]*}={value:[a-z=]*}").unwrap();
+        let captures = template.captures("a=b=c").unwrap();
+        assert_eq!(captures.get("key"), Some("a=b"));
+        assert_eq!(captures.get("value"), Some("c"));
+    }
+
+    #[test]
+    fn test_template_unicode_spans() {
+        let template = Template::new("💕{name:.+}💕").unwrap();
+        let captures = template.captures("💕héllo💕").unwrap();
+        assert_eq!(captures.get("name"), Some("héllo"));
+        assert_eq!(captures.span("name"), Some(4..10));
+    }
+
+    #[test]
+    fn test_template_syntax() {
+        // counts and escaped braces are not holes
+        let template = Template::new(r"a{2}\{{n:b{1,2}}\}").unwrap();
+        assert_eq!(template.hole_names().collect::<Vec<_>>(), vec!["n"]);
+        assert_eq!(template.captures("aa{bb}").unwrap().get("n"), Some("bb"));
+        assert_eq!(template.to_regex(), Regex::new(r"a{2}\{b{1,2}\}").unwrap());
+
+        assert!(Template::new("{a:x").is_err());
+        assert!(Template::new("{a:}").is_err());
+        assert!(Template::new("{a:x}{a:y}").is_err());
+        assert!(Template::new("{a:x{}").is_err());
+    }
+}
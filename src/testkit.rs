@@ -0,0 +1,289 @@
+//! Checks of the laws that derivatives must obey, for testing changes to the derivative,
+//! simplification or matching against many regexes at once.
+//!
+//! The fundamental law is that a regex `r` matches `cs` (the character `c` followed by the string
+//! `s`) exactly when the derivative of `r` with respect to `c` matches `s`. Printing a regex and
+//! parsing the pattern must also give a regex matching the same strings.
+//!
+//! This module is only available with the `testkit` feature.
+
+use crate::derivatives::Regex;
+use crate::generate::InputGenerator;
+use std::fmt::{Display, Formatter};
+
+/// The characters random strings are made of, which include one that random regexes never use.
+const RANDOM_CHARS: &[char] = &['a', 'b', 'c', 'd'];
+
+/// The longest random string checked.
+const RANDOM_MAX_LEN: u64 = 8;
+
+/// How many times unbounded repetitions are repeated at most beyond their minimum in the strings
+/// generated to match a regex.
+const RANDOM_MAX_REPEAT: usize = 3;
+
+/// A regex, character and string for which the derivative law does not hold, returned by
+/// `check_derivative_law`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivativeLawViolation {
+    /// The regex.
+    pub regex: Regex,
+    /// The character the derivative was taken with respect to.
+    pub c: char,
+    /// The rest of the string.
+    pub s: String,
+    /// Whether the regex matches `c` followed by `s`.
+    pub matches: bool,
+}
+
+impl Display for DerivativeLawViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (regex_does, derivative_does) = if self.matches {
+            ("matches", "does not match")
+        } else {
+            ("does not match", "matches")
+        };
+        write!(
+            f,
+            "{} {regex_does} {:?}, but its derivative with respect to {:?} {derivative_does} {:?}",
+            self.regex,
+            format!("{}{}", self.c, self.s),
+            self.c,
+            self.s
+        )
+    }
+}
+
+/// Checks that the regex matches `c` followed by `s` exactly when its derivative with respect to
+/// `c` matches `s`.
+pub fn check_derivative_law(regex: &Regex, c: char, s: &str) -> Result<(), DerivativeLawViolation> {
+    let matches = regex.matches(&format!("{c}{s}"));
+    if matches == regex.derivative(c).matches(s) {
+        Ok(())
+    } else {
+        Err(DerivativeLawViolation {
+            regex: regex.clone(),
+            c,
+            s: s.to_string(),
+            matches,
+        })
+    }
+}
+
+/// Checks the derivative law for the regex on `cases` strings generated from the seed (strings it
+/// matches, near misses and random strings), splitting each string at every character, so the law
+/// is also checked for the regex's derivatives with respect to each prefix.
+///
+/// The same seed always checks the same strings.
+pub fn check_derivative_law_random(
+    regex: &Regex,
+    seed: u64,
+    cases: usize,
+) -> Result<(), DerivativeLawViolation> {
+    // strings are kept short, since the law is checked at every character
+    let mut generator = InputGenerator::new(seed).max_repeat(RANDOM_MAX_REPEAT);
+    for case in 0..cases {
+        let s = match case % 3 {
+            0 => generator.matching(regex),
+            1 => generator.near_miss(regex),
+            _ => None,
+        };
+        let s = s.unwrap_or_else(|| {
+            let len = generator.next_in(1, RANDOM_MAX_LEN);
+            (0..len)
+                .map(|_| RANDOM_CHARS[generator.next_in(0, RANDOM_CHARS.len() as u64 - 1) as usize])
+                .collect()
+        });
+
+        let mut current = regex.clone();
+        for (i, c) in s.char_indices() {
+            check_derivative_law(&current, c, &s[i + c.len_utf8()..])?;
+            current = current.derivative(c);
+        }
+    }
+
+    Ok(())
+}
+
+/// A regex whose printed pattern does not parse back into a regex matching the same strings,
+/// returned by `check_round_trip`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundTripFailure {
+    /// The pattern could not be parsed.
+    Parse {
+        regex: Regex,
+        pattern: String,
+        error: String,
+    },
+    /// The pattern parsed into a regex that matches different strings.
+    Language {
+        regex: Regex,
+        pattern: String,
+        reparsed: Regex,
+    },
+}
+
+impl Display for RoundTripFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse { regex, error, .. } => {
+                write!(
+                    f,
+                    "{regex} is printed as a pattern that fails to parse: {error}"
+                )
+            }
+            Self::Language {
+                regex, reparsed, ..
+            } => write!(
+                f,
+                "{regex} is parsed back as {reparsed}, which matches different strings"
+            ),
+        }
+    }
+}
+
+/// Checks that printing the regex gives a pattern that parses into a regex matching the same
+/// strings.
+///
+/// The two regexes are compared with `Regex::is_subset_of` both ways, which explores the product of
+/// their derivative automata.
+pub fn check_round_trip(regex: &Regex) -> Result<(), RoundTripFailure> {
+    let pattern = regex.to_string();
+    let reparsed = match Regex::new(&pattern) {
+        Ok(reparsed) => reparsed,
+        Err(error) => {
+            return Err(RoundTripFailure::Parse {
+                regex: regex.clone(),
+                pattern,
+                error,
+            })
+        }
+    };
+
+    if regex.is_subset_of(&reparsed) && reparsed.is_subset_of(regex) {
+        Ok(())
+    } else {
+        Err(RoundTripFailure::Language {
+            regex: regex.clone(),
+            pattern,
+            reparsed,
+        })
+    }
+}
+
+mod tests {
+    #[allow(unused_imports)]
+    use super::{
+        check_derivative_law, check_derivative_law_random, check_round_trip,
+        DerivativeLawViolation, RoundTripFailure,
+    };
+    #[allow(unused_imports)]
+    use crate::generate::InputGenerator;
+    #[allow(unused_imports)]
+    use crate::Regex;
+
+    /// Patterns from the tests and benchmarks that the law is checked for.
+    #[allow(dead_code)]
+    const CORPUS: &[&str] = &[
+        "abcdef",
+        "a|b",
+        "a*",
+        "a+",
+        "a?",
+        "[a-z]",
+        r"\d\w",
+        "a{2,27}",
+        "(a*b*c*)*d+",
+        "((a|b|c)(d|e|f)(g|h|i))+",
+        "(a{2,5}b{3,7}c{1,9}){2,4}",
+        r"[a-zA-Z0-9_][a-z]{5,10}\d{3,6}",
+        "(a+)+b",
+        r"[a-zA-Z0-9._%+\-]+@[a-zA-Z0-9.\-]+\.[a-zA-Z]{2,}",
+        "[a-z--[aeiou]]+",
+        "[a-z&&[x-~]]*",
+        "(?i)ab|c",
+        "é💕|(ab){3,}",
+    ];
+
+    /// Returns the patterns of the pathological regex gallery used by the benchmarks.
+    #[cfg(test)]
+    fn pathological_patterns() -> Vec<String> {
+        let gallery: serde_json::Value =
+            serde_json::from_str(include_str!("../benches/pathological.json")).unwrap();
+        gallery
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["pattern"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_check_derivative_law() {
+        let regex = Regex::new("ab*").unwrap();
+        assert_eq!(check_derivative_law(&regex, 'a', "bb"), Ok(()));
+        assert_eq!(check_derivative_law(&regex, 'b', "a"), Ok(()));
+
+        let violation = DerivativeLawViolation {
+            regex,
+            c: 'a',
+            s: "b".to_string(),
+            matches: true,
+        };
+        assert_eq!(
+            violation.to_string(),
+            r#"a(b)* matches "ab", but its derivative with respect to 'a' does not match "b""#
+        );
+    }
+
+    #[test]
+    fn test_derivative_law_corpus() {
+        let patterns = CORPUS
+            .iter()
+            .map(ToString::to_string)
+            .chain(pathological_patterns());
+        for (seed, pattern) in patterns.enumerate() {
+            let regex = Regex::new(&pattern).unwrap();
+            if let Err(violation) = check_derivative_law_random(&regex, seed as u64, 12) {
+                panic!("{pattern}: {violation}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_derivative_law_random_regexes() {
+        let mut generator = InputGenerator::new(42);
+        for seed in 0..200 {
+            let regex = generator.regex(3);
+            if let Err(violation) = check_derivative_law_random(&regex, seed, 6) {
+                panic!("{violation}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_round_trip() {
+        assert_eq!(
+            check_round_trip(&Regex::new(r"(a|\*)+[\--\]]").unwrap()),
+            Ok(())
+        );
+
+        // `ε` is printed as the literal character
+        let failure = check_round_trip(&Regex::Epsilon).unwrap_err();
+        assert!(matches!(failure, RoundTripFailure::Language { .. }));
+        assert_eq!(
+            failure.to_string(),
+            "ε is parsed back as ε, which matches different strings"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_random_regexes() {
+        let mut generator = InputGenerator::new(42);
+        for _ in 0..300 {
+            let regex = generator.pattern_regex(3);
+            if let Err(failure) = check_round_trip(&regex) {
+                panic!("{failure}");
+            }
+        }
+    }
+}
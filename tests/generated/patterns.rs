@@ -0,0 +1,86 @@
+// Generated by rzozowski from the pattern pack patterns.pack.
+
+/// Returns `true` if the string matches the regex this was generated from.
+pub fn snake_case(s: &str) -> bool {
+    static ASCII_CLASSES: [u32; 128] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+        0, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+        2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0,
+    ];
+    static BOUNDARIES: [u32; 7] = [
+        0, 48, 58, 95, 96, 97, 123,
+    ];
+    static INTERVAL_CLASSES: [u32; 7] = [
+        0, 1, 0, 2, 0, 2, 0,
+    ];
+    static TRANSITIONS: [u32; 9] = [
+        1, 1, 2, 1, 1, 1, 1, 2, 2,
+    ];
+    static ACCEPTING: [bool; 3] = [
+        false, false, true,
+    ];
+    const CLASS_COUNT: usize = 3;
+    const DEAD: u32 = 1;
+
+    let mut state: u32 = 0;
+    for c in s.chars() {
+        let class = if c.is_ascii() {
+            ASCII_CLASSES[c as usize]
+        } else {
+            INTERVAL_CLASSES[BOUNDARIES.partition_point(|&boundary| boundary <= c as u32) - 1]
+        };
+        state = TRANSITIONS[state as usize * CLASS_COUNT + class as usize];
+        if state == DEAD {
+            return false;
+        }
+    }
+    ACCEPTING[state as usize]
+}
+
+/// Returns `true` if the string matches the regex this was generated from.
+pub fn version(s: &str) -> bool {
+    static ASCII_CLASSES: [u32; 128] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0,
+        2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    static BOUNDARIES: [u32; 5] = [
+        0, 46, 47, 48, 58,
+    ];
+    static INTERVAL_CLASSES: [u32; 5] = [
+        0, 1, 0, 2, 0,
+    ];
+    static TRANSITIONS: [u32; 21] = [
+        1, 1, 2, 1, 1, 1, 1, 3, 2, 1, 1, 4, 1, 5, 4, 1,
+        1, 6, 1, 1, 6,
+    ];
+    static ACCEPTING: [bool; 7] = [
+        false, false, false, false, false, false, true,
+    ];
+    const CLASS_COUNT: usize = 3;
+    const DEAD: u32 = 1;
+
+    let mut state: u32 = 0;
+    for c in s.chars() {
+        let class = if c.is_ascii() {
+            ASCII_CLASSES[c as usize]
+        } else {
+            INTERVAL_CLASSES[BOUNDARIES.partition_point(|&boundary| boundary <= c as u32) - 1]
+        };
+        state = TRANSITIONS[state as usize * CLASS_COUNT + class as usize];
+        if state == DEAD {
+            return false;
+        }
+    }
+    ACCEPTING[state as usize]
+}
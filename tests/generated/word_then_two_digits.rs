@@ -0,0 +1,44 @@
+// Generated by rzozowski from a compiled DFA.
+
+/// Returns `true` if the string matches the regex this was generated from.
+pub fn word_then_two_digits(s: &str) -> bool {
+    static ASCII_CLASSES: [u32; 128] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+        2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0,
+    ];
+    static BOUNDARIES: [u32; 7] = [
+        0, 48, 58, 97, 123, 233, 234,
+    ];
+    static INTERVAL_CLASSES: [u32; 7] = [
+        0, 1, 0, 2, 0, 3, 0,
+    ];
+    static TRANSITIONS: [u32; 24] = [
+        1, 1, 2, 3, 1, 1, 1, 1, 1, 4, 2, 1, 1, 1, 1, 3,
+        1, 5, 1, 1, 1, 1, 1, 1,
+    ];
+    static ACCEPTING: [bool; 6] = [
+        false, false, false, true, false, true,
+    ];
+    const CLASS_COUNT: usize = 4;
+    const DEAD: u32 = 1;
+
+    let mut state: u32 = 0;
+    for c in s.chars() {
+        let class = if c.is_ascii() {
+            ASCII_CLASSES[c as usize]
+        } else {
+            INTERVAL_CLASSES[BOUNDARIES.partition_point(|&boundary| boundary <= c as u32) - 1]
+        };
+        state = TRANSITIONS[state as usize * CLASS_COUNT + class as usize];
+        if state == DEAD {
+            return false;
+        }
+    }
+    ACCEPTING[state as usize]
+}
@@ -1,4 +1,5 @@
-use rzozowski::Regex;
+use rzozowski::build::compile_patterns;
+use rzozowski::{DfaRef, Node, Regex};
 
 #[test]
 fn test_parse_and_matches() {
@@ -18,3 +19,121 @@ fn test_equality() {
     let der = r.derivative('1');
     assert_eq!(der, Regex::new(r"\d{2,5}[a-z_]+").unwrap());
 }
+
+#[test]
+fn test_count_at_least() {
+    let regex = Regex::new("a{3,}b").unwrap();
+    assert!(!regex.matches("aab"));
+    assert!(regex.matches("aaab"));
+    assert!(regex.matches(&format!("{}b", "a".repeat(100))));
+
+    assert_eq!(regex.to_string(), "(a){3,}b");
+    assert_eq!(Regex::new(&regex.to_string()).unwrap(), regex);
+
+    // {n,} stays distinct from {n} and {n,m}
+    assert_ne!(regex, Regex::new("a{3}b").unwrap());
+    assert_ne!(regex, Regex::new("a{3,3}b").unwrap());
+    assert!(!Regex::new("a{3}b").unwrap().matches("aaaab"));
+}
+
+#[test]
+fn test_try_from() {
+    let regex = Regex::try_from("a+b").unwrap();
+    assert_eq!(regex, Regex::new("a+b").unwrap());
+
+    let regex: Regex = String::from("a+b").try_into().unwrap();
+    assert_eq!(regex, Regex::new("a+b").unwrap());
+
+    assert_eq!(Regex::try_from("(a"), Regex::new("(a"));
+    assert!(Regex::try_from(String::new()).is_err());
+}
+
+#[test]
+fn test_deep_regex() {
+    // recursion over the regex grows the stack on the heap, so a thread with a small stack can
+    // still handle deeply nested regexes
+    std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(|| {
+            let regex = Regex::new(&"a*b".repeat(5000)).unwrap();
+            assert_eq!(regex.clone(), regex);
+            assert_eq!(Regex::new(&regex.to_string()).unwrap(), regex);
+
+            let regex = Regex::new(&"a*b".repeat(100)).unwrap();
+            assert!(regex.matches(&"ab".repeat(100)));
+            assert!(!regex.matches(&"ab".repeat(99)));
+
+            // parsing rebalances the concatenation, but one built in code stays deep
+            let star = Regex::literal('a').star();
+            let deep = (0..5000).fold(Regex::Epsilon, |regex, _| {
+                Regex::Concat(Node::new(regex), Node::new(star.clone()))
+            });
+            assert_eq!(deep.clone(), deep);
+            assert!(deep.matches("aa"));
+            assert_eq!(deep.rebalance().simplify(), Regex::new("a*").unwrap());
+
+            let nested = format!("{}a{}", "(".repeat(5000), ")".repeat(5000));
+            assert!(Regex::new(&nested).unwrap().matches("a"));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+mod generated {
+    include!("generated/word_then_two_digits.rs");
+}
+
+mod generated_patterns {
+    include!("generated/patterns.rs");
+}
+
+#[test]
+fn test_generated_dfa() {
+    let regex = Regex::new(r"[a-z]+\d{2}|é+").unwrap();
+    // the generated function is what `Dfa::to_rust` writes now
+    let code = regex
+        .compile()
+        .unwrap()
+        .to_rust("word_then_two_digits")
+        .unwrap();
+    assert_eq!(code, include_str!("generated/word_then_two_digits.rs"));
+
+    for s in [
+        "ab12", "ab1", "12", "é", "ééé", "éa12", "zz99", "", "a b12", "abc123",
+    ] {
+        assert_eq!(generated::word_then_two_digits(s), regex.matches(s), "{s}");
+    }
+}
+
+#[test]
+fn test_compile_patterns() {
+    let out_dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("compile_patterns");
+    let code_path = compile_patterns("tests/generated/patterns.pack", &out_dir).unwrap();
+    // the generated functions are what `compile_patterns` writes now
+    assert_eq!(code_path, out_dir.join("patterns.rs"));
+    assert_eq!(
+        std::fs::read_to_string(&code_path).unwrap(),
+        include_str!("generated/patterns.rs")
+    );
+
+    let snake_case = Regex::new("[a-z_][a-z0-9_]*").unwrap();
+    let version = Regex::new(r"\d+\.\d+\.\d+").unwrap();
+    let snake_case_bytes = std::fs::read(out_dir.join("snake_case.dfa")).unwrap();
+    let snake_case_dfa = DfaRef::from_bytes(&snake_case_bytes).unwrap();
+    for s in [
+        "user_id", "_", "2fast", "", "1.80.0", "1.80", "10.2.33", "1..0", "a.b.c", "é",
+    ] {
+        assert_eq!(
+            generated_patterns::snake_case(s),
+            snake_case.matches(s),
+            "{s}"
+        );
+        assert_eq!(snake_case_dfa.matches(s), snake_case.matches(s), "{s}");
+        assert_eq!(generated_patterns::version(s), version.matches(s), "{s}");
+    }
+
+    assert!(compile_patterns("tests/generated/missing.pack", &out_dir)
+        .unwrap_err()
+        .starts_with("Cannot read tests/generated/missing.pack"));
+}